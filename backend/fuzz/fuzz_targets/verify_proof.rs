@@ -0,0 +1,39 @@
+//! Feed arbitrary proof bytes into the backend's proof parsing and verification.
+//!
+//! Input layout: `[leaf_index, total_leaves, proof_bytes..]`. The leaf claims an
+//! expiration that is not in the tree behind `ROOT`, so no input may verify; every
+//! call must return `Err` or `Ok(false)` without panicking.
+//!
+//! Run from `backend/` with `cargo +nightly fuzz run verify_proof`; the seed corpus
+//! in `fuzz/corpus/verify_proof` is picked up automatically.
+
+#![no_main]
+
+use backend::merkle::tree::{self, Expiration};
+use libfuzzer_sys::fuzz_target;
+
+// Root of the three-subscriber test fixture; `WALLET` is in it, but not with expiration 0
+const ROOT: &str = "3d60b36987fa781fd4f3eca87f8e835fa5ae2335917273dfa98f88ea95bbdffd";
+const WALLET: &str = "5sHXVAK46po96V9syX6Jhmav9qUagnKydnYwuE57KPat";
+
+fuzz_target!(|data: &[u8]| {
+    let [index, total, proof_bytes @ ..] = data else {
+        return;
+    };
+    let (index, total) = (*index as usize, *total as usize);
+    let expiration = Expiration::new(0).unwrap();
+
+    // Raw bytes, then the same bytes cut to whole hashes so content mutations
+    // reach the verification path instead of failing to parse
+    let whole_hashes = &proof_bytes[..proof_bytes.len() - proof_bytes.len() % 32];
+    for proof in [proof_bytes, whole_hashes] {
+        if let Ok(valid) =
+            tree::verify_subscription(ROOT, proof, WALLET, expiration, index, total)
+        {
+            assert!(!valid, "forged proof verified: {}", hex::encode(proof));
+        }
+
+        let leaf = tree::compute_leaf(WALLET, expiration).unwrap();
+        let _ = tree::recompute_root(proof, leaf, index, total);
+    }
+});
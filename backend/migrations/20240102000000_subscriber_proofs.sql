@@ -0,0 +1,7 @@
+-- TABLE 3: Subscriber Proofs (latest proof per wallet, regenerated after every root update)
+CREATE TABLE subscriber_proofs (
+    wallet              VARCHAR(44) PRIMARY KEY, -- Base58 Solana Address
+    proof_hex           TEXT NOT NULL,           -- Hex-encoded rs_merkle proof bytes
+    leaf_index          BIGINT NOT NULL,         -- Position of the leaf in the sorted tree
+    root_hex            VARCHAR(64) NOT NULL     -- Root the proof was generated against
+);
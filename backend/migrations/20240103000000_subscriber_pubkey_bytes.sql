@@ -0,0 +1,42 @@
+-- Raw 32-byte pubkeys next to the base58 wallet address, so tree builds skip base58 decoding
+
+-- Base58 (Bitcoin alphabet) to bytes; each leading '1' encodes a zero byte. NULL for
+-- anything that isn't a 32-byte pubkey, so a malformed row never fails this migration
+-- or an insert; validate_subscribers reports it and tree builds refuse it.
+CREATE FUNCTION base58_pubkey(encoded TEXT) RETURNS BYTEA AS $$
+DECLARE
+    alphabet CONSTANT TEXT := '123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz';
+    value NUMERIC := 0;
+    digit INT;
+    decoded BYTEA := ''::BYTEA;
+    leading_zeros INT := 0;
+BEGIN
+    FOR i IN 1..length(encoded) LOOP
+        digit := position(substr(encoded, i, 1) IN alphabet) - 1;
+        IF digit < 0 THEN
+            RETURN NULL;
+        END IF;
+        value := value * 58 + digit;
+    END LOOP;
+
+    WHILE value > 0 LOOP
+        decoded := set_byte('\x00'::BYTEA, 0, mod(value, 256)::INT) || decoded;
+        value := div(value, 256);
+    END LOOP;
+
+    WHILE leading_zeros < length(encoded) AND substr(encoded, leading_zeros + 1, 1) = '1' LOOP
+        leading_zeros := leading_zeros + 1;
+    END LOOP;
+
+    decoded := decode(repeat('00', leading_zeros), 'hex') || decoded;
+    IF length(decoded) <> 32 THEN
+        RETURN NULL;
+    END IF;
+    RETURN decoded;
+END;
+$$ LANGUAGE plpgsql IMMUTABLE STRICT;
+
+-- Generated from wallet_address: existing rows are backfilled when the column is added,
+-- and new rows never need to write it
+ALTER TABLE subscriber_storage
+    ADD COLUMN wallet_pubkey BYTEA GENERATED ALWAYS AS (base58_pubkey(wallet_address)) STORED;
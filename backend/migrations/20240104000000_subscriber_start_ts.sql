@@ -0,0 +1,4 @@
+-- Subscription start time, committed to by the leaf when the config's start_in_leaf is on.
+-- Existing rows default to 0, i.e. started at the epoch
+ALTER TABLE subscriber_storage
+    ADD COLUMN start_ts BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+-- When the subscriber last passed a backend membership check (NULL = never), for activity metrics
+ALTER TABLE subscriber_storage
+    ADD COLUMN last_verified_at TIMESTAMP;
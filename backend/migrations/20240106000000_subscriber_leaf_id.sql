@@ -0,0 +1,7 @@
+-- Monotonic insertion index, never reused or changed by upserts. Trees built in leaf_id
+-- order append new wallets instead of shifting existing leaves.
+-- Existing rows are numbered in arbitrary order
+ALTER TABLE subscriber_storage
+    ADD COLUMN leaf_id BIGINT GENERATED ALWAYS AS IDENTITY;
+
+CREATE UNIQUE INDEX subscriber_storage_leaf_id_idx ON subscriber_storage (leaf_id);
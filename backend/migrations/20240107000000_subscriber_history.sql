@@ -0,0 +1,35 @@
+-- Append-only log of subscriber changes, so the tree can be rebuilt as of any past time.
+-- Rows are written by trigger, covering every path that writes subscriber_storage
+CREATE TABLE subscriber_history (
+    id                  BIGSERIAL PRIMARY KEY,
+    wallet_address      VARCHAR(44) NOT NULL,
+    expiration_ts       BIGINT NOT NULL,
+    deleted             BOOLEAN NOT NULL DEFAULT FALSE,      -- Wallet removed at changed_at
+    changed_at          TIMESTAMP NOT NULL DEFAULT (now() AT TIME ZONE 'UTC')
+);
+
+CREATE INDEX subscriber_history_wallet_changed_at_idx
+    ON subscriber_history (wallet_address, changed_at);
+
+CREATE FUNCTION record_subscriber_history() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        INSERT INTO subscriber_history (wallet_address, expiration_ts, deleted)
+        VALUES (OLD.wallet_address, OLD.expiration_ts, TRUE);
+        RETURN OLD;
+    END IF;
+
+    INSERT INTO subscriber_history (wallet_address, expiration_ts)
+    VALUES (NEW.wallet_address, NEW.expiration_ts);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER subscriber_storage_history
+    AFTER INSERT OR UPDATE OF expiration_ts OR DELETE ON subscriber_storage
+    FOR EACH ROW EXECUTE FUNCTION record_subscriber_history();
+
+-- Existing subscribers enter the history as of their last update
+INSERT INTO subscriber_history (wallet_address, expiration_ts, changed_at)
+SELECT wallet_address, expiration_ts, COALESCE(last_updated_at, now() AT TIME ZONE 'UTC')
+FROM subscriber_storage;
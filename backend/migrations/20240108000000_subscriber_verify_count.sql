@@ -0,0 +1,4 @@
+-- Successful verifications per subscriber, for engagement analytics. Only incremented
+-- by callers that opt in to counting.
+ALTER TABLE subscriber_storage
+    ADD COLUMN verify_count BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,77 @@
+-- Audit detail on subscriber_history for dispute resolution: what kind of change each
+-- row is, and the expiration before and after. Still written by the one history
+-- trigger, inside the changing transaction, so it can never disagree with
+-- subscriber_storage.
+-- A DELETE is a 'revoke' unless its transaction sets merkle.audit_change to 'prune'
+ALTER TABLE subscriber_history
+    ADD COLUMN change_type       TEXT,
+    ADD COLUMN old_expiration_ts BIGINT,                     -- NULL for inserts
+    ADD COLUMN new_expiration_ts BIGINT;                     -- NULL for revokes and prunes
+
+-- Rows logged before this migration are classified against the wallet's previous row
+WITH changes AS (
+    SELECT id, deleted, expiration_ts,
+           LAG(expiration_ts) OVER wallet_rows AS previous_expiration_ts,
+           LAG(deleted) OVER wallet_rows AS previous_deleted
+    FROM subscriber_history
+    WINDOW wallet_rows AS (PARTITION BY wallet_address ORDER BY id)
+)
+UPDATE subscriber_history history
+SET change_type = CASE
+        WHEN changes.deleted THEN 'revoke'
+        WHEN changes.previous_deleted IS DISTINCT FROM FALSE THEN 'insert'
+        WHEN changes.expiration_ts > changes.previous_expiration_ts THEN 'renew'
+        ELSE 'update'
+    END,
+    old_expiration_ts = CASE
+        WHEN changes.deleted THEN changes.expiration_ts
+        WHEN changes.previous_deleted = FALSE THEN changes.previous_expiration_ts
+    END,
+    new_expiration_ts = CASE WHEN NOT changes.deleted THEN changes.expiration_ts END
+FROM changes
+WHERE history.id = changes.id;
+
+ALTER TABLE subscriber_history
+    ALTER COLUMN change_type SET NOT NULL,
+    ADD CONSTRAINT subscriber_history_change_type_check
+        CHECK (change_type IN ('insert', 'renew', 'update', 'revoke', 'prune'));
+
+CREATE OR REPLACE FUNCTION record_subscriber_history() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO subscriber_history
+            (wallet_address, expiration_ts, change_type, new_expiration_ts)
+        VALUES (NEW.wallet_address, NEW.expiration_ts, 'insert', NEW.expiration_ts);
+        RETURN NEW;
+    END IF;
+
+    IF TG_OP = 'UPDATE' THEN
+        -- Rewriting the same expiration (e.g. a repeated upsert) changes nothing
+        IF NEW.expiration_ts <> OLD.expiration_ts THEN
+            INSERT INTO subscriber_history
+                (wallet_address, expiration_ts, change_type, old_expiration_ts,
+                 new_expiration_ts)
+            VALUES (
+                NEW.wallet_address,
+                NEW.expiration_ts,
+                CASE WHEN NEW.expiration_ts > OLD.expiration_ts THEN 'renew' ELSE 'update' END,
+                OLD.expiration_ts,
+                NEW.expiration_ts
+            );
+        END IF;
+        RETURN NEW;
+    END IF;
+
+    INSERT INTO subscriber_history
+        (wallet_address, expiration_ts, deleted, change_type, old_expiration_ts)
+    VALUES (
+        OLD.wallet_address,
+        OLD.expiration_ts,
+        TRUE,
+        CASE WHEN current_setting('merkle.audit_change', true) = 'prune'
+             THEN 'prune' ELSE 'revoke' END,
+        OLD.expiration_ts
+    );
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
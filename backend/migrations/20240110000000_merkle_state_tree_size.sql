@@ -0,0 +1,5 @@
+-- Size of the tree behind each recorded root, so clients know the proof depth to
+-- expect. NULL for rows recorded before this migration or backfilled from chain events.
+ALTER TABLE merkle_state
+    ADD COLUMN total_leaves BIGINT,
+    ADD COLUMN tree_depth INTEGER;
@@ -0,0 +1,53 @@
+//! Check a subscriber CSV against the root stored on-chain, without database access.
+//!
+//! Usage: audit-root [--format human|json] <subscribers.csv>
+//!
+//! The CSV holds `wallet,expiration` lines (an optional header is skipped). Leaves
+//! follow the on-chain config's hash algorithm and layout. Reads `SOLANA_RPC_URL`
+//! and `PROGRAM_ID`; no keypair is needed. Exits nonzero when the roots differ.
+
+use anyhow::{Context, Result};
+use backend::cli::{self, OutputFormat};
+use backend::diagnose::{self, AuditResult};
+use backend::merkle::solana_client::SolanaClient;
+use solana_sdk::pubkey::Pubkey;
+use std::env;
+use std::path::Path;
+use std::process::ExitCode;
+use std::str::FromStr;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenvy::dotenv().ok();
+    let mut args: Vec<String> = env::args().collect();
+    match OutputFormat::take_from(&mut args) {
+        Ok(format) => match audit_root(&args).await {
+            Ok(audit) if !audit.matches => {
+                cli::report(format, Ok(audit));
+                ExitCode::FAILURE
+            }
+            result => cli::report(format, result),
+        },
+        Err(e) => cli::report::<AuditResult>(OutputFormat::Human, Err(e)),
+    }
+}
+
+async fn audit_root(args: &[String]) -> Result<AuditResult> {
+    if args.len() != 2 {
+        return Err(anyhow::anyhow!(
+            "Usage: {} [--format human|json] <subscribers.csv>",
+            args[0]
+        ));
+    }
+
+    let rpc_url =
+        env::var("SOLANA_RPC_URL").unwrap_or_else(|_| "http://localhost:8899".to_string());
+    let mut chain = SolanaClient::read_only(&rpc_url);
+    if let Ok(program_id) = env::var("PROGRAM_ID") {
+        let program_id = Pubkey::from_str(&program_id)
+            .with_context(|| format!("Invalid program ID {}", program_id))?;
+        chain = chain.with_program_id(program_id);
+    }
+
+    diagnose::audit_root(Path::new(&args[1]), &chain).await
+}
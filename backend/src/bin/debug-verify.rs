@@ -0,0 +1,59 @@
+//! Explain why a wallet does or doesn't verify against the current on-chain root.
+//!
+//! Usage: debug-verify [--format human|json] <wallet> <expiration>
+//!
+//! Rebuilds the tree from Postgres with the backend's leaf layout and prints each
+//! step: leaf hash, proof siblings, recomputed root, local and on-chain roots, and
+//! the verdict. Reads the backend's `Config`.
+
+use anyhow::{Context, Result};
+use backend::cli::{self, OutputFormat};
+use backend::config::Config;
+use backend::diagnose::{self, VerifyTrace};
+use backend::merkle::solana_client::SolanaClient;
+use backend::merkle::tree::Expiration;
+use sqlx::PgPool;
+use std::env;
+use std::process::ExitCode;
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenvy::dotenv().ok();
+    let mut args: Vec<String> = env::args().collect();
+    match OutputFormat::take_from(&mut args) {
+        Ok(format) => cli::report(format, debug_verify(&args).await),
+        Err(e) => cli::report::<VerifyTrace>(OutputFormat::Human, Err(e)),
+    }
+}
+
+async fn debug_verify(args: &[String]) -> Result<VerifyTrace> {
+    if args.len() != 3 {
+        return Err(anyhow::anyhow!(
+            "Usage: {} [--format human|json] <wallet> <expiration>",
+            args[0]
+        ));
+    }
+    let wallet = &args[1];
+    let expiration: i64 = args[2]
+        .parse()
+        .with_context(|| format!("Invalid expiration {}", args[2]))?;
+    let expiration = Expiration::new(expiration)?;
+
+    let config = Config::from_env()?;
+    let pool = PgPool::connect(&config.database_url)
+        .await
+        .context("Failed to connect to Postgres")?;
+    let chain = SolanaClient::new(&config.rpc_url, &config.authority_keypair_path)?
+        .with_program_id(config.program_id)
+        .with_chain_domain(config.chain_domain);
+
+    diagnose::debug_verify(
+        &pool,
+        &chain,
+        config.leaf_secret(),
+        config.expiration_in_leaf,
+        wallet,
+        expiration,
+    )
+    .await
+}
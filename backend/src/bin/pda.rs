@@ -0,0 +1,57 @@
+//! Print the config PDA for a program, without an RPC connection or keypair.
+//!
+//! Usage: pda [--format human|json] [program_id]
+//!
+//! Defaults to the program ID the backend is built for.
+
+use anyhow::{Context, Result};
+use backend::cli::{self, OutputFormat};
+use backend::merkle::solana_client::{derive_config_pda, PROGRAM_ID};
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use std::env;
+use std::fmt;
+use std::process::ExitCode;
+use std::str::FromStr;
+
+#[derive(Serialize)]
+struct PdaOutput {
+    program_id: String,
+    config_pda: String,
+    bump: u8,
+}
+
+impl fmt::Display for PdaOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Program ID: {}", self.program_id)?;
+        writeln!(f, "Config PDA: {}", self.config_pda)?;
+        write!(f, "Bump: {}", self.bump)
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = env::args().collect();
+    match OutputFormat::take_from(&mut args) {
+        Ok(format) => cli::report(format, pda(&args)),
+        Err(e) => cli::report::<PdaOutput>(OutputFormat::Human, Err(e)),
+    }
+}
+
+fn pda(args: &[String]) -> Result<PdaOutput> {
+    if args.len() > 2 {
+        return Err(anyhow::anyhow!(
+            "Usage: {} [--format human|json] [program_id]",
+            args[0]
+        ));
+    }
+    let program_id = args.get(1).map(String::as_str).unwrap_or(PROGRAM_ID);
+    let program_id = Pubkey::from_str(program_id)
+        .with_context(|| format!("Invalid program ID {}", program_id))?;
+
+    let (pda, bump) = derive_config_pda(&program_id);
+    Ok(PdaOutput {
+        program_id: program_id.to_string(),
+        config_pda: pda.to_string(),
+        bump,
+    })
+}
@@ -0,0 +1,136 @@
+//! Generate a proof from a JSON subscriber list, without Postgres.
+//!
+//! Usage: prove [--format human|json] <subscribers.json> <wallet>
+//!
+//! The input is a JSON array of `{ "wallet": "<base58>", "expiration": <unix_ts> }`.
+
+use anyhow::{Context, Result};
+use backend::cli::{self, OutputFormat};
+use backend::merkle::tree::{self, Expiration, SerializedProof};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::process::ExitCode;
+
+#[derive(Deserialize)]
+struct SubscriberEntry {
+    wallet: String,
+    expiration: i64,
+}
+
+#[derive(Serialize)]
+struct ProveOutput {
+    root_hex: String,
+    proof: SerializedProof,
+}
+
+impl fmt::Display for ProveOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Root Hash: {}", self.root_hex)?;
+        let proof = serde_json::to_string_pretty(&self.proof).map_err(|_| fmt::Error)?;
+        write!(f, "{}", proof)
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = env::args().collect();
+    match OutputFormat::take_from(&mut args) {
+        Ok(format) => cli::report(format, prove(&args)),
+        Err(e) => cli::report::<ProveOutput>(OutputFormat::Human, Err(e)),
+    }
+}
+
+fn prove(args: &[String]) -> Result<ProveOutput> {
+    if args.len() != 3 {
+        return Err(anyhow::anyhow!(
+            "Usage: {} [--format human|json] <subscribers.json> <wallet>",
+            args[0]
+        ));
+    }
+    let (input_path, wallet) = (&args[1], &args[2]);
+
+    let raw =
+        fs::read_to_string(input_path).with_context(|| format!("Failed to read {}", input_path))?;
+    let entries: Vec<SubscriberEntry> =
+        serde_json::from_str(&raw).context("Subscriber list must be a JSON array")?;
+
+    let subscribers = entries
+        .into_iter()
+        .map(|entry| Ok((entry.wallet, Expiration::new(entry.expiration)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let (root_hash, merkle_tree, subscriber_data) = tree::build_tree_from_subscribers(subscribers)?;
+
+    let (proof_bytes, index) = tree::get_proof_for_user(&merkle_tree, &subscriber_data, wallet)
+        .ok_or_else(|| anyhow::anyhow!("Wallet {} is not in the subscriber list", wallet))?;
+
+    let proof = SerializedProof {
+        wallet: wallet.clone(),
+        expiration: subscriber_data[index].1.as_i64(),
+        proof_hex: hex::encode(proof_bytes),
+        leaf_index: index,
+        total_leaves: subscriber_data.len(),
+        leaf_salt: None,
+        valid_as_of_root: root_hash.clone(),
+        estimated_stale_after: None,
+    };
+
+    Ok(ProveOutput {
+        root_hex: root_hash,
+        proof,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn json_output_carries_a_verifiable_proof() -> Result<()> {
+        let path = env::temp_dir().join(format!("prove-subscribers-{}.json", std::process::id()));
+        let wallet = "11111111111111111111111111111111";
+        fs::write(
+            &path,
+            json!([
+                { "wallet": wallet, "expiration": 1_700_000_000 },
+                { "wallet": "AHpuc2M3wbZceufaiE4Q2wyDXh198ymB1SxxpbxCzj3H", "expiration": 1_800_000_000 },
+            ])
+            .to_string(),
+        )?;
+        let args = |wallet: &str| {
+            vec![
+                "prove".to_string(),
+                path.to_string_lossy().into_owned(),
+                wallet.to_string(),
+            ]
+        };
+
+        let output: Value =
+            serde_json::from_str(&cli::render(OutputFormat::Json, &prove(&args(wallet))))?;
+        let root_hex = output["root_hex"].as_str().unwrap();
+        let proof: SerializedProof = serde_json::from_value(output["proof"].clone())?;
+        assert_eq!(proof.valid_as_of_root, root_hex);
+        assert!(tree::verify_subscription(
+            root_hex,
+            &hex::decode(&proof.proof_hex)?,
+            wallet,
+            Expiration::new(proof.expiration)?,
+            proof.leaf_index,
+            proof.total_leaves,
+        )?);
+
+        let missing = Pubkey::new_unique().to_string();
+        let output: Value =
+            serde_json::from_str(&cli::render(OutputFormat::Json, &prove(&args(&missing))))?;
+        assert!(output["error"]
+            .as_str()
+            .unwrap()
+            .contains("is not in the subscriber list"));
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+//! Output shared by the command-line tools in `src/bin`: every tool takes a global
+//! `--format human|json` flag, and reports its result (or error) in that format.
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::json;
+use std::fmt::Display;
+use std::process::ExitCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Decorated text for people (the default)
+    #[default]
+    Human,
+    /// One JSON document on stdout, for scripts
+    Json,
+}
+
+impl OutputFormat {
+    /// Remove `--format <human|json>` (or `--format=<...>`) from `args`, wherever it
+    /// appears, leaving the positional arguments
+    pub fn take_from(args: &mut Vec<String>) -> Result<Self> {
+        let Some(position) = args
+            .iter()
+            .position(|arg| arg == "--format" || arg.starts_with("--format="))
+        else {
+            return Ok(Self::Human);
+        };
+        let flag = args.remove(position);
+        let value = match flag.strip_prefix("--format=") {
+            Some(value) => value.to_string(),
+            None if position < args.len() => args.remove(position),
+            None => return Err(anyhow::anyhow!("--format needs a value: human or json")),
+        };
+        match value.as_str() {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            other => Err(anyhow::anyhow!(
+                "Unknown format {:?}; expected human or json",
+                other
+            )),
+        }
+    }
+}
+
+/// `result` as it's printed in `format`. Errors become `{ "error": ... }` in JSON.
+pub fn render<T: Serialize + Display>(format: OutputFormat, result: &Result<T>) -> String {
+    match (format, result) {
+        (OutputFormat::Human, Ok(output)) => output.to_string(),
+        (OutputFormat::Human, Err(e)) => format!("Error: {:#}", e),
+        (OutputFormat::Json, Ok(output)) => serde_json::to_string_pretty(output)
+            .unwrap_or_else(|e| json!({ "error": e.to_string() }).to_string()),
+        (OutputFormat::Json, Err(e)) => json!({ "error": format!("{:#}", e) }).to_string(),
+    }
+}
+
+/// Print `result` and turn it into the process exit code. JSON always goes to
+/// stdout so pipelines get a parseable document either way; human errors go to stderr.
+pub fn report<T: Serialize + Display>(format: OutputFormat, result: Result<T>) -> ExitCode {
+    let rendered = render(format, &result);
+    match (&result, format) {
+        (Err(_), OutputFormat::Human) => eprintln!("{}", rendered),
+        _ => println!("{}", rendered),
+    }
+    if result.is_ok() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn format_flag_is_taken_from_anywhere_in_the_args() -> Result<()> {
+        let mut plain = args(&["prove", "subs.json", "wallet"]);
+        assert_eq!(OutputFormat::take_from(&mut plain)?, OutputFormat::Human);
+        assert_eq!(plain, args(&["prove", "subs.json", "wallet"]));
+
+        let mut spaced = args(&["prove", "--format", "json", "subs.json", "wallet"]);
+        assert_eq!(OutputFormat::take_from(&mut spaced)?, OutputFormat::Json);
+        assert_eq!(spaced, args(&["prove", "subs.json", "wallet"]));
+
+        let mut joined = args(&["prove", "subs.json", "wallet", "--format=json"]);
+        assert_eq!(OutputFormat::take_from(&mut joined)?, OutputFormat::Json);
+        assert_eq!(joined, args(&["prove", "subs.json", "wallet"]));
+
+        assert!(OutputFormat::take_from(&mut args(&["prove", "--format"])).is_err());
+        assert!(OutputFormat::take_from(&mut args(&["prove", "--format=yaml"])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn json_errors_are_reported_as_an_error_object() {
+        let result: Result<String> = Err(anyhow::anyhow!("Wallet is not in the list"));
+        let rendered: Value = serde_json::from_str(&render(OutputFormat::Json, &result)).unwrap();
+        assert_eq!(rendered, json!({ "error": "Wallet is not in the list" }));
+        assert_eq!(
+            render(OutputFormat::Human, &result),
+            "Error: Wallet is not in the list"
+        );
+    }
+}
@@ -0,0 +1,476 @@
+use anyhow::{Context, Result};
+use solana_client::rpc_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use sqlx::postgres::{PgConnectOptions, PgSslMode};
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::merkle::solana_client::{chain_domain_from_name, keypair_from_base58, PROGRAM_ID};
+
+/// Every backend setting, read and validated once at startup (see the `.env` example
+/// in the README), feature switches included. Nothing else in the service reads the
+/// environment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// `DATABASE_URL`, required
+    pub database_url: String,
+    /// `DATABASE_READ_URL`: replica for tree builds; unset reads from the primary
+    pub database_read_url: Option<String>,
+    /// `SOLANA_RPC_URL`
+    pub rpc_url: String,
+    /// `PROGRAM_ID`, defaulting to the deployed program
+    pub program_id: Pubkey,
+    /// `SOLANA_KEYPAIR_PATH`
+    pub authority_keypair_path: String,
+    /// `SYNC_INTERVAL_SECS`, at least 1
+    pub sync_interval_secs: u64,
+    /// `SOLANA_COMMITMENT`: `processed`, `confirmed` or `finalized`
+    pub commitment: CommitmentConfig,
+    /// `DB_MAX_CONNECTIONS`, at least 1
+    pub db_max_connections: u32,
+    /// `RPC_TIMEOUT_SECS`: deadline for each RPC call, at least 1
+    pub rpc_timeout_secs: u64,
+    /// `DB_SSL_MODE` (`disable` … `verify-full`), overriding any `sslmode` in the
+    /// database URLs; unset leaves the URLs' own (default `prefer`)
+    pub db_ssl_mode: Option<String>,
+    /// `DB_SSL_ROOT_CERT`: CA certificate checked under `verify-ca`/`verify-full`
+    pub db_ssl_root_cert: Option<String>,
+    /// `EXPIRATION_IN_LEAF`: `false` builds static allowlist leaves without
+    /// expirations (must match the on-chain config)
+    pub expiration_in_leaf: bool,
+    /// `DROP_EXPIRED_LEAVES`: leave expired subscribers out of synced trees and run
+    /// the expiration sweeper
+    pub drop_expired_leaves: bool,
+    /// `SWEEP_INTERVAL_SECS`: longest the expiration sweeper sleeps between checks,
+    /// at least 1
+    pub sweep_interval_secs: u64,
+    /// `CHAIN_DOMAIN`: tag of the cluster name roots are bound to; unset is all zeros
+    pub chain_domain: [u8; 8],
+    /// `AUTHORITY_SECRET_KEY`: base58 keypair used instead of `SOLANA_KEYPAIR_PATH`
+    pub authority_secret_key: Option<Secret<String>>,
+    /// `LEAF_SALT_SECRET`: 64 hex chars; unset builds unsalted leaves
+    pub leaf_salt_secret: Option<Secret<[u8; 32]>>,
+    /// `API_BIND_ADDR`: serve HTTP and keep syncing there; unset runs one sync and exits
+    pub api_bind_addr: Option<String>,
+    /// `JWT_SESSION_SECS`: lifetime of `POST /verify` session tokens, at least 1
+    pub jwt_session_secs: i64,
+    /// `JWT_ES256_KEY_PATH`: PEM key signing session tokens, preferred over HS256
+    pub jwt_es256_key_path: Option<String>,
+    /// `JWT_HS256_SECRET`: shared secret signing session tokens without an ES256 key
+    pub jwt_hs256_secret: Option<Secret<String>>,
+}
+
+/// A setting kept out of `Debug` output and error messages
+#[derive(Clone, PartialEq)]
+pub struct Secret<T>(pub T);
+
+impl<T> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+impl Config {
+    /// Load from the process environment
+    pub fn from_env() -> Result<Self> {
+        Self::from_lookup(|key| env::var(key).ok())
+    }
+
+    /// Load through `lookup` (variable name to value), reporting every missing or
+    /// invalid variable in one error rather than stopping at the first
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut problems = Vec::new();
+        let mut raw = |key: &str, default: Option<&str>| {
+            let value = lookup(key)
+                .filter(|value| !value.is_empty())
+                .or(default.map(str::to_string));
+            if value.is_none() {
+                problems.push(format!("{} is missing", key));
+            }
+            value
+        };
+
+        let database_url = raw("DATABASE_URL", None);
+        let rpc_url = raw("SOLANA_RPC_URL", Some("http://localhost:8899"));
+        let program_id = raw("PROGRAM_ID", Some(PROGRAM_ID));
+        let authority_keypair_path = raw("SOLANA_KEYPAIR_PATH", Some("./backend-authority.json"));
+        let sync_interval_secs = raw("SYNC_INTERVAL_SECS", Some("60"));
+        let commitment = raw("SOLANA_COMMITMENT", Some("confirmed"));
+        let db_max_connections = raw("DB_MAX_CONNECTIONS", Some("5"));
+        let rpc_timeout_secs = raw("RPC_TIMEOUT_SECS", Some("30"));
+        let expiration_in_leaf = raw("EXPIRATION_IN_LEAF", Some("true"));
+        let drop_expired_leaves = raw("DROP_EXPIRED_LEAVES", Some("false"));
+        let sweep_interval_secs = raw("SWEEP_INTERVAL_SECS", Some("60"));
+        let jwt_session_secs = raw("JWT_SESSION_SECS", Some("3600"));
+        let database_read_url = lookup("DATABASE_READ_URL").filter(|url| !url.is_empty());
+        let db_ssl_mode = lookup("DB_SSL_MODE").filter(|mode| !mode.is_empty());
+        let db_ssl_root_cert = lookup("DB_SSL_ROOT_CERT").filter(|path| !path.is_empty());
+        let chain_domain = lookup("CHAIN_DOMAIN")
+            .filter(|name| !name.is_empty())
+            .map_or([0u8; 8], |name| chain_domain_from_name(&name));
+        let authority_secret_key = lookup("AUTHORITY_SECRET_KEY")
+            .filter(|secret| !secret.is_empty())
+            .and_then(|secret| {
+                parsed_secret(&mut problems, "AUTHORITY_SECRET_KEY", &secret, |secret| {
+                    keypair_from_base58(secret).ok().map(|_| secret.to_string())
+                })
+            })
+            .map(Secret);
+        let leaf_salt_secret = lookup("LEAF_SALT_SECRET")
+            .filter(|secret| !secret.is_empty())
+            .and_then(|secret| {
+                parsed_secret(&mut problems, "LEAF_SALT_SECRET", &secret, |secret| {
+                    hex::decode(secret).ok()?.try_into().ok()
+                })
+            })
+            .map(Secret);
+        let api_bind_addr = lookup("API_BIND_ADDR").filter(|addr| !addr.is_empty());
+        let jwt_es256_key_path = lookup("JWT_ES256_KEY_PATH").filter(|path| !path.is_empty());
+        let jwt_hs256_secret = lookup("JWT_HS256_SECRET")
+            .filter(|secret| !secret.is_empty())
+            .map(Secret);
+
+        let program_id = program_id.and_then(|id| {
+            parsed(&mut problems, "PROGRAM_ID", &id, |id| {
+                Pubkey::from_str(id).ok()
+            })
+        });
+        let sync_interval_secs = sync_interval_secs.and_then(|secs| {
+            parsed(&mut problems, "SYNC_INTERVAL_SECS", &secs, |secs| {
+                secs.parse::<u64>().ok().filter(|&secs| secs > 0)
+            })
+        });
+        let commitment = commitment
+            .and_then(|level| parsed(&mut problems, "SOLANA_COMMITMENT", &level, parse_commitment));
+        let db_max_connections = db_max_connections.and_then(|max| {
+            parsed(&mut problems, "DB_MAX_CONNECTIONS", &max, |max| {
+                max.parse::<u32>().ok().filter(|&max| max > 0)
+            })
+        });
+        let rpc_timeout_secs = rpc_timeout_secs.and_then(|secs| {
+            parsed(&mut problems, "RPC_TIMEOUT_SECS", &secs, |secs| {
+                secs.parse::<u64>().ok().filter(|&secs| secs > 0)
+            })
+        });
+        let expiration_in_leaf = expiration_in_leaf
+            .and_then(|flag| parsed(&mut problems, "EXPIRATION_IN_LEAF", &flag, parse_bool));
+        let drop_expired_leaves = drop_expired_leaves
+            .and_then(|flag| parsed(&mut problems, "DROP_EXPIRED_LEAVES", &flag, parse_bool));
+        let sweep_interval_secs = sweep_interval_secs.and_then(|secs| {
+            parsed(&mut problems, "SWEEP_INTERVAL_SECS", &secs, |secs| {
+                secs.parse::<u64>().ok().filter(|&secs| secs > 0)
+            })
+        });
+
+        let jwt_session_secs = jwt_session_secs.and_then(|secs| {
+            parsed(&mut problems, "JWT_SESSION_SECS", &secs, |secs| {
+                secs.parse::<i64>().ok().filter(|&secs| secs > 0)
+            })
+        });
+
+        if let Some(mode) = &db_ssl_mode {
+            parsed(&mut problems, "DB_SSL_MODE", mode, |mode| {
+                PgSslMode::from_str(mode).ok()
+            });
+        }
+
+        let invalid = |problems: &[String]| {
+            anyhow::anyhow!("Invalid configuration:\n  - {}", problems.join("\n  - "))
+        };
+        let (
+            Some(database_url),
+            Some(rpc_url),
+            Some(program_id),
+            Some(authority_keypair_path),
+            Some(sync_interval_secs),
+            Some(commitment),
+            Some(db_max_connections),
+            Some(rpc_timeout_secs),
+            Some(expiration_in_leaf),
+            Some(drop_expired_leaves),
+            Some(sweep_interval_secs),
+            Some(jwt_session_secs),
+        ) = (
+            database_url,
+            rpc_url,
+            program_id,
+            authority_keypair_path,
+            sync_interval_secs,
+            commitment,
+            db_max_connections,
+            rpc_timeout_secs,
+            expiration_in_leaf,
+            drop_expired_leaves,
+            sweep_interval_secs,
+            jwt_session_secs,
+        )
+        else {
+            return Err(invalid(&problems));
+        };
+        // Optional settings that were set but invalid
+        if !problems.is_empty() {
+            return Err(invalid(&problems));
+        }
+        Ok(Self {
+            database_url,
+            database_read_url,
+            rpc_url,
+            program_id,
+            authority_keypair_path,
+            sync_interval_secs,
+            commitment,
+            db_max_connections,
+            rpc_timeout_secs,
+            db_ssl_mode,
+            db_ssl_root_cert,
+            expiration_in_leaf,
+            drop_expired_leaves,
+            sweep_interval_secs,
+            chain_domain,
+            authority_secret_key,
+            leaf_salt_secret,
+            api_bind_addr,
+            jwt_session_secs,
+            jwt_es256_key_path,
+            jwt_hs256_secret,
+        })
+    }
+
+    /// Connect options for `database_url` with the configured TLS settings applied
+    pub fn db_connect_options(&self, database_url: &str) -> Result<PgConnectOptions> {
+        let mut options =
+            PgConnectOptions::from_str(database_url).context("Invalid database URL")?;
+        if let Some(mode) = &self.db_ssl_mode {
+            options = options.ssl_mode(PgSslMode::from_str(mode)?);
+        }
+        if let Some(path) = &self.db_ssl_root_cert {
+            options = options.ssl_root_cert(path.as_str());
+        }
+        Ok(options)
+    }
+
+    pub fn sync_interval(&self) -> Duration {
+        Duration::from_secs(self.sync_interval_secs)
+    }
+
+    pub fn rpc_timeout(&self) -> Duration {
+        Duration::from_secs(self.rpc_timeout_secs)
+    }
+
+    pub fn sweep_interval(&self) -> Duration {
+        Duration::from_secs(self.sweep_interval_secs)
+    }
+
+    pub fn leaf_secret(&self) -> Option<&[u8; 32]> {
+        self.leaf_salt_secret.as_ref().map(|secret| &secret.0)
+    }
+}
+
+/// `raw` run through `parse`, noting in `problems` when it isn't a valid `key`
+fn parsed<T>(
+    problems: &mut Vec<String>,
+    key: &str,
+    raw: &str,
+    parse: impl FnOnce(&str) -> Option<T>,
+) -> Option<T> {
+    let value = parse(raw);
+    if value.is_none() {
+        problems.push(format!("{} is invalid: {:?}", key, raw));
+    }
+    value
+}
+
+/// `parsed` for secrets, which are never echoed into the error
+fn parsed_secret<T>(
+    problems: &mut Vec<String>,
+    key: &str,
+    raw: &str,
+    parse: impl FnOnce(&str) -> Option<T>,
+) -> Option<T> {
+    let value = parse(raw);
+    if value.is_none() {
+        problems.push(format!("{} is invalid", key));
+    }
+    value
+}
+
+/// Boolean switches are spelled out, so a typo is reported rather than read as off
+pub fn parse_bool(flag: &str) -> Option<bool> {
+    match flag {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_commitment(level: &str) -> Option<CommitmentConfig> {
+    match level {
+        "processed" => Some(CommitmentConfig::processed()),
+        "confirmed" => Some(CommitmentConfig::confirmed()),
+        "finalized" => Some(CommitmentConfig::finalized()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::Keypair;
+    use std::collections::HashMap;
+
+    fn load(vars: &[(&str, &str)]) -> Result<Config> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        Config::from_lookup(|key| vars.get(key).cloned())
+    }
+
+    #[test]
+    fn loads_a_complete_config() -> Result<()> {
+        let program_id = Pubkey::new_unique();
+        let authority_secret_key = Keypair::new().to_base58_string();
+        let config = load(&[
+            ("DATABASE_URL", "postgresql://localhost/merkle_subscription"),
+            ("SOLANA_RPC_URL", "https://api.devnet.solana.com"),
+            ("PROGRAM_ID", &program_id.to_string()),
+            ("SOLANA_KEYPAIR_PATH", "/secrets/authority.json"),
+            ("SYNC_INTERVAL_SECS", "30"),
+            ("SOLANA_COMMITMENT", "finalized"),
+            ("DB_MAX_CONNECTIONS", "20"),
+            ("RPC_TIMEOUT_SECS", "10"),
+            ("EXPIRATION_IN_LEAF", "false"),
+            ("DROP_EXPIRED_LEAVES", "true"),
+            ("SWEEP_INTERVAL_SECS", "15"),
+            ("CHAIN_DOMAIN", "devnet"),
+            ("AUTHORITY_SECRET_KEY", &authority_secret_key),
+            ("LEAF_SALT_SECRET", &"ab".repeat(32)),
+            ("API_BIND_ADDR", "0.0.0.0:3000"),
+            ("JWT_SESSION_SECS", "900"),
+            ("JWT_ES256_KEY_PATH", "./session-es256.pem"),
+            ("JWT_HS256_SECRET", "shared-secret"),
+        ])?;
+
+        assert_eq!(
+            config,
+            Config {
+                database_url: "postgresql://localhost/merkle_subscription".to_string(),
+                database_read_url: None,
+                rpc_url: "https://api.devnet.solana.com".to_string(),
+                program_id,
+                authority_keypair_path: "/secrets/authority.json".to_string(),
+                sync_interval_secs: 30,
+                commitment: CommitmentConfig::finalized(),
+                db_max_connections: 20,
+                rpc_timeout_secs: 10,
+                db_ssl_mode: None,
+                db_ssl_root_cert: None,
+                expiration_in_leaf: false,
+                drop_expired_leaves: true,
+                sweep_interval_secs: 15,
+                chain_domain: chain_domain_from_name("devnet"),
+                authority_secret_key: Some(Secret(authority_secret_key)),
+                leaf_salt_secret: Some(Secret([0xab; 32])),
+                api_bind_addr: Some("0.0.0.0:3000".to_string()),
+                jwt_session_secs: 900,
+                jwt_es256_key_path: Some("./session-es256.pem".to_string()),
+                jwt_hs256_secret: Some(Secret("shared-secret".to_string())),
+            }
+        );
+
+        // Everything but the database has a default
+        let config = load(&[("DATABASE_URL", "postgresql://localhost/db")])?;
+        assert_eq!(config.program_id, Pubkey::from_str(PROGRAM_ID)?);
+        assert_eq!(config.commitment, CommitmentConfig::confirmed());
+        assert_eq!(config.sync_interval(), Duration::from_secs(60));
+        assert_eq!(config.rpc_timeout(), Duration::from_secs(30));
+        assert!(config.expiration_in_leaf);
+        assert!(!config.drop_expired_leaves);
+        assert_eq!(config.sweep_interval(), Duration::from_secs(60));
+        assert_eq!(config.chain_domain, [0u8; 8]);
+        assert_eq!(config.leaf_secret(), None);
+        assert_eq!(config.api_bind_addr, None);
+        assert_eq!(config.jwt_session_secs, 3600);
+        assert_eq!(config.jwt_es256_key_path, None);
+        assert_eq!(config.jwt_hs256_secret, None);
+        Ok(())
+    }
+
+    #[test]
+    fn db_connect_options_apply_the_configured_ssl_mode() -> Result<()> {
+        let url = "postgresql://localhost/merkle_subscription?sslmode=disable";
+
+        // Unset keeps whatever the URL asks for, for local development
+        let config = load(&[("DATABASE_URL", url)])?;
+        let options = config.db_connect_options(url)?;
+        assert!(matches!(options.get_ssl_mode(), PgSslMode::Disable));
+
+        let config = load(&[("DATABASE_URL", url), ("DB_SSL_MODE", "require")])?;
+        let options = config.db_connect_options(url)?;
+        assert!(matches!(options.get_ssl_mode(), PgSslMode::Require));
+
+        let config = load(&[
+            ("DATABASE_URL", url),
+            ("DB_SSL_MODE", "verify-full"),
+            ("DB_SSL_ROOT_CERT", "/etc/ssl/rds-ca.pem"),
+        ])?;
+        assert_eq!(
+            config.db_ssl_root_cert.as_deref(),
+            Some("/etc/ssl/rds-ca.pem")
+        );
+        let options = config.db_connect_options(url)?;
+        assert!(matches!(options.get_ssl_mode(), PgSslMode::VerifyFull));
+
+        assert!(load(&[("DATABASE_URL", url), ("DB_SSL_MODE", "always")]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn reports_every_missing_and_invalid_field_at_once() {
+        let err = load(&[
+            ("PROGRAM_ID", "not-a-pubkey"),
+            ("SYNC_INTERVAL_SECS", "0"),
+            ("SOLANA_COMMITMENT", "eventually"),
+            ("DROP_EXPIRED_LEAVES", "yes"),
+            ("SWEEP_INTERVAL_SECS", "0"),
+            ("JWT_SESSION_SECS", "-1"),
+        ])
+        .unwrap_err()
+        .to_string();
+
+        for key in [
+            "DATABASE_URL is missing",
+            "PROGRAM_ID",
+            "SYNC_INTERVAL_SECS",
+            "SOLANA_COMMITMENT",
+            "DROP_EXPIRED_LEAVES",
+            "SWEEP_INTERVAL_SECS",
+            "JWT_SESSION_SECS",
+        ] {
+            assert!(err.contains(key), "{} not reported in {}", key, err);
+        }
+        assert!(!err.contains("DB_MAX_CONNECTIONS"));
+        assert!(!err.contains("EXPIRATION_IN_LEAF"));
+    }
+
+    #[test]
+    fn invalid_optional_settings_fail_without_echoing_secrets() {
+        let secret = "cd".repeat(31);
+        let err = load(&[
+            ("DATABASE_URL", "postgresql://localhost/db"),
+            ("LEAF_SALT_SECRET", &secret),
+            ("AUTHORITY_SECRET_KEY", "not-a-keypair"),
+            ("JWT_HS256_SECRET", "shared-secret"),
+        ])
+        .unwrap_err()
+        .to_string();
+
+        assert!(err.contains("LEAF_SALT_SECRET is invalid"), "{}", err);
+        assert!(err.contains("AUTHORITY_SECRET_KEY is invalid"), "{}", err);
+        assert!(!err.contains(&secret) && !err.contains("not-a-keypair"));
+        assert!(!err.contains("shared-secret"));
+        assert_eq!(format!("{:?}", Secret("hunter2")), "<redacted>");
+    }
+}
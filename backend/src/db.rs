@@ -0,0 +1,80 @@
+use anyhow::{Context, Result};
+use sqlx::migrate::Migrator;
+use sqlx::PgPool;
+
+/// Pools by role: `write` is the primary, which every write and migration goes to;
+/// `read` serves the heavy reads such as tree builds and may be a read replica.
+/// A lagging replica only delays a subscriber change by a sync pass.
+#[derive(Clone)]
+pub struct AppPools {
+    pub write: PgPool,
+    pub read: PgPool,
+}
+
+impl AppPools {
+    pub fn new(write: PgPool, read: PgPool) -> Self {
+        Self { write, read }
+    }
+
+    /// Both roles on one pool, for deployments without a replica
+    pub fn single(pool: PgPool) -> Self {
+        Self::new(pool.clone(), pool)
+    }
+}
+
+/// SQL migrations from `backend/migrations`, embedded at compile time
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Apply any pending migrations.
+///
+/// Safe to call on every startup: applied versions are tracked in
+/// `_sqlx_migrations` and skipped.
+pub async fn run_migrations(pool: &PgPool) -> Result<()> {
+    MIGRATOR
+        .run(pool)
+        .await
+        .context("Failed to run database migrations")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::tree;
+    use crate::test_fixtures;
+    use sqlx::postgres::PgPoolOptions;
+
+    #[sqlx::test(migrations = false)]
+    async fn migrations_bootstrap_empty_db(pool: PgPool) -> Result<()> {
+        run_migrations(&pool).await?;
+        run_migrations(&pool).await?;
+
+        test_fixtures::seed_subscribers(&pool).await?;
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM subscriber_storage")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(count, test_fixtures::SUBSCRIBERS.len() as i64);
+
+        sqlx::query("SELECT id, root_hash FROM merkle_state")
+            .fetch_all(&pool)
+            .await?;
+        sqlx::query("SELECT wallet, root_hex FROM subscriber_proofs")
+            .fetch_all(&pool)
+            .await?;
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn tree_builds_read_from_the_read_pool(pool: PgPool) -> Result<()> {
+        // A separately connected pool stands in for a replica of the same database
+        let replica = PgPoolOptions::new()
+            .max_connections(1)
+            .connect_with((*pool.connect_options()).clone())
+            .await?;
+        let pools = AppPools::new(pool, replica);
+
+        let expected_root = test_fixtures::seed_subscribers(&pools.write).await?;
+        let (root_hash, _, _) = tree::build_tree_from_db(&pools.read).await?;
+        assert_eq!(root_hash, expected_root);
+        Ok(())
+    }
+}
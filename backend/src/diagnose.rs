@@ -0,0 +1,700 @@
+use crate::merkle::chain::ChainClient;
+use crate::merkle::tree::{
+    self, DoubleSha256Hasher, Expiration, HashAlgo, Keccak256Hasher, MerkleRoot, Sha256Hasher,
+};
+use crate::server::{self, TreeSnapshot, VerificationOutcome};
+use anyhow::{Context, Result};
+use chrono::Utc;
+use rs_merkle::{Hasher, MerkleTree};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::fmt;
+use std::path::Path;
+
+/// Result of one startup check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckStatus {
+    Passed,
+    Failed(String),
+    /// Not run because a check it depends on failed
+    Skipped,
+}
+
+impl CheckStatus {
+    fn from_result<T>(result: anyhow::Result<T>) -> (Self, Option<T>) {
+        match result {
+            Ok(value) => (CheckStatus::Passed, Some(value)),
+            Err(e) => (CheckStatus::Failed(format!("{:#}", e)), None),
+        }
+    }
+
+    fn failed_unless(ok: bool, reason: impl FnOnce() -> String) -> Self {
+        if ok {
+            CheckStatus::Passed
+        } else {
+            CheckStatus::Failed(reason())
+        }
+    }
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Passed => write!(f, "✅ ok"),
+            CheckStatus::Failed(reason) => write!(f, "❌ {}", reason),
+            CheckStatus::Skipped => write!(f, "⏭️  skipped"),
+        }
+    }
+}
+
+/// Every stage between the database and the on-chain root, checked in pipeline order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StartupDiagnosis {
+    pub db_reachable: CheckStatus,
+    pub has_subscribers: CheckStatus,
+    pub local_root: CheckStatus,
+    pub program_deployed: CheckStatus,
+    pub config_exists: CheckStatus,
+    pub root_matches: CheckStatus,
+    pub authority_matches: CheckStatus,
+}
+
+impl StartupDiagnosis {
+    fn checks(&self) -> [(&'static str, &CheckStatus); 7] {
+        [
+            ("Database reachable", &self.db_reachable),
+            ("Subscribers present", &self.has_subscribers),
+            ("Local root computed", &self.local_root),
+            ("Program deployed", &self.program_deployed),
+            ("On-chain config exists", &self.config_exists),
+            ("On-chain root matches", &self.root_matches),
+            ("Authority matches keypair", &self.authority_matches),
+        ]
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.checks()
+            .iter()
+            .all(|(_, status)| **status == CheckStatus::Passed)
+    }
+}
+
+impl fmt::Display for StartupDiagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, status) in self.checks() {
+            writeln!(f, "   {:<28}{}", name, status)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every check and report each one, so a root divergence at startup points at
+/// its cause (unreachable DB, empty subscriber set, wrong cluster or program ID, stale
+/// on-chain root, wrong keypair) instead of just failing.
+///
+/// The local root is built with the same layout the backend syncs with. A root
+/// mismatch is normal right before a sync pushes the new root.
+pub async fn diagnose_startup(
+    pool: &PgPool,
+    chain: &dyn ChainClient,
+    leaf_secret: Option<&[u8; 32]>,
+    expiration_in_leaf: bool,
+) -> StartupDiagnosis {
+    let (db_reachable, _) = CheckStatus::from_result(
+        sqlx::query("SELECT 1")
+            .execute(pool)
+            .await
+            .map_err(anyhow::Error::from),
+    );
+
+    let mut has_subscribers = CheckStatus::Skipped;
+    let mut local_root = CheckStatus::Skipped;
+    let mut local_root_hex = None;
+    if db_reachable == CheckStatus::Passed {
+        let count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM subscriber_storage")
+            .fetch_one(pool)
+            .await;
+        match count {
+            Ok(0) => {
+                has_subscribers = CheckStatus::Failed("No subscribers in the database".to_string())
+            }
+            Ok(_) => {
+                has_subscribers = CheckStatus::Passed;
+                (local_root, local_root_hex) = CheckStatus::from_result(
+                    tree::build_tree_from_db_with_layout(pool, leaf_secret, expiration_in_leaf)
+                        .await
+                        .map(|(root_hash, _, _)| root_hash),
+                );
+            }
+            Err(e) => has_subscribers = CheckStatus::Failed(e.to_string()),
+        }
+    }
+
+    let (program_deployed, _) = CheckStatus::from_result(chain.verify_program_deployed().await);
+    let (config_exists, config) = CheckStatus::from_result(chain.fetch_config().await);
+
+    let root_matches = match (&config, &local_root_hex) {
+        (Some(config), _) if config.merkle_root == tree::UNSET_ROOT => {
+            CheckStatus::Failed("on-chain root is not set".to_string())
+        }
+        (Some(config), Some(local_root_hex)) => {
+            let on_chain = hex::encode(config.merkle_root);
+            CheckStatus::failed_unless(on_chain == *local_root_hex, || {
+                format!("on-chain {} != local {}", on_chain, local_root_hex)
+            })
+        }
+        _ => CheckStatus::Skipped,
+    };
+
+    let authority_matches = match &config {
+        Some(config) => {
+            let authority = chain.authority();
+            let can_sign = config.authority == authority || config.authorities.contains(&authority);
+            CheckStatus::failed_unless(can_sign, || {
+                format!(
+                    "keypair {} is not an authority of the config (authority {})",
+                    authority, config.authority
+                )
+            })
+        }
+        None => CheckStatus::Skipped,
+    };
+
+    StartupDiagnosis {
+        db_reachable,
+        has_subscribers,
+        local_root,
+        program_deployed,
+        config_exists,
+        root_matches,
+        authority_matches,
+    }
+}
+
+/// Why a `VerifyTrace` passed or failed, most fundamental cause first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub enum TraceVerdict {
+    Valid,
+    /// The wallet has no leaf in the tree at all
+    NotInTree,
+    /// The wallet is in the tree, but with a different expiration than the one given
+    ExpirationMismatch {
+        in_tree: i64,
+    },
+    /// The wallet's proof doesn't verify against the stored root
+    Failed {
+        outcome: VerificationOutcome,
+    },
+}
+
+/// Every intermediate value of one wallet's verification, for support debugging
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VerifyTrace {
+    pub wallet: String,
+    pub expiration: i64,
+    /// Leaf the program would rebuild from this wallet and expiration
+    pub leaf_hex: String,
+    pub leaf_index: Option<usize>,
+    pub total_leaves: usize,
+    pub proof_siblings: Vec<String>,
+    /// Root the proof leads to from `leaf_hex`
+    pub recomputed_root: Option<String>,
+    pub local_root: String,
+    /// On-chain root; `None` if it couldn't be fetched, in which case the local root
+    /// stands in for it
+    pub stored_root: Option<String>,
+    pub verdict: TraceVerdict,
+}
+
+impl fmt::Display for VerifyTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let or_none = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+        writeln!(f, "   Wallet:          {}", self.wallet)?;
+        writeln!(f, "   Expiration:      {}", self.expiration)?;
+        writeln!(f, "   Leaf:            {}", self.leaf_hex)?;
+        match self.leaf_index {
+            Some(index) => writeln!(f, "   Leaf index:      {} of {}", index, self.total_leaves)?,
+            None => writeln!(
+                f,
+                "   Leaf index:      not in tree ({} leaves)",
+                self.total_leaves
+            )?,
+        }
+        for (level, sibling) in self.proof_siblings.iter().enumerate() {
+            writeln!(f, "   Sibling {:<8} {}", level, sibling)?;
+        }
+        writeln!(f, "   Recomputed root: {}", or_none(&self.recomputed_root))?;
+        writeln!(f, "   Local root:      {}", self.local_root)?;
+        writeln!(f, "   Stored root:     {}", or_none(&self.stored_root))?;
+        match &self.verdict {
+            TraceVerdict::Valid => writeln!(f, "   Result:          ✅ valid"),
+            TraceVerdict::NotInTree => {
+                writeln!(f, "   Result:          ❌ wallet is not in the tree")
+            }
+            TraceVerdict::ExpirationMismatch { in_tree } => writeln!(
+                f,
+                "   Result:          ❌ tree has expiration {} for this wallet",
+                in_tree
+            ),
+            TraceVerdict::Failed { outcome } => {
+                writeln!(f, "   Result:          ❌ {:?}", outcome)
+            }
+        }
+    }
+}
+
+/// Locate `wallet` in `snapshot`, build its proof, and verify it against
+/// `stored_root` (the local root if `None`) the way the program would, recording each
+/// step. The leaf uses the given salt secret and layout.
+pub fn trace_verification(
+    snapshot: &TreeSnapshot,
+    leaf_secret: Option<&[u8; 32]>,
+    expiration_in_leaf: bool,
+    stored_root: Option<[u8; 32]>,
+    wallet: &str,
+    expiration: Expiration,
+    now_ts: i64,
+) -> Result<VerifyTrace> {
+    trace_verification_with::<Sha256Hasher>(
+        (&snapshot.root_hex, &snapshot.tree, &snapshot.subscribers),
+        leaf_secret,
+        expiration_in_leaf,
+        stored_root,
+        wallet,
+        expiration,
+        now_ts,
+    )
+}
+
+/// Same as `trace_verification`, for a `(root_hex, tree, subscribers)` built under an
+/// arbitrary hasher, which hashes the leaf and proof as well
+pub fn trace_verification_with<H: Hasher<Hash = [u8; 32]>>(
+    (root_hex, merkle_tree, subscribers): (&str, &MerkleTree<H>, &[(String, Expiration)]),
+    leaf_secret: Option<&[u8; 32]>,
+    expiration_in_leaf: bool,
+    stored_root: Option<[u8; 32]>,
+    wallet: &str,
+    expiration: Expiration,
+    now_ts: i64,
+) -> Result<VerifyTrace> {
+    let salt = leaf_secret
+        .map(|secret| tree::leaf_salt(secret, wallet))
+        .transpose()?;
+    let leaf_expiration = expiration_in_leaf.then_some(expiration);
+    let leaf = tree::compute_leaf_parts::<H>(wallet, leaf_expiration, salt.as_ref())?;
+    let total_leaves = subscribers.len();
+
+    let mut trace = VerifyTrace {
+        wallet: wallet.to_string(),
+        expiration: expiration.as_i64(),
+        leaf_hex: hex::encode(leaf),
+        leaf_index: None,
+        total_leaves,
+        proof_siblings: Vec::new(),
+        recomputed_root: None,
+        local_root: root_hex.to_string(),
+        stored_root: stored_root.map(hex::encode),
+        verdict: TraceVerdict::NotInTree,
+    };
+    let Some((proof_bytes, index)) = tree::get_proof_for_user(merkle_tree, subscribers, wallet)
+    else {
+        return Ok(trace);
+    };
+
+    trace.leaf_index = Some(index);
+    trace.proof_siblings = proof_bytes.chunks(32).map(hex::encode).collect();
+    trace.recomputed_root = tree::recompute_root_with::<H>(&proof_bytes, leaf, index, total_leaves)
+        .map(hex::encode)
+        .ok();
+
+    let (_, in_tree) = subscribers[index];
+    trace.verdict = if expiration_in_leaf && in_tree != expiration {
+        TraceVerdict::ExpirationMismatch {
+            in_tree: in_tree.as_i64(),
+        }
+    } else {
+        let root = match stored_root {
+            Some(root) => root,
+            None => MerkleRoot::from_hex(root_hex)?.to_bytes(),
+        };
+        match server::verification_outcome_with::<H>(
+            root,
+            &proof_bytes,
+            leaf,
+            leaf_expiration,
+            index,
+            total_leaves,
+            now_ts,
+        ) {
+            VerificationOutcome::Valid => TraceVerdict::Valid,
+            outcome => TraceVerdict::Failed { outcome },
+        }
+    };
+    Ok(trace)
+}
+
+/// `trace_verification` against a tree freshly rebuilt from the database and the
+/// current on-chain root, hashed with the on-chain config's algorithm (sha256 when
+/// the config can't be read)
+pub async fn debug_verify(
+    pool: &PgPool,
+    chain: &dyn ChainClient,
+    leaf_secret: Option<&[u8; 32]>,
+    expiration_in_leaf: bool,
+    wallet: &str,
+    expiration: Expiration,
+) -> Result<VerifyTrace> {
+    let (_, _, subscribers) =
+        tree::build_tree_from_db_with_layout(pool, leaf_secret, expiration_in_leaf).await?;
+    let (stored_root, hash_algo) = match chain.fetch_config().await {
+        Ok(config) => (Some(config.merkle_root), config.hash_algo),
+        Err(e) => {
+            eprintln!(
+                "⚠️  On-chain root unavailable, tracing against the local root: {}",
+                e
+            );
+            (None, HashAlgo::Sha256)
+        }
+    };
+    let trace = match hash_algo {
+        HashAlgo::Sha256 => trace_rebuilt::<Sha256Hasher>,
+        HashAlgo::Keccak256 => trace_rebuilt::<Keccak256Hasher>,
+        HashAlgo::DoubleSha256 => trace_rebuilt::<DoubleSha256Hasher>,
+    };
+    trace(
+        subscribers,
+        leaf_secret,
+        expiration_in_leaf,
+        stored_root,
+        wallet,
+        expiration,
+    )
+}
+
+/// `trace_verification_with` over `subscribers` rebuilt into a tree under hasher `H`
+fn trace_rebuilt<H: Hasher<Hash = [u8; 32]>>(
+    subscribers: Vec<(String, Expiration)>,
+    leaf_secret: Option<&[u8; 32]>,
+    expiration_in_leaf: bool,
+    stored_root: Option<[u8; 32]>,
+    wallet: &str,
+    expiration: Expiration,
+) -> Result<VerifyTrace> {
+    let (root_hex, merkle_tree, subscribers) =
+        tree::build_tree_with_layout::<H>(subscribers, leaf_secret, expiration_in_leaf)?;
+    trace_verification_with::<H>(
+        (&root_hex, &merkle_tree, &subscribers),
+        leaf_secret,
+        expiration_in_leaf,
+        stored_root,
+        wallet,
+        expiration,
+        Utc::now().timestamp(),
+    )
+}
+
+/// Root rebuilt from a subscriber CSV next to the root stored on-chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuditResult {
+    pub subscribers: usize,
+    pub csv_root: String,
+    pub on_chain_root: String,
+    pub matches: bool,
+}
+
+impl fmt::Display for AuditResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "   Subscribers:   {}", self.subscribers)?;
+        writeln!(f, "   CSV root:      {}", self.csv_root)?;
+        writeln!(f, "   On-chain root: {}", self.on_chain_root)?;
+        if self.matches {
+            writeln!(f, "   Result:        ✅ roots match")
+        } else {
+            writeln!(f, "   Result:        ❌ roots differ")
+        }
+    }
+}
+
+/// Parse `wallet,expiration` lines. Blank lines are skipped, and so is a first line
+/// whose expiration isn't a number (a header).
+pub fn subscribers_from_csv(csv: &str) -> Result<Vec<(String, Expiration)>> {
+    let mut subscribers = Vec::new();
+    for (index, line) in csv.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (wallet, expiration) = line
+            .split_once(',')
+            .with_context(|| format!("Line {}: expected wallet,expiration", index + 1))?;
+        let expiration = match expiration.trim().parse::<i64>() {
+            Ok(expiration) => expiration,
+            Err(_) if index == 0 => continue,
+            Err(e) => {
+                return Err(anyhow::anyhow!(
+                    "Line {}: invalid expiration: {}",
+                    index + 1,
+                    e
+                ))
+            }
+        };
+        let expiration =
+            Expiration::new(expiration).with_context(|| format!("Line {}", index + 1))?;
+        subscribers.push((wallet.trim().to_string(), expiration));
+    }
+    Ok(subscribers)
+}
+
+/// Rebuild the root from the subscriber CSV at `csv_path` and compare it with the
+/// on-chain root, so an auditor with the data and an RPC endpoint can check a
+/// published root without database access.
+///
+/// Leaves follow the on-chain config's hash algorithm, expiration layout and pubkey
+/// encoding, giving the same leaves as `build_tree_from_subscribers` for the default
+/// config. Salted deployments can't be audited this way, since the salt needs the
+/// backend's secret.
+pub async fn audit_root(csv_path: &Path, chain: &dyn ChainClient) -> Result<AuditResult> {
+    let csv = std::fs::read_to_string(csv_path)
+        .with_context(|| format!("Failed to read {}", csv_path.display()))?;
+    let subscribers = subscribers_from_csv(&csv)?;
+    let count = subscribers.len();
+    let config = chain.fetch_config().await?;
+
+    let (layout, encoding) = (config.expiration_in_leaf, config.pubkey_leaf_encoding);
+    let csv_root = match config.hash_algo {
+        HashAlgo::Sha256 => {
+            tree::build_tree_with_encoding::<Sha256Hasher>(subscribers, None, layout, encoding)
+                .map(|(root_hash, _, _)| root_hash)
+        }
+        HashAlgo::Keccak256 => {
+            tree::build_tree_with_encoding::<Keccak256Hasher>(subscribers, None, layout, encoding)
+                .map(|(root_hash, _, _)| root_hash)
+        }
+        HashAlgo::DoubleSha256 => tree::build_tree_with_encoding::<DoubleSha256Hasher>(
+            subscribers,
+            None,
+            layout,
+            encoding,
+        )
+        .map(|(root_hash, _, _)| root_hash),
+    }?;
+    let on_chain_root = hex::encode(config.merkle_root);
+
+    Ok(AuditResult {
+        subscribers: count,
+        matches: csv_root == on_chain_root,
+        csv_root,
+        on_chain_root,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{self, MockChainClient};
+    use solana_sdk::pubkey::Pubkey;
+
+    fn expected_root_bytes() -> [u8; 32] {
+        hex::decode(test_fixtures::EXPECTED_ROOT)
+            .unwrap()
+            .try_into()
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn healthy_pipeline_passes_every_check(pool: PgPool) {
+        test_fixtures::seed_subscribers(&pool).await.unwrap();
+        let chain = MockChainClient::with_root(expected_root_bytes());
+
+        let diagnosis = diagnose_startup(&pool, &chain, None, true).await;
+        assert!(diagnosis.is_healthy(), "{}", diagnosis);
+    }
+
+    #[sqlx::test]
+    async fn unreachable_database_skips_dependent_checks(pool: PgPool) {
+        let chain = MockChainClient::with_root(expected_root_bytes());
+        pool.close().await;
+
+        let diagnosis = diagnose_startup(&pool, &chain, None, true).await;
+        assert!(matches!(diagnosis.db_reachable, CheckStatus::Failed(_)));
+        assert_eq!(diagnosis.has_subscribers, CheckStatus::Skipped);
+        assert_eq!(diagnosis.local_root, CheckStatus::Skipped);
+        assert_eq!(diagnosis.root_matches, CheckStatus::Skipped);
+        // The chain side is still checked
+        assert_eq!(diagnosis.config_exists, CheckStatus::Passed);
+    }
+
+    #[sqlx::test]
+    async fn empty_subscriber_set_is_reported(pool: PgPool) {
+        let chain = MockChainClient::with_root(expected_root_bytes());
+
+        let diagnosis = diagnose_startup(&pool, &chain, None, true).await;
+        assert_eq!(diagnosis.db_reachable, CheckStatus::Passed);
+        assert!(matches!(diagnosis.has_subscribers, CheckStatus::Failed(_)));
+        assert_eq!(diagnosis.local_root, CheckStatus::Skipped);
+        assert_eq!(diagnosis.root_matches, CheckStatus::Skipped);
+    }
+
+    #[sqlx::test]
+    async fn missing_config_is_reported(pool: PgPool) {
+        test_fixtures::seed_subscribers(&pool).await.unwrap();
+        let chain = MockChainClient::default();
+
+        let diagnosis = diagnose_startup(&pool, &chain, None, true).await;
+        assert_eq!(diagnosis.local_root, CheckStatus::Passed);
+        assert!(matches!(diagnosis.config_exists, CheckStatus::Failed(_)));
+        assert_eq!(diagnosis.root_matches, CheckStatus::Skipped);
+        assert_eq!(diagnosis.authority_matches, CheckStatus::Skipped);
+    }
+
+    #[sqlx::test]
+    async fn stale_on_chain_root_is_reported(pool: PgPool) {
+        test_fixtures::seed_subscribers(&pool).await.unwrap();
+        let chain = MockChainClient::with_root([7u8; 32]);
+
+        let diagnosis = diagnose_startup(&pool, &chain, None, true).await;
+        let CheckStatus::Failed(reason) = &diagnosis.root_matches else {
+            panic!("expected a root mismatch, got {}", diagnosis.root_matches);
+        };
+        assert!(reason.contains(test_fixtures::EXPECTED_ROOT));
+        assert_eq!(diagnosis.authority_matches, CheckStatus::Passed);
+    }
+
+    #[sqlx::test]
+    async fn unset_on_chain_root_is_reported(pool: PgPool) {
+        test_fixtures::seed_subscribers(&pool).await.unwrap();
+        let chain = MockChainClient::with_root(tree::UNSET_ROOT);
+
+        let diagnosis = diagnose_startup(&pool, &chain, None, true).await;
+        assert_eq!(
+            diagnosis.root_matches,
+            CheckStatus::Failed("on-chain root is not set".to_string())
+        );
+    }
+
+    #[sqlx::test]
+    async fn debug_verify_traces_present_and_absent_wallets(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+        let chain = MockChainClient::with_root(expected_root_bytes());
+
+        let (wallet, expiration) = test_fixtures::SUBSCRIBERS[2];
+        let expiration = Expiration::new(expiration)?;
+        let trace = debug_verify(&pool, &chain, None, true, wallet, expiration).await?;
+        assert_eq!(trace.verdict, TraceVerdict::Valid, "{}", trace);
+        assert_eq!(trace.leaf_index, Some(2));
+        assert_eq!(
+            trace.leaf_hex,
+            hex::encode(tree::compute_leaf(wallet, expiration)?)
+        );
+        assert_eq!(trace.proof_siblings.len(), 1);
+        assert_eq!(
+            trace.recomputed_root.as_deref(),
+            Some(test_fixtures::EXPECTED_ROOT)
+        );
+        assert_eq!(
+            trace.stored_root.as_deref(),
+            Some(test_fixtures::EXPECTED_ROOT)
+        );
+
+        // Right wallet, wrong expiration
+        let wrong = Expiration::new(expiration.as_i64() + 1)?;
+        let trace = debug_verify(&pool, &chain, None, true, wallet, wrong).await?;
+        assert_eq!(
+            trace.verdict,
+            TraceVerdict::ExpirationMismatch {
+                in_tree: expiration.as_i64()
+            }
+        );
+
+        let absent = "So11111111111111111111111111111111111111112";
+        let trace = debug_verify(&pool, &chain, None, true, absent, expiration).await?;
+        assert_eq!(trace.verdict, TraceVerdict::NotInTree);
+        assert_eq!(trace.leaf_index, None);
+        assert!(trace.proof_siblings.is_empty());
+        assert!(trace.to_string().contains("not in the tree"));
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn debug_verify_hashes_with_the_configured_algorithm(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+        let double_root =
+            tree::compute_root_with_algo(test_fixtures::subscribers(), HashAlgo::DoubleSha256)?;
+        let chain = MockChainClient::with_root(MerkleRoot::from_hex(&double_root)?.to_bytes());
+        chain.config.lock().unwrap().as_mut().unwrap().hash_algo = HashAlgo::DoubleSha256;
+
+        let (wallet, expiration) = test_fixtures::SUBSCRIBERS[2];
+        let expiration = Expiration::new(expiration)?;
+        let trace = debug_verify(&pool, &chain, None, true, wallet, expiration).await?;
+        assert_eq!(trace.verdict, TraceVerdict::Valid, "{}", trace);
+        assert_eq!(
+            trace.leaf_hex,
+            hex::encode(tree::compute_leaf_with::<DoubleSha256Hasher>(
+                wallet, expiration
+            )?)
+        );
+        assert_eq!(trace.local_root, double_root);
+        assert_eq!(trace.recomputed_root.as_deref(), Some(double_root.as_str()));
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn foreign_keypair_is_reported(pool: PgPool) {
+        test_fixtures::seed_subscribers(&pool).await.unwrap();
+        let chain = MockChainClient {
+            authority: Pubkey::new_unique(),
+            ..MockChainClient::with_root(expected_root_bytes())
+        };
+
+        let diagnosis = diagnose_startup(&pool, &chain, None, true).await;
+        assert_eq!(diagnosis.root_matches, CheckStatus::Passed);
+        assert!(matches!(
+            diagnosis.authority_matches,
+            CheckStatus::Failed(_)
+        ));
+        assert!(!diagnosis.is_healthy());
+    }
+
+    fn write_csv(name: &str, rows: &[(&str, i64)]) -> std::path::PathBuf {
+        let mut csv = "wallet_address,expiration_ts\n".to_string();
+        for (wallet, expiration) in rows {
+            csv.push_str(&format!("{},{}\n", wallet, expiration));
+        }
+        let path = std::env::temp_dir().join(format!("{}-{}.csv", name, std::process::id()));
+        std::fs::write(&path, csv).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn audit_root_compares_a_csv_with_the_on_chain_root() -> Result<()> {
+        let chain = MockChainClient::with_root(expected_root_bytes());
+
+        let path = write_csv("audit-matching", &test_fixtures::SUBSCRIBERS);
+        let audit = audit_root(&path, &chain).await?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(audit.subscribers, 3);
+        assert_eq!(audit.csv_root, test_fixtures::EXPECTED_ROOT);
+        assert_eq!(audit.on_chain_root, test_fixtures::EXPECTED_ROOT);
+        assert!(audit.matches);
+
+        // One renewal the on-chain root doesn't reflect yet
+        let mut rows = test_fixtures::SUBSCRIBERS;
+        rows[2].1 += 86_400;
+        let path = write_csv("audit-diverged", &rows);
+        let audit = audit_root(&path, &chain).await?;
+        std::fs::remove_file(&path)?;
+        assert_ne!(audit.csv_root, audit.on_chain_root);
+        assert!(!audit.matches);
+        assert!(audit.to_string().contains("roots differ"));
+        Ok(())
+    }
+
+    #[test]
+    fn subscribers_from_csv_reports_the_bad_line() {
+        let err = subscribers_from_csv("wallet,expiration\n\nabc,1700000000\nabc,soon\n")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Line 4"), "{}", err);
+    }
+}
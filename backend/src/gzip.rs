@@ -0,0 +1,73 @@
+//! Optional gzip for proof exports and HTTP responses, compiled in with the `gzip`
+//! feature. Without it, compressing or decompressing reports an error.
+
+use anyhow::Result;
+use std::path::Path;
+
+/// Responses smaller than this are sent uncompressed; gzip overhead isn't worth it
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Whether `path` names a gzip file (`.gz`)
+pub fn is_gzip_path(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "gz")
+}
+
+/// Whether an `Accept-Encoding` header value allows gzip (and doesn't refuse it with `q=0`)
+pub fn accepts_gzip(accept_encoding: &str) -> bool {
+    accept_encoding.split(',').any(|coding| {
+        let mut params = coding.split(';').map(str::trim);
+        let name = params.next().unwrap_or_default();
+        let refused = params.any(|param| {
+            param
+                .strip_prefix("q=")
+                .and_then(|q| q.parse::<f32>().ok())
+                .is_some_and(|q| q == 0.0)
+        });
+        (name.eq_ignore_ascii_case("gzip") || name == "*") && !refused
+    })
+}
+
+#[cfg(feature = "gzip")]
+pub fn compress(data: &[u8]) -> Result<Vec<u8>> {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+#[cfg(feature = "gzip")]
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    let mut decompressed = Vec::new();
+    GzDecoder::new(data).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+#[cfg(not(feature = "gzip"))]
+pub fn compress(_data: &[u8]) -> Result<Vec<u8>> {
+    Err(anyhow::anyhow!("gzip support requires the `gzip` feature"))
+}
+
+#[cfg(not(feature = "gzip"))]
+pub fn decompress(_data: &[u8]) -> Result<Vec<u8>> {
+    Err(anyhow::anyhow!("gzip support requires the `gzip` feature"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_accept_encoding() {
+        assert!(accepts_gzip("gzip"));
+        assert!(accepts_gzip("deflate, GZIP;q=0.5, br"));
+        assert!(accepts_gzip("*"));
+        assert!(!accepts_gzip("deflate, br"));
+        assert!(!accepts_gzip("gzip;q=0"));
+        assert!(!accepts_gzip(""));
+    }
+}
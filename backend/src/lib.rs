@@ -0,0 +1,15 @@
+pub mod cli;
+pub mod config;
+pub mod db;
+pub mod diagnose;
+pub mod gzip;
+pub mod merkle;
+pub mod model;
+pub mod revenue;
+pub mod server;
+pub mod session;
+pub mod sync;
+pub mod telemetry;
+
+#[cfg(test)]
+mod test_fixtures;
@@ -1,78 +1,155 @@
 use anyhow::{Context, Result};
-use sqlx::postgres::{PgPool, PgPoolOptions};
-use std::env;
+use backend::config::Config;
+use backend::{db, diagnose, merkle, server, session, sync};
+use chrono::Utc;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::signature::read_keypair_file;
+use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions};
+use std::sync::Arc;
 use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
-mod merkle;
-mod model;
-
-pub async fn get_db_pool() -> Result<PgPool> {
-    let database_url =
-        env::var("DATABASE_URL").context("DATABASE_URL must be set in environment or .env file")?;
+/// The primary from `DATABASE_URL`, plus a read replica for tree builds when
+/// `DATABASE_READ_URL` is set (otherwise the primary serves reads too)
+pub async fn get_db_pools(config: &Config) -> Result<db::AppPools> {
+    let write = connect_pool(
+        config.db_connect_options(&config.database_url)?,
+        config.db_max_connections,
+    )
+    .await?;
+    match &config.database_read_url {
+        Some(read_url) => Ok(db::AppPools::new(
+            write,
+            connect_pool(
+                config.db_connect_options(read_url)?,
+                config.db_max_connections,
+            )
+            .await?,
+        )),
+        None => Ok(db::AppPools::single(write)),
+    }
+}
 
+async fn connect_pool(options: PgConnectOptions, max_connections: u32) -> Result<PgPool> {
     let pool = PgPoolOptions::new()
-        .max_connections(5)
+        .max_connections(max_connections)
         .acquire_timeout(Duration::from_secs(5))
-        .connect(&database_url)
+        .connect_with(options)
         .await
         .context("Failed to connect to Postgres. Ensure the service is running.")?;
 
     Ok(pool)
 }
 
+/// Signer for `POST /verify` session tokens: an ES256 key from `JWT_ES256_KEY_PATH`,
+/// else an HS256 `JWT_HS256_SECRET`, else none. Sessions last `JWT_SESSION_SECS`.
+fn session_issuer(config: &Config) -> Result<Option<session::SessionIssuer>> {
+    let session_secs = config.jwt_session_secs;
+    if let Some(path) = &config.jwt_es256_key_path {
+        let pem = std::fs::read(path).with_context(|| format!("Failed to read {}", path))?;
+        return Ok(Some(session::SessionIssuer::es256_pem(&pem, session_secs)?));
+    }
+    Ok(config
+        .jwt_hs256_secret
+        .as_ref()
+        .map(|secret| session::SessionIssuer::hs256(secret.0.as_bytes(), session_secs)))
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenvy::dotenv().context("Failed to load .env file")?;
+    let config = Config::from_env()?;
 
-    let pool = get_db_pool().await?;
+    let pools = get_db_pools(&config).await?;
+    let pool = pools.write.clone();
     println!("✅ Successfully connected to database!");
 
+    db::run_migrations(&pool).await?;
+    println!("✅ Database schema up to date");
+
     // Initialize Solana client
-    let rpc_url =
-        env::var("SOLANA_RPC_URL").unwrap_or_else(|_| "http://localhost:8899".to_string());
-    let keypair_path =
-        env::var("SOLANA_KEYPAIR_PATH").unwrap_or_else(|_| "./backend-authority.json".to_string());
+    // Network name the config is bound to; roots are only pushed to a matching config
+    let chain_domain = config.chain_domain;
+
+    // A base58 AUTHORITY_SECRET_KEY (e.g. an injected secret) takes precedence over the keypair file
+    let authority_keypair = match &config.authority_secret_key {
+        Some(secret) => merkle::solana_client::keypair_from_base58(&secret.0)?,
+        None => read_keypair_file(&config.authority_keypair_path)
+            .map_err(|e| anyhow::anyhow!("Failed to read authority keypair: {}", e))?,
+    };
 
-    let solana_client = merkle::solana_client::SolanaClient::new(&rpc_url, &keypair_path)?;
-    println!("✅ Connected to Solana RPC: {}", rpc_url);
+    let solana_client = merkle::solana_client::SolanaClient::with_rpc_client(
+        RpcClient::new_with_timeout_and_commitment(
+            config.rpc_url.clone(),
+            config.rpc_timeout(),
+            config.commitment,
+        ),
+        merkle::solana_client::keypair_from_bytes(&authority_keypair.to_bytes())?,
+    )
+    .with_program_id(config.program_id)
+    .with_rpc_timeout(config.rpc_timeout())
+    .with_chain_domain(chain_domain);
+    println!("✅ Solana RPC: {}", config.rpc_url);
 
-    // Check if config account exists, if not initialize it
+    // Check if config account exists, if not initialize it. Only the database is
+    // required to start; without the RPC, proofs are served and sync keeps retrying.
     println!("\n🔍 Checking program config...");
-    match solana_client.get_current_root().await {
-        Ok(current_root) => {
-            println!("   ✅ Config account exists");
-            println!("   Current root: {}", hex::encode(current_root));
-        }
-        Err(_) => {
-            println!("   ⚠️  Config account not found, initializing...");
-            let initial_root = [0u8; 32];
-            match solana_client.initialize_config(initial_root).await {
-                Ok(sig) => {
-                    println!("   ✅ Config initialized! Signature: {}", sig);
-                }
-                Err(e) => {
-                    eprintln!("   ❌ Failed to initialize: {}", e);
-                    return Err(e);
-                }
-            }
-        }
-    }
+    let mut chain_degraded = !sync::check_chain_at_startup(&solana_client).await;
+
+    // Optional secret for salted leaves (64 hex chars); unset builds the plain tree
+    let leaf_secret = config.leaf_secret().copied();
+
+    // Static allowlist mode: leaves omit expirations (must match the on-chain config)
+    let expiration_in_leaf = config.expiration_in_leaf;
+    // Opt-in: synced trees leave out expired subscribers, re-pushed as each expires
+    let drop_expired_leaves = config.drop_expired_leaves;
+
+    // Report each pipeline stage, so a divergence below points at its cause
+    println!("\n🩺 Startup diagnosis:");
+    print!(
+        "{}",
+        diagnose::diagnose_startup(
+            &pool,
+            &solana_client,
+            leaf_secret.as_ref(),
+            expiration_in_leaf
+        )
+        .await
+    );
 
-    // 1. Build Merkle Tree from database
-    let (root_hash, tree, subscriber_data) = merkle::tree::build_tree_from_db(&pool).await?;
+    // 1. Build Merkle Tree from database, as sync passes do, so the first pass finds
+    // this root already pushed
+    let active_at = drop_expired_leaves.then(|| Utc::now().timestamp());
+    let (root_hash, tree, subscriber_data) = merkle::tree::build_tree_from_db_active_at(
+        &pools.read,
+        active_at,
+        leaf_secret.as_ref(),
+        expiration_in_leaf,
+    )
+    .await?;
     let total_leaves = subscriber_data.len();
     println!("\n🌲 Merkle Tree Built:");
     println!("   Root Hash: {}", root_hash);
     println!("   Total subscribers: {}", total_leaves);
 
     // 2. Convert hex root to bytes
-    let root_bytes: [u8; 32] = hex::decode(&root_hash)?
-        .try_into()
-        .map_err(|_| anyhow::anyhow!("Root must be 32 bytes"))?;
+    let root_bytes = merkle::tree::MerkleRoot::from_hex(&root_hash)?.to_bytes();
 
     // 3. Update the merkle root on-chain
     println!("\n📤 Syncing merkle root to Solana...");
-    match solana_client.update_merkle_root(root_bytes).await {
+    // Guarded like every sync pass: a refused root is recorded unsynced and retried,
+    // still guarded, once the chain would accept it
+    let pushed = if chain_degraded {
+        Err(anyhow::anyhow!("RPC unavailable at startup"))
+    } else {
+        async {
+            let on_chain = solana_client.fetch_config().await?;
+            sync::check_pushable(&on_chain, &root_hash, total_leaves)?;
+            solana_client.update_merkle_root(root_bytes).await
+        }
+        .await
+    };
+    match pushed {
         Ok(signature) => {
             println!("✅ Successfully updated on-chain!");
 
@@ -80,6 +157,7 @@ async fn main() -> Result<()> {
             merkle::updatestate::update_merkle_state(
                 &pool,
                 &root_hash,
+                total_leaves,
                 Some(signature.to_string()),
             )
             .await?;
@@ -91,11 +169,17 @@ async fn main() -> Result<()> {
             eprintln!("        Make sure local validator is running: solana-test-validator");
 
             // Still save to database but mark as not synced
-            merkle::updatestate::update_merkle_state(&pool, &root_hash, None).await?;
+            merkle::updatestate::update_merkle_state(&pool, &root_hash, total_leaves, None).await?;
+            chain_degraded = true;
         }
     }
 
-    // 5. Verify a user proof (off-chain verification test)
+    // 5. Regenerate the persisted proof for every subscriber against the new root
+    let regenerated =
+        merkle::proofstore::regenerate_all_proofs(&pool, &tree, &subscriber_data).await?;
+    println!("✅ Regenerated {} subscriber proofs", regenerated);
+
+    // 6. Verify a user proof (off-chain verification test)
     println!("\n🔐 Testing Proof Verification...");
     if let Some((first_user, expiration)) = subscriber_data.first() {
         println!("   User: {}", first_user);
@@ -104,14 +188,29 @@ async fn main() -> Result<()> {
         if let Some((proof_bytes, index)) =
             merkle::tree::get_proof_for_user(&tree, &subscriber_data, first_user)
         {
-            let is_valid = merkle::tree::verify_subscription(
-                &root_hash,
-                &proof_bytes,
-                first_user,
-                *expiration,
-                index,
-                total_leaves,
-            )?;
+            let salt = leaf_secret
+                .map(|secret| merkle::tree::leaf_salt(&secret, first_user))
+                .transpose()?;
+            let is_valid = if expiration_in_leaf {
+                merkle::tree::verify_salted_subscription(
+                    &root_hash,
+                    &proof_bytes,
+                    first_user,
+                    *expiration,
+                    salt.as_ref(),
+                    index,
+                    total_leaves,
+                )?
+            } else {
+                merkle::tree::verify_allowlist_membership(
+                    &root_hash,
+                    &proof_bytes,
+                    first_user,
+                    salt.as_ref(),
+                    index,
+                    total_leaves,
+                )?
+            };
 
             println!(
                 "   Off-chain verification: {}",
@@ -120,18 +219,22 @@ async fn main() -> Result<()> {
         }
     }
 
-    // 6. Test tampering detection
+    // 7. Test tampering detection (allowlist leaves have no expiration to tamper with)
     println!("\n🧪 Testing Tampering Detection...");
-    if let Some((first_user, _)) = subscriber_data.first() {
+    if let Some((first_user, _)) = subscriber_data.first().filter(|_| expiration_in_leaf) {
         if let Some((proof_bytes, index)) =
             merkle::tree::get_proof_for_user(&tree, &subscriber_data, first_user)
         {
-            let fake_expiration = 9999999999i64;
-            let is_valid_tamper = merkle::tree::verify_subscription(
+            let fake_expiration = merkle::tree::Expiration::new(9999999999)?;
+            let salt = leaf_secret
+                .map(|secret| merkle::tree::leaf_salt(&secret, first_user))
+                .transpose()?;
+            let is_valid_tamper = merkle::tree::verify_salted_subscription(
                 &root_hash,
                 &proof_bytes,
                 first_user,
                 fake_expiration,
+                salt.as_ref(),
                 index,
                 total_leaves,
             )?;
@@ -144,6 +247,89 @@ async fn main() -> Result<()> {
                     "✓ REJECTED (Correct)"
                 }
             );
+            let tampered_leaf = merkle::tree::compute_salted_leaf_with::<merkle::tree::Sha256Hasher>(
+                first_user,
+                fake_expiration,
+                salt.as_ref(),
+            )?;
+            println!(
+                "   Recomputed root: {} (expected {})",
+                merkle::tree::recompute_root_hex(&proof_bytes, tampered_leaf, index, total_leaves)?,
+                root_hash
+            );
+        }
+    }
+
+    // 8. Serve proofs over HTTP and keep syncing if a bind address is configured
+    if let Some(bind_addr) = &config.api_bind_addr {
+        let sync_interval = config.sync_interval();
+        let solana_client = Arc::new(solana_client);
+        let mut state = server::AppState::new((root_hash, tree, subscriber_data).into())
+            .with_leaf_secret(leaf_secret)
+            .with_expiration_in_leaf(expiration_in_leaf)
+            .with_drop_expired_leaves(drop_expired_leaves)
+            .with_sync_interval(Some(sync_interval))
+            .with_chain(solana_client.clone())
+            .with_pool(pools.read.clone())
+            .with_attestation_signer(Arc::new(authority_keypair));
+        state.set_chain_degraded(chain_degraded);
+        if let Some(issuer) = session_issuer(&config)? {
+            state = state.with_session_issuer(Arc::new(issuer));
+        }
+        #[cfg(feature = "metrics")]
+        {
+            state = state.with_metrics(backend::telemetry::install_prometheus()?);
+        }
+
+        let shutdown = CancellationToken::new();
+        tokio::spawn(sync::shutdown_on_signal(shutdown.clone()));
+
+        let sync_loop = {
+            let (pools, solana_client, state) =
+                (pools.clone(), solana_client.clone(), state.clone());
+            tokio::spawn(sync::run_sync_loop(
+                sync_interval,
+                shutdown.clone(),
+                move || {
+                    let (pools, solana_client, state) =
+                        (pools.clone(), solana_client.clone(), state.clone());
+                    async move { sync::sync_once(&pools, &*solana_client, &state).await }
+                },
+            ))
+        };
+
+        let sweeper = drop_expired_leaves.then(|| {
+            let sweep_interval = config.sweep_interval();
+            let (pools, solana_client, state, shutdown) = (
+                pools.clone(),
+                solana_client.clone(),
+                state.clone(),
+                shutdown.clone(),
+            );
+            tokio::spawn(async move {
+                sync::run_expiration_sweeper(
+                    &pools,
+                    &*solana_client,
+                    &state,
+                    sweep_interval,
+                    shutdown,
+                )
+                .await
+            })
+        });
+
+        let listener = tokio::net::TcpListener::bind(bind_addr)
+            .await
+            .with_context(|| format!("Failed to bind {}", bind_addr))?;
+        println!("\n🌐 Serving proofs on http://{}", bind_addr);
+        axum::serve(listener, server::router(state))
+            .with_graceful_shutdown(shutdown.clone().cancelled_owned())
+            .await?;
+
+        // The server has drained; wait for the sync loop to finish its iteration
+        sync_loop.await?;
+        if let Some(sweeper) = sweeper {
+            sweeper.await?;
         }
     }
 
@@ -0,0 +1,283 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+use super::solana_client::SolanaClient;
+use super::tree::{HashAlgo, PubkeyLeafEncoding};
+
+/// The on-chain operations the sync logic needs, so it can run against a mock
+/// instead of a validator
+#[async_trait]
+pub trait ChainClient: Send + Sync {
+    /// Key this client signs root updates with
+    fn authority(&self) -> Pubkey;
+
+    /// Fail unless the program this client targets is deployed on its cluster
+    async fn verify_program_deployed(&self) -> Result<()>;
+
+    /// Merkle root currently stored in the config account
+    async fn get_current_root(&self) -> Result<[u8; 32]>;
+
+    /// Replace the on-chain merkle root
+    async fn update_merkle_root(&self, new_root: [u8; 32]) -> Result<Signature>;
+
+    /// The full decoded config account
+    async fn fetch_config(&self) -> Result<OnChainConfig>;
+
+    /// Create the config with `initial_root` unless it already exists;
+    /// `None` means it was already there
+    async fn initialize_config_if_needed(
+        &self,
+        initial_root: [u8; 32],
+    ) -> Result<Option<Signature>>;
+}
+
+/// Replaced roots the program keeps for its verify window (`ROOT_HISTORY_LEN` on-chain)
+pub const ROOT_HISTORY_LEN: usize = 8;
+
+/// Decoded `SubscriptionConfig` account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OnChainConfig {
+    pub authority: Pubkey,
+    pub merkle_root: [u8; 32],
+    pub bump: u8,
+    pub required_mint: Option<Pubkey>,
+    pub clock_skew_secs: i64,
+    pub hash_algo: HashAlgo,
+    pub verification_cache_ttl_secs: i64,
+    pub authorities: Vec<Pubkey>,
+    pub threshold: u8,
+    pub expiration_in_leaf: bool,
+    pub min_interval_secs: i64,
+    pub chain_domain: [u8; 8],
+    pub root_history: [[u8; 32]; ROOT_HISTORY_LEN],
+    pub root_history_head: u8,
+    pub max_verify_root_age: u8,
+    pub start_in_leaf: bool,
+    pub verbose_logging: bool,
+    pub max_total_leaves: u64,
+    pub lifetime_root: [u8; 32],
+    pub last_updated: i64,
+    pub min_update_interval_secs: i64,
+    pub pubkey_leaf_encoding: PubkeyLeafEncoding,
+}
+
+impl OnChainConfig {
+    /// The config as the program's `initialize` leaves it, with an unset chain domain
+    pub fn initial(authority: Pubkey, merkle_root: [u8; 32]) -> Self {
+        Self {
+            authority,
+            merkle_root,
+            bump: 0,
+            required_mint: None,
+            clock_skew_secs: 0,
+            hash_algo: HashAlgo::Sha256,
+            verification_cache_ttl_secs: 0,
+            authorities: Vec::new(),
+            threshold: 0,
+            expiration_in_leaf: true,
+            min_interval_secs: 0,
+            chain_domain: [0u8; 8],
+            root_history: [[0u8; 32]; ROOT_HISTORY_LEN],
+            root_history_head: 0,
+            max_verify_root_age: 0,
+            start_in_leaf: false,
+            verbose_logging: true,
+            max_total_leaves: 0,
+            lifetime_root: [0u8; 32],
+            last_updated: 0,
+            min_update_interval_secs: 0,
+            pubkey_leaf_encoding: PubkeyLeafEncoding::RawBytes,
+        }
+    }
+
+    /// Whether verification accepts a tree of `total_leaves` (`max_total_leaves` 0 is
+    /// unbounded). The backend won't push a root its members couldn't verify against.
+    pub fn allows_total_leaves(&self, total_leaves: usize) -> bool {
+        self.max_total_leaves == 0 || total_leaves as u64 <= self.max_total_leaves
+    }
+
+    /// Decode raw account data (8-byte Anchor discriminator, then Borsh fields in
+    /// declaration order). Configs at an older layout must be grown with
+    /// `realloc_config` first.
+    pub fn from_account_data(account_data: &[u8]) -> Result<Self> {
+        let mut reader = BorshReader(
+            account_data
+                .get(8..)
+                .ok_or_else(|| anyhow::anyhow!("Invalid account data length"))?,
+        );
+        let config = Self {
+            authority: reader.pubkey()?,
+            merkle_root: reader.array()?,
+            bump: reader.u8()?,
+            required_mint: match reader.u8()? {
+                0 => None,
+                _ => Some(reader.pubkey()?),
+            },
+            clock_skew_secs: reader.i64()?,
+            hash_algo: HashAlgo::from_u8(reader.u8()?)?,
+            verification_cache_ttl_secs: reader.i64()?,
+            authorities: (0..u32::from_le_bytes(reader.array()?))
+                .map(|_| reader.pubkey())
+                .collect::<Result<_>>()?,
+            threshold: reader.u8()?,
+            expiration_in_leaf: reader.u8()? != 0,
+            min_interval_secs: reader.i64()?,
+            chain_domain: reader.array()?,
+            root_history: reader.hashes()?,
+            root_history_head: reader.u8()?,
+            max_verify_root_age: reader.u8()?,
+            start_in_leaf: reader.u8()? != 0,
+            verbose_logging: reader.u8()? != 0,
+            max_total_leaves: reader.u64()?,
+            lifetime_root: reader.array()?,
+            last_updated: reader.i64()?,
+            min_update_interval_secs: reader.i64()?,
+            pubkey_leaf_encoding: PubkeyLeafEncoding::from_u8(reader.u8()?)?,
+        };
+        Ok(config)
+    }
+}
+
+struct BorshReader<'a>(&'a [u8]);
+
+impl BorshReader<'_> {
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let (head, rest) = self
+            .0
+            .split_first_chunk::<N>()
+            .context("Config account is shorter than the current layout; run realloc_config")?;
+        self.0 = rest;
+        Ok(*head)
+    }
+
+    fn hashes<const N: usize>(&mut self) -> Result<[[u8; 32]; N]> {
+        let mut hashes = [[0u8; 32]; N];
+        for hash in &mut hashes {
+            *hash = self.array()?;
+        }
+        Ok(hashes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        self.array::<1>().map(|[byte]| byte)
+    }
+
+    fn i64(&mut self) -> Result<i64> {
+        self.array().map(i64::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn pubkey(&mut self) -> Result<Pubkey> {
+        self.array().map(Pubkey::new_from_array)
+    }
+}
+
+#[async_trait]
+impl ChainClient for SolanaClient {
+    fn authority(&self) -> Pubkey {
+        SolanaClient::authority(self)
+    }
+
+    async fn verify_program_deployed(&self) -> Result<()> {
+        SolanaClient::verify_program_deployed(self).await
+    }
+
+    async fn get_current_root(&self) -> Result<[u8; 32]> {
+        SolanaClient::get_current_root(self).await
+    }
+
+    async fn update_merkle_root(&self, new_root: [u8; 32]) -> Result<Signature> {
+        SolanaClient::update_merkle_root(self, new_root).await
+    }
+
+    async fn fetch_config(&self) -> Result<OnChainConfig> {
+        SolanaClient::fetch_config(self).await
+    }
+
+    async fn initialize_config_if_needed(
+        &self,
+        initial_root: [u8; 32],
+    ) -> Result<Option<Signature>> {
+        SolanaClient::initialize_config_if_needed(self, initial_root).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_config_account_data() -> Result<()> {
+        let authority = Pubkey::new_unique();
+        let co_signer = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+
+        let mut data = vec![0u8; 8];
+        data.extend_from_slice(authority.as_ref());
+        data.extend_from_slice(&[7u8; 32]);
+        data.push(254);
+        data.push(1);
+        data.extend_from_slice(mint.as_ref());
+        data.extend_from_slice(&30i64.to_le_bytes());
+        data.push(HashAlgo::Keccak256.to_u8());
+        data.extend_from_slice(&60i64.to_le_bytes());
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(authority.as_ref());
+        data.extend_from_slice(co_signer.as_ref());
+        data.push(2);
+        data.push(0);
+        data.extend_from_slice(&5i64.to_le_bytes());
+        data.extend_from_slice(b"devnet\0\0");
+        for slot in 0..ROOT_HISTORY_LEN as u8 {
+            data.extend_from_slice(&[slot; 32]);
+        }
+        data.push(3);
+        data.push(2);
+        data.push(1);
+        data.push(0);
+        data.extend_from_slice(&1024u64.to_le_bytes());
+        data.extend_from_slice(&[9u8; 32]);
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+        data.extend_from_slice(&300i64.to_le_bytes());
+        data.push(PubkeyLeafEncoding::Base58String.to_u8());
+
+        let config = OnChainConfig::from_account_data(&data)?;
+        assert_eq!(
+            config,
+            OnChainConfig {
+                authority,
+                merkle_root: [7u8; 32],
+                bump: 254,
+                required_mint: Some(mint),
+                clock_skew_secs: 30,
+                hash_algo: HashAlgo::Keccak256,
+                verification_cache_ttl_secs: 60,
+                authorities: vec![authority, co_signer],
+                threshold: 2,
+                expiration_in_leaf: false,
+                min_interval_secs: 5,
+                chain_domain: *b"devnet\0\0",
+                root_history: std::array::from_fn(|slot| [slot as u8; 32]),
+                root_history_head: 3,
+                max_verify_root_age: 2,
+                start_in_leaf: true,
+                verbose_logging: false,
+                max_total_leaves: 1024,
+                lifetime_root: [9u8; 32],
+                last_updated: 1_700_000_000,
+                min_update_interval_secs: 300,
+                pubkey_leaf_encoding: PubkeyLeafEncoding::Base58String,
+            }
+        );
+        assert!(config.allows_total_leaves(1024));
+        assert!(!config.allows_total_leaves(1025));
+
+        // Legacy layout (before realloc_config) is reported, not misread
+        assert!(OnChainConfig::from_account_data(&data[..8 + 32 + 32 + 1]).is_err());
+        Ok(())
+    }
+}
@@ -11,7 +11,7 @@ pub async fn generate_and_store_keys(pool: &PgPool, count: usize) -> Result<()>
         let pubkey = kp.pubkey().to_string();
 
         // 2. Set expiration (e.g., 30 days from now)
-        let expiration_ts = (Utc::now().timestamp() + (30 * 24 * 60 * 60)) as i64;
+        let expiration_ts = Utc::now().timestamp() + (30 * 24 * 60 * 60);
 
         // 3. Set last updated timestamp (using naive datetime for the DB)
         let last_updated_at = Utc::now().naive_utc();
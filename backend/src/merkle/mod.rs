@@ -1,4 +1,7 @@
+pub mod chain;
 pub mod generator;
+pub mod proofstore;
 pub mod solana_client;
+pub mod subscriber;
 pub mod tree;
 pub mod updatestate;
@@ -0,0 +1,181 @@
+use anyhow::{Context, Result};
+use rs_merkle::MerkleTree;
+use sqlx::PgPool;
+use std::fs;
+use std::path::Path;
+
+use super::tree::{self, Expiration, SerializedProof, Sha256Hasher};
+use crate::gzip;
+
+/// Compute every subscriber's proof against the current root and persist it.
+///
+/// Rows left over from a previous root (including wallets no longer in the tree)
+/// are removed, so the table always reflects exactly the given tree.
+/// Returns the number of proofs written.
+pub async fn regenerate_all_proofs(
+    pool: &PgPool,
+    tree: &MerkleTree<Sha256Hasher>,
+    subscribers: &[(String, Expiration)],
+) -> Result<usize> {
+    let root_hex = tree
+        .root_hex()
+        .ok_or_else(|| anyhow::anyhow!("Cannot generate proofs for an empty tree"))?;
+
+    let mut tx = pool.begin().await?;
+
+    for (index, (wallet, _)) in subscribers.iter().enumerate() {
+        let proof_hex = hex::encode(tree.proof(&[index]).to_bytes());
+
+        sqlx::query!(
+            "INSERT INTO subscriber_proofs (wallet, proof_hex, leaf_index, root_hex)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (wallet) DO UPDATE
+             SET proof_hex = EXCLUDED.proof_hex,
+                 leaf_index = EXCLUDED.leaf_index,
+                 root_hex = EXCLUDED.root_hex",
+            wallet,
+            proof_hex,
+            index as i64,
+            root_hex
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    // Drop proofs for wallets that are no longer part of the tree
+    sqlx::query!(
+        "DELETE FROM subscriber_proofs WHERE root_hex <> $1",
+        root_hex
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(subscribers.len())
+}
+
+/// Write every subscriber's proof to `path` as a JSON array of `SerializedProof`, for
+/// bulk distribution. A `.gz` path (e.g. `proofs.json.gz`) is gzip-compressed, which
+/// needs the `gzip` feature. Returns the number of proofs written.
+pub fn export_all_proofs(
+    tree: &MerkleTree<Sha256Hasher>,
+    subscribers: &[(String, Expiration)],
+    leaf_secret: Option<&[u8; 32]>,
+    path: &Path,
+) -> Result<usize> {
+    let wallets: Vec<String> = subscribers
+        .iter()
+        .map(|(wallet, _)| wallet.clone())
+        .collect();
+    let proofs: Vec<SerializedProof> =
+        tree::get_proofs_for_users(tree, subscribers, &wallets, leaf_secret)
+            .into_iter()
+            .flatten()
+            .collect();
+
+    let mut bytes = serde_json::to_vec(&proofs)?;
+    if gzip::is_gzip_path(path) {
+        bytes = gzip::compress(&bytes)?;
+    }
+    fs::write(path, bytes).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(proofs.len())
+}
+
+/// Read proofs written by `export_all_proofs`, decompressing `.gz` files
+pub fn read_exported_proofs(path: &Path) -> Result<Vec<SerializedProof>> {
+    let mut bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    if gzip::is_gzip_path(path) {
+        bytes = gzip::decompress(&bytes)?;
+    }
+    serde_json::from_slice(&bytes)
+        .with_context(|| format!("Malformed proof export {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+
+    fn round_trip(file_name: &str) -> Result<()> {
+        let (_, tree, subscribers) =
+            tree::build_tree_from_subscribers(test_fixtures::subscribers())?;
+        let path = std::env::temp_dir().join(format!("{}-{}", std::process::id(), file_name));
+
+        assert_eq!(
+            export_all_proofs(&tree, &subscribers, None, &path)?,
+            subscribers.len()
+        );
+        let proofs = read_exported_proofs(&path)?;
+        fs::remove_file(&path)?;
+
+        assert_eq!(proofs.len(), subscribers.len());
+        for (proof, (wallet, expiration)) in proofs.iter().zip(&subscribers) {
+            assert_eq!(&proof.wallet, wallet);
+            assert_eq!(proof.expiration, expiration.as_i64());
+            assert_eq!(proof.valid_as_of_root, test_fixtures::EXPECTED_ROOT);
+            let proof_bytes = hex::decode(&proof.proof_hex)?;
+            assert!(tree::verify_subscription(
+                &proof.valid_as_of_root,
+                &proof_bytes,
+                wallet,
+                *expiration,
+                proof.leaf_index,
+                proof.total_leaves
+            )?);
+        }
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn regeneration_replaces_every_row_after_a_subscriber_change(pool: PgPool) -> Result<()> {
+        let (_, tree, subscribers) =
+            tree::build_tree_from_subscribers(test_fixtures::subscribers())?;
+        regenerate_all_proofs(&pool, &tree, &subscribers).await?;
+
+        // One subscriber renews, another is removed
+        let mut changed = test_fixtures::subscribers();
+        changed[0].1 = Expiration::new(changed[0].1.as_i64() + 86_400)?;
+        let (removed, _) = changed.pop().unwrap();
+        let (root_hex, tree, subscribers) = tree::build_tree_from_subscribers(changed)?;
+        assert_ne!(root_hex, test_fixtures::EXPECTED_ROOT);
+        assert_eq!(
+            regenerate_all_proofs(&pool, &tree, &subscribers).await?,
+            subscribers.len()
+        );
+
+        let rows: Vec<(String, String, i64, String)> =
+            sqlx::query_as("SELECT wallet, proof_hex, leaf_index, root_hex FROM subscriber_proofs")
+                .fetch_all(&pool)
+                .await?;
+        assert_eq!(rows.len(), subscribers.len());
+        assert!(rows.iter().all(|(wallet, ..)| *wallet != removed));
+        for (wallet, proof_hex, leaf_index, row_root) in rows {
+            assert_eq!(row_root, root_hex);
+            let (_, expiration) = subscribers
+                .iter()
+                .find(|(subscriber, _)| *subscriber == wallet)
+                .unwrap();
+            assert!(tree::verify_subscription(
+                &row_root,
+                &hex::decode(&proof_hex)?,
+                wallet.as_str(),
+                *expiration,
+                leaf_index as usize,
+                subscribers.len()
+            )?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn export_round_trips() -> Result<()> {
+        round_trip("proofs.json")
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn compressed_export_round_trips() -> Result<()> {
+        round_trip("proofs.json.gz")
+    }
+}
@@ -1,56 +1,573 @@
 use anyhow::{Context, Result};
-use solana_client::{rpc_client::RpcClient, rpc_config::CommitmentConfig};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use rs_merkle::Hasher;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient},
+    rpc_config::{CommitmentConfig, RpcAccountInfoConfig, RpcTransactionConfig},
+    rpc_custom_error::JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED,
+    rpc_request::{RpcError, RpcRequest, RpcResponseErrorData},
+    rpc_response::{Response, UiAccount, UiAccountEncoding},
+};
 use solana_sdk::{
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
     signature::{read_keypair_file, Keypair, Signature, Signer},
     transaction::Transaction,
 };
+use std::fmt;
+use std::future::Future;
 use std::str::FromStr;
+use std::time::Duration;
+
+use super::chain::OnChainConfig;
+use super::tree::{proof_depth, Expiration, HashAlgo, Sha256Hasher};
 
 // System program ID
 const SYSTEM_PROGRAM_ID: &str = "11111111111111111111111111111111";
 // Your deployed program ID from target/deploy/merkle_program-keypair.json
-const PROGRAM_ID: &str = "AHpuc2M3wbZceufaiE4Q2wyDXh198ymB1SxxpbxCzj3H";
+pub const PROGRAM_ID: &str = "AHpuc2M3wbZceufaiE4Q2wyDXh198ymB1SxxpbxCzj3H";
+// Loaders a deployed program account can be owned by
+const BPF_LOADER_IDS: [&str; 4] = [
+    "BPFLoaderUpgradeab1e11111111111111111111111",
+    "BPFLoader2111111111111111111111111111111111",
+    "BPFLoader1111111111111111111111111111111111",
+    "LoaderV411111111111111111111111111111111111",
+];
+
+// Limits used to size verify_batch transactions
+const MAX_TRANSACTION_SIZE: usize = 1232;
+// Fee payer signature, message header, payer/config/program keys, blockhash,
+// compact-u16 length prefixes, and the discriminator + entry count + total_leaves args
+const VERIFY_BATCH_FIXED_BYTES: usize = 64 + 3 + 3 * 32 + 32 + 7 + 8 + 4 + 8;
+// Per user: signature, account key, account index, proof length prefix, expiration, leaf_index,
+// and a leaf salt (counted as present so salted trees never overflow)
+const VERIFY_BATCH_ENTRY_BYTES: usize = 64 + 32 + 1 + 4 + 8 + 8 + 1 + 32;
+const COMPUTE_UNIT_LIMIT: u64 = 200_000;
+// Conservative estimates for in-program hashing and per-instruction overhead
+const COMPUTE_UNITS_FIXED: u64 = 20_000;
+const COMPUTE_UNITS_PER_ENTRY: u64 = 15_000;
+const COMPUTE_UNITS_PER_HASH: u64 = 5_000;
+// Base fee charged per transaction signature
+const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+// How long get_root_at_slot waits for the node to reach a future slot (~400ms per slot)
+const SLOT_POLL_INTERVAL: Duration = Duration::from_millis(400);
+const SLOT_POLL_ATTEMPTS: usize = 50;
+
+// Submissions of a transaction that was dropped before executing, each with a fresh blockhash
+const SEND_ATTEMPTS: usize = 3;
+
+// Deadline for one RPC-backed read, unless configured with `with_rpc_timeout`
+pub const DEFAULT_RPC_TIMEOUT: Duration = Duration::from_secs(30);
+
+// How long send_and_confirm_many polls before reporting still-unseen transactions as dropped
+const CONFIRM_POLL_INTERVAL: Duration = Duration::from_millis(400);
+const CONFIRM_POLL_ATTEMPTS: usize = 75;
+// Most signatures getSignatureStatuses accepts per request
+const MAX_SIGNATURE_STATUSES_PER_REQUEST: usize = 256;
+// Most signatures getSignaturesForAddress returns per page
+const SIGNATURES_PAGE_LIMIT: usize = 1000;
+
+// How a transaction error reports the System program's AccountAlreadyInUse (error 0)
+const ACCOUNT_ALREADY_IN_USE_ERROR: &str = "custom program error: 0x0";
+
+// Anchor event discriminator of RootUpdated: sha256("event:RootUpdated")[..8]
+const ROOT_UPDATED_DISCRIMINATOR: [u8; 8] = [94, 53, 22, 128, 141, 113, 98, 231];
+
+/// Largest number of users whose proofs fit in one `verify_batch` transaction,
+/// bounded by both transaction size and the compute budget (always at least 1)
+pub fn max_batch_size(total_leaves: usize) -> usize {
+    let depth = proof_depth(total_leaves);
+
+    let entry_bytes = VERIFY_BATCH_ENTRY_BYTES + 32 * depth;
+    let by_size = (MAX_TRANSACTION_SIZE - VERIFY_BATCH_FIXED_BYTES) / entry_bytes;
+
+    // One hash for the leaf plus one per proof level
+    let entry_compute = COMPUTE_UNITS_PER_ENTRY + COMPUTE_UNITS_PER_HASH * (depth as u64 + 1);
+    let by_compute = ((COMPUTE_UNIT_LIMIT - COMPUTE_UNITS_FIXED) / entry_compute) as usize;
+
+    by_size.min(by_compute).max(1)
+}
+
+/// Split items into chunks of `max_batch_size(total_leaves)`
+pub fn split_into_batches<T>(items: &[T], total_leaves: usize) -> Vec<&[T]> {
+    items.chunks(max_batch_size(total_leaves)).collect()
+}
+
+/// Budget for verifying every subscriber through `verify_batch`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyPlan {
+    pub batch_size: usize,
+    pub transactions: usize,
+    /// Base signature fees: the fee payer plus every user, on each transaction
+    pub lamports: u64,
+    /// Sending the transactions one after another, each waiting for confirmation
+    pub wall_clock: Duration,
+}
+
+/// Plan a re-verification of `total_subscribers` users against a tree of
+/// `total_leaves`, assuming each transaction takes `confirmation_latency` to confirm
+pub fn plan_mass_verify(
+    total_subscribers: usize,
+    total_leaves: usize,
+    confirmation_latency: Duration,
+) -> VerifyPlan {
+    let batch_size = max_batch_size(total_leaves);
+    let transactions = total_subscribers.div_ceil(batch_size);
+    let signatures = (transactions + total_subscribers) as u64;
+
+    VerifyPlan {
+        batch_size,
+        transactions,
+        lamports: signatures * LAMPORTS_PER_SIGNATURE,
+        wall_clock: confirmation_latency * transactions as u32,
+    }
+}
+
+// A single verify_subscription transaction paid for by the user: signature count + signature,
+// message header, key count + user/config/program keys, blockhash, instruction count,
+// program index, account count + 6 account indices (unused optional accounts use the program ID)
+const VERIFY_TX_FIXED_BYTES: usize = 1 + 64 + 3 + 1 + 3 * 32 + 32 + 1 + 1 + 1 + 6;
+// Discriminator + proof length prefix + expiration + leaf_index + total_leaves + leaf_salt tag
+// + proof_root tag + start_ts tag + expected_user tag
+const VERIFY_ARGS_FIXED_BYTES: usize = 8 + 4 + 8 + 8 + 8 + 1 + 1 + 1 + 1;
+
+/// Expected serialized size of an unsalted `verify_subscription` transaction for a tree
+/// of `total_leaves`, so callers can warn before building one that won't fit.
+/// A leaf salt or a named proof root adds 32 bytes each.
+pub fn estimated_verify_tx_size(total_leaves: usize) -> usize {
+    let data_len = VERIFY_ARGS_FIXED_BYTES + 32 * proof_depth(total_leaves);
+    // compact-u16 length prefix for the instruction data
+    let data_len_prefix = if data_len < 0x80 { 1 } else { 2 };
+    VERIFY_TX_FIXED_BYTES + data_len_prefix + data_len
+}
+
+/// Build a `verify_subscription` instruction for `user`, without token gate, receipt
+/// or rate limit account. `proof_root` names the root the proof was built against,
+/// which the program accepts only within its configured verify window. No start time
+/// is passed, so configs with `start_in_leaf` on reject it, and no expected user.
+/// Pass the client's `program_id()`, as for `derive_config_pda`.
+pub fn verify_subscription_instruction(
+    program_id: &Pubkey,
+    user: &Pubkey,
+    proof_bytes: &[u8],
+    expiration: Expiration,
+    leaf_index: usize,
+    total_leaves: usize,
+    leaf_salt: Option<[u8; 32]>,
+    proof_root: Option<[u8; 32]>,
+) -> Result<Instruction> {
+    let program_id = *program_id;
+    let (config_pda, _bump) = derive_config_pda(&program_id);
+
+    // Build instruction data: discriminator + proof_bytes + expiration + leaf_index + total_leaves
+    // + leaf_salt + proof_root + start_ts + expected_user
+    let mut instruction_data = Vec::new();
+    instruction_data.extend_from_slice(&instruction_discriminator("verify_subscription"));
+    instruction_data.extend_from_slice(&(proof_bytes.len() as u32).to_le_bytes());
+    instruction_data.extend_from_slice(proof_bytes);
+    instruction_data.extend_from_slice(&expiration.to_le_bytes());
+    instruction_data.extend_from_slice(&(leaf_index as u64).to_le_bytes());
+    instruction_data.extend_from_slice(&(total_leaves as u64).to_le_bytes());
+    encode_optional_hash(&mut instruction_data, leaf_salt);
+    encode_optional_hash(&mut instruction_data, proof_root);
+    instruction_data.push(0); // start_ts: None
+    instruction_data.push(0); // expected_user: None
+
+    // Anchor passes the program ID in place of omitted optional accounts
+    Ok(Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(config_pda, false),
+            AccountMeta::new(*user, true),
+            AccountMeta::new_readonly(program_id, false), // token_account
+            AccountMeta::new_readonly(program_id, false), // receipt
+            AccountMeta::new_readonly(program_id, false), // rate_limit
+            AccountMeta::new_readonly(program_id, false), // system_program
+        ],
+        data: instruction_data,
+    })
+}
+
+/// `verify_subscription` arguments as JSON for the Anchor TypeScript client, keyed by
+/// the instruction's argument names: the proof as a byte array, and 64-bit integers
+/// as decimal strings (JS numbers lose precision past 2^53) to wrap in `BN`.
+/// The optional arguments are `null`, for an unsalted proof against the current root.
+pub fn to_anchor_verify_args(
+    proof_bytes: &[u8],
+    expiration: Expiration,
+    leaf_index: usize,
+    total_leaves: usize,
+) -> serde_json::Value {
+    serde_json::json!({
+        "proof_bytes": proof_bytes,
+        "expiration": expiration.as_i64().to_string(),
+        "leaf_index": leaf_index.to_string(),
+        "total_leaves": total_leaves.to_string(),
+        "leaf_salt": null,
+        "proof_root": null,
+        "start_ts": null,
+        "expected_user": null,
+    })
+}
+
+/// One user's proof for `SolanaClient::verify_batch`
+pub struct BatchVerifyEntry<'a> {
+    pub user: &'a Keypair,
+    pub proof_bytes: Vec<u8>,
+    pub expiration: Expiration,
+    pub leaf_index: usize,
+    pub leaf_salt: Option<[u8; 32]>,
+}
+
+/// Borsh `Option<[u8; 32]>`: a 0/1 tag, then the bytes if present
+fn encode_optional_hash(instruction_data: &mut Vec<u8>, hash: Option<[u8; 32]>) {
+    match hash {
+        Some(hash) => {
+            instruction_data.push(1);
+            instruction_data.extend_from_slice(&hash);
+        }
+        None => instruction_data.push(0),
+    }
+}
+
+/// The RPC node could not serve config state pinned to the requested slot.
+///
+/// Standard RPC nodes only answer with their latest state, so a slot can only be
+/// read while it is current; older slots need an archival snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoricalStateUnavailable {
+    pub requested_slot: u64,
+    pub served_slot: u64,
+}
+
+impl fmt::Display for HistoricalStateUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Historical state unavailable: requested slot {} but RPC served slot {}",
+            self.requested_slot, self.served_slot
+        )
+    }
+}
+
+impl std::error::Error for HistoricalStateUnavailable {}
+
+/// An RPC call didn't complete within the client's per-call deadline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcTimeout {
+    pub timeout: Duration,
+}
+
+impl fmt::Display for RpcTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RPC call timed out after {:?}", self.timeout)
+    }
+}
+
+impl std::error::Error for RpcTimeout {}
+
+/// A transaction executed (or failed preflight simulation) with an error.
+///
+/// Unlike a dropped transaction this is terminal: resubmitting would fail the same way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionFailed {
+    pub signature: Signature,
+    pub error: String,
+    /// Anchor error name from the program logs (e.g. `Unauthorized`), when available
+    pub program_error: Option<String>,
+}
+
+impl fmt::Display for TransactionFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Transaction {} failed: {}", self.signature, self.error)?;
+        if let Some(program_error) = &self.program_error {
+            write!(f, " ({})", program_error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TransactionFailed {}
+
+impl TransactionFailed {
+    /// Whether an account the transaction creates already exists: the System
+    /// program's `AccountAlreadyInUse` (custom error 0, surfaced through the CPI).
+    /// The program's own errors start at 6000, so this can't be one of them.
+    pub fn is_account_already_in_use(&self) -> bool {
+        self.error.contains(ACCOUNT_ALREADY_IN_USE_ERROR)
+    }
+}
+
+/// How a transaction whose `send_and_confirm_transaction` errored actually ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendOutcome {
+    /// Confirmed after all; only waiting for the confirmation failed
+    Landed,
+    /// Never executed, so resubmitting with a fresh blockhash is safe
+    Dropped,
+    /// Executed with an error; must not be resubmitted
+    Failed(TransactionFailed),
+}
+
+impl SendOutcome {
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, SendOutcome::Dropped)
+    }
+}
+
+/// Anchor error name from transaction logs, e.g. `Unauthorized` from
+/// "AnchorError occurred. Error Code: Unauthorized. Error Number: 6000. ..."
+pub fn program_error_from_logs(logs: &[String]) -> Option<String> {
+    logs.iter().find_map(|line| {
+        let code = line.split("Error Code: ").nth(1)?;
+        code.split('.').next().map(str::to_string)
+    })
+}
+
+/// Decoded `RootUpdated` event, emitted by `update_root`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootUpdated {
+    pub old_root: [u8; 32],
+    pub new_root: [u8; 32],
+    pub updated_by: Pubkey,
+    pub timestamp: i64,
+}
+
+/// `RootUpdated` events in transaction logs, in emission order. Anchor logs events as
+/// `Program data: <base64>`; other events and undecodable lines are skipped.
+pub fn root_updates_from_logs(logs: &[String]) -> Vec<RootUpdated> {
+    logs.iter()
+        .filter_map(|line| {
+            let data = BASE64.decode(line.strip_prefix("Program data: ")?).ok()?;
+            let event = data.strip_prefix(&ROOT_UPDATED_DISCRIMINATOR[..])?;
+            let (old_root, event) = event.split_first_chunk::<32>()?;
+            let (new_root, event) = event.split_first_chunk::<32>()?;
+            let (updated_by, event) = event.split_first_chunk::<32>()?;
+            let (timestamp, _) = event.split_first_chunk::<8>()?;
+            Some(RootUpdated {
+                old_root: *old_root,
+                new_root: *new_root,
+                updated_by: Pubkey::new_from_array(*updated_by),
+                timestamp: i64::from_le_bytes(*timestamp),
+            })
+        })
+        .collect()
+}
+
+/// The on-chain config is bound to a different network than this backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainDomainMismatch {
+    pub expected: [u8; 8],
+    pub on_chain: [u8; 8],
+}
+
+impl fmt::Display for ChainDomainMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Chain domain mismatch: backend is configured for {} but the config is bound to {}",
+            hex::encode(self.expected),
+            hex::encode(self.on_chain)
+        )
+    }
+}
+
+impl std::error::Error for ChainDomainMismatch {}
+
+/// Address and bump of the config PDA (seed `"config"`) for `program_id`.
+///
+/// Each deployment has exactly one config, so the program ID alone determines it.
+pub fn derive_config_pda(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config"], program_id)
+}
+
+/// Anchor instruction discriminator: the first 8 bytes of `Sha256("global:<name>")`,
+/// with `instruction_name` in the program's snake_case
+pub fn instruction_discriminator(instruction_name: &str) -> [u8; 8] {
+    let hash = Sha256Hasher::hash(format!("global:{}", instruction_name).as_bytes());
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash[..8]);
+    discriminator
+}
+
+/// Chain domain for a cluster name: the first 8 bytes of `Sha256(name)`
+pub fn chain_domain_from_name(cluster_name: &str) -> [u8; 8] {
+    let hash = Sha256Hasher::hash(cluster_name.as_bytes());
+    let mut domain = [0u8; 8];
+    domain.copy_from_slice(&hash[..8]);
+    domain
+}
+
+/// Extract the merkle root from raw `SubscriptionConfig` account data
+fn root_from_config_data(account_data: &[u8]) -> Result<[u8; 32]> {
+    // Anchor account layout: 8-byte discriminator + account data
+    // SubscriptionConfig: authority(32) + merkle_root(32) + bump(1)
+    if account_data.len() < 8 + 32 + 32 {
+        return Err(anyhow::anyhow!("Invalid account data length"));
+    }
+
+    // Root is at offset 8 (discriminator) + 32 (authority) = 40
+    let mut root = [0u8; 32];
+    root.copy_from_slice(&account_data[40..72]);
+
+    Ok(root)
+}
+
+fn confirmed_rpc_client(rpc_url: &str) -> RpcClient {
+    RpcClient::new_with_timeout_and_commitment(
+        rpc_url.to_string(),
+        DEFAULT_RPC_TIMEOUT,
+        CommitmentConfig::confirmed(),
+    )
+}
+
+/// Parse a 64-byte ed25519 keypair (secret key, then public key), rejecting input
+/// whose public half doesn't belong to its secret half
+pub fn keypair_from_bytes(keypair_bytes: &[u8]) -> Result<Keypair> {
+    anyhow::ensure!(
+        keypair_bytes.len() == 64,
+        "Authority keypair must be 64 bytes, got {}",
+        keypair_bytes.len()
+    );
+    Keypair::try_from(keypair_bytes)
+        .map_err(|e| anyhow::anyhow!("Invalid authority keypair: {}", e))
+}
+
+/// Parse a base58-encoded 64-byte keypair, as in `AUTHORITY_SECRET_KEY`
+pub fn keypair_from_base58(secret: &str) -> Result<Keypair> {
+    let keypair_bytes = bs58::decode(secret.trim())
+        .into_vec()
+        .context("Authority secret key must be base58")?;
+    keypair_from_bytes(&keypair_bytes)
+}
 
 pub struct SolanaClient {
     rpc_client: RpcClient,
     authority_keypair: Keypair,
+    chain_domain: [u8; 8],
+    program_id: Pubkey,
+    rpc_timeout: Duration,
 }
 
 impl SolanaClient {
     /// Initialize Solana client with RPC URL and authority keypair path
     pub fn new(rpc_url: &str, keypair_path: &str) -> Result<Self> {
-        let rpc_client =
-            RpcClient::new_with_commitment(rpc_url.to_string(), CommitmentConfig::confirmed());
-
         let authority_keypair = read_keypair_file(keypair_path)
             .map_err(|e| anyhow::anyhow!("Failed to read authority keypair: {}", e))?;
 
-        Ok(Self {
+        Ok(Self::with_rpc_client(
+            confirmed_rpc_client(rpc_url),
+            authority_keypair,
+        ))
+    }
+
+    /// Initialize from the authority's 64 raw keypair bytes, for keys injected as
+    /// secrets rather than mounted as files
+    pub fn from_keypair_bytes(rpc_url: &str, keypair_bytes: &[u8]) -> Result<Self> {
+        Ok(Self::with_rpc_client(
+            confirmed_rpc_client(rpc_url),
+            keypair_from_bytes(keypair_bytes)?,
+        ))
+    }
+
+    /// Initialize from a base58 secret key, the format wallets export it in
+    pub fn from_base58_secret(rpc_url: &str, secret: &str) -> Result<Self> {
+        Ok(Self::with_rpc_client(
+            confirmed_rpc_client(rpc_url),
+            keypair_from_base58(secret)?,
+        ))
+    }
+
+    /// Initialize for reads only, such as auditing the on-chain root without the
+    /// authority keypair; transactions are signed by a throwaway key the program rejects
+    pub fn read_only(rpc_url: &str) -> Self {
+        Self::with_rpc_client(confirmed_rpc_client(rpc_url), Keypair::new())
+    }
+
+    /// Build from an existing RPC client (e.g. a mock) and authority keypair
+    pub fn with_rpc_client(rpc_client: RpcClient, authority_keypair: Keypair) -> Self {
+        Self {
             rpc_client,
             authority_keypair,
-        })
+            chain_domain: [0u8; 8],
+            program_id: Pubkey::from_str(PROGRAM_ID).expect("PROGRAM_ID is a valid pubkey"),
+            rpc_timeout: DEFAULT_RPC_TIMEOUT,
+        }
+    }
+
+    /// Deadline for each RPC-backed read. The blocking `RpcClient` can only be
+    /// interrupted by its own HTTP timeout, so build it with the same value
+    /// (`RpcClient::new_with_timeout_and_commitment`).
+    pub fn with_rpc_timeout(mut self, rpc_timeout: Duration) -> Self {
+        self.rpc_timeout = rpc_timeout;
+        self
+    }
+
+    /// Program this client talks to; defaults to `PROGRAM_ID`
+    pub fn with_program_id(mut self, program_id: Pubkey) -> Self {
+        self.program_id = program_id;
+        self
+    }
+
+    /// Network this backend pushes roots to (see `chain_domain_from_name`); written at
+    /// initialization and checked before every root push. Defaults to all zeroes.
+    pub fn with_chain_domain(mut self, chain_domain: [u8; 8]) -> Self {
+        self.chain_domain = chain_domain;
+        self
+    }
+
+    /// Public key of the loaded authority keypair
+    pub fn authority(&self) -> Pubkey {
+        self.authority_keypair.pubkey()
+    }
+
+    /// Program this client talks to (see `with_program_id`)
+    pub fn program_id(&self) -> Pubkey {
+        self.program_id
+    }
+
+    /// Refuse to touch a config bound to another network
+    async fn ensure_chain_domain(&self) -> Result<()> {
+        let on_chain = self.fetch_config().await?.chain_domain;
+        if on_chain != self.chain_domain {
+            return Err(ChainDomainMismatch {
+                expected: self.chain_domain,
+                on_chain,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Run `call` under the per-call deadline, reporting an expired deadline (ours, or
+    /// the HTTP client's own) as `RpcTimeout`
+    async fn with_deadline<T>(&self, call: impl Future<Output = Result<T>>) -> Result<T> {
+        let timeout = self.rpc_timeout;
+        match tokio::time::timeout(timeout, call).await {
+            Ok(Err(e)) if is_http_timeout(&e) => Err(RpcTimeout { timeout }.into()),
+            Ok(result) => result,
+            Err(_elapsed) => Err(RpcTimeout { timeout }.into()),
+        }
     }
 
     /// Derive the config PDA (must match the Anchor program)
     fn get_config_pda(&self) -> Result<(Pubkey, u8)> {
-        let program_id = Pubkey::from_str(PROGRAM_ID)?;
-        let (pda, bump) = Pubkey::find_program_address(&[b"config"], &program_id);
-        Ok((pda, bump))
+        let program_id = self.program_id;
+        Ok(derive_config_pda(&program_id))
     }
 
     /// Initialize the subscription config with an initial merkle root
     pub async fn initialize_config(&self, initial_root: [u8; 32]) -> Result<Signature> {
-        let program_id = Pubkey::from_str(PROGRAM_ID)?;
+        let program_id = self.program_id;
         let (config_pda, _bump) = self.get_config_pda()?;
 
-        // Build instruction data: discriminator (8 bytes) + root (32 bytes)
-        // Discriminator from IDL: [175, 175, 109, 31, 13, 152, 155, 237]
+        // Build instruction data: discriminator (8 bytes) + root (32 bytes) + chain domain (8 bytes)
         let mut instruction_data = Vec::new();
-        let discriminator: [u8; 8] = [175, 175, 109, 31, 13, 152, 155, 237];
-        instruction_data.extend_from_slice(&discriminator);
+        instruction_data.extend_from_slice(&instruction_discriminator("initialize"));
         instruction_data.extend_from_slice(&initial_root);
+        instruction_data.extend_from_slice(&self.chain_domain);
 
         let instruction = Instruction {
             program_id,
@@ -71,16 +588,66 @@ impl SolanaClient {
         Ok(signature)
     }
 
-    /// Update the merkle root on-chain
+    /// Initialize the config with `initial_root` unless the account already exists.
+    ///
+    /// Returns `None` if it did; other RPC errors are surfaced rather than taken
+    /// as a missing account.
+    pub async fn initialize_config_if_needed(
+        &self,
+        initial_root: [u8; 32],
+    ) -> Result<Option<Signature>> {
+        let (config_pda, _bump) = self.get_config_pda()?;
+        let existing = self
+            .with_deadline(async {
+                self.rpc_client
+                    .get_account_with_commitment(&config_pda, self.rpc_client.commitment())
+                    .context("Failed to look up config account")
+            })
+            .await?
+            .value;
+        if existing.is_some() {
+            return Ok(None);
+        }
+
+        let result = self.initialize_config(initial_root).await;
+        self.resolve_initialize_race(result).await
+    }
+
+    /// Another process may create the config between the existence check and our
+    /// init landing. Our init then fails with "already in use", which means the
+    /// config exists, so it's reported like a config that was already there once it
+    /// reads back. Any other failure is returned as is.
+    async fn resolve_initialize_race(
+        &self,
+        result: Result<Signature>,
+    ) -> Result<Option<Signature>> {
+        let error = match result {
+            Ok(signature) => return Ok(Some(signature)),
+            Err(error) => error,
+        };
+        let lost_race = error
+            .downcast_ref::<TransactionFailed>()
+            .is_some_and(TransactionFailed::is_account_already_in_use);
+        if !lost_race {
+            return Err(error);
+        }
+
+        self.fetch_config()
+            .await
+            .context("Config init reported the account in use, but it can't be read")?;
+        println!("   Config was initialized concurrently by another process");
+        Ok(None)
+    }
+
+    /// Update the merkle root on-chain, after checking the config's chain domain
     pub async fn update_merkle_root(&self, new_root: [u8; 32]) -> Result<Signature> {
-        let program_id = Pubkey::from_str(PROGRAM_ID)?;
+        let program_id = self.program_id;
         let (config_pda, _bump) = self.get_config_pda()?;
+        self.ensure_chain_domain().await?;
 
         // Build instruction data: discriminator + new_root
-        // Discriminator from IDL: [58, 195, 57, 246, 116, 198, 170, 138]
         let mut instruction_data = Vec::new();
-        let discriminator: [u8; 8] = [58, 195, 57, 246, 116, 198, 170, 138];
-        instruction_data.extend_from_slice(&discriminator);
+        instruction_data.extend_from_slice(&instruction_discriminator("update_root"));
         instruction_data.extend_from_slice(&new_root);
 
         let instruction = Instruction {
@@ -101,40 +668,1070 @@ impl SolanaClient {
         Ok(signature)
     }
 
+    /// Switch the on-chain hash algorithm together with a root computed under it
+    pub async fn migrate_hash_algo(
+        &self,
+        hash_algo: HashAlgo,
+        new_root: [u8; 32],
+    ) -> Result<Signature> {
+        let program_id = self.program_id;
+        let (config_pda, _bump) = self.get_config_pda()?;
+        self.ensure_chain_domain().await?;
+
+        // Build instruction data: discriminator + hash_algo (borsh enum tag) + new_root
+        let mut instruction_data = Vec::new();
+        instruction_data.extend_from_slice(&instruction_discriminator("migrate_hash_algo"));
+        instruction_data.push(hash_algo.to_u8());
+        instruction_data.extend_from_slice(&new_root);
+
+        let instruction = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(config_pda, false),
+                AccountMeta::new_readonly(self.authority_keypair.pubkey(), true),
+            ],
+            data: instruction_data,
+        };
+
+        let signature = self.send_transaction(&[instruction]).await?;
+
+        println!("✅ Migrated hash algorithm on-chain");
+        println!("   Algorithm: {:?}", hash_algo);
+        println!("   New Root: {}", hex::encode(new_root));
+        println!("   Signature: {}", signature);
+
+        Ok(signature)
+    }
+
+    /// Switch the on-chain leaf layout (static allowlist when `expiration_in_leaf` is
+    /// false) together with a root built under it
+    pub async fn set_expiration_in_leaf(
+        &self,
+        expiration_in_leaf: bool,
+        new_root: [u8; 32],
+    ) -> Result<Signature> {
+        let program_id = self.program_id;
+        let (config_pda, _bump) = self.get_config_pda()?;
+        self.ensure_chain_domain().await?;
+
+        // Build instruction data: discriminator + expiration_in_leaf (borsh bool) + new_root
+        let mut instruction_data = Vec::new();
+        instruction_data.extend_from_slice(&instruction_discriminator("set_expiration_in_leaf"));
+        instruction_data.push(expiration_in_leaf as u8);
+        instruction_data.extend_from_slice(&new_root);
+
+        let instruction = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(config_pda, false),
+                AccountMeta::new_readonly(self.authority_keypair.pubkey(), true),
+            ],
+            data: instruction_data,
+        };
+
+        let signature = self.send_transaction(&[instruction]).await?;
+
+        println!("✅ Updated leaf layout on-chain");
+        println!("   Expiration in leaf: {}", expiration_in_leaf);
+        println!("   New Root: {}", hex::encode(new_root));
+        println!("   Signature: {}", signature);
+
+        Ok(signature)
+    }
+
+    /// Switch leaves to include each subscription's start time (or back), together
+    /// with a root built under the new layout
+    pub async fn set_start_in_leaf(
+        &self,
+        start_in_leaf: bool,
+        new_root: [u8; 32],
+    ) -> Result<Signature> {
+        let program_id = self.program_id;
+        let (config_pda, _bump) = self.get_config_pda()?;
+        self.ensure_chain_domain().await?;
+
+        // Build instruction data: discriminator + start_in_leaf (borsh bool) + new_root
+        let mut instruction_data = Vec::new();
+        instruction_data.extend_from_slice(&instruction_discriminator("set_start_in_leaf"));
+        instruction_data.push(start_in_leaf as u8);
+        instruction_data.extend_from_slice(&new_root);
+
+        let instruction = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(config_pda, false),
+                AccountMeta::new_readonly(self.authority_keypair.pubkey(), true),
+            ],
+            data: instruction_data,
+        };
+
+        let signature = self.send_transaction(&[instruction]).await?;
+
+        println!("✅ Updated leaf layout on-chain");
+        println!("   Start in leaf: {}", start_in_leaf);
+        println!("   New Root: {}", hex::encode(new_root));
+        println!("   Signature: {}", signature);
+
+        Ok(signature)
+    }
+
+    /// Grow a config account created under an older program version to the current
+    /// layout; the authority pays the extra rent. Harmless if already current.
+    pub async fn realloc_config(&self) -> Result<Signature> {
+        let program_id = self.program_id;
+        let (config_pda, _bump) = self.get_config_pda()?;
+        let system_program_id = Pubkey::from_str(SYSTEM_PROGRAM_ID)?;
+
+        let instruction = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(config_pda, false),
+                AccountMeta::new(self.authority_keypair.pubkey(), true),
+                AccountMeta::new_readonly(system_program_id, false),
+            ],
+            data: instruction_discriminator("realloc_config").to_vec(),
+        };
+
+        let signature = self.send_transaction(&[instruction]).await?;
+
+        println!("✅ Config account resized to the current layout");
+        println!("   Signature: {}", signature);
+
+        Ok(signature)
+    }
+
+    /// Check that this client's program is deployed on its cluster: the account
+    /// exists, is executable and is owned by a BPF loader. Catches a backend pointed
+    /// at the wrong cluster before any instruction is sent.
+    pub async fn verify_program_deployed(&self) -> Result<()> {
+        let program_id = self.program_id;
+        let account = self
+            .with_deadline(async {
+                self.rpc_client
+                    .get_account_with_commitment(&program_id, self.rpc_client.commitment())
+                    .context("Failed to fetch program account")
+            })
+            .await?
+            .value
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Program {} is not deployed on this cluster (check SOLANA_RPC_URL)",
+                    program_id
+                )
+            })?;
+
+        if !account.executable {
+            return Err(anyhow::anyhow!(
+                "Account {} exists but is not an executable program",
+                program_id
+            ));
+        }
+        let owner = account.owner.to_string();
+        if !BPF_LOADER_IDS.contains(&owner.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Program {} is owned by {}, not a BPF loader",
+                program_id,
+                owner
+            ));
+        }
+        Ok(())
+    }
+
+    /// Check that the config's stored `bump` is the canonical bump derived locally for
+    /// the config seed. A mismatch means the account wasn't created by this program's
+    /// `initialize` (or the seeds drifted), and PDA-signed instructions would fail.
+    pub async fn verify_config_bump(&self) -> Result<bool> {
+        let (_, canonical_bump) = self.get_config_pda()?;
+        let config = self.fetch_config().await?;
+        if config.bump != canonical_bump {
+            eprintln!(
+                "⚠️  Config bump {} does not match the canonical bump {}",
+                config.bump, canonical_bump
+            );
+        }
+        Ok(config.bump == canonical_bump)
+    }
+
     /// Get the current merkle root from on-chain config
     pub async fn get_current_root(&self) -> Result<[u8; 32]> {
         let (config_pda, _bump) = self.get_config_pda()?;
 
         let account_data = self
-            .rpc_client
-            .get_account_data(&config_pda)
-            .context("Failed to fetch config account. Has it been initialized?")?;
+            .with_deadline(async {
+                self.rpc_client
+                    .get_account_data(&config_pda)
+                    .context("Failed to fetch config account. Has it been initialized?")
+            })
+            .await?;
+
+        root_from_config_data(&account_data)
+    }
+
+    /// Fetch and decode the whole config account
+    pub async fn fetch_config(&self) -> Result<OnChainConfig> {
+        let (config_pda, _bump) = self.get_config_pda()?;
+
+        let account_data = self
+            .with_deadline(async {
+                self.rpc_client
+                    .get_account_data(&config_pda)
+                    .context("Failed to fetch config account. Has it been initialized?")
+            })
+            .await?;
+
+        OnChainConfig::from_account_data(&account_data)
+    }
+
+    /// Get the on-chain merkle root as of `slot`, waiting for the node to reach it
+    /// if it is in the future.
+    ///
+    /// Fails with `HistoricalStateUnavailable` when the node can only answer from a
+    /// later slot, which is the case for any past slot on a non-archival RPC.
+    pub async fn get_root_at_slot(&self, slot: u64) -> Result<[u8; 32]> {
+        let (config_pda, _bump) = self.get_config_pda()?;
+        let config = RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(CommitmentConfig::confirmed()),
+            min_context_slot: Some(slot),
+            ..RpcAccountInfoConfig::default()
+        };
+
+        for _ in 0..SLOT_POLL_ATTEMPTS {
+            // Raw request: the typed getters rewrap RPC errors and drop the error code
+            let response: Response<Option<UiAccount>> = match self.rpc_client.send(
+                RpcRequest::GetAccountInfo,
+                serde_json::json!([config_pda.to_string(), config]),
+            ) {
+                Ok(response) => response,
+                Err(e) if is_min_context_slot_not_reached(e.kind()) => {
+                    tokio::time::sleep(SLOT_POLL_INTERVAL).await;
+                    continue;
+                }
+                Err(e) => return Err(e).context("Failed to fetch config account"),
+            };
 
-        // Anchor account layout: 8-byte discriminator + account data
-        // SubscriptionConfig: authority(32) + merkle_root(32) + bump(1)
-        if account_data.len() < 8 + 32 + 32 {
-            return Err(anyhow::anyhow!("Invalid account data length"));
+            if response.context.slot != slot {
+                return Err(HistoricalStateUnavailable {
+                    requested_slot: slot,
+                    served_slot: response.context.slot,
+                }
+                .into());
+            }
+            let account_data = response
+                .value
+                .and_then(|account| account.data.decode())
+                .ok_or_else(|| anyhow::anyhow!("Config account not found at slot {}", slot))?;
+            return root_from_config_data(&account_data);
         }
 
-        // Root is at offset 8 (discriminator) + 32 (authority) = 40
-        let mut root = [0u8; 32];
-        root.copy_from_slice(&account_data[40..72]);
+        Err(anyhow::anyhow!("Timed out waiting for slot {}", slot))
+    }
+
+    /// Every `RootUpdated` event emitted against the config account, oldest first, with
+    /// the transaction that emitted it. Only as complete as the node's transaction
+    /// history; failed transactions are skipped.
+    pub async fn root_update_history(&self) -> Result<Vec<(Signature, RootUpdated)>> {
+        let (config_pda, _bump) = self.get_config_pda()?;
+        let mut transactions = Vec::new();
+        let mut before = None;
+
+        // Pages come newest first
+        loop {
+            let page = self
+                .rpc_client
+                .get_signatures_for_address_with_config(
+                    &config_pda,
+                    GetConfirmedSignaturesForAddress2Config {
+                        before,
+                        until: None,
+                        limit: Some(SIGNATURES_PAGE_LIMIT),
+                        commitment: Some(CommitmentConfig::confirmed()),
+                    },
+                )
+                .context("Failed to fetch config transaction history")?;
+
+            for status in page.iter().filter(|status| status.err.is_none()) {
+                let signature = Signature::from_str(&status.signature)?;
+                let logs = self
+                    .transaction_logs(&signature)
+                    .ok_or_else(|| anyhow::anyhow!("No logs for transaction {}", signature))?;
+                transactions.push((signature, root_updates_from_logs(&logs)));
+            }
+
+            match page.last() {
+                Some(oldest) if page.len() == SIGNATURES_PAGE_LIMIT => {
+                    before = Some(Signature::from_str(&oldest.signature)?);
+                }
+                _ => break,
+            }
+        }
+
+        Ok(transactions
+            .into_iter()
+            .rev()
+            .flat_map(|(signature, events)| events.into_iter().map(move |event| (signature, event)))
+            .collect())
+    }
+
+    /// Verify many users on-chain, split into as many `verify_batch` transactions
+    /// as needed to stay under size and compute limits. Submitted sequentially.
+    pub async fn verify_batch(
+        &self,
+        entries: &[BatchVerifyEntry<'_>],
+        total_leaves: usize,
+    ) -> Result<Vec<Signature>> {
+        let program_id = self.program_id;
+        let (config_pda, _bump) = self.get_config_pda()?;
+
+        let batches = split_into_batches(entries, total_leaves);
+        println!(
+            "📦 Verifying {} users in batches of {} ({} transactions)",
+            entries.len(),
+            max_batch_size(total_leaves),
+            batches.len()
+        );
+
+        let mut signatures = Vec::with_capacity(batches.len());
+        for batch in batches {
+            // Build instruction data: discriminator + Vec<BatchEntry> + total_leaves
+            let mut instruction_data = Vec::new();
+            instruction_data.extend_from_slice(&instruction_discriminator("verify_batch"));
+            instruction_data.extend_from_slice(&(batch.len() as u32).to_le_bytes());
+            for entry in batch {
+                instruction_data.extend_from_slice(&(entry.proof_bytes.len() as u32).to_le_bytes());
+                instruction_data.extend_from_slice(&entry.proof_bytes);
+                instruction_data.extend_from_slice(&entry.expiration.to_le_bytes());
+                instruction_data.extend_from_slice(&(entry.leaf_index as u64).to_le_bytes());
+                encode_optional_hash(&mut instruction_data, entry.leaf_salt);
+            }
+            instruction_data.extend_from_slice(&(total_leaves as u64).to_le_bytes());
+
+            // Config first, then each user as a signer in entry order
+            let mut accounts = vec![AccountMeta::new_readonly(config_pda, false)];
+            accounts.extend(
+                batch
+                    .iter()
+                    .map(|entry| AccountMeta::new_readonly(entry.user.pubkey(), true)),
+            );
+
+            let instruction = Instruction {
+                program_id,
+                accounts,
+                data: instruction_data,
+            };
+
+            let users: Vec<&Keypair> = batch.iter().map(|entry| entry.user).collect();
+            signatures.push(
+                self.send_transaction_with_signers(&[instruction], &users)
+                    .await?,
+            );
+        }
 
-        Ok(root)
+        Ok(signatures)
+    }
+
+    /// Submit already-signed transactions together, then confirm them with one
+    /// `getSignatureStatuses` poll per round instead of one confirmation per transaction.
+    ///
+    /// Returns each transaction's signature and outcome in input order. `Dropped`
+    /// transactions never appeared before the poll gave up and are safe to rebuild
+    /// with a fresh blockhash and resubmit; the rest are settled.
+    pub async fn send_and_confirm_many(
+        &self,
+        transactions: &[Transaction],
+    ) -> Result<Vec<(Signature, SendOutcome)>> {
+        let mut outcomes: Vec<(Signature, Option<SendOutcome>)> = transactions
+            .iter()
+            .map(|transaction| {
+                let signature = transaction.signatures[0];
+                let outcome = match self.rpc_client.send_transaction(transaction) {
+                    Ok(_) => None,
+                    Err(e) => Some(self.classify_send_error(&signature, &e)),
+                };
+                (signature, outcome)
+            })
+            .collect();
+
+        for attempt in 0..CONFIRM_POLL_ATTEMPTS {
+            let pending: Vec<usize> = (0..outcomes.len())
+                .filter(|&i| outcomes[i].1.is_none())
+                .collect();
+            if pending.is_empty() {
+                break;
+            }
+            if attempt > 0 {
+                tokio::time::sleep(CONFIRM_POLL_INTERVAL).await;
+            }
+
+            for chunk in pending.chunks(MAX_SIGNATURE_STATUSES_PER_REQUEST) {
+                let signatures: Vec<Signature> = chunk.iter().map(|&i| outcomes[i].0).collect();
+                let statuses = self
+                    .rpc_client
+                    .get_signature_statuses(&signatures)
+                    .context("Failed to fetch signature statuses")?
+                    .value;
+
+                for (&i, status) in chunk.iter().zip(statuses) {
+                    let Some(status) = status else { continue };
+                    let signature = outcomes[i].0;
+                    outcomes[i].1 = match status.err {
+                        Some(e) => Some(SendOutcome::Failed(TransactionFailed {
+                            signature,
+                            error: e.to_string(),
+                            program_error: self
+                                .transaction_logs(&signature)
+                                .as_deref()
+                                .and_then(program_error_from_logs),
+                        })),
+                        None if status.satisfies_commitment(self.rpc_client.commitment()) => {
+                            Some(SendOutcome::Landed)
+                        }
+                        // Processed but not yet confirmed: keep polling
+                        None => None,
+                    };
+                }
+            }
+        }
+
+        Ok(outcomes
+            .into_iter()
+            .map(|(signature, outcome)| (signature, outcome.unwrap_or(SendOutcome::Dropped)))
+            .collect())
     }
 
     /// Helper to reduce code duplication
     async fn send_transaction(&self, instructions: &[Instruction]) -> Result<Signature> {
-        let recent_blockhash = self.rpc_client.get_latest_blockhash()?;
+        self.send_transaction_with_signers(instructions, &[]).await
+    }
+
+    /// Send with the authority as fee payer plus any additional required signers
+    async fn send_transaction_with_signers(
+        &self,
+        instructions: &[Instruction],
+        extra_signers: &[&Keypair],
+    ) -> Result<Signature> {
+        let mut signers = vec![&self.authority_keypair];
+        signers.extend_from_slice(extra_signers);
+
+        for attempt in 1..=SEND_ATTEMPTS {
+            let recent_blockhash = self.rpc_client.get_latest_blockhash()?;
+            let transaction = Transaction::new_signed_with_payer(
+                instructions,
+                Some(&self.authority_keypair.pubkey()),
+                &signers,
+                recent_blockhash,
+            );
+            let signature = transaction.signatures[0];
+
+            let error = match self.rpc_client.send_and_confirm_transaction(&transaction) {
+                Ok(signature) => return Ok(signature),
+                Err(e) => e,
+            };
+            match self.classify_send_error(&signature, &error) {
+                SendOutcome::Landed => return Ok(signature),
+                SendOutcome::Dropped => {
+                    eprintln!(
+                        "⚠️  Transaction {} dropped (attempt {}/{}): {}",
+                        signature, attempt, SEND_ATTEMPTS, error
+                    );
+                }
+                SendOutcome::Failed(failed) => return Err(failed.into()),
+            }
+        }
+
+        Err(anyhow::anyhow!(
+            "Failed to send transaction: dropped {} times",
+            SEND_ATTEMPTS
+        ))
+    }
+
+    /// Decide whether a failed send executed, using the preflight result if the
+    /// node simulated it, otherwise the signature's status on chain
+    fn classify_send_error(&self, signature: &Signature, error: &ClientError) -> SendOutcome {
+        if let ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            message,
+            data: RpcResponseErrorData::SendTransactionPreflightFailure(simulation),
+            ..
+        }) = error.kind()
+        {
+            return SendOutcome::Failed(TransactionFailed {
+                signature: *signature,
+                error: message.clone(),
+                program_error: simulation.logs.as_deref().and_then(program_error_from_logs),
+            });
+        }
+
+        match self.rpc_client.get_signature_status(signature) {
+            Ok(None) => SendOutcome::Dropped,
+            Ok(Some(Ok(()))) => SendOutcome::Landed,
+            Ok(Some(Err(e))) => SendOutcome::Failed(TransactionFailed {
+                signature: *signature,
+                error: e.to_string(),
+                program_error: self
+                    .transaction_logs(signature)
+                    .as_deref()
+                    .and_then(program_error_from_logs),
+            }),
+            // Unknown status: it may have executed, so don't risk submitting it twice
+            Err(e) => SendOutcome::Failed(TransactionFailed {
+                signature: *signature,
+                error: format!("{} (status unavailable: {})", error, e),
+                program_error: None,
+            }),
+        }
+    }
+
+    /// Log messages of a confirmed transaction, if the node still has them
+    fn transaction_logs(&self, signature: &Signature) -> Option<Vec<String>> {
+        let config = RpcTransactionConfig {
+            commitment: Some(CommitmentConfig::confirmed()),
+            max_supported_transaction_version: Some(0),
+            ..RpcTransactionConfig::default()
+        };
+        let transaction = self
+            .rpc_client
+            .get_transaction_with_config(signature, config)
+            .ok()?;
+        transaction.transaction.meta?.log_messages.into()
+    }
+}
+
+fn is_http_timeout(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<ClientError>().map(ClientError::kind),
+            Some(ClientErrorKind::Reqwest(e)) if e.is_timeout()
+        )
+    })
+}
+
+fn is_min_context_slot_not_reached(kind: &ClientErrorKind) -> bool {
+    matches!(
+        kind,
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. })
+            if *code == JSON_RPC_SERVER_ERROR_MIN_CONTEXT_SLOT_NOT_REACHED
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::chain::ROOT_HISTORY_LEN;
+    use crate::merkle::tree::{build_tree_from_subscribers, get_proof_for_user};
+    use crate::test_fixtures;
+    use serde_json::json;
+    use solana_sdk::hash::Hash;
+    use std::collections::HashMap;
+
+    // Current-layout config account data as `initialize` leaves it
+    fn config_account_data(root: [u8; 32], chain_domain: [u8; 8]) -> Vec<u8> {
+        let mut data = vec![0u8; 8 + 32];
+        data.extend_from_slice(&root);
+        data.push(255);
+        data.push(0); // required_mint: None
+        data.extend_from_slice(&0i64.to_le_bytes());
+        data.push(HashAlgo::Sha256.to_u8());
+        data.extend_from_slice(&0i64.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.push(0); // threshold
+        data.push(1); // expiration_in_leaf
+        data.extend_from_slice(&0i64.to_le_bytes());
+        data.extend_from_slice(&chain_domain);
+        data.resize(data.len() + 32 * ROOT_HISTORY_LEN + 2, 0); // empty root history, window 0
+        data.push(0); // start_in_leaf
+        data.push(1); // verbose_logging
+        data.extend_from_slice(&0u64.to_le_bytes()); // max_total_leaves: unbounded
+        data.extend_from_slice(&[0u8; 32]); // lifetime_root: none
+        data.extend_from_slice(&0i64.to_le_bytes()); // last_updated: never
+        data.extend_from_slice(&0i64.to_le_bytes()); // min_update_interval_secs: off
+        data.push(0); // pubkey_leaf_encoding: RawBytes
+        data
+    }
+
+    // Client whose getAccountInfo answers with `data` as the config, served at `served_slot`
+    fn mock_client_with_config(data: Vec<u8>, served_slot: u64) -> SolanaClient {
+        let response = json!({
+            "context": { "slot": served_slot },
+            "value": {
+                "lamports": 1_000_000,
+                "data": [bs58::encode(&data).into_string(), "base58"],
+                "owner": PROGRAM_ID,
+                "executable": false,
+                "rentEpoch": 0,
+                "space": data.len(),
+            },
+        });
+        let mocks = HashMap::from([(RpcRequest::GetAccountInfo, response)]);
+        let rpc_client = RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks);
+        SolanaClient::with_rpc_client(rpc_client, Keypair::new())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn verify_config_bump_flags_a_non_canonical_bump() -> Result<()> {
+        let (_, canonical_bump) = derive_config_pda(&Pubkey::from_str(PROGRAM_ID)?);
+        let bump_offset = 8 + 32 + 32;
+
+        let mut data = config_account_data([7u8; 32], [0u8; 8]);
+        data[bump_offset] = canonical_bump;
+        assert!(
+            mock_client_with_config(data, 1)
+                .verify_config_bump()
+                .await?
+        );
+
+        let mut data = config_account_data([7u8; 32], [0u8; 8]);
+        data[bump_offset] = canonical_bump.wrapping_sub(1);
+        assert!(
+            !mock_client_with_config(data, 1)
+                .verify_config_bump()
+                .await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn slow_rpc_reads_fail_with_rpc_timeout() -> Result<()> {
+        // An endpoint that accepts connections but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}", listener.local_addr()?);
+        std::thread::spawn(move || {
+            let mut held = Vec::new();
+            for connection in listener.incoming() {
+                held.push(connection);
+            }
+        });
+
+        let timeout = Duration::from_millis(200);
+        let client = SolanaClient::with_rpc_client(
+            RpcClient::new_with_timeout_and_commitment(url, timeout, CommitmentConfig::confirmed()),
+            Keypair::new(),
+        )
+        .with_rpc_timeout(timeout);
+
+        let error = client.get_current_root().await.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<RpcTimeout>(),
+            Some(&RpcTimeout { timeout })
+        );
+        let error = client.verify_program_deployed().await.unwrap_err();
+        assert!(error.downcast_ref::<RpcTimeout>().is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_discriminators_match_the_idl() {
+        assert_eq!(
+            instruction_discriminator("initialize"),
+            [175, 175, 109, 31, 13, 152, 155, 237]
+        );
+        // The values previously copied from the IDL
+        let known: [(&str, [u8; 8]); 7] = [
+            ("update_root", [58, 195, 57, 246, 116, 198, 170, 138]),
+            ("migrate_hash_algo", [90, 113, 202, 202, 22, 182, 68, 165]),
+            (
+                "set_expiration_in_leaf",
+                [107, 203, 78, 150, 25, 200, 4, 205],
+            ),
+            ("set_start_in_leaf", [233, 247, 208, 152, 1, 7, 201, 59]),
+            ("realloc_config", [39, 50, 148, 55, 7, 85, 147, 114]),
+            ("verify_subscription", [33, 129, 229, 123, 142, 29, 34, 186]),
+            ("verify_batch", [207, 55, 42, 119, 105, 251, 88, 199]),
+        ];
+        for (name, discriminator) in known {
+            assert_eq!(instruction_discriminator(name), discriminator, "{}", name);
+        }
+    }
+
+    fn mock_client_at_slot(root: [u8; 32], served_slot: u64) -> SolanaClient {
+        mock_client_with_config(config_account_data(root, [0u8; 8]), served_slot)
+    }
+
+    #[test]
+    fn executed_failures_are_terminal_and_missing_transactions_retry() {
+        let signature = Signature::default();
+        let error = ClientError::from(ClientErrorKind::Custom(
+            "unable to confirm transaction".to_string(),
+        ));
+        let outcome = |url: &str| {
+            let rpc_client = RpcClient::new_mock(url.to_string());
+            SolanaClient::with_rpc_client(rpc_client, Keypair::new())
+                .classify_send_error(&signature, &error)
+        };
+
+        // Executed with a program error: resubmitting must not happen
+        let failed = outcome("instruction_error");
+        assert!(matches!(failed, SendOutcome::Failed(_)));
+        assert!(!failed.is_retryable());
+
+        // Never landed: safe to resubmit
+        let dropped = outcome("sig_not_found");
+        assert_eq!(dropped, SendOutcome::Dropped);
+        assert!(dropped.is_retryable());
+
+        assert_eq!(outcome("succeeds"), SendOutcome::Landed);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn confirms_many_transactions_with_one_status_poll() -> Result<()> {
+        let payer = Keypair::new();
+        let transactions: Vec<Transaction> = (0..3u8)
+            .map(|i| {
+                let instruction = Instruction {
+                    program_id: Pubkey::new_unique(),
+                    accounts: vec![],
+                    data: vec![i],
+                };
+                Transaction::new_signed_with_payer(
+                    &[instruction],
+                    Some(&payer.pubkey()),
+                    &[&payer],
+                    Hash::default(),
+                )
+            })
+            .collect();
+
+        // Mocked responses are served once; a second poll would get the default
+        // all-successful statuses instead of the third transaction's failure
+        let confirmed = json!({
+            "slot": 1,
+            "confirmations": 0,
+            "status": { "Ok": null },
+            "err": null,
+            "confirmationStatus": "confirmed",
+        });
+        let failed = json!({
+            "slot": 1,
+            "confirmations": 0,
+            "status": { "Err": "AccountInUse" },
+            "err": "AccountInUse",
+            "confirmationStatus": "confirmed",
+        });
+        let statuses = json!({
+            "context": { "slot": 1 },
+            "value": [confirmed.clone(), confirmed, failed],
+        });
+        let mocks = HashMap::from([(RpcRequest::GetSignatureStatuses, statuses)]);
+        let rpc_client = RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks);
+        let client = SolanaClient::with_rpc_client(rpc_client, payer);
+
+        let outcomes = client.send_and_confirm_many(&transactions).await?;
+        let signatures: Vec<Signature> = outcomes.iter().map(|(signature, _)| *signature).collect();
+        let expected: Vec<Signature> = transactions.iter().map(|tx| tx.signatures[0]).collect();
+        assert_eq!(signatures, expected);
+        assert_eq!(outcomes[0].1, SendOutcome::Landed);
+        assert_eq!(outcomes[1].1, SendOutcome::Landed);
+        let SendOutcome::Failed(failure) = &outcomes[2].1 else {
+            panic!("expected a failure, got {:?}", outcomes[2].1);
+        };
+        assert_eq!(failure.signature, expected[2]);
+        Ok(())
+    }
+
+    // Client whose getAccountInfo answers with `value` for any account
+    fn mock_client_with_account(value: serde_json::Value) -> SolanaClient {
+        let response = json!({ "context": { "slot": 1 }, "value": value });
+        let mocks = HashMap::from([(RpcRequest::GetAccountInfo, response)]);
+        let rpc_client = RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks);
+        SolanaClient::with_rpc_client(rpc_client, Keypair::new())
+    }
+
+    fn account_json(owner: &str, executable: bool) -> serde_json::Value {
+        json!({
+            "lamports": 1_000_000,
+            "data": ["", "base58"],
+            "owner": owner,
+            "executable": executable,
+            "rentEpoch": 0,
+            "space": 0,
+        })
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn program_must_be_deployed_and_executable() {
+        let loader = BPF_LOADER_IDS[0];
+        mock_client_with_account(account_json(loader, true))
+            .verify_program_deployed()
+            .await
+            .unwrap();
+
+        let missing = mock_client_with_account(serde_json::Value::Null)
+            .verify_program_deployed()
+            .await
+            .unwrap_err();
+        assert!(missing.to_string().contains("not deployed"));
+
+        // Something else lives at the program address on this cluster
+        assert!(mock_client_with_account(account_json(loader, false))
+            .verify_program_deployed()
+            .await
+            .is_err());
+        assert!(
+            mock_client_with_account(account_json(SYSTEM_PROGRAM_ID, true))
+                .verify_program_deployed()
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn derives_the_config_pda_for_a_known_program() -> Result<()> {
+        let program_id = Pubkey::from_str(PROGRAM_ID)?;
+        let (pda, bump) = derive_config_pda(&program_id);
+        assert_eq!(
+            pda,
+            Pubkey::from_str("EujujjftxdBHckFWTAB91zLQjsTBLvGgTxMvPkmGe977")?
+        );
+        assert_eq!(bump, 254);
+
+        // Another program gets its own config
+        assert_ne!(derive_config_pda(&Pubkey::new_unique()).0, pda);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn init_losing_the_race_resolves_to_the_existing_config() -> Result<()> {
+        let failure = |error: &str| -> Result<Signature> {
+            Err(TransactionFailed {
+                signature: Signature::default(),
+                error: error.to_string(),
+                program_error: None,
+            }
+            .into())
+        };
+        let in_use = "Transaction simulation failed: Error processing Instruction 0: \
+                      custom program error: 0x0";
+
+        // The account appeared after the existence check: success, nothing sent by us
+        let client = mock_client_with_config(config_account_data([7u8; 32], [0u8; 8]), 1);
+        assert_eq!(client.resolve_initialize_race(failure(in_use)).await?, None);
+
+        // Genuine failures are still errors
+        let client = mock_client_with_config(config_account_data([7u8; 32], [0u8; 8]), 1);
+        let unauthorized = "Error processing Instruction 0: custom program error: 0x1770";
+        assert!(client
+            .resolve_initialize_race(failure(unauthorized))
+            .await
+            .is_err());
+
+        // "In use" without a readable config isn't trusted
+        let client =
+            SolanaClient::with_rpc_client(RpcClient::new_mock("fails".to_string()), Keypair::new());
+        assert!(client
+            .resolve_initialize_race(failure(in_use))
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn loads_the_authority_from_a_base58_secret() -> Result<()> {
+        let authority = Keypair::new();
+        let secret = bs58::encode(authority.to_bytes()).into_string();
+
+        let client = SolanaClient::from_base58_secret("http://localhost:8899", &secret)?;
+        assert_eq!(client.authority(), authority.pubkey());
+        let client =
+            SolanaClient::from_keypair_bytes("http://localhost:8899", &authority.to_bytes())?;
+        assert_eq!(client.authority(), authority.pubkey());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_malformed_authority_secrets() {
+        let authority = Keypair::new().to_bytes();
+
+        // Wrong length, not base58, and a public half from another key
+        assert!(keypair_from_bytes(&authority[..32]).is_err());
+        assert!(keypair_from_base58("not-base58-0OIl").is_err());
+        let mut mismatched = authority;
+        mismatched[32..].copy_from_slice(&Keypair::new().pubkey().to_bytes());
+        assert!(keypair_from_bytes(&mismatched).is_err());
+    }
+
+    #[test]
+    fn anchor_verify_args_use_strings_for_64_bit_values() -> Result<()> {
+        let expiration = Expiration::new(i64::MAX)?;
+        let args = to_anchor_verify_args(&[1, 2, 255], expiration, 5, 9);
+        assert_eq!(
+            args,
+            json!({
+                "proof_bytes": [1, 2, 255],
+                "expiration": "9223372036854775807",
+                "leaf_index": "5",
+                "total_leaves": "9",
+                "leaf_salt": null,
+                "proof_root": null,
+                "start_ts": null,
+                "expected_user": null,
+            })
+        );
+        assert!(args["expiration"].is_string());
+        Ok(())
+    }
+
+    #[test]
+    fn decodes_anchor_error_from_logs() {
+        let logs = [
+            "Program AHpuc2M3wbZceufaiE4Q2wyDXh198ymB1SxxpbxCzj3H invoke [1]".to_string(),
+            "Program log: AnchorError occurred. Error Code: Unauthorized. Error Number: 6000. \
+             Error Message: You are not authorized to update the root.."
+                .to_string(),
+        ];
+        assert_eq!(
+            program_error_from_logs(&logs).as_deref(),
+            Some("Unauthorized")
+        );
+        assert_eq!(program_error_from_logs(&logs[..1]), None);
+    }
+
+    #[test]
+    fn decodes_root_updated_events_from_logs() {
+        let logs: Vec<String> = test_fixtures::ROOT_UPDATE_LOGS
+            .iter()
+            .flatten()
+            .map(|line| line.to_string())
+            .collect();
+        let events = root_updates_from_logs(&logs);
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[0],
+            RootUpdated {
+                old_root: [0u8; 32],
+                new_root: [1u8; 32],
+                updated_by: Pubkey::new_from_array([2u8; 32]),
+                timestamp: 1_700_000_000,
+            }
+        );
+        assert_eq!(events[1].old_root, events[0].new_root);
+        assert_eq!(
+            hex::encode(events[1].new_root),
+            test_fixtures::EXPECTED_ROOT
+        );
+
+        // Other programs' event data is ignored
+        let foreign = [format!("Program data: {}", BASE64.encode([9u8; 112]))];
+        assert!(root_updates_from_logs(&foreign).is_empty());
+    }
+
+    #[test]
+    fn splits_fifty_users_by_tree_depth() {
+        let users: Vec<usize> = (0..50).collect();
+
+        // Depth 20: only one user's proof fits per transaction
+        assert_eq!(max_batch_size(1 << 20), 1);
+        assert_eq!(split_into_batches(&users, 1 << 20).len(), 50);
+
+        // Depth 10: two users per transaction
+        assert_eq!(max_batch_size(1024), 2);
+        assert_eq!(split_into_batches(&users, 1024).len(), 25);
+    }
+
+    #[test]
+    fn mass_verify_plan_matches_the_batch_splitter() {
+        let users: Vec<usize> = (0..51).collect();
+        let latency = Duration::from_millis(500);
+
+        for total_leaves in [3, 1024, 1 << 20] {
+            let plan = plan_mass_verify(users.len(), total_leaves, latency);
+            let batches = split_into_batches(&users, total_leaves);
+            assert_eq!(plan.transactions, batches.len());
+            assert!(batches.iter().all(|batch| batch.len() <= plan.batch_size));
+            assert_eq!(
+                plan.lamports,
+                (batches.len() + users.len()) as u64 * LAMPORTS_PER_SIGNATURE
+            );
+            assert_eq!(plan.wall_clock, latency * batches.len() as u32);
+        }
+
+        assert_eq!(plan_mass_verify(0, 1024, latency).transactions, 0);
+    }
+
+    #[test]
+    fn verify_tx_size_estimate_matches_serialized_transaction() -> Result<()> {
+        let user = Keypair::new();
+        let expiration = Expiration::new(1_800_000_000)?;
+
+        let mut subscribers = vec![(user.pubkey().to_string(), expiration)];
+        subscribers.extend((0..7).map(|_| (Keypair::new().pubkey().to_string(), expiration)));
+        let (_, tree, subscribers) = build_tree_from_subscribers(subscribers)?;
+
+        let (proof_bytes, index) =
+            get_proof_for_user(&tree, &subscribers, &user.pubkey().to_string()).unwrap();
+        // A program deployed under another id gets instructions for that id
+        let client =
+            SolanaClient::read_only("http://localhost:8899").with_program_id(Pubkey::new_unique());
+        let instruction = verify_subscription_instruction(
+            &client.program_id(),
+            &user.pubkey(),
+            &proof_bytes,
+            expiration,
+            index,
+            8,
+            None,
+            None,
+        )?;
+        assert_eq!(instruction.program_id, client.program_id());
+        assert_eq!(
+            instruction.accounts[0].pubkey,
+            derive_config_pda(&client.program_id()).0
+        );
         let transaction = Transaction::new_signed_with_payer(
-            instructions,
-            Some(&self.authority_keypair.pubkey()),
-            &[&self.authority_keypair],
-            recent_blockhash,
+            &[instruction],
+            Some(&user.pubkey()),
+            &[&user],
+            Hash::default(),
         );
 
-        self.rpc_client
-            .send_and_confirm_transaction(&transaction)
-            .context("Failed to send transaction")
+        assert_eq!(
+            estimated_verify_tx_size(8),
+            bincode::serialize(&transaction)?.len()
+        );
+        Ok(())
+    }
+
+    // The blocking RpcClient needs a multi-threaded runtime inside async code
+    #[tokio::test(flavor = "multi_thread")]
+    async fn mismatched_chain_domain_blocks_root_push() -> Result<()> {
+        let devnet = chain_domain_from_name("devnet");
+        let mainnet = chain_domain_from_name("mainnet-beta");
+        assert_ne!(devnet, mainnet);
+
+        let client = mock_client_with_config(config_account_data([7u8; 32], devnet), 0)
+            .with_chain_domain(mainnet);
+        let err = client.update_merkle_root([9u8; 32]).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<ChainDomainMismatch>(),
+            Some(&ChainDomainMismatch {
+                expected: mainnet,
+                on_chain: devnet,
+            })
+        );
+
+        let client = client.with_chain_domain(devnet);
+        client.ensure_chain_domain().await?;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn root_at_slot_uses_slot_pinned_data() -> Result<()> {
+        let root = [7u8; 32];
+
+        assert_eq!(
+            mock_client_at_slot(root, 42).get_root_at_slot(42).await?,
+            root
+        );
+
+        let err = mock_client_at_slot(root, 50)
+            .get_root_at_slot(42)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<HistoricalStateUnavailable>(),
+            Some(&HistoricalStateUnavailable {
+                requested_slot: 42,
+                served_slot: 50,
+            })
+        );
+        Ok(())
     }
 }
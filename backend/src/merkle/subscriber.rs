@@ -0,0 +1,580 @@
+use anyhow::Result;
+use chrono::Utc;
+use solana_sdk::pubkey::Pubkey;
+use sqlx::PgPool;
+use std::str::FromStr;
+
+use super::tree::{self, Expiration};
+use crate::model::{SubscriberAudit, SubscriberStorage};
+
+/// Whether `upsert_subscriber` created a new row or renewed an existing one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpsertResult {
+    Inserted,
+    Updated,
+}
+
+/// Add a subscriber, or update the expiration if the wallet already exists.
+///
+/// Relies on the unique constraint on `subscriber_storage.wallet_address`
+/// (its primary key) for the `ON CONFLICT` target.
+pub async fn upsert_subscriber(
+    pool: &PgPool,
+    wallet: &str,
+    expiration_ts: Expiration,
+) -> Result<UpsertResult> {
+    let last_updated_at = Utc::now().naive_utc();
+
+    // xmax is 0 only for a freshly inserted row, which tells the two branches apart
+    let inserted = sqlx::query_scalar!(
+        r#"INSERT INTO subscriber_storage (wallet_address, expiration_ts, last_updated_at)
+           VALUES ($1, $2, $3)
+           ON CONFLICT (wallet_address) DO UPDATE
+           SET expiration_ts = EXCLUDED.expiration_ts,
+               last_updated_at = EXCLUDED.last_updated_at
+           RETURNING (xmax = 0) AS "inserted!""#,
+        wallet,
+        expiration_ts.as_i64(),
+        last_updated_at
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(if inserted {
+        UpsertResult::Inserted
+    } else {
+        UpsertResult::Updated
+    })
+}
+
+/// Which subscribers `extend_all_expirations` moves
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtensionFilter {
+    All,
+    /// Only subscribers still active at `now_ts` (expiring after it)
+    Active {
+        now_ts: i64,
+    },
+    Wallets(Vec<String>),
+}
+
+/// Shift the expiration of every subscriber matching `filter` by `by_secs` in one
+/// `UPDATE` (e.g. a free month for everyone), returning how many rows changed.
+///
+/// Negative shifts are refused unless `allow_reduction` is set; a reduction never
+/// takes an expiration below 0. Rebuild the tree and push its root afterwards.
+pub async fn extend_all_expirations(
+    pool: &PgPool,
+    by_secs: i64,
+    filter: ExtensionFilter,
+    allow_reduction: bool,
+) -> Result<u64> {
+    if by_secs < 0 && !allow_reduction {
+        return Err(anyhow::anyhow!(
+            "Refusing to reduce expirations by {}s without allow_reduction",
+            -by_secs
+        ));
+    }
+    let last_updated_at = Utc::now().naive_utc();
+    let (active_at, wallets) = match filter {
+        ExtensionFilter::All => (None, None),
+        ExtensionFilter::Active { now_ts } => (Some(now_ts), None),
+        ExtensionFilter::Wallets(wallets) => (None, Some(wallets)),
+    };
+
+    let result = sqlx::query!(
+        r#"UPDATE subscriber_storage
+           SET expiration_ts = GREATEST(expiration_ts + $1, 0),
+               last_updated_at = $2
+           WHERE ($3::BIGINT IS NULL OR expiration_ts > $3)
+             AND ($4::TEXT[] IS NULL OR wallet_address = ANY($4))"#,
+        by_secs,
+        last_updated_at,
+        active_at,
+        wallets.as_deref()
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Remove `wallet` from the subscriber set, audited as a `revoke`. Returns whether it
+/// was there. Rebuild the tree and push its root afterwards.
+pub async fn revoke_subscriber(pool: &PgPool, wallet: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM subscriber_storage WHERE wallet_address = $1")
+        .bind(wallet)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Remove every subscriber expired at `now_ts` (expiring at or before it), audited
+/// as `prune`s, returning how many were removed
+pub async fn prune_expired(pool: &PgPool, now_ts: i64) -> Result<u64> {
+    let mut tx = pool.begin().await?;
+    // Read by the history trigger; local to this transaction
+    sqlx::query("SELECT set_config('merkle.audit_change', 'prune', true)")
+        .execute(&mut *tx)
+        .await?;
+    let result = sqlx::query("DELETE FROM subscriber_storage WHERE expiration_ts <= $1")
+        .bind(now_ts)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Every audited change to `wallet`, oldest first
+pub async fn audit_log(pool: &PgPool, wallet: &str) -> Result<Vec<SubscriberAudit>> {
+    // changed_at has no zone; read it back as UTC
+    let entries = sqlx::query_as::<_, SubscriberAudit>(
+        "SELECT wallet_address, change_type, old_expiration_ts, new_expiration_ts,
+                changed_at AT TIME ZONE 'UTC' AS changed_at
+         FROM subscriber_history
+         WHERE wallet_address = $1
+         ORDER BY id",
+    )
+    .bind(wallet)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(entries)
+}
+
+/// Record that `wallet` just passed a membership check, for activity metrics
+pub async fn mark_verified(pool: &PgPool, wallet: &str) -> Result<()> {
+    let verified_at = Utc::now().naive_utc();
+
+    sqlx::query!(
+        "UPDATE subscriber_storage SET last_verified_at = $2 WHERE wallet_address = $1",
+        wallet,
+        verified_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Count one more successful verification for `wallet`. Atomic, so concurrent
+/// verifications are all counted.
+pub async fn increment_verify_count(pool: &PgPool, wallet: &str) -> Result<()> {
+    sqlx::query(
+        "UPDATE subscriber_storage SET verify_count = verify_count + 1 WHERE wallet_address = $1",
+    )
+    .bind(wallet)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// `tree::verify_subscription`, recording the wallet's activity with `mark_verified`
+/// when the proof is valid, and with `count_verifications` also bumping its
+/// `verify_count` (an extra write per verification, so off unless wanted).
+///
+/// Opt-in for callers that want activity metrics. Failing to record is logged and
+/// never changes the verification result.
+#[allow(clippy::too_many_arguments)]
+pub async fn verify_and_mark_verified(
+    pool: &PgPool,
+    root_hex: &str,
+    proof_bytes: &[u8],
+    wallet: &str,
+    expiration: Expiration,
+    index: usize,
+    total_subscribers: usize,
+    count_verifications: bool,
+) -> Result<bool> {
+    let is_valid = tree::verify_subscription(
+        root_hex,
+        proof_bytes,
+        wallet,
+        expiration,
+        index,
+        total_subscribers,
+    )?;
+
+    if is_valid {
+        if let Err(e) = mark_verified(pool, wallet).await {
+            eprintln!("⚠️ Failed to record verification for {}: {}", wallet, e);
+        }
+        if count_verifications {
+            if let Err(e) = increment_verify_count(pool, wallet).await {
+                eprintln!("⚠️ Failed to count verification for {}: {}", wallet, e);
+            }
+        }
+    }
+    Ok(is_valid)
+}
+
+/// Subscribers whose wallet starts with `wallet_prefix`, in wallet order, at most `limit`.
+///
+/// `%` and `_` in the prefix match literally rather than as `LIKE` wildcards.
+pub async fn search_subscribers(
+    pool: &PgPool,
+    wallet_prefix: &str,
+    limit: i64,
+) -> Result<Vec<SubscriberStorage>> {
+    let pattern = format!(
+        "{}%",
+        wallet_prefix
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+
+    // The timestamp columns have no zone; read them back as UTC
+    let subscribers = sqlx::query_as::<_, SubscriberStorage>(
+        r#"SELECT wallet_address, wallet_pubkey, expiration_ts, start_ts,
+                  last_updated_at AT TIME ZONE 'UTC' AS last_updated_at,
+                  last_verified_at AT TIME ZONE 'UTC' AS last_verified_at,
+                  leaf_id, verify_count
+           FROM subscriber_storage
+           WHERE wallet_address LIKE $1 ESCAPE '\'
+           ORDER BY wallet_address COLLATE "C"
+           LIMIT $2"#,
+    )
+    .bind(pattern)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(subscribers)
+}
+
+/// Wallets in `subscriber_storage` that aren't valid Solana pubkeys (base58 that
+/// decodes to exactly 32 bytes), sorted. Read-only: nothing is modified or built.
+///
+/// Run before a tree build to clean up rows that would otherwise fail it midway.
+pub async fn validate_subscribers(pool: &PgPool) -> Result<Vec<String>> {
+    let wallets = sqlx::query_scalar!(
+        "SELECT wallet_address FROM subscriber_storage ORDER BY wallet_address"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(wallets
+        .into_iter()
+        .filter(|wallet| Pubkey::from_str(wallet).is_err())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+    use chrono::NaiveDateTime;
+
+    const WALLET: &str = "5sHXVAK46po96V9syX6Jhmav9qUagnKydnYwuE57KPat";
+
+    async fn stored_expiration(pool: &PgPool) -> Result<i64> {
+        Ok(sqlx::query_scalar!(
+            "SELECT expiration_ts FROM subscriber_storage WHERE wallet_address = $1",
+            WALLET
+        )
+        .fetch_one(pool)
+        .await?)
+    }
+
+    async fn last_verified_at(pool: &PgPool, wallet: &str) -> Result<Option<NaiveDateTime>> {
+        Ok(sqlx::query_scalar!(
+            "SELECT last_verified_at FROM subscriber_storage WHERE wallet_address = $1",
+            wallet
+        )
+        .fetch_one(pool)
+        .await?)
+    }
+
+    #[sqlx::test]
+    async fn only_successful_verification_marks_activity(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+        let (root_hash, tree, subscribers) =
+            tree::build_tree_from_subscribers(test_fixtures::subscribers())?;
+        let (wallet, expiration) = &subscribers[1];
+        let (proof_bytes, index) = tree::get_proof_for_user(&tree, &subscribers, wallet).unwrap();
+        let total = subscribers.len();
+
+        // A tampered expiration fails and leaves no activity behind
+        let tampered = Expiration::new(expiration.as_i64() + 1)?;
+        let verify = |expiration| {
+            verify_and_mark_verified(
+                &pool,
+                &root_hash,
+                &proof_bytes,
+                wallet,
+                expiration,
+                index,
+                total,
+                false,
+            )
+        };
+        assert!(!verify(tampered).await?);
+        assert_eq!(last_verified_at(&pool, wallet).await?, None);
+
+        assert!(verify(*expiration).await?);
+        assert!(last_verified_at(&pool, wallet).await?.is_some());
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn counted_verifications_increment_per_success(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+        let (root_hash, tree, subscribers) =
+            tree::build_tree_from_subscribers(test_fixtures::subscribers())?;
+        let (wallet, expiration) = &subscribers[1];
+        let (proof_bytes, index) = tree::get_proof_for_user(&tree, &subscribers, wallet).unwrap();
+        let total = subscribers.len();
+        let verify = |expiration, count_verifications| {
+            verify_and_mark_verified(
+                &pool,
+                &root_hash,
+                &proof_bytes,
+                wallet,
+                expiration,
+                index,
+                total,
+                count_verifications,
+            )
+        };
+        let verify_count = || async {
+            sqlx::query_scalar::<_, i64>(
+                "SELECT verify_count FROM subscriber_storage WHERE wallet_address = $1",
+            )
+            .bind(wallet)
+            .fetch_one(&pool)
+            .await
+        };
+
+        assert!(verify(*expiration, true).await?);
+        assert!(verify(*expiration, true).await?);
+        assert_eq!(verify_count().await?, 2);
+
+        // Failures and uncounted verifications leave it alone
+        assert!(!verify(Expiration::new(expiration.as_i64() + 1)?, true).await?);
+        assert!(verify(*expiration, false).await?);
+        assert_eq!(verify_count().await?, 2);
+        Ok(())
+    }
+
+    fn audit_changes(entries: &[SubscriberAudit]) -> Vec<(&str, Option<i64>, Option<i64>)> {
+        entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.change_type.as_str(),
+                    entry.old_expiration_ts,
+                    entry.new_expiration_ts,
+                )
+            })
+            .collect()
+    }
+
+    #[sqlx::test]
+    async fn every_mutation_writes_an_audit_entry(pool: PgPool) -> Result<()> {
+        let other = "11111111111111111111111111111111";
+
+        upsert_subscriber(&pool, WALLET, Expiration::new(1_000)?).await?;
+        upsert_subscriber(&pool, WALLET, Expiration::new(2_000)?).await?;
+        // Rewriting the same expiration isn't a change
+        upsert_subscriber(&pool, WALLET, Expiration::new(2_000)?).await?;
+        extend_all_expirations(&pool, -500, ExtensionFilter::All, true).await?;
+        assert!(revoke_subscriber(&pool, WALLET).await?);
+        assert!(!revoke_subscriber(&pool, WALLET).await?);
+
+        assert_eq!(
+            audit_changes(&audit_log(&pool, WALLET).await?),
+            vec![
+                ("insert", None, Some(1_000)),
+                ("renew", Some(1_000), Some(2_000)),
+                ("update", Some(2_000), Some(1_500)),
+                ("revoke", Some(1_500), None),
+            ]
+        );
+
+        // Pruning is told apart from revoking, and only removes the expired
+        upsert_subscriber(&pool, WALLET, Expiration::new(1_000)?).await?;
+        upsert_subscriber(&pool, other, Expiration::new(5_000)?).await?;
+        assert_eq!(prune_expired(&pool, 1_000).await?, 1);
+        let entries = audit_log(&pool, WALLET).await?;
+        assert_eq!(
+            audit_changes(&entries[entries.len() - 1..]),
+            vec![("prune", Some(1_000), None)]
+        );
+        assert_eq!(
+            audit_changes(&audit_log(&pool, other).await?),
+            vec![("insert", None, Some(5_000))]
+        );
+
+        // The prune setting doesn't leak into later deletes on the connection
+        assert!(revoke_subscriber(&pool, other).await?);
+        let entries = audit_log(&pool, other).await?;
+        assert_eq!(entries.last().unwrap().change_type, "revoke");
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn generated_subscribers_are_audited_as_inserts(pool: PgPool) -> Result<()> {
+        crate::merkle::generator::generate_and_store_keys(&pool, 3).await?;
+
+        let inserts: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM subscriber_history
+             WHERE change_type = 'insert' AND old_expiration_ts IS NULL",
+        )
+        .fetch_one(&pool)
+        .await?;
+        assert_eq!(inserts, 3);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn validate_subscribers_reports_only_invalid_wallets(pool: PgPool) -> Result<()> {
+        let expiration = Expiration::new(1_700_000_000)?;
+        // Valid base58, but decoding to 4 and 3 bytes rather than 32, and not base58
+        for wallet in [
+            WALLET,
+            "1111",
+            "11111111111111111111111111111111",
+            "abc",
+            "not-base58!",
+        ] {
+            upsert_subscriber(&pool, wallet, expiration).await?;
+        }
+
+        assert_eq!(
+            validate_subscribers(&pool).await?,
+            vec!["1111", "abc", "not-base58!"]
+        );
+        Ok(())
+    }
+
+    fn wallets(subscribers: &[SubscriberStorage]) -> Vec<&str> {
+        subscribers
+            .iter()
+            .map(|subscriber| subscriber.wallet_address.as_str())
+            .collect()
+    }
+
+    #[sqlx::test]
+    async fn search_matches_wallet_prefixes_up_to_the_limit(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+        let also_ones = "11111111111111111111111111111112";
+        upsert_subscriber(&pool, also_ones, Expiration::new(1_900_000_000)?).await?;
+
+        let found = search_subscribers(&pool, "111", 10).await?;
+        assert_eq!(
+            wallets(&found),
+            vec!["11111111111111111111111111111111", also_ones]
+        );
+        assert_eq!(found[1].expiration_ts, 1_900_000_000);
+        assert_eq!(search_subscribers(&pool, "111", 1).await?.len(), 1);
+
+        assert!(search_subscribers(&pool, "zzz", 10).await?.is_empty());
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn search_treats_like_wildcards_literally(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+
+        // Unescaped, these would match every wallet or any wallet starting with "5"
+        assert!(search_subscribers(&pool, "%", 10).await?.is_empty());
+        assert!(search_subscribers(&pool, "5_", 10).await?.is_empty());
+        assert_eq!(search_subscribers(&pool, "", 10).await?.len(), 3);
+        Ok(())
+    }
+
+    async fn expirations(pool: &PgPool) -> Result<Vec<(String, i64)>> {
+        Ok(sqlx::query_as::<_, (String, i64)>(
+            r#"SELECT wallet_address, expiration_ts FROM subscriber_storage
+               ORDER BY wallet_address COLLATE "C""#,
+        )
+        .fetch_all(pool)
+        .await?)
+    }
+
+    const MONTH: i64 = 30 * 24 * 60 * 60;
+
+    #[sqlx::test]
+    async fn extends_every_subscriber(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+
+        let extended = extend_all_expirations(&pool, MONTH, ExtensionFilter::All, false).await?;
+        assert_eq!(extended, 3);
+        assert_eq!(
+            expirations(&pool).await?,
+            vec![
+                (
+                    "11111111111111111111111111111111".to_string(),
+                    1_700_000_000 + MONTH
+                ),
+                (WALLET.to_string(), 1_769_665_096 + MONTH),
+                (
+                    "AHpuc2M3wbZceufaiE4Q2wyDXh198ymB1SxxpbxCzj3H".to_string(),
+                    1_800_000_000 + MONTH
+                ),
+            ]
+        );
+
+        // Taking the month back needs the explicit flag
+        assert!(
+            extend_all_expirations(&pool, -MONTH, ExtensionFilter::All, false)
+                .await
+                .is_err()
+        );
+        assert_eq!(
+            extend_all_expirations(&pool, -MONTH, ExtensionFilter::All, true).await?,
+            3
+        );
+        assert_eq!(stored_expiration(&pool).await?, 1_769_665_096);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn extends_only_active_subscribers(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+
+        // 111…1 expired at 1_700_000_000 and keeps its expiration
+        let filter = ExtensionFilter::Active {
+            now_ts: 1_750_000_000,
+        };
+        assert_eq!(
+            extend_all_expirations(&pool, MONTH, filter, false).await?,
+            2
+        );
+        let stored = expirations(&pool).await?;
+        assert_eq!(stored[0].1, 1_700_000_000);
+        assert_eq!(stored[1].1, 1_769_665_096 + MONTH);
+        assert_eq!(stored[2].1, 1_800_000_000 + MONTH);
+
+        let filter = ExtensionFilter::Wallets(vec![WALLET.to_string()]);
+        assert_eq!(
+            extend_all_expirations(&pool, MONTH, filter, false).await?,
+            1
+        );
+        assert_eq!(stored_expiration(&pool).await?, 1_769_665_096 + 2 * MONTH);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn inserts_new_subscriber(pool: PgPool) -> Result<()> {
+        let result = upsert_subscriber(&pool, WALLET, Expiration::new(1_700_000_000)?).await?;
+
+        assert_eq!(result, UpsertResult::Inserted);
+        assert_eq!(stored_expiration(&pool).await?, 1_700_000_000);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn updates_existing_subscriber(pool: PgPool) -> Result<()> {
+        upsert_subscriber(&pool, WALLET, Expiration::new(1_700_000_000)?).await?;
+        let result = upsert_subscriber(&pool, WALLET, Expiration::new(1_800_000_000)?).await?;
+
+        assert_eq!(result, UpsertResult::Updated);
+        assert_eq!(stored_expiration(&pool).await?, 1_800_000_000);
+        Ok(())
+    }
+}
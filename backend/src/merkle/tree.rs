@@ -1,7 +1,129 @@
 use anyhow::{Context, Result};
 use rs_merkle::{Hasher, MerkleProof, MerkleTree};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+use solana_sdk::pubkey::Pubkey;
 use sqlx::PgPool;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Mutex;
+
+use crate::telemetry;
+
+/// Root of a config that was never given one. The program rejects verification
+/// against it, and the backend treats it as "no root" rather than a real tree.
+pub const UNSET_ROOT: [u8; 32] = [0u8; 32];
+
+/// Subscription expiration as a Unix timestamp.
+///
+/// Construction rejects negative values so they can never end up in a leaf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Expiration(i64);
+
+impl Expiration {
+    pub fn new(timestamp: i64) -> Result<Self> {
+        if timestamp < 0 {
+            return Err(anyhow::anyhow!(
+                "Expiration must be non-negative, got {}",
+                timestamp
+            ));
+        }
+        Ok(Self(timestamp))
+    }
+
+    pub fn as_i64(self) -> i64 {
+        self.0
+    }
+
+    /// Little-endian bytes as hashed into the leaf (matches on-chain `i64::to_le_bytes`)
+    pub fn to_le_bytes(self) -> [u8; 8] {
+        self.0.to_le_bytes()
+    }
+}
+
+impl TryFrom<i64> for Expiration {
+    type Error = anyhow::Error;
+
+    fn try_from(timestamp: i64) -> Result<Self> {
+        Self::new(timestamp)
+    }
+}
+
+impl fmt::Display for Expiration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A 32-byte merkle root, parsed from and printed as 64 hex chars.
+///
+/// Roots are stored as hex and sent on-chain as bytes; converting through this type
+/// keeps the length check in one place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MerkleRoot([u8; 32]);
+
+impl MerkleRoot {
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Parse 64 hex chars, rejecting anything that isn't exactly 32 bytes
+    pub fn from_hex(root_hex: &str) -> Result<Self> {
+        let bytes = hex::decode(root_hex).context("Root must be hex")?;
+        let bytes: [u8; 32] = bytes.try_into().map_err(|bytes: Vec<u8>| {
+            anyhow::anyhow!("Root must be 32 bytes, got {}", bytes.len())
+        })?;
+        Ok(Self(bytes))
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    pub fn to_bytes(self) -> [u8; 32] {
+        self.0
+    }
+
+    /// Whether this is the all-zero placeholder of a config with no root yet
+    pub fn is_unset(&self) -> bool {
+        self.0 == UNSET_ROOT
+    }
+}
+
+impl From<[u8; 32]> for MerkleRoot {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<MerkleRoot> for [u8; 32] {
+    fn from(root: MerkleRoot) -> Self {
+        root.0
+    }
+}
+
+impl FromStr for MerkleRoot {
+    type Err = anyhow::Error;
+
+    fn from_str(root_hex: &str) -> Result<Self> {
+        Self::from_hex(root_hex)
+    }
+}
+
+impl fmt::Display for MerkleRoot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_hex())
+    }
+}
 
 #[derive(Clone)]
 pub struct Sha256Hasher {}
@@ -15,98 +137,1950 @@ impl Hasher for Sha256Hasher {
     }
 }
 
-pub async fn build_tree_from_db(
+#[derive(Clone)]
+pub struct Keccak256Hasher {}
+
+impl Hasher for Keccak256Hasher {
+    type Hash = [u8; 32];
+    fn hash(data: &[u8]) -> [u8; 32] {
+        let mut hasher = Keccak256::new();
+        hasher.update(data);
+        hasher.finalize().into()
+    }
+}
+
+/// `sha256(sha256(data))`, for interop with Bitcoin-derived tooling
+#[derive(Clone)]
+pub struct DoubleSha256Hasher {}
+
+impl Hasher for DoubleSha256Hasher {
+    type Hash = [u8; 32];
+    fn hash(data: &[u8]) -> [u8; 32] {
+        Sha256Hasher::hash(&Sha256Hasher::hash(data))
+    }
+}
+
+/// (Hex root, tree, subscribers in leaf order)
+pub type BuiltTree<H = Sha256Hasher> = (String, MerkleTree<H>, Vec<(String, Expiration)>);
+
+/// A `BuiltTree` whose leaves include start times, with each subscriber's start in leaf order
+pub type WindowedTree<H = Sha256Hasher> = (BuiltTree<H>, Vec<i64>);
+
+/// Hash function the tree is built with (variant order matches the on-chain `HashAlgo`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgo {
+    Sha256,
+    Keccak256,
+    DoubleSha256,
+}
+
+impl HashAlgo {
+    /// Borsh enum tag used in instruction data
+    pub fn to_u8(self) -> u8 {
+        match self {
+            HashAlgo::Sha256 => 0,
+            HashAlgo::Keccak256 => 1,
+            HashAlgo::DoubleSha256 => 2,
+        }
+    }
+
+    pub fn from_u8(tag: u8) -> Result<Self> {
+        match tag {
+            0 => Ok(HashAlgo::Sha256),
+            1 => Ok(HashAlgo::Keccak256),
+            2 => Ok(HashAlgo::DoubleSha256),
+            _ => Err(anyhow::anyhow!("Unknown hash algorithm tag {}", tag)),
+        }
+    }
+}
+
+/// Hex root of the subscriber set under the given hash algorithm
+pub fn compute_root_with_algo(
+    subscribers: Vec<(String, Expiration)>,
+    hash_algo: HashAlgo,
+) -> Result<String> {
+    match hash_algo {
+        HashAlgo::Sha256 => build_tree_from_subscribers_with::<Sha256Hasher>(subscribers)
+            .map(|(root_hash, _, _)| root_hash),
+        HashAlgo::Keccak256 => build_tree_from_subscribers_with::<Keccak256Hasher>(subscribers)
+            .map(|(root_hash, _, _)| root_hash),
+        HashAlgo::DoubleSha256 => {
+            build_tree_from_subscribers_with::<DoubleSha256Hasher>(subscribers)
+                .map(|(root_hash, _, _)| root_hash)
+        }
+    }
+}
+
+pub async fn build_tree_from_db(pool: &PgPool) -> Result<BuiltTree> {
+    build_tree_from_db_with_salt(pool, None).await
+}
+
+/// Same as `build_tree_from_db`, with per-wallet leaf salts derived from `leaf_secret`
+pub async fn build_tree_from_db_with_salt(
+    pool: &PgPool,
+    leaf_secret: Option<&[u8; 32]>,
+) -> Result<BuiltTree> {
+    build_tree_from_db_with_layout(pool, leaf_secret, true).await
+}
+
+/// Same as `build_tree_from_db_with_salt`; with `expiration_in_leaf` false the stored
+/// expirations are left out of the leaves (must match the on-chain config flag)
+pub async fn build_tree_from_db_with_layout(
+    pool: &PgPool,
+    leaf_secret: Option<&[u8; 32]>,
+    expiration_in_leaf: bool,
+) -> Result<BuiltTree> {
+    build_tree_from_db_active_at(pool, None, leaf_secret, expiration_in_leaf).await
+}
+
+/// Same as `build_tree_from_db_with_layout`; with `active_at` set, subscribers whose
+/// expiration is at or before it are left out of the tree
+pub async fn build_tree_from_db_active_at(
+    pool: &PgPool,
+    active_at: Option<i64>,
+    leaf_secret: Option<&[u8; 32]>,
+    expiration_in_leaf: bool,
+) -> Result<BuiltTree> {
+    let started = Instant::now();
+    // Fetch wallet, its raw pubkey bytes (decoded once by Postgres) and expiration,
+    // already in leaf order. COLLATE "C" compares bytes like Rust's `str::cmp`; the
+    // default collation would order mixed-case base58 differently.
+    let rows = sqlx::query_as::<_, (String, Option<Vec<u8>>, i64)>(
+        r#"SELECT wallet_address, wallet_pubkey, expiration_ts FROM subscriber_storage
+           WHERE ($1::BIGINT IS NULL OR expiration_ts > $1)
+           ORDER BY wallet_address COLLATE "C""#,
+    )
+    .bind(active_at)
+    .fetch_all(pool)
+    .await?;
+
+    let subscribers = rows
+        .into_iter()
+        .map(|(wallet, pubkey, exp)| {
+            let pubkey = stored_pubkey(&wallet, pubkey)?;
+            let exp = Expiration::new(exp)
+                .with_context(|| format!("Invalid expiration for {}", wallet))?;
+            Ok((wallet, pubkey, exp))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if subscribers.is_empty() {
+        return Err(anyhow::anyhow!("No subscribers found in database"));
+    }
+
+    let built = build_tree_from_pubkey_bytes::<Sha256Hasher>(
+        subscribers,
+        leaf_secret,
+        expiration_in_leaf,
+        true,
+    )?;
+    metrics::histogram!(telemetry::TREE_BUILD_SECONDS).record(started.elapsed().as_secs_f64());
+    Ok(built)
+}
+
+/// The 32 pubkey bytes Postgres stored for `wallet`; NULL means the address isn't a
+/// valid pubkey (see `subscriber::validate_subscribers`)
+fn stored_pubkey(wallet: &str, pubkey: Option<Vec<u8>>) -> Result<[u8; 32]> {
+    pubkey
+        .and_then(|pubkey| pubkey.try_into().ok())
+        .ok_or_else(|| anyhow::anyhow!("{} is not a valid Solana pubkey", wallet))
+}
+
+/// SHA-256 over every `(wallet_pubkey, expiration_ts)` pair in the same order
+/// `build_tree_from_db_with_layout` builds leaves, computed by Postgres so no rows
+/// are fetched. An unchanged digest means an unchanged tree, so a sync pass can
+/// skip the rebuild.
+pub async fn subscriber_set_digest(pool: &PgPool) -> Result<[u8; 32]> {
+    subscriber_set_digest_active_at(pool, None).await
+}
+
+/// `subscriber_set_digest` over the subscribers `build_tree_from_db_active_at` keeps
+/// for `active_at`, so an expiration passing changes the digest too
+pub async fn subscriber_set_digest_active_at(
+    pool: &PgPool,
+    active_at: Option<i64>,
+) -> Result<[u8; 32]> {
+    // Each pair is a fixed 40 bytes (big-endian expiration from int8send), so the
+    // concatenation can only be split one way. A wallet with no stored pubkey stands in
+    // as the hash of its address, so adding one still changes the digest and the
+    // rebuild reports it.
+    let (digest,): (Vec<u8>,) = sqlx::query_as(
+        r#"SELECT sha256(COALESCE(
+               string_agg(COALESCE(wallet_pubkey, sha256(convert_to(wallet_address, 'UTF8')))
+                          || int8send(expiration_ts), ''::bytea
+                          ORDER BY wallet_address COLLATE "C"),
+               ''::bytea))
+           FROM subscriber_storage
+           WHERE ($1::BIGINT IS NULL OR expiration_ts > $1)"#,
+    )
+    .bind(active_at)
+    .fetch_one(pool)
+    .await?;
+
+    digest
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Subscriber set digest must be 32 bytes"))
+}
+
+/// The soonest expiration after `after_ts` among `subscribers`, when the set of
+/// unexpired subscribers next changes
+pub fn next_expiration(subscribers: &[(String, Expiration)], after_ts: i64) -> Option<i64> {
+    subscribers
+        .iter()
+        .map(|(_, expiration)| expiration.as_i64())
+        .filter(|&expiration| expiration > after_ts)
+        .min()
+}
+
+/// Single-flight `build_tree_from_db_with_layout`: concurrent callers share one build
+/// and its result, so a burst of rebuilds (e.g. cache misses right after a root
+/// change) queries the database once. Keep one cache per leaf layout.
+#[derive(Default)]
+pub struct TreeCache {
+    built: Mutex<Option<Arc<BuiltTree>>>,
+    builds: AtomicUsize,
+}
+
+impl TreeCache {
+    /// The cached tree, built first if there is none. Callers arriving during a build
+    /// wait for it rather than starting their own; a failed build isn't cached.
+    pub async fn get_or_build(
+        &self,
+        pool: &PgPool,
+        leaf_secret: Option<&[u8; 32]>,
+        expiration_in_leaf: bool,
+    ) -> Result<Arc<BuiltTree>> {
+        let mut built = self.built.lock().await;
+        if let Some(tree) = built.as_ref() {
+            return Ok(tree.clone());
+        }
+
+        self.builds.fetch_add(1, Ordering::Relaxed);
+        let tree =
+            Arc::new(build_tree_from_db_with_layout(pool, leaf_secret, expiration_in_leaf).await?);
+        *built = Some(tree.clone());
+        Ok(tree)
+    }
+
+    /// Drop the cached tree so the next call rebuilds; call whenever the root changes
+    pub async fn invalidate(&self) {
+        *self.built.lock().await = None;
+    }
+
+    /// Database builds started so far
+    pub fn build_count(&self) -> usize {
+        self.builds.load(Ordering::Relaxed)
+    }
+}
+
+/// Rebuild the tree as it stood at `as_of_ts` (Unix seconds) from `subscriber_history`,
+/// e.g. to prove a wallet was a member at some past time. Only rebuilds the plain
+/// layout; see `build_tree_at_with_layout`.
+pub async fn build_tree_at(pool: &PgPool, as_of_ts: i64) -> Result<BuiltTree> {
+    build_tree_at_with_layout(pool, as_of_ts, None, true).await
+}
+
+/// Same as `build_tree_at`, under the leaf layout in use at that time
+pub async fn build_tree_at_with_layout(
     pool: &PgPool,
-) -> Result<(String, MerkleTree<Sha256Hasher>, Vec<(String, i64)>)> {
-    // 1. Fetch both wallet and expiration
+    as_of_ts: i64,
+    leaf_secret: Option<&[u8; 32]>,
+    expiration_in_leaf: bool,
+) -> Result<BuiltTree> {
+    // Latest change per wallet at or before the cutoff; wallets whose latest change
+    // is a removal weren't members then
     let rows = sqlx::query_as::<_, (String, i64)>(
-        "SELECT wallet_address, expiration_ts FROM subscriber_storage",
+        r#"SELECT wallet_address, expiration_ts FROM (
+               SELECT DISTINCT ON (wallet_address) wallet_address, expiration_ts, deleted
+               FROM subscriber_history
+               WHERE changed_at <= to_timestamp($1) AT TIME ZONE 'UTC'
+               ORDER BY wallet_address, changed_at DESC, id DESC
+           ) latest
+           WHERE NOT deleted
+           ORDER BY wallet_address COLLATE "C""#,
     )
+    .bind(as_of_ts)
     .fetch_all(pool)
     .await?;
 
-    let mut subscribers = rows;
+    let subscribers = rows
+        .into_iter()
+        .map(|(wallet, exp)| {
+            let pubkey = decode_pubkey(&wallet)?;
+            let exp = Expiration::new(exp)
+                .with_context(|| format!("Invalid expiration for {}", wallet))?;
+            Ok((wallet, pubkey, exp))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if subscribers.is_empty() {
+        return Err(anyhow::anyhow!("No subscribers as of {}", as_of_ts));
+    }
+
+    build_tree_from_pubkey_bytes::<Sha256Hasher>(subscribers, leaf_secret, expiration_in_leaf, true)
+}
+
+/// Same as `build_tree_from_db_with_layout`, but with leaves in `leaf_id` (insertion)
+/// order instead of wallet order.
+///
+/// New wallets are appended, so existing leaves keep their index and proofs across
+/// inserts; removing a wallet still shifts the leaves after it. Gives a different root
+/// than the wallet-ordered build, so one deployment must stick to one ordering.
+pub async fn build_tree_from_db_by_leaf_id(
+    pool: &PgPool,
+    leaf_secret: Option<&[u8; 32]>,
+    expiration_in_leaf: bool,
+) -> Result<BuiltTree> {
+    let rows = sqlx::query_as::<_, (String, Option<Vec<u8>>, i64)>(
+        "SELECT wallet_address, wallet_pubkey, expiration_ts FROM subscriber_storage
+         ORDER BY leaf_id",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let subscribers = rows
+        .into_iter()
+        .map(|(wallet, pubkey, exp)| {
+            let pubkey = stored_pubkey(&wallet, pubkey)?;
+            let exp = Expiration::new(exp)
+                .with_context(|| format!("Invalid expiration for {}", wallet))?;
+            Ok((wallet, pubkey, None, exp))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if subscribers.is_empty() {
+        return Err(anyhow::anyhow!("No subscribers found in database"));
+    }
+
+    build_tree_from_leaf_parts::<Sha256Hasher>(
+        subscribers,
+        leaf_secret,
+        expiration_in_leaf,
+        PubkeyLeafEncoding::RawBytes,
+        LeafOrder::AsGiven,
+    )
+    .map(|(built, _)| built)
+}
+
+/// Same as `build_tree_from_db_with_layout`, for time-window memberships whose leaves
+/// also commit to the stored `start_ts` (must match the on-chain `start_in_leaf` flag)
+pub async fn build_windowed_tree_from_db(
+    pool: &PgPool,
+    leaf_secret: Option<&[u8; 32]>,
+    expiration_in_leaf: bool,
+) -> Result<WindowedTree> {
+    let rows = sqlx::query_as::<_, (String, Option<Vec<u8>>, i64, i64)>(
+        r#"SELECT wallet_address, wallet_pubkey, start_ts, expiration_ts FROM subscriber_storage
+           ORDER BY wallet_address COLLATE "C""#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let subscribers = rows
+        .into_iter()
+        .map(|(wallet, pubkey, start_ts, exp)| {
+            let pubkey = stored_pubkey(&wallet, pubkey)?;
+            let exp = Expiration::new(exp)
+                .with_context(|| format!("Invalid expiration for {}", wallet))?;
+            Ok((wallet, pubkey, Some(start_ts), exp))
+        })
+        .collect::<Result<Vec<_>>>()?;
     if subscribers.is_empty() {
         return Err(anyhow::anyhow!("No subscribers found in database"));
     }
 
+    let (built, starts) = build_tree_from_leaf_parts::<Sha256Hasher>(
+        subscribers,
+        leaf_secret,
+        expiration_in_leaf,
+        PubkeyLeafEncoding::RawBytes,
+        LeafOrder::PresortedByWallet,
+    )?;
+    Ok((built, starts.into_iter().flatten().collect()))
+}
+
+/// Build a tree whose leaves are `Hash(pubkey + start [+ expiration] [+ salt])` from
+/// `(wallet, start_ts, expiration)` entries
+pub fn build_windowed_tree<H: Hasher<Hash = [u8; 32]>>(
+    subscribers: Vec<(String, i64, Expiration)>,
+    leaf_secret: Option<&[u8; 32]>,
+    expiration_in_leaf: bool,
+) -> Result<WindowedTree<H>> {
+    let subscribers = subscribers
+        .into_iter()
+        .map(|(wallet, start_ts, exp)| {
+            let pubkey = decode_pubkey(&wallet)?;
+            Ok((wallet, pubkey, Some(start_ts), exp))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let (built, starts) = build_tree_from_leaf_parts::<H>(
+        subscribers,
+        leaf_secret,
+        expiration_in_leaf,
+        PubkeyLeafEncoding::RawBytes,
+        LeafOrder::SortByWallet,
+    )?;
+    Ok((built, starts.into_iter().flatten().collect()))
+}
+
+/// Build the tree from an in-memory subscriber list (no database required).
+///
+/// Returns the hex root, the tree, and the subscribers in leaf order.
+pub fn build_tree_from_subscribers(subscribers: Vec<(String, Expiration)>) -> Result<BuiltTree> {
+    build_tree_from_subscribers_with::<Sha256Hasher>(subscribers)
+}
+
+/// Same as `build_tree_from_subscribers`, under an arbitrary hasher
+pub fn build_tree_from_subscribers_with<H: Hasher<Hash = [u8; 32]>>(
+    subscribers: Vec<(String, Expiration)>,
+) -> Result<BuiltTree<H>> {
+    build_salted_tree_with::<H>(subscribers, None)
+}
+
+/// Build a tree whose leaves are salted per wallet (see `leaf_salt`); `None` builds
+/// the usual unsalted tree
+pub fn build_salted_tree_with<H: Hasher<Hash = [u8; 32]>>(
+    subscribers: Vec<(String, Expiration)>,
+    leaf_secret: Option<&[u8; 32]>,
+) -> Result<BuiltTree<H>> {
+    build_tree_with_layout::<H>(subscribers, leaf_secret, true)
+}
+
+/// Build a tree whose leaves include each expiration, or only the pubkey (and salt)
+/// for a static allowlist when `expiration_in_leaf` is false
+pub fn build_tree_with_layout<H: Hasher<Hash = [u8; 32]>>(
+    subscribers: Vec<(String, Expiration)>,
+    leaf_secret: Option<&[u8; 32]>,
+    expiration_in_leaf: bool,
+) -> Result<BuiltTree<H>> {
+    build_tree_with_encoding::<H>(
+        subscribers,
+        leaf_secret,
+        expiration_in_leaf,
+        PubkeyLeafEncoding::RawBytes,
+    )
+}
+
+/// Same as `build_tree_with_layout`, hashing each pubkey into its leaf per `encoding`
+pub fn build_tree_with_encoding<H: Hasher<Hash = [u8; 32]>>(
+    subscribers: Vec<(String, Expiration)>,
+    leaf_secret: Option<&[u8; 32]>,
+    expiration_in_leaf: bool,
+    encoding: PubkeyLeafEncoding,
+) -> Result<BuiltTree<H>> {
+    let subscribers = subscribers
+        .into_iter()
+        .map(|(wallet, exp)| {
+            let pubkey = decode_pubkey(&wallet)?;
+            Ok((wallet, pubkey, None, exp))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    build_tree_from_leaf_parts::<H>(
+        subscribers,
+        leaf_secret,
+        expiration_in_leaf,
+        encoding,
+        LeafOrder::SortByWallet,
+    )
+    .map(|(built, _)| built)
+}
+
+/// Same as `build_tree_with_layout`, for subscribers whose base58 wallet has already
+/// been decoded to its raw pubkey bytes, skipping the decode on the hot path.
+///
+/// With `presorted`, the caller guarantees the input is already ordered by wallet
+/// (e.g. by the query) and the sort is skipped; debug builds assert it.
+pub fn build_tree_from_pubkey_bytes<H: Hasher<Hash = [u8; 32]>>(
+    subscribers: Vec<(String, [u8; 32], Expiration)>,
+    leaf_secret: Option<&[u8; 32]>,
+    expiration_in_leaf: bool,
+    presorted: bool,
+) -> Result<BuiltTree<H>> {
+    let subscribers = subscribers
+        .into_iter()
+        .map(|(wallet, pubkey, exp)| (wallet, pubkey, None, exp))
+        .collect();
+    build_tree_from_leaf_parts::<H>(
+        subscribers,
+        leaf_secret,
+        expiration_in_leaf,
+        PubkeyLeafEncoding::RawBytes,
+        LeafOrder::by_wallet(presorted),
+    )
+    .map(|(built, _)| built)
+}
+
+/// How a wallet's pubkey enters its leaf (variant order matches the on-chain
+/// `PubkeyLeafEncoding`). `RawBytes`, the default, hashes the 32 decoded bytes, which
+/// the program takes from `user_key.to_bytes()`; `Base58String` hashes the base58 text
+/// instead, for trees built by tooling that hashes the string form. The two never
+/// produce the same leaf, so a tree must be built with the config's encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PubkeyLeafEncoding {
+    #[default]
+    RawBytes,
+    Base58String,
+}
+
+impl PubkeyLeafEncoding {
+    /// Borsh enum tag used in instruction and account data
+    pub fn to_u8(self) -> u8 {
+        match self {
+            PubkeyLeafEncoding::RawBytes => 0,
+            PubkeyLeafEncoding::Base58String => 1,
+        }
+    }
+
+    pub fn from_u8(tag: u8) -> Result<Self> {
+        match tag {
+            0 => Ok(PubkeyLeafEncoding::RawBytes),
+            1 => Ok(PubkeyLeafEncoding::Base58String),
+            _ => Err(anyhow::anyhow!("Unknown pubkey leaf encoding tag {}", tag)),
+        }
+    }
+}
+
+/// How the shared builder orders its input into leaves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LeafOrder {
+    /// Sort by wallet here
+    SortByWallet,
+    /// Already sorted by wallet by the caller; debug builds assert it
+    PresortedByWallet,
+    /// Keep the caller's order as-is (e.g. by `leaf_id`)
+    AsGiven,
+}
+
+impl LeafOrder {
+    fn by_wallet(presorted: bool) -> Self {
+        if presorted {
+            LeafOrder::PresortedByWallet
+        } else {
+            LeafOrder::SortByWallet
+        }
+    }
+}
+
+/// Shared builder: orders the input per `order`, hashes each leaf from its parts,
+/// and returns the optional start times in leaf order next to the built tree
+fn build_tree_from_leaf_parts<H: Hasher<Hash = [u8; 32]>>(
+    mut subscribers: Vec<(String, [u8; 32], Option<i64>, Expiration)>,
+    leaf_secret: Option<&[u8; 32]>,
+    expiration_in_leaf: bool,
+    encoding: PubkeyLeafEncoding,
+    order: LeafOrder,
+) -> Result<(BuiltTree<H>, Vec<Option<i64>>)> {
+    if subscribers.is_empty() {
+        return Err(anyhow::anyhow!("Cannot build a tree without subscribers"));
+    }
+
     // Sort by wallet_address to keep the tree deterministic
-    subscribers.sort_by(|a, b| a.0.cmp(&b.0));
+    match order {
+        LeafOrder::SortByWallet => subscribers.sort_by(|a, b| a.0.cmp(&b.0)),
+        LeafOrder::PresortedByWallet => debug_assert!(
+            subscribers.windows(2).all(|pair| pair[0].0 <= pair[1].0),
+            "Subscribers passed as presorted are not sorted by wallet"
+        ),
+        LeafOrder::AsGiven => {}
+    }
 
-    // 2. Generate Leaves: Hash(PubKey_BYTES + Expiration)
-    // ⚠️ CRITICAL: Must decode base58 pubkey to 32 bytes (matches Solana's user_key.to_bytes())
+    // Generate Leaves: Hash(PubKey_BYTES [+ Start] [+ Expiration] [+ Salt])
     let leaves: Vec<[u8; 32]> = subscribers
         .iter()
-        .map(|(pk_str, exp)| {
-            // Decode base58 pubkey to 32 bytes
-            let pubkey_bytes = bs58::decode(pk_str)
-                .into_vec()
-                .expect("Invalid base58 pubkey in database");
-
-            if pubkey_bytes.len() != 32 {
-                panic!("Pubkey must be exactly 32 bytes");
-            }
-
-            let mut payload = Vec::with_capacity(40);
-            payload.extend_from_slice(&pubkey_bytes);
-            payload.extend_from_slice(&exp.to_le_bytes());
-            Sha256Hasher::hash(&payload)
+        .map(|(_, pubkey, start_ts, exp)| {
+            let salt = leaf_secret.map(|secret| leaf_salt_from_bytes(secret, pubkey));
+            let exp = expiration_in_leaf.then_some(*exp);
+            encoded_leaf_from_parts::<H>(encoding, pubkey, *start_ts, exp, salt.as_ref())
         })
         .collect();
 
-    let merkle_tree = MerkleTree::<Sha256Hasher>::from_leaves(&leaves);
+    let merkle_tree = MerkleTree::<H>::from_leaves(&leaves);
     let root = merkle_tree
         .root()
         .ok_or_else(|| anyhow::anyhow!("Failed to generate root"))?;
+    if cfg!(any(debug_assertions, feature = "verify_after_build")) {
+        self_check_tree(&merkle_tree, &leaves)?;
+    }
 
-    Ok((hex::encode(root), merkle_tree, subscribers))
+    let (subscribers, starts) = subscribers
+        .into_iter()
+        .map(|(wallet, _, start_ts, exp)| ((wallet, exp), start_ts))
+        .unzip();
+    Ok(((hex::encode(root), merkle_tree, subscribers), starts))
 }
 
-/// Returns (Serialized Proof Bytes, Leaf Index)
-pub fn get_proof_for_user(
-    tree: &MerkleTree<Sha256Hasher>,
-    subscribers: &[(String, i64)],
+/// Prove a randomly chosen leaf and check the proof against the tree's root, so a
+/// leaf-construction bug or an `rs_merkle` behaviour change fails the build instead of
+/// being pushed on-chain. Runs after every build in debug builds and with the
+/// `verify_after_build` feature; returns the index it checked.
+pub fn self_check_tree<H: Hasher<Hash = [u8; 32]>>(
+    tree: &MerkleTree<H>,
+    leaves: &[[u8; 32]],
+) -> Result<usize> {
+    use std::hash::{BuildHasher, Hasher as _};
+
+    let root = tree
+        .root()
+        .ok_or_else(|| anyhow::anyhow!("Tree has no root"))?;
+    if leaves.is_empty() {
+        return Err(anyhow::anyhow!("Tree self-check needs at least one leaf"));
+    }
+    // RandomState is seeded per process, which is all the randomness this needs
+    let index = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish() as usize
+        % leaves.len();
+
+    let proof = tree.proof(&[index]);
+    if !proof.verify(root, &[index], &[leaves[index]], leaves.len()) {
+        return Err(anyhow::anyhow!(
+            "Tree self-check failed: proof for leaf {} of {} does not verify against {}",
+            index,
+            leaves.len(),
+            hex::encode(root)
+        ));
+    }
+    Ok(index)
+}
+
+/// Leaf for a subscriber: `Hash(pubkey_bytes[32] + expiration_le[8])`.
+///
+/// Mirrors the on-chain reconstruction in `verify_subscription`, which always derives
+/// the leaf from the signing user's key. A leaf must never be accepted from a caller
+/// without re-deriving it this way, otherwise anyone could present another member's leaf.
+pub fn compute_leaf(user_pubkey: &str, expiration: Expiration) -> Result<[u8; 32]> {
+    compute_leaf_with::<Sha256Hasher>(user_pubkey, expiration)
+}
+
+/// Same as `compute_leaf`, under an arbitrary hasher
+pub fn compute_leaf_with<H: Hasher<Hash = [u8; 32]>>(
+    user_pubkey: &str,
+    expiration: Expiration,
+) -> Result<[u8; 32]> {
+    compute_salted_leaf_with::<H>(user_pubkey, expiration, None)
+}
+
+/// Leaf with an optional salt appended: `Hash(pubkey_bytes[32] + expiration_le[8] + salt[32])`.
+///
+/// Matches the on-chain leaf when the same salt is passed as `leaf_salt`.
+pub fn compute_salted_leaf_with<H: Hasher<Hash = [u8; 32]>>(
+    user_pubkey: &str,
+    expiration: Expiration,
+    salt: Option<&[u8; 32]>,
+) -> Result<[u8; 32]> {
+    compute_leaf_parts::<H>(user_pubkey, Some(expiration), salt)
+}
+
+/// Leaf with the expiration optional as well: `None` gives the static allowlist leaf
+/// `Hash(pubkey_bytes[32] [+ salt[32]])` used when the config's `expiration_in_leaf` is off
+pub fn compute_leaf_parts<H: Hasher<Hash = [u8; 32]>>(
     user_pubkey: &str,
+    expiration: Option<Expiration>,
+    salt: Option<&[u8; 32]>,
+) -> Result<[u8; 32]> {
+    let pubkey_bytes = decode_pubkey(user_pubkey)?;
+    Ok(leaf_from_pubkey_bytes::<H>(&pubkey_bytes, expiration, salt))
+}
+
+/// Same as `compute_leaf_parts`, from raw pubkey bytes
+pub fn leaf_from_pubkey_bytes<H: Hasher<Hash = [u8; 32]>>(
+    pubkey_bytes: &[u8; 32],
+    expiration: Option<Expiration>,
+    salt: Option<&[u8; 32]>,
+) -> [u8; 32] {
+    leaf_from_parts::<H>(pubkey_bytes, None, expiration, salt)
+}
+
+/// Leaf with every optional part:
+/// `Hash(pubkey_bytes[32] [+ start_le[8]] [+ expiration_le[8]] [+ salt[32]])`.
+///
+/// A start time is included only for configs with `start_in_leaf` on, where the
+/// program also checks that it isn't in the future.
+pub fn leaf_from_parts<H: Hasher<Hash = [u8; 32]>>(
+    pubkey_bytes: &[u8; 32],
+    start_ts: Option<i64>,
+    expiration: Option<Expiration>,
+    salt: Option<&[u8; 32]>,
+) -> [u8; 32] {
+    encoded_leaf_from_parts::<H>(
+        PubkeyLeafEncoding::RawBytes,
+        pubkey_bytes,
+        start_ts,
+        expiration,
+        salt,
+    )
+}
+
+/// Same as `leaf_from_parts`, with the pubkey hashed per `encoding`. The salt is
+/// always derived from the raw bytes.
+pub fn encoded_leaf_from_parts<H: Hasher<Hash = [u8; 32]>>(
+    encoding: PubkeyLeafEncoding,
+    pubkey_bytes: &[u8; 32],
+    start_ts: Option<i64>,
+    expiration: Option<Expiration>,
+    salt: Option<&[u8; 32]>,
+) -> [u8; 32] {
+    let mut payload = Vec::with_capacity(100);
+    match encoding {
+        PubkeyLeafEncoding::RawBytes => payload.extend_from_slice(pubkey_bytes),
+        PubkeyLeafEncoding::Base58String => {
+            payload.extend_from_slice(bs58::encode(pubkey_bytes).into_string().as_bytes())
+        }
+    }
+    if let Some(start_ts) = start_ts {
+        payload.extend_from_slice(&start_ts.to_le_bytes());
+    }
+    if let Some(expiration) = expiration {
+        payload.extend_from_slice(&expiration.to_le_bytes());
+    }
+    if let Some(salt) = salt {
+        payload.extend_from_slice(salt);
+    }
+    H::hash(&payload)
+}
+
+/// Per-wallet leaf salt: `Sha256(leaf_secret + pubkey_bytes)`.
+///
+/// The secret stays in the backend. Each wallet receives only its own salt with its
+/// proof, so observers of published proofs can't recompute other members' leaves,
+/// and a salt revealed by one wallet's verify transaction says nothing about others.
+pub fn leaf_salt(leaf_secret: &[u8; 32], user_pubkey: &str) -> Result<[u8; 32]> {
+    Ok(leaf_salt_from_bytes(
+        leaf_secret,
+        &decode_pubkey(user_pubkey)?,
+    ))
+}
+
+/// Same as `leaf_salt`, from raw pubkey bytes
+pub fn leaf_salt_from_bytes(leaf_secret: &[u8; 32], pubkey_bytes: &[u8; 32]) -> [u8; 32] {
+    let mut payload = Vec::with_capacity(64);
+    payload.extend_from_slice(leaf_secret);
+    payload.extend_from_slice(pubkey_bytes);
+    Sha256Hasher::hash(&payload)
+}
+
+/// A wallet given either as base58 text or as raw pubkey bytes.
+///
+/// Callers already holding a `Pubkey` pass it directly and skip the base58 round-trip;
+/// the DB-backed flows keep passing the stored string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PubkeyInput<'a> {
+    Base58(&'a str),
+    Bytes([u8; 32]),
+}
+
+impl PubkeyInput<'_> {
+    pub fn to_bytes(self) -> Result<[u8; 32]> {
+        match self {
+            Self::Base58(user_pubkey) => decode_pubkey(user_pubkey),
+            Self::Bytes(bytes) => Ok(bytes),
+        }
+    }
+
+    /// Whether this is the wallet stored as `wallet`; byte inputs are encoded once by the caller
+    fn matches(&self, wallet: &str, encoded: Option<&str>) -> bool {
+        match self {
+            Self::Base58(user_pubkey) => *user_pubkey == wallet,
+            Self::Bytes(_) => encoded == Some(wallet),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for PubkeyInput<'a> {
+    fn from(user_pubkey: &'a str) -> Self {
+        Self::Base58(user_pubkey)
+    }
+}
+
+impl<'a> From<&'a String> for PubkeyInput<'a> {
+    fn from(user_pubkey: &'a String) -> Self {
+        Self::Base58(user_pubkey)
+    }
+}
+
+impl From<[u8; 32]> for PubkeyInput<'_> {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self::Bytes(bytes)
+    }
+}
+
+impl From<Pubkey> for PubkeyInput<'_> {
+    fn from(pubkey: Pubkey) -> Self {
+        Self::Bytes(pubkey.to_bytes())
+    }
+}
+
+impl From<&Pubkey> for PubkeyInput<'_> {
+    fn from(pubkey: &Pubkey) -> Self {
+        Self::Bytes(pubkey.to_bytes())
+    }
+}
+
+fn decode_pubkey(user_pubkey: &str) -> Result<[u8; 32]> {
+    // ⚠️ CRITICAL: Decode base58 pubkey to bytes (matches on-chain user_key.to_bytes())
+    let pubkey_bytes = bs58::decode(user_pubkey)
+        .into_vec()
+        .with_context(|| format!("Invalid base58 pubkey: {}", user_pubkey))?;
+
+    pubkey_bytes
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("Pubkey must be 32 bytes"))
+}
+
+/// Everything a client needs to call `verify_subscription` on-chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SerializedProof {
+    pub wallet: String,
+    pub expiration: i64,
+    pub proof_hex: String,
+    pub leaf_index: usize,
+    pub total_leaves: usize,
+    /// Hex leaf salt to pass as `leaf_salt` when the tree is salted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leaf_salt: Option<String>,
+    /// Hex root this proof verifies against; it goes stale once the on-chain root changes
+    pub valid_as_of_root: String,
+    /// Unix time by which the next sync may have replaced the root, when syncing
+    /// on a regular cadence; refetch the proof after this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_stale_after: Option<i64>,
+}
+
+/// Upper bound on the number of sibling hashes in a proof: ceil(log2(total_leaves))
+pub fn proof_depth(total_leaves: usize) -> usize {
+    if total_leaves <= 1 {
+        return 0;
+    }
+    (usize::BITS - (total_leaves - 1).leading_zeros()) as usize
+}
+
+/// Whether going from `old_count` to `new_count` leaves changes `proof_depth`, i.e.
+/// crosses a power of two. Every member's proof then gains or loses a hash, which
+/// can push verify transactions that used to fit over the size limit.
+pub fn will_depth_change(old_count: usize, new_count: usize) -> bool {
+    proof_depth(old_count) != proof_depth(new_count)
+}
+
+/// Number of sibling hashes in a well-formed proof for leaf `index`, or `None` if the
+/// index is outside the tree. An odd node out is promoted without a sibling, so it
+/// contributes no hash at that level.
+pub fn expected_proof_len(index: usize, total_leaves: usize) -> Option<usize> {
+    if index >= total_leaves {
+        return None;
+    }
+
+    let (mut index, mut layer_len, mut hashes) = (index, total_leaves, 0);
+    while layer_len > 1 {
+        if index ^ 1 < layer_len {
+            hashes += 1;
+        }
+        index /= 2;
+        layer_len = layer_len.div_ceil(2);
+    }
+    Some(hashes)
+}
+
+/// Parse untrusted proof bytes, rejecting any proof that isn't exactly the hashes
+/// leaf `index` of `total_leaves` needs (no trailing bytes) before rs_merkle walks its
+/// layers. The program applies the same check (`MalformedProofLength`).
+fn parse_proof<H: Hasher<Hash = [u8; 32]>>(
+    proof_bytes: &[u8],
+    index: usize,
+    total_leaves: usize,
+) -> Result<MerkleProof<H>> {
+    let expected = expected_proof_len(index, total_leaves).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Leaf index {} out of range for {} leaves",
+            index,
+            total_leaves
+        )
+    })?;
+    if proof_bytes.len() != expected * 32 {
+        return Err(anyhow::anyhow!(
+            "Malformed proof length: {} bytes, expected {} for leaf {} of {}",
+            proof_bytes.len(),
+            expected * 32,
+            index,
+            total_leaves
+        ));
+    }
+
+    MerkleProof::<H>::try_from(proof_bytes).map_err(|_| anyhow::anyhow!("Invalid proof format"))
+}
+
+/// Leaf indices in `new_tree` whose clients must refetch after a change: the
+/// `changed_indices` themselves, plus every leaf whose sibling hashes differ from its
+/// proof in `old_tree` (or that had no leaf there).
+///
+/// A changed leaf alters each of its ancestors, and every other leaf has one of those
+/// ancestors as the sibling where their paths meet, so with a fixed leaf count a
+/// change reaches all other proofs; only indices past the new end are ever spared.
+/// The comparison is exact, so pushes can rely on it rather than notify blindly.
+pub fn affected_by_change<H: Hasher<Hash = [u8; 32]>>(
+    old_tree: &MerkleTree<H>,
+    new_tree: &MerkleTree<H>,
+    changed_indices: &[usize],
+) -> Vec<usize> {
+    let (old_len, new_len) = (old_tree.leaves_len(), new_tree.leaves_len());
+    (0..new_len)
+        .filter(|&index| {
+            changed_indices.contains(&index)
+                || index >= old_len
+                || old_tree.proof(&[index]).proof_hashes()
+                    != new_tree.proof(&[index]).proof_hashes()
+        })
+        .collect()
+}
+
+/// Returns (Serialized Proof Bytes, Leaf Index)
+pub fn get_proof_for_user<'a, H: Hasher>(
+    tree: &MerkleTree<H>,
+    subscribers: &[(String, Expiration)],
+    user_pubkey: impl Into<PubkeyInput<'a>>,
 ) -> Option<(Vec<u8>, usize)> {
-    let index = subscribers.iter().position(|(pk, _)| pk == user_pubkey)?;
+    let user_pubkey = user_pubkey.into();
+    let encoded = match user_pubkey {
+        PubkeyInput::Bytes(bytes) => Some(bs58::encode(bytes).into_string()),
+        PubkeyInput::Base58(_) => None,
+    };
+    let index = subscribers
+        .iter()
+        .position(|(pk, _)| user_pubkey.matches(pk, encoded.as_deref()))?;
     let proof = tree.proof(&[index]);
 
     Some((proof.to_bytes(), index))
 }
 
-pub fn verify_subscription(
+/// Proofs for several wallets in one pass; `None` for wallets not in the tree.
+///
+/// The wallet-to-index map is built once rather than scanning per wallet.
+pub fn get_proofs_for_users<H: Hasher>(
+    tree: &MerkleTree<H>,
+    subscribers: &[(String, Expiration)],
+    wallets: &[String],
+    leaf_secret: Option<&[u8; 32]>,
+) -> Vec<Option<SerializedProof>> {
+    let root_hex = tree.root_hex().unwrap_or_default();
+    let positions: HashMap<&str, usize> = subscribers
+        .iter()
+        .enumerate()
+        .map(|(index, (pk, _))| (pk.as_str(), index))
+        .collect();
+
+    wallets
+        .iter()
+        .map(|wallet| {
+            let index = *positions.get(wallet.as_str())?;
+            Some(serialized_proof_at(
+                tree,
+                subscribers,
+                index,
+                leaf_secret,
+                &root_hex,
+            ))
+        })
+        .collect()
+}
+
+fn serialized_proof_at<H: Hasher>(
+    tree: &MerkleTree<H>,
+    subscribers: &[(String, Expiration)],
+    index: usize,
+    leaf_secret: Option<&[u8; 32]>,
+    root_hex: &str,
+) -> SerializedProof {
+    let (wallet, expiration) = &subscribers[index];
+    SerializedProof {
+        wallet: wallet.clone(),
+        expiration: expiration.as_i64(),
+        proof_hex: hex::encode(tree.proof(&[index]).to_bytes()),
+        leaf_index: index,
+        total_leaves: subscribers.len(),
+        leaf_salt: leaf_secret
+            .and_then(|secret| leaf_salt(secret, wallet).ok())
+            .map(hex::encode),
+        valid_as_of_root: root_hex.to_string(),
+        estimated_stale_after: None,
+    }
+}
+
+/// Proof that `wallet` is not in a tree sorted by wallet: the members on either side
+/// of where it would sort, each with its membership proof. `below` is `None` when the
+/// wallet sorts before every member, `above` when it sorts after every member.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NonMembershipProof {
+    pub wallet: String,
+    pub below: Option<SerializedProof>,
+    pub above: Option<SerializedProof>,
+}
+
+/// Absence proof for `wallet`, or `None` if it is a member.
+///
+/// Only meaningful for trees built sorted by wallet (the default order, not
+/// `build_tree_from_db_by_leaf_id`): adjacent leaves then bracket every wallet that
+/// could sort between them, so a wallet between two adjacent members can't be a
+/// member itself. Leaves are taken to be unsalted with the expiration included.
+pub fn get_non_membership_proof<H: Hasher>(
+    tree: &MerkleTree<H>,
+    subscribers: &[(String, Expiration)],
+    wallet: &str,
+) -> Option<NonMembershipProof> {
+    debug_assert!(
+        subscribers.windows(2).all(|pair| pair[0].0 <= pair[1].0),
+        "Non-membership proofs need a tree sorted by wallet"
+    );
+    let position = subscribers
+        .binary_search_by(|(pk, _)| pk.as_str().cmp(wallet))
+        .err()?;
+    let root_hex = tree.root_hex().unwrap_or_default();
+    let bracket = |index: usize| serialized_proof_at(tree, subscribers, index, None, &root_hex);
+
+    Some(NonMembershipProof {
+        wallet: wallet.to_string(),
+        below: position.checked_sub(1).map(bracket),
+        above: (position < subscribers.len()).then(|| bracket(position)),
+    })
+}
+
+/// Check a `NonMembershipProof` against `root_hex`: the brackets must be adjacent
+/// leaves (or the first or last leaf alone), sort strictly around the wallet, and
+/// each verify as a member. Trusts that the tree behind the root was sorted.
+pub fn verify_non_membership(root_hex: &str, proof: &NonMembershipProof) -> Result<bool> {
+    let (below, above) = (proof.below.as_ref(), proof.above.as_ref());
+    let adjacent = match (below, above) {
+        (Some(below), Some(above)) => {
+            above.total_leaves == below.total_leaves && above.leaf_index == below.leaf_index + 1
+        }
+        (Some(below), None) => below.leaf_index + 1 == below.total_leaves,
+        (None, Some(above)) => above.leaf_index == 0,
+        (None, None) => false,
+    };
+    let wallet = proof.wallet.as_str();
+    let brackets_wallet = below.is_none_or(|below| below.wallet.as_str() < wallet)
+        && above.is_none_or(|above| wallet < above.wallet.as_str());
+    if !adjacent || !brackets_wallet {
+        return Ok(false);
+    }
+
+    for bracket in [below, above].into_iter().flatten() {
+        let salt = bracket
+            .leaf_salt
+            .as_deref()
+            .map(|salt_hex| -> Result<[u8; 32]> {
+                hex::decode(salt_hex)?
+                    .try_into()
+                    .map_err(|_| anyhow::anyhow!("Leaf salt must be 32 bytes"))
+            })
+            .transpose()?;
+        let is_member = verify_salted_subscription(
+            root_hex,
+            &hex::decode(&bracket.proof_hex).context("Proof must be hex")?,
+            bracket.wallet.as_str(),
+            Expiration::new(bracket.expiration)?,
+            salt.as_ref(),
+            bracket.leaf_index,
+            bracket.total_leaves,
+        )?;
+        if !is_member {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+pub fn verify_subscription<'a>(
+    root_hex: &str,
+    proof_bytes: &[u8],
+    user_pubkey: impl Into<PubkeyInput<'a>>,
+    expiration_ts: Expiration,
+    index: usize,
+    total_subscribers: usize,
+) -> Result<bool> {
+    verify_salted_subscription(
+        root_hex,
+        proof_bytes,
+        user_pubkey,
+        expiration_ts,
+        None,
+        index,
+        total_subscribers,
+    )
+}
+
+/// Same as `verify_subscription`, for a leaf built with `salt`
+pub fn verify_salted_subscription<'a>(
+    root_hex: &str,
+    proof_bytes: &[u8],
+    user_pubkey: impl Into<PubkeyInput<'a>>,
+    expiration_ts: Expiration,
+    salt: Option<&[u8; 32]>,
+    index: usize,
+    total_subscribers: usize,
+) -> Result<bool> {
+    // Reconstruct the SAME leaf: Hash(PubKey_BYTES + Expiration [+ Salt])
+    let pubkey_bytes = user_pubkey.into().to_bytes()?;
+    let leaf = leaf_from_pubkey_bytes::<Sha256Hasher>(&pubkey_bytes, Some(expiration_ts), salt);
+    verify_leaf(root_hex, proof_bytes, leaf, index, total_subscribers)
+}
+
+/// Same as `verify_subscription`, under the hash algorithm the on-chain config names
+pub fn verify_subscription_with_algo<'a>(
+    hash_algo: HashAlgo,
+    root_hex: &str,
+    proof_bytes: &[u8],
+    user_pubkey: impl Into<PubkeyInput<'a>>,
+    expiration_ts: Expiration,
+    index: usize,
+    total_subscribers: usize,
+) -> Result<bool> {
+    let pubkey_bytes = user_pubkey.into().to_bytes()?;
+    let verify = match hash_algo {
+        HashAlgo::Sha256 => verify_pubkey_leaf::<Sha256Hasher>,
+        HashAlgo::Keccak256 => verify_pubkey_leaf::<Keccak256Hasher>,
+        HashAlgo::DoubleSha256 => verify_pubkey_leaf::<DoubleSha256Hasher>,
+    };
+    verify(
+        root_hex,
+        proof_bytes,
+        &pubkey_bytes,
+        expiration_ts,
+        index,
+        total_subscribers,
+    )
+}
+
+/// The unsalted subscription leaf for `pubkey_bytes`, checked under hasher `H`
+fn verify_pubkey_leaf<H: Hasher<Hash = [u8; 32]>>(
+    root_hex: &str,
+    proof_bytes: &[u8],
+    pubkey_bytes: &[u8; 32],
+    expiration_ts: Expiration,
+    index: usize,
+    total_subscribers: usize,
+) -> Result<bool> {
+    let leaf = leaf_from_pubkey_bytes::<H>(pubkey_bytes, Some(expiration_ts), None);
+    verify_leaf_with::<H>(root_hex, proof_bytes, leaf, index, total_subscribers)
+}
+
+/// Verify membership in a static allowlist tree, whose leaves are `Hash(pubkey [+ salt])`
+pub fn verify_allowlist_membership(
     root_hex: &str,
     proof_bytes: &[u8],
     user_pubkey: &str,
-    expiration_ts: i64,
+    salt: Option<&[u8; 32]>,
+    index: usize,
+    total_subscribers: usize,
+) -> Result<bool> {
+    let leaf = compute_leaf_parts::<Sha256Hasher>(user_pubkey, None, salt)?;
+    verify_leaf(root_hex, proof_bytes, leaf, index, total_subscribers)
+}
+
+/// Same as `verify_subscription`, for an unsalted leaf that also commits to `start_ts`.
+/// Salted windowed leaves are checked with `leaf_from_parts` and `verify_leaf`.
+pub fn verify_windowed_subscription<'a>(
+    root_hex: &str,
+    proof_bytes: &[u8],
+    user_pubkey: impl Into<PubkeyInput<'a>>,
+    start_ts: i64,
+    expiration_ts: Expiration,
+    index: usize,
+    total_subscribers: usize,
+) -> Result<bool> {
+    let pubkey_bytes = user_pubkey.into().to_bytes()?;
+    let leaf =
+        leaf_from_parts::<Sha256Hasher>(&pubkey_bytes, Some(start_ts), Some(expiration_ts), None);
+    verify_leaf(root_hex, proof_bytes, leaf, index, total_subscribers)
+}
+
+/// Check `proof_bytes` for an already computed `leaf` against `root_hex`
+pub fn verify_leaf(
+    root_hex: &str,
+    proof_bytes: &[u8],
+    leaf: [u8; 32],
+    index: usize,
+    total_subscribers: usize,
+) -> Result<bool> {
+    verify_leaf_with::<Sha256Hasher>(root_hex, proof_bytes, leaf, index, total_subscribers)
+}
+
+/// Same as `verify_leaf`, under an arbitrary hasher
+pub fn verify_leaf_with<H: Hasher<Hash = [u8; 32]>>(
+    root_hex: &str,
+    proof_bytes: &[u8],
+    leaf: [u8; 32],
     index: usize,
     total_subscribers: usize,
 ) -> Result<bool> {
     // 1. Decode root
-    let root_vec = hex::decode(root_hex).context("Invalid root hex")?;
-    let root: [u8; 32] = root_vec
-        .try_into()
-        .map_err(|_| anyhow::anyhow!("Root must be 32 bytes"))?;
+    let root = MerkleRoot::from_hex(root_hex).context("Invalid root hex")?;
+    if root.is_unset() {
+        return Err(anyhow::anyhow!("Root is not set (all zeros)"));
+    }
 
     // 2. Parse proof
-    let proof = MerkleProof::<Sha256Hasher>::try_from(proof_bytes)
-        .map_err(|_| anyhow::anyhow!("Invalid proof format"))?;
+    let proof = parse_proof::<H>(proof_bytes, index, total_subscribers)?;
 
-    // 3. Reconstruct the SAME leaf: Hash(PubKey_BYTES + Expiration)
-    // ⚠️ CRITICAL: Decode base58 pubkey to bytes (matches on-chain user_key.to_bytes())
-    let pubkey_bytes = bs58::decode(user_pubkey)
-        .into_vec()
-        .context("Invalid base58 pubkey")?;
+    // 3. Verify
+    Ok(proof.verify(root.to_bytes(), &[index], &[leaf], total_subscribers))
+}
+
+/// Fold a proof's sibling hashes with `leaf` and return the root they produce.
+///
+/// When verification fails, comparing this against the expected root shows whether
+/// the path itself is wrong or the leaf was (a correct path with a wrong leaf still
+/// yields a root, just not the expected one).
+pub fn recompute_root(
+    proof_bytes: &[u8],
+    leaf: [u8; 32],
+    index: usize,
+    total_subscribers: usize,
+) -> Result<[u8; 32]> {
+    recompute_root_with::<Sha256Hasher>(proof_bytes, leaf, index, total_subscribers)
+}
+
+/// Same as `recompute_root`, under an arbitrary hasher
+pub fn recompute_root_with<H: Hasher<Hash = [u8; 32]>>(
+    proof_bytes: &[u8],
+    leaf: [u8; 32],
+    index: usize,
+    total_subscribers: usize,
+) -> Result<[u8; 32]> {
+    let proof = parse_proof::<H>(proof_bytes, index, total_subscribers)?;
+    proof
+        .root(&[index], &[leaf], total_subscribers)
+        .map_err(|e| anyhow::anyhow!("Failed to recompute root: {}", e))
+}
+
+/// Hex-encoded `recompute_root`, for logging next to the expected root
+pub fn recompute_root_hex(
+    proof_bytes: &[u8],
+    leaf: [u8; 32],
+    index: usize,
+    total_subscribers: usize,
+) -> Result<String> {
+    recompute_root(proof_bytes, leaf, index, total_subscribers).map(hex::encode)
+}
+
+/// Verify a proof for a leaf the client computed itself.
+///
+/// The supplied leaf is only trusted after it matches the leaf re-derived from
+/// `user_pubkey` and `expiration_ts`; a mismatch is rejected as an error.
+pub fn verify_precomputed_leaf(
+    root_hex: &str,
+    proof_bytes: &[u8],
+    leaf: [u8; 32],
+    user_pubkey: &str,
+    expiration_ts: Expiration,
+    index: usize,
+    total_subscribers: usize,
+) -> Result<bool> {
+    if leaf != compute_leaf(user_pubkey, expiration_ts)? {
+        return Err(anyhow::anyhow!(
+            "Supplied leaf does not match Hash(pubkey + expiration) for {}",
+            user_pubkey
+        ));
+    }
+
+    verify_subscription(
+        root_hex,
+        proof_bytes,
+        user_pubkey,
+        expiration_ts,
+        index,
+        total_subscribers,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures;
+    use chrono::Utc;
+
+    #[test]
+    fn merkle_root_round_trips_through_hex() -> Result<()> {
+        let root = MerkleRoot::from_hex(test_fixtures::EXPECTED_ROOT)?;
+        assert_eq!(root.to_hex(), test_fixtures::EXPECTED_ROOT);
+        assert_eq!(root.to_string(), test_fixtures::EXPECTED_ROOT);
+        assert_eq!(test_fixtures::EXPECTED_ROOT.parse::<MerkleRoot>()?, root);
+        assert_eq!(MerkleRoot::from(root.to_bytes()), root);
+        assert!(!root.is_unset());
+        assert!(MerkleRoot::new(UNSET_ROOT).is_unset());
+
+        // Uppercase hex parses to the same root but prints lowercase
+        let upper = MerkleRoot::from_hex(&test_fixtures::EXPECTED_ROOT.to_uppercase())?;
+        assert_eq!(upper, root);
+        Ok(())
+    }
+
+    #[test]
+    fn expiration_rejects_negative_timestamps() -> Result<()> {
+        assert!(Expiration::new(-1).is_err());
+        assert!(Expiration::try_from(-1).is_err());
+        assert!(Expiration::try_from(i64::MIN).is_err());
+
+        // Zero, the epoch, is a valid (long past) expiration
+        assert_eq!(Expiration::new(0)?.as_i64(), 0);
+        assert_eq!(Expiration::try_from(0)?, Expiration::new(0)?);
+        Ok(())
+    }
+
+    #[test]
+    fn merkle_root_rejects_wrong_lengths_and_non_hex() {
+        let short = &test_fixtures::EXPECTED_ROOT[..62];
+        let long = format!("{}00", test_fixtures::EXPECTED_ROOT);
+        for root_hex in ["", short, long.as_str()] {
+            let err = MerkleRoot::from_hex(root_hex).unwrap_err();
+            assert!(err.to_string().contains("32 bytes"), "{}", err);
+        }
+        // Odd length and non-hex chars fail to decode at all
+        assert!(MerkleRoot::from_hex(&test_fixtures::EXPECTED_ROOT[..63]).is_err());
+        assert!(MerkleRoot::from_hex(&"zz".repeat(32)).is_err());
+    }
+
+    #[sqlx::test]
+    async fn builds_tree_from_seeded_db(pool: PgPool) -> Result<()> {
+        let expected_root = test_fixtures::seed_subscribers(&pool).await?;
+
+        let (root_hash, _, subscribers) = build_tree_from_db(&pool).await?;
+
+        assert_eq!(root_hash, expected_root);
+        assert_eq!(subscribers.len(), test_fixtures::SUBSCRIBERS.len());
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn subscriber_set_digest_tracks_tree_inputs(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+        let digest = subscriber_set_digest(&pool).await?;
+        assert_eq!(subscriber_set_digest(&pool).await?, digest);
+
+        // Any expiration change is seen, and undoing it restores the digest
+        let (wallet, expiration_ts) = test_fixtures::SUBSCRIBERS[2];
+        let set_expiration = |expiration_ts: i64| {
+            sqlx::query(
+                "UPDATE subscriber_storage SET expiration_ts = $1 WHERE wallet_address = $2",
+            )
+            .bind(expiration_ts)
+            .bind(wallet)
+            .execute(&pool)
+        };
+        set_expiration(expiration_ts + 1).await?;
+        assert_ne!(subscriber_set_digest(&pool).await?, digest);
+        set_expiration(expiration_ts).await?;
+        assert_eq!(subscriber_set_digest(&pool).await?, digest);
+
+        // Columns that aren't in the leaves don't count
+        sqlx::query("UPDATE subscriber_storage SET start_ts = 1, last_verified_at = now()")
+            .execute(&pool)
+            .await?;
+        assert_eq!(subscriber_set_digest(&pool).await?, digest);
+
+        // Nor does a subscriber added then removed
+        let added = Pubkey::new_unique().to_string();
+        crate::merkle::subscriber::upsert_subscriber(&pool, &added, Expiration::new(1)?).await?;
+        assert_ne!(subscriber_set_digest(&pool).await?, digest);
+        sqlx::query("DELETE FROM subscriber_storage WHERE wallet_address = $1")
+            .bind(&added)
+            .execute(&pool)
+            .await?;
+        assert_eq!(subscriber_set_digest(&pool).await?, digest);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn stored_pubkey_bytes_build_the_base58_root(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+
+        let rows = sqlx::query_as::<_, (String, Vec<u8>)>(
+            "SELECT wallet_address, wallet_pubkey FROM subscriber_storage",
+        )
+        .fetch_all(&pool)
+        .await?;
+        for (wallet, pubkey) in rows {
+            assert_eq!(pubkey, bs58::decode(&wallet).into_vec()?);
+        }
+
+        let secret = [3u8; 32];
+        let (from_bytes, _, _) = build_tree_from_db_with_salt(&pool, Some(&secret)).await?;
+        let (from_base58, _, _) =
+            build_salted_tree_with::<Sha256Hasher>(test_fixtures::subscribers(), Some(&secret))?;
+        assert_eq!(from_bytes, from_base58);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn invalid_wallets_store_no_pubkey_and_fail_the_build(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+        let digest = subscriber_set_digest(&pool).await?;
+
+        // Not base58 at all, then base58 that decodes to 3 bytes: stored, not raised
+        for wallet in ["not-base58!", "abc"] {
+            crate::merkle::subscriber::upsert_subscriber(&pool, wallet, Expiration::new(1)?)
+                .await?;
+            let (pubkey,): (Option<Vec<u8>>,) = sqlx::query_as(
+                "SELECT wallet_pubkey FROM subscriber_storage WHERE wallet_address = $1",
+            )
+            .bind(wallet)
+            .fetch_one(&pool)
+            .await?;
+            assert_eq!(pubkey, None);
+        }
+
+        assert_ne!(subscriber_set_digest(&pool).await?, digest);
+        let err = build_tree_from_db(&pool).await.unwrap_err().to_string();
+        assert!(err.contains("abc"), "{}", err);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn windowed_tree_commits_to_stored_start_times(pool: PgPool) -> Result<()> {
+        let plain_root = test_fixtures::seed_subscribers(&pool).await?;
+        // Second in leaf order
+        let (wallet, expiration_ts) = test_fixtures::SUBSCRIBERS[0];
+        let start_ts = 1_700_000_000;
+        sqlx::query("UPDATE subscriber_storage SET start_ts = $1 WHERE wallet_address = $2")
+            .bind(start_ts)
+            .bind(wallet)
+            .execute(&pool)
+            .await?;
+
+        let ((root_hash, tree, subscribers), starts) =
+            build_windowed_tree_from_db(&pool, None, true).await?;
+        assert_ne!(root_hash, plain_root);
+        assert_eq!(starts, vec![0, start_ts, 0]);
+
+        let (proof_bytes, index) = get_proof_for_user(&tree, &subscribers, wallet).unwrap();
+        let expiration = Expiration::new(expiration_ts)?;
+        let total = subscribers.len();
+        let verify = |start_ts| {
+            verify_windowed_subscription(
+                &root_hash,
+                &proof_bytes,
+                wallet,
+                start_ts,
+                expiration,
+                index,
+                total,
+            )
+        };
+        assert!(verify(start_ts)?);
+        // Claiming an earlier start (e.g. for grandfathered pricing) breaks the proof
+        assert!(!verify(0)?);
+
+        // The in-memory builder gives the same root
+        let entries = test_fixtures::subscribers()
+            .into_iter()
+            .map(|(member, exp)| {
+                let member_start = if member == wallet { start_ts } else { 0 };
+                (member, member_start, exp)
+            })
+            .collect();
+        let ((in_memory_root, _, _), _) = build_windowed_tree::<Sha256Hasher>(entries, None, true)?;
+        assert_eq!(in_memory_root, root_hash);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn leaf_id_order_keeps_indices_stable_across_inserts(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+
+        let (root_hash, _, before) = build_tree_from_db_by_leaf_id(&pool, None, true).await?;
+        // Insertion order, not wallet order
+        assert_eq!(before, test_fixtures::subscribers());
+        assert_ne!(root_hash, test_fixtures::EXPECTED_ROOT);
+
+        // Sorts second by wallet, so it would shift every later leaf in the default order
+        let new_wallet = "11111111111111111111111111111112";
+        sqlx::query(
+            "INSERT INTO subscriber_storage (wallet_address, expiration_ts, last_updated_at) VALUES ($1, $2, $3)",
+        )
+        .bind(new_wallet)
+        .bind(1_900_000_000_i64)
+        .bind(Utc::now().naive_utc())
+        .execute(&pool)
+        .await?;
+
+        let (root_hash, tree, after) = build_tree_from_db_by_leaf_id(&pool, None, true).await?;
+        assert_eq!(after[..before.len()], before[..]);
+        assert_eq!(after[before.len()].0, new_wallet);
+
+        // Proofs are still generated and verified against the insertion-ordered leaves
+        let (wallet, expiration) = &after[0];
+        let (proof_bytes, index) = get_proof_for_user(&tree, &after, wallet).unwrap();
+        assert_eq!(index, 0);
+        assert!(verify_subscription(
+            &root_hash,
+            &proof_bytes,
+            wallet,
+            *expiration,
+            index,
+            after.len()
+        )?);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn tree_at_past_timestamp_excludes_later_changes(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+        // Backdate the seeded history so the later changes land after the cutoff
+        sqlx::query("UPDATE subscriber_history SET changed_at = to_timestamp(1600000000) AT TIME ZONE 'UTC'")
+            .execute(&pool)
+            .await?;
+        let as_of_ts = 1_650_000_000;
+
+        let added = "11111111111111111111111111111112";
+        sqlx::query(
+            "INSERT INTO subscriber_storage (wallet_address, expiration_ts, last_updated_at) VALUES ($1, $2, $3)",
+        )
+        .bind(added)
+        .bind(1_900_000_000_i64)
+        .bind(Utc::now().naive_utc())
+        .execute(&pool)
+        .await?;
+        let (removed, _) = test_fixtures::SUBSCRIBERS[2];
+        sqlx::query("DELETE FROM subscriber_storage WHERE wallet_address = $1")
+            .bind(removed)
+            .execute(&pool)
+            .await?;
+
+        let (root_hash, tree, subscribers) = build_tree_at(&pool, as_of_ts).await?;
+        assert_eq!(root_hash, test_fixtures::EXPECTED_ROOT);
+        assert!(subscribers.iter().all(|(wallet, _)| wallet != added));
+        let (proof_bytes, index) = get_proof_for_user(&tree, &subscribers, removed).unwrap();
+        assert!(verify_subscription(
+            &root_hash,
+            &proof_bytes,
+            removed,
+            subscribers[index].1,
+            index,
+            subscribers.len()
+        )?);
+
+        // Today's tree has the addition and not the removal, like the live build
+        let (now_root, _, now_subscribers) =
+            build_tree_at(&pool, Utc::now().timestamp() + 1).await?;
+        let (live_root, _, _) = build_tree_from_db(&pool).await?;
+        assert_eq!(now_root, live_root);
+        assert!(now_subscribers.iter().any(|(wallet, _)| wallet == added));
+        assert!(now_subscribers.iter().all(|(wallet, _)| wallet != removed));
 
-    if pubkey_bytes.len() != 32 {
-        return Err(anyhow::anyhow!("Pubkey must be 32 bytes"));
+        // Before anyone subscribed there is no tree
+        assert!(build_tree_at(&pool, 1_500_000_000).await.is_err());
+        Ok(())
     }
 
-    let mut payload = Vec::with_capacity(40);
-    payload.extend_from_slice(&pubkey_bytes);
-    payload.extend_from_slice(&expiration_ts.to_le_bytes());
-    let leaf = Sha256Hasher::hash(&payload);
+    #[test]
+    fn double_sha256_tree_matches_pinned_root_and_verifies() -> Result<()> {
+        const DOUBLE_SHA256_ROOT: &str =
+            "eb94ec0a157769043068fe7175746e0c66f8c65c93651a29a223471eabbd0fc8";
+        assert_eq!(
+            compute_root_with_algo(test_fixtures::subscribers(), HashAlgo::DoubleSha256)?,
+            DOUBLE_SHA256_ROOT
+        );
+        assert_eq!(
+            HashAlgo::from_u8(HashAlgo::DoubleSha256.to_u8())?,
+            HashAlgo::DoubleSha256
+        );
 
-    // 4. Verify
-    Ok(proof.verify(root, &[index], &[leaf], total_subscribers))
+        let (root_hash, tree, subscribers) =
+            build_tree_from_subscribers_with::<DoubleSha256Hasher>(test_fixtures::subscribers())?;
+        assert_eq!(root_hash, DOUBLE_SHA256_ROOT);
+        let (wallet, expiration) = &subscribers[1];
+        let (proof_bytes, index) = get_proof_for_user(&tree, &subscribers, wallet).unwrap();
+        let verify_under = |hash_algo| {
+            verify_subscription_with_algo(
+                hash_algo,
+                &root_hash,
+                &proof_bytes,
+                wallet.as_str(),
+                *expiration,
+                index,
+                subscribers.len(),
+            )
+        };
+
+        assert!(verify_under(HashAlgo::DoubleSha256)?);
+        let leaf = compute_leaf_with::<DoubleSha256Hasher>(wallet, *expiration)?;
+        assert_eq!(
+            hex::encode(recompute_root_with::<DoubleSha256Hasher>(
+                &proof_bytes,
+                leaf,
+                index,
+                subscribers.len()
+            )?),
+            DOUBLE_SHA256_ROOT
+        );
+        // The same proof under single sha256 is rejected, as by `verify_subscription`
+        assert!(!verify_under(HashAlgo::Sha256)?);
+        assert!(!verify_subscription(
+            &root_hash,
+            &proof_bytes,
+            wallet.as_str(),
+            *expiration,
+            index,
+            subscribers.len()
+        )?);
+        Ok(())
+    }
+
+    fn fixture_pubkey_bytes() -> Result<Vec<(String, [u8; 32], Expiration)>> {
+        test_fixtures::subscribers()
+            .into_iter()
+            .map(|(wallet, exp)| Ok((wallet.clone(), decode_pubkey(&wallet)?, exp)))
+            .collect()
+    }
+
+    #[test]
+    fn presorted_input_skips_the_sort_with_the_same_root() -> Result<()> {
+        let mut subscribers = fixture_pubkey_bytes()?;
+        subscribers.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let (root_hash, _, _) =
+            build_tree_from_pubkey_bytes::<Sha256Hasher>(subscribers, None, true, true)?;
+        assert_eq!(root_hash, test_fixtures::EXPECTED_ROOT);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "not sorted")]
+    fn unsorted_input_passed_as_presorted_is_caught() {
+        // `SUBSCRIBERS` is deliberately not in wallet order
+        let subscribers = fixture_pubkey_bytes().unwrap();
+        let _ = build_tree_from_pubkey_bytes::<Sha256Hasher>(subscribers, None, true, true);
+    }
+
+    #[test]
+    fn self_check_passes_for_a_valid_build_and_catches_wrong_leaves() -> Result<()> {
+        let (_, tree, _) = build_tree_from_subscribers(test_fixtures::subscribers())?;
+        let leaves = tree.leaves().unwrap();
+        let index = self_check_tree(&tree, &leaves)?;
+        assert!(index < leaves.len());
+
+        // Leaves that don't belong to the tree (e.g. built in a different order) fail
+        let wrong_leaves = vec![[9u8; 32]; leaves.len()];
+        assert!(self_check_tree(&tree, &wrong_leaves).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn a_single_leaf_change_reaches_every_sibling_path() -> Result<()> {
+        let leaves: Vec<[u8; 32]> = (0..4u8).map(|n| Sha256Hasher::hash(&[n])).collect();
+        let old_tree = MerkleTree::<Sha256Hasher>::from_leaves(&leaves);
+        assert!(affected_by_change(&old_tree, &old_tree, &[]).is_empty());
+
+        // Leaf 3 changes: leaf 2 has it as its level-0 sibling, leaves 0 and 1 have its
+        // parent as their level-1 sibling, and leaf 3's own proof is unchanged
+        let mut changed = leaves.clone();
+        changed[3] = Sha256Hasher::hash(b"renewed");
+        let new_tree = MerkleTree::<Sha256Hasher>::from_leaves(&changed);
+        assert_eq!(affected_by_change(&old_tree, &new_tree, &[]), vec![0, 1, 2]);
+        assert_eq!(
+            affected_by_change(&old_tree, &new_tree, &[3]),
+            vec![0, 1, 2, 3]
+        );
+
+        // Dropping the last leaf spares nobody still in the tree
+        let shrunk = MerkleTree::<Sha256Hasher>::from_leaves(&leaves[..3]);
+        assert_eq!(affected_by_change(&old_tree, &shrunk, &[]), vec![0, 1, 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn recomputed_root_matches_only_for_the_true_leaf() -> Result<()> {
+        let (root_hash, tree, subscribers) =
+            build_tree_from_subscribers(test_fixtures::subscribers())?;
+        let (wallet, expiration) = &subscribers[1];
+        let (proof_bytes, index) = get_proof_for_user(&tree, &subscribers, wallet).unwrap();
+
+        let leaf = compute_leaf(wallet, *expiration)?;
+        let recomputed = recompute_root_hex(&proof_bytes, leaf, index, subscribers.len())?;
+        assert_eq!(recomputed, root_hash);
+
+        let tampered = compute_leaf(wallet, Expiration::new(expiration.as_i64() + 1)?)?;
+        let recomputed = recompute_root_hex(&proof_bytes, tampered, index, subscribers.len())?;
+        assert_ne!(recomputed, root_hash);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn concurrent_rebuilds_share_one_build(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+        let cache = Arc::new(TreeCache::default());
+
+        let tasks: Vec<_> = (0..32)
+            .map(|_| {
+                let (cache, pool) = (cache.clone(), pool.clone());
+                tokio::spawn(async move { cache.get_or_build(&pool, None, true).await })
+            })
+            .collect();
+        for task in tasks {
+            let (root_hash, _, _) = &*task.await??;
+            assert_eq!(root_hash, test_fixtures::EXPECTED_ROOT);
+        }
+        assert_eq!(cache.build_count(), 1);
+
+        cache.invalidate().await;
+        cache.get_or_build(&pool, None, true).await?;
+        assert_eq!(cache.build_count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn depth_changes_only_across_powers_of_two() {
+        assert_eq!(proof_depth(4), 2);
+        assert_eq!(proof_depth(5), 3);
+        assert!(will_depth_change(4, 5));
+        assert!(will_depth_change(8, 9));
+        assert!(will_depth_change(9, 8));
+        assert!(will_depth_change(1, 2));
+
+        assert!(!will_depth_change(5, 8));
+        assert!(!will_depth_change(9, 16));
+        assert!(!will_depth_change(0, 1));
+    }
+
+    #[test]
+    fn unset_root_never_verifies() -> Result<()> {
+        let (_, tree, subscribers) = build_tree_from_subscribers(test_fixtures::subscribers())?;
+        let (wallet, expiration) = &subscribers[1];
+        let (proof_bytes, index) = get_proof_for_user(&tree, &subscribers, wallet).unwrap();
+
+        let error = verify_subscription(
+            &hex::encode(UNSET_ROOT),
+            &proof_bytes,
+            wallet.as_str(),
+            *expiration,
+            index,
+            subscribers.len(),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("not set"));
+        Ok(())
+    }
+
+    #[test]
+    fn salted_tree_differs_and_verifies_with_salt() -> Result<()> {
+        let secret = [9u8; 32];
+        let (plain_root, _, _) = build_tree_from_subscribers(test_fixtures::subscribers())?;
+        let (salted_root, tree, subscribers) =
+            build_salted_tree_with::<Sha256Hasher>(test_fixtures::subscribers(), Some(&secret))?;
+        assert_ne!(salted_root, plain_root);
+
+        let (wallet, expiration) = &subscribers[0];
+        let (proof_bytes, index) = get_proof_for_user(&tree, &subscribers, wallet).unwrap();
+        let salt = leaf_salt(&secret, wallet)?;
+        let verify = |salt: Option<&[u8; 32]>| {
+            let total = subscribers.len();
+            verify_salted_subscription(
+                &salted_root,
+                &proof_bytes,
+                wallet,
+                *expiration,
+                salt,
+                index,
+                total,
+            )
+        };
+        assert!(verify(Some(&salt))?);
+        assert!(!verify(None)?);
+        Ok(())
+    }
+
+    #[test]
+    fn allowlist_tree_ignores_expirations() -> Result<()> {
+        let (expiring_root, _, _) = build_tree_from_subscribers(test_fixtures::subscribers())?;
+        let (allowlist_root, tree, subscribers) =
+            build_tree_with_layout::<Sha256Hasher>(test_fixtures::subscribers(), None, false)?;
+        assert_ne!(allowlist_root, expiring_root);
+
+        // Any expiration builds the same allowlist
+        let renewed = test_fixtures::subscribers()
+            .into_iter()
+            .map(|(wallet, _)| (wallet, Expiration::new(0).unwrap()))
+            .collect();
+        let (renewed_root, _, _) = build_tree_with_layout::<Sha256Hasher>(renewed, None, false)?;
+        assert_eq!(renewed_root, allowlist_root);
+
+        let (wallet, expiration) = &subscribers[2];
+        let (proof_bytes, index) = get_proof_for_user(&tree, &subscribers, wallet).unwrap();
+        let total = subscribers.len();
+        assert!(verify_allowlist_membership(
+            &allowlist_root,
+            &proof_bytes,
+            wallet,
+            None,
+            index,
+            total
+        )?);
+        assert!(!verify_subscription(
+            &allowlist_root,
+            &proof_bytes,
+            wallet,
+            *expiration,
+            index,
+            total
+        )?);
+        Ok(())
+    }
+
+    #[test]
+    fn non_members_get_absence_proofs_and_members_do_not() -> Result<()> {
+        let (root, tree, subscribers) = build_tree_from_subscribers(test_fixtures::subscribers())?;
+
+        // Sorts between the first two members
+        let between = "4Nd1mBQtrMJVYVfKf2PJy9NZUZdTAsp7D4xWLs4gDB4T";
+        let proof = get_non_membership_proof(&tree, &subscribers, between).unwrap();
+        assert_eq!(proof.below.as_ref().unwrap().leaf_index, 0);
+        assert_eq!(proof.above.as_ref().unwrap().leaf_index, 1);
+        assert!(verify_non_membership(&root, &proof)?);
+
+        // Sorts after every member, so only the last leaf brackets it
+        let after = "So11111111111111111111111111111111111111112";
+        let proof = get_non_membership_proof(&tree, &subscribers, after).unwrap();
+        assert_eq!(proof.below.as_ref().unwrap().leaf_index, 2);
+        assert!(proof.above.is_none());
+        assert!(verify_non_membership(&root, &proof)?);
+
+        // A member has no absence proof, and can't borrow a neighbour's
+        let (member, _) = &subscribers[1];
+        assert!(get_non_membership_proof(&tree, &subscribers, member).is_none());
+        let borrowed = NonMembershipProof {
+            wallet: member.clone(),
+            ..get_non_membership_proof(&tree, &subscribers, between).unwrap()
+        };
+        assert!(!verify_non_membership(&root, &borrowed)?);
+
+        // Brackets that aren't adjacent leave room for a member between them
+        let mut gapped = get_non_membership_proof(&tree, &subscribers, between).unwrap();
+        gapped.above =
+            get_proofs_for_users(&tree, &subscribers, &[subscribers[2].0.clone()], None).remove(0);
+        assert!(!verify_non_membership(&root, &gapped)?);
+        Ok(())
+    }
+
+    #[test]
+    fn pubkey_leaf_encodings_build_distinct_trees_that_each_verify() -> Result<()> {
+        let encodings = [
+            PubkeyLeafEncoding::RawBytes,
+            PubkeyLeafEncoding::Base58String,
+        ];
+        let (raw_root, _, _) = build_tree_with_encoding::<Sha256Hasher>(
+            test_fixtures::subscribers(),
+            None,
+            true,
+            encodings[0],
+        )?;
+        let (base58_root, _, _) = build_tree_with_encoding::<Sha256Hasher>(
+            test_fixtures::subscribers(),
+            None,
+            true,
+            encodings[1],
+        )?;
+        assert_eq!(raw_root, test_fixtures::EXPECTED_ROOT);
+        assert_ne!(base58_root, raw_root);
+
+        // Each tree verifies leaves rebuilt under its own encoding, and only those
+        for built_with in encodings {
+            let (root, tree, subscribers) = build_tree_with_encoding::<Sha256Hasher>(
+                test_fixtures::subscribers(),
+                None,
+                true,
+                built_with,
+            )?;
+            let (wallet, expiration) = &subscribers[0];
+            let (proof_bytes, index) = get_proof_for_user(&tree, &subscribers, wallet).unwrap();
+            for encoding in encodings {
+                let leaf = encoded_leaf_from_parts::<Sha256Hasher>(
+                    encoding,
+                    &decode_pubkey(wallet)?,
+                    None,
+                    Some(*expiration),
+                    None,
+                );
+                assert_eq!(
+                    verify_leaf(&root, &proof_bytes, leaf, index, subscribers.len())?,
+                    encoding == built_with
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn pubkey_inputs_match_base58_inputs() -> Result<()> {
+        let (root_hash, tree, subscribers) =
+            build_tree_from_subscribers(test_fixtures::subscribers())?;
+        let total = subscribers.len();
+
+        for (wallet, expiration) in &subscribers {
+            let pubkey: Pubkey = wallet.parse()?;
+            let from_str = get_proof_for_user(&tree, &subscribers, wallet.as_str()).unwrap();
+            assert_eq!(
+                get_proof_for_user(&tree, &subscribers, pubkey),
+                Some(from_str.clone())
+            );
+            assert_eq!(
+                get_proof_for_user(&tree, &subscribers, pubkey.to_bytes()),
+                Some(from_str.clone())
+            );
+
+            let (proof_bytes, index) = from_str;
+            let tampered = Expiration::new(expiration.as_i64() + 1)?;
+            for exp in [*expiration, tampered] {
+                assert_eq!(
+                    verify_subscription(&root_hash, &proof_bytes, &pubkey, exp, index, total)?,
+                    verify_subscription(&root_hash, &proof_bytes, wallet, exp, index, total)?
+                );
+            }
+        }
+
+        assert!(get_proof_for_user(&tree, &subscribers, Pubkey::new_unique()).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_proofs_with_the_wrong_shape() -> Result<()> {
+        let subscribers: Vec<_> = (0..7u8)
+            .map(|i| {
+                let wallet = bs58::encode([i + 1; 32]).into_string();
+                (wallet, Expiration::new(1_800_000_000).unwrap())
+            })
+            .collect();
+        let (root_hash, tree, subscribers) = build_tree_from_subscribers(subscribers)?;
+        for (index, (wallet, _)) in subscribers.iter().enumerate() {
+            let (proof_bytes, _) = get_proof_for_user(&tree, &subscribers, wallet).unwrap();
+            assert_eq!(Some(proof_bytes.len() / 32), expected_proof_len(index, 7));
+        }
+
+        let (wallet, expiration) = &subscribers[6];
+        let (proof_bytes, index) = get_proof_for_user(&tree, &subscribers, wallet).unwrap();
+        let verify = |proof: &[u8], index: usize, total: usize| {
+            verify_subscription(&root_hash, proof, wallet, *expiration, index, total)
+        };
+        assert!(verify(&proof_bytes, index, 7)?);
+        assert!(verify(&proof_bytes[32..], index, 7).is_err());
+        assert!(verify(&[proof_bytes.clone(), vec![0; 32]].concat(), index, 7).is_err());
+        assert!(verify(&proof_bytes, 7, 7).is_err());
+        assert!(verify(&proof_bytes, 0, 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn proofs_must_be_exactly_their_expected_length() -> Result<()> {
+        let (root_hash, tree, subscribers) =
+            build_tree_from_subscribers(test_fixtures::subscribers())?;
+        let (wallet, expiration) = &subscribers[0];
+        let (proof_bytes, index) = get_proof_for_user(&tree, &subscribers, wallet).unwrap();
+        let verify =
+            |proof: &[u8]| verify_subscription(&root_hash, proof, wallet, *expiration, index, 3);
+        assert!(verify(&proof_bytes)?);
+
+        // Trailing garbage, whole hash or not, is rejected rather than ignored
+        for trailing in [vec![0u8], vec![0u8; 31], vec![0u8; 32]] {
+            let err = verify(&[proof_bytes.clone(), trailing].concat()).unwrap_err();
+            assert!(err.to_string().contains("Malformed proof length"));
+        }
+        Ok(())
+    }
 }
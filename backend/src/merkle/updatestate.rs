@@ -1,23 +1,34 @@
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use solana_sdk::signature::Signature;
 use sqlx::PgPool;
 
+use super::solana_client::{RootUpdated, SolanaClient};
+use super::tree::{self, Expiration};
+use crate::model::MerkleState;
+
+/// Record `root_hex`, the root of a `total_leaves`-leaf tree, with its proof depth
 pub async fn update_merkle_state(
     pool: &PgPool,
     root_hex: &str,
+    total_leaves: usize,
     tx_signature: Option<String>,
 ) -> Result<()> {
     let is_synced = tx_signature.is_some();
     let created_at = Utc::now().naive_utc();
+    let tree_depth = tree::proof_depth(total_leaves) as i32;
 
     // Store the updated RootHash into the db
     sqlx::query!(
-        "INSERT INTO merkle_state (root_hash, is_synced_on_chain, tx_signature, created_at) 
-         VALUES ($1, $2, $3, $4)",
+        "INSERT INTO merkle_state
+             (root_hash, is_synced_on_chain, tx_signature, created_at, total_leaves, tree_depth)
+         VALUES ($1, $2, $3, $4, $5, $6)",
         root_hex,
         is_synced,
         tx_signature,
-        created_at
+        created_at,
+        total_leaves as i64,
+        tree_depth
     )
     .execute(pool)
     .await?;
@@ -25,6 +36,39 @@ pub async fn update_merkle_state(
     Ok(())
 }
 
+/// Signature of the latest on-chain sync recorded for `root_hash`, if any
+pub async fn synced_signature(pool: &PgPool, root_hash: &str) -> Result<Option<String>> {
+    let signature = sqlx::query_scalar!(
+        "SELECT tx_signature FROM merkle_state
+         WHERE root_hash = $1 AND is_synced_on_chain
+         ORDER BY id DESC
+         LIMIT 1",
+        root_hash
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(signature.flatten())
+}
+
+/// The `limit` most recently recorded roots, newest first
+pub async fn merkle_state_history(pool: &PgPool, limit: i64) -> Result<Vec<MerkleState>> {
+    // The timestamp column has no zone; read it back as UTC
+    let states = sqlx::query_as::<_, MerkleState>(
+        "SELECT id, root_hash, COALESCE(is_synced_on_chain, FALSE) AS is_synced_on_chain,
+                tx_signature, created_at AT TIME ZONE 'UTC' AS created_at,
+                total_leaves, tree_depth
+         FROM merkle_state
+         ORDER BY id DESC
+         LIMIT $1",
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(states)
+}
+
 /// Update existing merkle state with transaction signature
 pub async fn sync_merkle_state_on_chain(
     pool: &PgPool,
@@ -43,3 +87,310 @@ pub async fn sync_merkle_state_on_chain(
 
     Ok(())
 }
+
+/// Rebuild `merkle_state` rows for roots the program's on-chain `RootUpdated` history
+/// has but the table doesn't, e.g. after it was wiped. Returns how many were added.
+pub async fn backfill_from_chain(pool: &PgPool, client: &SolanaClient) -> Result<usize> {
+    let updates = client.root_update_history().await?;
+    backfill_root_updates(pool, &updates).await
+}
+
+/// Record each event's new root as synced by the transaction that emitted it, dated
+/// by the event, unless `merkle_state` already has that root
+pub async fn backfill_root_updates(
+    pool: &PgPool,
+    updates: &[(Signature, RootUpdated)],
+) -> Result<usize> {
+    let mut inserted = 0;
+    for (signature, event) in updates {
+        let root_hex = hex::encode(event.new_root);
+        let created_at = DateTime::from_timestamp(event.timestamp, 0)
+            .ok_or_else(|| anyhow::anyhow!("Invalid event timestamp {}", event.timestamp))?
+            .naive_utc();
+
+        let result = sqlx::query!(
+            "INSERT INTO merkle_state (root_hash, is_synced_on_chain, tx_signature, created_at)
+             SELECT $1, TRUE, $2, $3
+             WHERE NOT EXISTS (SELECT 1 FROM merkle_state WHERE root_hash = $1)",
+            root_hex,
+            signature.to_string(),
+            created_at
+        )
+        .execute(pool)
+        .await?;
+        inserted += result.rows_affected() as usize;
+    }
+
+    Ok(inserted)
+}
+
+/// Verify a proof against a root recorded in `merkle_state`, answering whether the
+/// wallet was a member as of that root. Errors if the root was never recorded.
+pub async fn verify_against_historical_root(
+    pool: &PgPool,
+    root_hash: &str,
+    proof_bytes: &[u8],
+    wallet: &str,
+    expiration: Expiration,
+    index: usize,
+    total_subscribers: usize,
+) -> Result<bool> {
+    let recorded = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM merkle_state WHERE root_hash = $1) AS \"recorded!\"",
+        root_hash
+    )
+    .fetch_one(pool)
+    .await?;
+    if !recorded {
+        return Err(anyhow::anyhow!(
+            "Root {} was never recorded in merkle_state",
+            root_hash
+        ));
+    }
+
+    tree::verify_subscription(
+        root_hash,
+        proof_bytes,
+        wallet,
+        expiration,
+        index,
+        total_subscribers,
+    )
+}
+
+/// Like `verify_against_historical_root`, but the proof is checked at the leaf index
+/// the wallet had in that root's tree. A client's own index goes stale as soon as the
+/// subscriber set, and so the leaf ordering, changes.
+///
+/// The tree is rebuilt from `subscriber_history` as of when the root was first
+/// recorded (plain layout) and must reproduce the root. `Ok(false)` when the wallet
+/// wasn't a member then.
+pub async fn verify_in_historical_order(
+    pool: &PgPool,
+    root_hash: &str,
+    proof_bytes: &[u8],
+    wallet: &str,
+    expiration: Expiration,
+) -> Result<bool> {
+    // Rounded up: the root is recorded after its tree was built from the table
+    let recorded_at = sqlx::query_scalar::<_, Option<i64>>(
+        "SELECT CEIL(EXTRACT(EPOCH FROM MIN(created_at)))::BIGINT
+         FROM merkle_state WHERE root_hash = $1",
+    )
+    .bind(root_hash)
+    .fetch_one(pool)
+    .await?
+    .ok_or_else(|| anyhow::anyhow!("Root {} was never recorded in merkle_state", root_hash))?;
+
+    let (rebuilt_root, _, subscribers) = tree::build_tree_at(pool, recorded_at).await?;
+    if rebuilt_root != root_hash {
+        return Err(anyhow::anyhow!(
+            "Subscriber history as of {} rebuilds {}, not {}",
+            recorded_at,
+            rebuilt_root,
+            root_hash
+        ));
+    }
+    let Some(index) = subscribers.iter().position(|(pk, _)| pk == wallet) else {
+        return Ok(false);
+    };
+
+    tree::verify_subscription(
+        root_hash,
+        proof_bytes,
+        wallet,
+        expiration,
+        index,
+        subscribers.len(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::solana_client::root_updates_from_logs;
+    use crate::test_fixtures;
+
+    #[sqlx::test]
+    async fn backfills_roots_missing_from_event_logs(pool: PgPool) -> Result<()> {
+        let updates: Vec<(Signature, RootUpdated)> = test_fixtures::ROOT_UPDATE_LOGS
+            .iter()
+            .zip(1u8..)
+            .flat_map(|(logs, n)| {
+                let logs: Vec<String> = logs.iter().map(|line| line.to_string()).collect();
+                root_updates_from_logs(&logs)
+                    .into_iter()
+                    .map(move |event| (Signature::from([n; 64]), event))
+            })
+            .collect();
+        assert_eq!(updates.len(), 2);
+
+        // The latest root survived; only the earlier one is rebuilt
+        update_merkle_state(&pool, test_fixtures::EXPECTED_ROOT, 3, None).await?;
+        assert_eq!(backfill_root_updates(&pool, &updates).await?, 1);
+        assert_eq!(backfill_root_updates(&pool, &updates).await?, 0);
+
+        let (signature, _) = &updates[0];
+        assert_eq!(
+            synced_signature(&pool, &hex::encode([1u8; 32])).await?,
+            Some(signature.to_string())
+        );
+        let created_at = sqlx::query_scalar::<_, i64>(
+            "SELECT EXTRACT(EPOCH FROM created_at AT TIME ZONE 'UTC')::BIGINT
+             FROM merkle_state WHERE root_hash = $1",
+        )
+        .bind(hex::encode([1u8; 32]))
+        .fetch_one(&pool)
+        .await?;
+        assert_eq!(created_at, 1_700_000_000);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn records_tree_size_and_depth_with_each_root(pool: PgPool) -> Result<()> {
+        let sizes = [1usize, 2, 3, 1024, 1025];
+        for total_leaves in sizes {
+            let root_hex = format!("{:064x}", total_leaves);
+            update_merkle_state(&pool, &root_hex, total_leaves, None).await?;
+        }
+
+        // Newest first
+        let states = merkle_state_history(&pool, sizes.len() as i64).await?;
+        let rows: Vec<(i64, i32)> = states
+            .iter()
+            .rev()
+            .map(|state| (state.total_leaves.unwrap(), state.tree_depth.unwrap()))
+            .collect();
+        assert_eq!(rows.len(), sizes.len());
+        for (total_leaves, tree_depth) in &rows {
+            assert_eq!(
+                *tree_depth as usize,
+                tree::proof_depth(*total_leaves as usize)
+            );
+        }
+        // One leaf past a power of two adds a level
+        assert_eq!(rows[3], (1024, 10));
+        assert_eq!(rows[4], (1025, 11));
+
+        // Backfilled rows don't know their tree's size
+        backfill_root_updates(
+            &pool,
+            &[(
+                Signature::from([1u8; 64]),
+                RootUpdated {
+                    old_root: [0u8; 32],
+                    new_root: [7u8; 32],
+                    updated_by: Default::default(),
+                    timestamp: 1_700_000_000,
+                },
+            )],
+        )
+        .await?;
+        let latest = &merkle_state_history(&pool, 1).await?[0];
+        assert_eq!((latest.total_leaves, latest.tree_depth), (None, None));
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn verifies_against_recorded_root_only(pool: PgPool) -> Result<()> {
+        let (root_hash, tree, subscribers) =
+            tree::build_tree_from_subscribers(test_fixtures::subscribers())?;
+        let (wallet, expiration) = &subscribers[0];
+        let (proof_bytes, index) = tree::get_proof_for_user(&tree, &subscribers, wallet).unwrap();
+        let total = subscribers.len();
+
+        // The root stays verifiable after newer roots are recorded on top of it
+        update_merkle_state(&pool, &root_hash, subscribers.len(), None).await?;
+        update_merkle_state(&pool, &"ab".repeat(32), subscribers.len() + 1, None).await?;
+        assert!(
+            verify_against_historical_root(
+                &pool,
+                &root_hash,
+                &proof_bytes,
+                wallet,
+                *expiration,
+                index,
+                total
+            )
+            .await?
+        );
+
+        let unknown_root = "cd".repeat(32);
+        let result = verify_against_historical_root(
+            &pool,
+            &unknown_root,
+            &proof_bytes,
+            wallet,
+            *expiration,
+            index,
+            total,
+        )
+        .await;
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn old_order_proofs_verify_at_their_historical_index(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+        sqlx::query(
+            "UPDATE subscriber_history SET changed_at = to_timestamp(1600000000) AT TIME ZONE 'UTC'",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "INSERT INTO merkle_state (root_hash, is_synced_on_chain, created_at)
+             VALUES ($1, TRUE, to_timestamp(1600000100) AT TIME ZONE 'UTC')",
+        )
+        .bind(test_fixtures::EXPECTED_ROOT)
+        .execute(&pool)
+        .await?;
+        let (_, old_tree, old_subscribers) =
+            tree::build_tree_from_subscribers(test_fixtures::subscribers())?;
+        let (wallet, expiration) = test_fixtures::SUBSCRIBERS[0];
+        let (proof_bytes, old_index) =
+            tree::get_proof_for_user(&old_tree, &old_subscribers, wallet).unwrap();
+
+        // A wallet sorting first shifts everyone's index in the live tree
+        let added = "11111111111111111111111111111112";
+        crate::merkle::subscriber::upsert_subscriber(&pool, added, Expiration::new(1)?).await?;
+        let (_, _, live_subscribers) = tree::build_tree_from_db(&pool).await?;
+        let live_index = live_subscribers
+            .iter()
+            .position(|(pk, _)| pk == wallet)
+            .unwrap();
+        assert_ne!(live_index, old_index);
+
+        assert!(
+            verify_in_historical_order(
+                &pool,
+                test_fixtures::EXPECTED_ROOT,
+                &proof_bytes,
+                wallet,
+                Expiration::new(expiration)?,
+            )
+            .await?
+        );
+        // The added wallet wasn't a member as of that root
+        assert!(
+            !verify_in_historical_order(
+                &pool,
+                test_fixtures::EXPECTED_ROOT,
+                &proof_bytes,
+                added,
+                Expiration::new(1)?,
+            )
+            .await?
+        );
+        assert!(verify_in_historical_order(
+            &pool,
+            &"cd".repeat(32),
+            &proof_bytes,
+            wallet,
+            Expiration::new(expiration)?,
+        )
+        .await
+        .is_err());
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
 
@@ -6,8 +6,24 @@ use sqlx::prelude::FromRow;
 
 pub struct SubscriberStorage {
     pub wallet_address: String,
-    pub expiration_ts: i64, // BIGINT - Unix timestamp
+    pub wallet_pubkey: Option<Vec<u8>>, // BYTEA - pubkey bytes, NULL if the address is invalid
+    pub expiration_ts: i64,             // BIGINT - Unix timestamp
+    pub start_ts: i64, // BIGINT - Unix timestamp the subscription started (0 = epoch)
     pub last_updated_at: DateTime<Utc>,
+    pub last_verified_at: Option<DateTime<Utc>>, // NULL until the first recorded verification
+    pub leaf_id: i64, // BIGINT - monotonic insertion index, assigned by Postgres
+    pub verify_count: i64, // BIGINT - successful verifications, when counting is enabled
+}
+
+/// One `subscriber_history` row read as an audit entry: a subscriber change as
+/// recorded by its trigger
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct SubscriberAudit {
+    pub wallet_address: String,
+    pub change_type: String, // insert, renew, update, revoke or prune
+    pub old_expiration_ts: Option<i64>, // NULL for inserts
+    pub new_expiration_ts: Option<i64>, // NULL for revokes and prunes
+    pub changed_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
@@ -17,4 +33,70 @@ pub struct MerkleState {
     pub is_synced_on_chain: bool,
     pub tx_signature: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub total_leaves: Option<i64>, // BIGINT - tree size; NULL for rows recorded before it was
+    pub tree_depth: Option<i32>,   // INTEGER - proof length in hashes (`tree::proof_depth`)
+}
+
+/// `MerkleState` as served over HTTP: no row id, and the sync flag and timestamp in
+/// wire form, so schema changes don't reach clients
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleStateResponse {
+    pub root_hash: String,
+    pub synced: bool,
+    pub tx_signature: Option<String>,
+    /// RFC 3339, UTC, to the second
+    pub created_at: String,
+    /// Leaves in the root's tree, if recorded
+    pub total_leaves: Option<i64>,
+    /// Sibling hashes in each proof against this root, if recorded
+    pub tree_depth: Option<i32>,
+}
+
+impl From<MerkleState> for MerkleStateResponse {
+    fn from(state: MerkleState) -> Self {
+        Self {
+            root_hash: state.root_hash,
+            synced: state.is_synced_on_chain,
+            tx_signature: state.tx_signature,
+            created_at: state.created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            total_leaves: state.total_leaves,
+            tree_depth: state.tree_depth,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn merkle_state_response_hides_the_row_id() {
+        let state = MerkleState {
+            id: 42,
+            root_hash: "ab".repeat(32),
+            is_synced_on_chain: true,
+            tx_signature: Some("5sig".to_string()),
+            created_at: Utc.timestamp_opt(1_769_665_096, 0).unwrap(),
+            total_leaves: Some(3),
+            tree_depth: Some(2),
+        };
+
+        let response = MerkleStateResponse::from(state);
+        assert_eq!(
+            response,
+            MerkleStateResponse {
+                root_hash: "ab".repeat(32),
+                synced: true,
+                tx_signature: Some("5sig".to_string()),
+                created_at: "2026-01-29T05:38:16Z".to_string(),
+                total_leaves: Some(3),
+                tree_depth: Some(2),
+            }
+        );
+
+        let json = serde_json::to_value(&response).unwrap();
+        assert!(json.get("id").is_none());
+        assert!(json.get("is_synced_on_chain").is_none());
+    }
 }
@@ -0,0 +1,178 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::PgPool;
+
+/// Length of one paid renewal, treated as a 30-day month
+pub const RENEWAL_PERIOD_SECS: i64 = 30 * 86_400;
+
+/// Longest horizon a projection covers (ten years), which bounds the cohort list
+pub const MAX_HORIZON_DAYS: i64 = 3_650;
+
+/// Active subscribers whose current period ends in one 30-day slice of the horizon
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExpirationCohort {
+    pub period_start_ts: i64,
+    pub period_end_ts: i64,
+    pub expiring: i64,
+}
+
+/// Recurring revenue over a horizon, assuming every active subscriber renews
+/// monthly at a fixed price from their current expiration on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RevenueProjection {
+    pub active_subscribers: i64,
+    /// Cohorts in horizon order; subscribers expiring after the horizon aren't in any
+    pub cohorts: Vec<ExpirationCohort>,
+    pub projected_renewals: i64,
+    pub projected_revenue_cents: i64,
+}
+
+/// Project renewal revenue for the next `horizon_days` from `now_ts`. Read-only.
+pub async fn project_revenue(
+    pool: &PgPool,
+    monthly_price_cents: i64,
+    horizon_days: i64,
+    now_ts: i64,
+) -> Result<RevenueProjection> {
+    let expirations = sqlx::query_scalar::<_, i64>(
+        "SELECT expiration_ts FROM subscriber_storage WHERE expiration_ts > $1",
+    )
+    .bind(now_ts)
+    .fetch_all(pool)
+    .await?;
+
+    project_from_expirations(&expirations, monthly_price_cents, horizon_days, now_ts)
+}
+
+/// `project_revenue` over expirations already in hand; expired ones are ignored
+pub fn project_from_expirations(
+    expirations: &[i64],
+    monthly_price_cents: i64,
+    horizon_days: i64,
+    now_ts: i64,
+) -> Result<RevenueProjection> {
+    if monthly_price_cents < 0 || horizon_days < 0 {
+        return Err(anyhow::anyhow!(
+            "Price and horizon must be non-negative, got {} cents over {} days",
+            monthly_price_cents,
+            horizon_days
+        ));
+    }
+    if horizon_days > MAX_HORIZON_DAYS {
+        return Err(anyhow::anyhow!(
+            "Horizon of {} days exceeds the maximum of {}",
+            horizon_days,
+            MAX_HORIZON_DAYS
+        ));
+    }
+    let horizon_end = now_ts.saturating_add(horizon_days * 86_400);
+    let period_count = (horizon_end - now_ts).div_ceil(RENEWAL_PERIOD_SECS);
+
+    let mut cohorts: Vec<ExpirationCohort> = (0..period_count)
+        .map(|period| {
+            let period_start_ts = now_ts + period * RENEWAL_PERIOD_SECS;
+            ExpirationCohort {
+                period_start_ts,
+                period_end_ts: period_start_ts
+                    .saturating_add(RENEWAL_PERIOD_SECS)
+                    .min(horizon_end),
+                expiring: 0,
+            }
+        })
+        .collect();
+    let mut active_subscribers = 0;
+    let mut projected_renewals = 0;
+
+    for &expiration in expirations
+        .iter()
+        .filter(|&&expiration| expiration > now_ts)
+    {
+        active_subscribers += 1;
+        if expiration >= horizon_end {
+            continue;
+        }
+        cohorts[((expiration - now_ts) / RENEWAL_PERIOD_SECS) as usize].expiring += 1;
+        // Renewals fall at the expiration and every period after it within the horizon
+        projected_renewals += (horizon_end - expiration - 1) / RENEWAL_PERIOD_SECS + 1;
+    }
+    let projected_revenue_cents = projected_renewals
+        .checked_mul(monthly_price_cents)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Projected revenue of {} renewals at {} cents overflows",
+                projected_renewals,
+                monthly_price_cents
+            )
+        })?;
+
+    Ok(RevenueProjection {
+        active_subscribers,
+        cohorts,
+        projected_renewals,
+        projected_revenue_cents,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::{subscriber, tree::Expiration};
+    use crate::test_fixtures;
+
+    const NOW_TS: i64 = 1_760_000_000;
+    const DAY: i64 = 86_400;
+
+    #[sqlx::test]
+    async fn projects_renewals_for_a_seeded_cohort(pool: PgPool) -> Result<()> {
+        // Active: SUBSCRIBERS[0] ~112 days out, SUBSCRIBERS[2] past a one-year horizon.
+        // SUBSCRIBERS[1] has already expired.
+        test_fixtures::seed_subscribers(&pool).await?;
+        let soon = "So11111111111111111111111111111111111111112";
+        subscriber::upsert_subscriber(&pool, soon, Expiration::new(NOW_TS + 10 * DAY)?).await?;
+
+        let projection = project_revenue(&pool, 999, 365, NOW_TS).await?;
+        assert_eq!(projection.active_subscribers, 3);
+        assert_eq!(projection.cohorts.len(), 13);
+        let expiring: Vec<i64> = projection.cohorts.iter().map(|c| c.expiring).collect();
+        assert_eq!(expiring, [1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(projection.cohorts[12].period_end_ts, NOW_TS + 365 * DAY);
+
+        // Day 10 renews 12 times within the year, day ~112 renews 9 times
+        assert_eq!(projection.projected_renewals, 12 + 9);
+        assert_eq!(projection.projected_revenue_cents, 21 * 999);
+        Ok(())
+    }
+
+    #[test]
+    fn renewal_on_the_horizon_edge_is_excluded() -> Result<()> {
+        let expirations = [NOW_TS + 30 * DAY, NOW_TS + 60 * DAY, NOW_TS - DAY];
+        let projection = project_from_expirations(&expirations, 500, 60, NOW_TS)?;
+        assert_eq!(projection.active_subscribers, 2);
+        assert_eq!(projection.projected_renewals, 1);
+        assert_eq!(projection.projected_revenue_cents, 500);
+
+        assert!(project_from_expirations(&expirations, -1, 60, NOW_TS).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn oversized_horizons_and_overflowing_totals_are_rejected() -> Result<()> {
+        let expirations = [NOW_TS + DAY];
+
+        // Rejected before any cohorts are allocated
+        let err = project_from_expirations(&expirations, 500, i64::MAX, NOW_TS).unwrap_err();
+        assert!(err.to_string().contains("exceeds the maximum"), "{}", err);
+        assert!(project_from_expirations(&expirations, 500, MAX_HORIZON_DAYS + 1, NOW_TS).is_err());
+
+        let projection = project_from_expirations(&expirations, 500, MAX_HORIZON_DAYS, NOW_TS)?;
+        assert_eq!(projection.cohorts.len(), 122);
+        assert_eq!(projection.projected_renewals, 122);
+
+        // 122 renewals at this price don't fit in i64 cents
+        assert!(
+            project_from_expirations(&expirations, i64::MAX / 100, MAX_HORIZON_DAYS, NOW_TS)
+                .is_err()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,1877 @@
+use crate::diagnose::{self, VerifyTrace};
+use crate::merkle::chain::ChainClient;
+use crate::merkle::tree::{
+    self, BuiltTree, DoubleSha256Hasher, Expiration, HashAlgo, Keccak256Hasher, MerkleRoot,
+    SerializedProof, Sha256Hasher, TreeCache,
+};
+use crate::merkle::updatestate;
+use crate::model::{MerkleStateResponse, SubscriberStorage};
+use crate::session::SessionIssuer;
+use crate::telemetry;
+use anyhow::{Context, Result};
+use axum::extract::{Json, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::{get, post};
+use axum::Router;
+#[cfg(feature = "gzip")]
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderValue},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use rs_merkle::{Hasher, MerkleTree};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+/// Most wallets accepted by a single `POST /proofs` request
+pub const MAX_BATCH_WALLETS: usize = 100;
+
+/// `POST /verify` header naming a hex root to verify against instead of the on-chain one
+pub const EXPECTED_ROOT_HEADER: &str = "x-expected-root";
+
+/// Most outcomes `VerifyCache` holds; reaching it starts the cache over
+pub const VERIFY_CACHE_CAPACITY: usize = 10_000;
+
+/// Longest an attestation from `POST /attest` stays valid
+pub const ATTESTATION_TTL_SECS: i64 = 300;
+
+/// Roots `GET /root/history` returns without a `limit`
+pub const DEFAULT_ROOT_HISTORY_LIMIT: i64 = 20;
+
+/// Most roots a single `GET /root/history` request returns
+pub const MAX_ROOT_HISTORY_LIMIT: i64 = 100;
+
+/// The tree proofs are currently served from
+pub struct TreeSnapshot {
+    pub root_hex: String,
+    pub tree: MerkleTree<Sha256Hasher>,
+    pub subscribers: Vec<(String, Expiration)>,
+}
+
+impl From<BuiltTree> for TreeSnapshot {
+    fn from((root_hex, tree, subscribers): BuiltTree) -> Self {
+        Self {
+            root_hex,
+            tree,
+            subscribers,
+        }
+    }
+}
+
+/// On-disk form of a `TreeSnapshot`: the tree is rebuilt from its leaves on load
+#[derive(Serialize, Deserialize)]
+struct SnapshotFile {
+    root_hex: String,
+    leaves: Vec<String>,
+    subscribers: Vec<(String, i64)>,
+}
+
+/// Write `snapshot` to `path` so a restart can serve it without rebuilding from the DB.
+///
+/// Written to a sibling temp file and renamed into place, so a crash mid-write
+/// never leaves a truncated snapshot at `path`.
+pub fn save_tree(snapshot: &TreeSnapshot, path: &Path) -> Result<()> {
+    let file = SnapshotFile {
+        root_hex: snapshot.root_hex.clone(),
+        leaves: snapshot
+            .tree
+            .leaves()
+            .unwrap_or_default()
+            .iter()
+            .map(hex::encode)
+            .collect(),
+        subscribers: snapshot
+            .subscribers
+            .iter()
+            .map(|(wallet, expiration)| (wallet.clone(), expiration.as_i64()))
+            .collect(),
+    };
+
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, serde_json::to_vec(&file)?)
+        .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+/// Read a snapshot written by `save_tree`, rejecting it unless the tree rebuilt
+/// from its leaves has the recorded root
+pub fn load_tree(path: &Path) -> Result<TreeSnapshot> {
+    let raw = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let file: SnapshotFile = serde_json::from_slice(&raw)
+        .with_context(|| format!("Malformed tree snapshot {}", path.display()))?;
+
+    if file.leaves.len() != file.subscribers.len() {
+        return Err(anyhow::anyhow!(
+            "Tree snapshot has {} leaves for {} subscribers",
+            file.leaves.len(),
+            file.subscribers.len()
+        ));
+    }
+
+    let leaves = file
+        .leaves
+        .iter()
+        .map(|leaf| {
+            hex::decode(leaf)?
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("Leaf must be 32 bytes"))
+        })
+        .collect::<Result<Vec<[u8; 32]>>>()?;
+    let tree = MerkleTree::<Sha256Hasher>::from_leaves(&leaves);
+
+    let root_hex = tree.root_hex().unwrap_or_default();
+    if root_hex != file.root_hex {
+        return Err(anyhow::anyhow!(
+            "Tree snapshot is corrupt: leaves give root {}, file records {}",
+            root_hex,
+            file.root_hex
+        ));
+    }
+
+    let subscribers = file
+        .subscribers
+        .into_iter()
+        .map(|(wallet, expiration)| Ok((wallet, Expiration::new(expiration)?)))
+        .collect::<Result<_>>()?;
+    Ok(TreeSnapshot {
+        root_hex,
+        tree,
+        subscribers,
+    })
+}
+
+/// Gate-and-read: check that `wallet` is in `snapshot` and unexpired at `now_ts`, and
+/// if so return its stored record in the same call.
+///
+/// `None` when the wallet isn't in the tree, has expired, or its proof doesn't verify.
+/// Checks the plain subscription leaf, so salted and allowlist trees always give `None`.
+pub async fn verify_and_fetch(
+    pool: &PgPool,
+    snapshot: &TreeSnapshot,
+    wallet: &str,
+    now_ts: i64,
+) -> Result<Option<SubscriberStorage>> {
+    let Some((proof_bytes, index)) =
+        tree::get_proof_for_user(&snapshot.tree, &snapshot.subscribers, wallet)
+    else {
+        return Ok(None);
+    };
+
+    // Same rule as on-chain: valid while the expiration is still in the future
+    let expiration = snapshot.subscribers[index].1;
+    if expiration.as_i64() <= now_ts {
+        return Ok(None);
+    }
+    let is_valid = tree::verify_subscription(
+        &snapshot.root_hex,
+        &proof_bytes,
+        wallet,
+        expiration,
+        index,
+        snapshot.subscribers.len(),
+    )?;
+    if !is_valid {
+        return Ok(None);
+    }
+
+    // The timestamp columns have no zone; read them back as UTC
+    let record = sqlx::query_as::<_, SubscriberStorage>(
+        "SELECT wallet_address, wallet_pubkey, expiration_ts, start_ts,
+                last_updated_at AT TIME ZONE 'UTC' AS last_updated_at,
+                last_verified_at AT TIME ZONE 'UTC' AS last_verified_at,
+                leaf_id, verify_count
+         FROM subscriber_storage
+         WHERE wallet_address = $1",
+    )
+    .bind(wallet)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(record)
+}
+
+/// Why a proof does or doesn't verify, in the order the checks run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerificationOutcome {
+    Valid,
+    /// The chain has no root yet (`UNSET_ROOT`), so nothing verifies
+    RootNotSet,
+    /// The proof bytes don't parse or don't fit the leaf index and tree size
+    MalformedProof,
+    /// The wallet sits at a different index in the current tree, e.g. a stale index
+    /// reused after the subscriber set changed
+    IndexMismatch,
+    /// The proof leads to a different root, e.g. one the chain has since replaced
+    RootMismatch,
+    /// The proof is for the current root, but the subscription has expired
+    Expired,
+}
+
+impl VerificationOutcome {
+    /// The serialized name, e.g. for metric labels
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Valid => "valid",
+            Self::RootNotSet => "root_not_set",
+            Self::MalformedProof => "malformed_proof",
+            Self::IndexMismatch => "index_mismatch",
+            Self::RootMismatch => "root_mismatch",
+            Self::Expired => "expired",
+        }
+    }
+}
+
+/// Check a client's proof for `leaf` against `root` the way the program would:
+/// valid while the expiration (if the layout has one) is still in the future
+pub fn verification_outcome(
+    root: [u8; 32],
+    proof_bytes: &[u8],
+    leaf: [u8; 32],
+    expiration: Option<Expiration>,
+    index: usize,
+    total_leaves: usize,
+    now_ts: i64,
+) -> VerificationOutcome {
+    verification_outcome_with::<Sha256Hasher>(
+        root,
+        proof_bytes,
+        leaf,
+        expiration,
+        index,
+        total_leaves,
+        now_ts,
+    )
+}
+
+/// Same as `verification_outcome`, for a leaf and proof under an arbitrary hasher
+pub fn verification_outcome_with<H: Hasher<Hash = [u8; 32]>>(
+    root: [u8; 32],
+    proof_bytes: &[u8],
+    leaf: [u8; 32],
+    expiration: Option<Expiration>,
+    index: usize,
+    total_leaves: usize,
+    now_ts: i64,
+) -> VerificationOutcome {
+    if root == tree::UNSET_ROOT {
+        return VerificationOutcome::RootNotSet;
+    }
+    match tree::recompute_root_with::<H>(proof_bytes, leaf, index, total_leaves) {
+        Err(_) => VerificationOutcome::MalformedProof,
+        Ok(recomputed) if recomputed != root => VerificationOutcome::RootMismatch,
+        Ok(_) if expiration.is_some_and(|expiration| expiration.as_i64() <= now_ts) => {
+            VerificationOutcome::Expired
+        }
+        Ok(_) => VerificationOutcome::Valid,
+    }
+}
+
+/// Same as `verification_outcome`, under the hash algorithm the on-chain config names
+#[allow(clippy::too_many_arguments)]
+pub fn verification_outcome_with_algo(
+    hash_algo: HashAlgo,
+    root: [u8; 32],
+    proof_bytes: &[u8],
+    leaf: [u8; 32],
+    expiration: Option<Expiration>,
+    index: usize,
+    total_leaves: usize,
+    now_ts: i64,
+) -> VerificationOutcome {
+    let outcome = match hash_algo {
+        HashAlgo::Sha256 => verification_outcome_with::<Sha256Hasher>,
+        HashAlgo::Keccak256 => verification_outcome_with::<Keccak256Hasher>,
+        HashAlgo::DoubleSha256 => verification_outcome_with::<DoubleSha256Hasher>,
+    };
+    outcome(
+        root,
+        proof_bytes,
+        leaf,
+        expiration,
+        index,
+        total_leaves,
+        now_ts,
+    )
+}
+
+/// `tree::compute_leaf_parts` under the hash algorithm the on-chain config names
+fn leaf_with_algo(
+    hash_algo: HashAlgo,
+    wallet: &str,
+    expiration: Option<Expiration>,
+    salt: Option<&[u8; 32]>,
+) -> Result<[u8; 32]> {
+    let compute = match hash_algo {
+        HashAlgo::Sha256 => tree::compute_leaf_parts::<Sha256Hasher>,
+        HashAlgo::Keccak256 => tree::compute_leaf_parts::<Keccak256Hasher>,
+        HashAlgo::DoubleSha256 => tree::compute_leaf_parts::<DoubleSha256Hasher>,
+    };
+    compute(wallet, expiration, salt)
+}
+
+/// `verification_outcome` results for the current on-chain root, so repeated checks
+/// of the same proof skip rehashing. Entries are keyed by the leaf (which commits to
+/// wallet, expiration and salt) and the proof, and all dropped when the root changes.
+#[derive(Default)]
+pub struct VerifyCache {
+    root: Option<(HashAlgo, [u8; 32])>,
+    outcomes: HashMap<([u8; 32], Vec<u8>, usize, usize), VerificationOutcome>,
+    hits: usize,
+}
+
+impl VerifyCache {
+    /// `verification_outcome_with_algo`, reused from an earlier call against the same
+    /// root and algorithm. A cached `Valid` is only reused before `expiration`; past it
+    /// the check reruns.
+    #[allow(clippy::too_many_arguments)]
+    pub fn outcome(
+        &mut self,
+        hash_algo: HashAlgo,
+        root: [u8; 32],
+        proof_bytes: &[u8],
+        leaf: [u8; 32],
+        expiration: Option<Expiration>,
+        index: usize,
+        total_leaves: usize,
+        now_ts: i64,
+    ) -> VerificationOutcome {
+        if self.root != Some((hash_algo, root)) {
+            self.outcomes.clear();
+            self.root = Some((hash_algo, root));
+        }
+
+        let key = (leaf, proof_bytes.to_vec(), index, total_leaves);
+        if let Some(&outcome) = self.outcomes.get(&key) {
+            let expired = expiration.is_some_and(|expiration| expiration.as_i64() <= now_ts);
+            if outcome != VerificationOutcome::Valid || !expired {
+                self.hits += 1;
+                return outcome;
+            }
+        }
+
+        let outcome = verification_outcome_with_algo(
+            hash_algo,
+            root,
+            proof_bytes,
+            leaf,
+            expiration,
+            index,
+            total_leaves,
+            now_ts,
+        );
+        if self.outcomes.len() >= VERIFY_CACHE_CAPACITY {
+            self.outcomes.clear();
+        }
+        self.outcomes.insert(key, outcome);
+        outcome
+    }
+
+    /// Calls answered from the cache
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+}
+
+/// Whether `leaf_index` is where `wallet` sits in `subscribers` (leaf order). A wallet
+/// that isn't in the list can't be checked and passes.
+pub fn leaf_index_matches(
+    subscribers: &[(String, Expiration)],
+    wallet: &str,
+    leaf_index: usize,
+) -> bool {
+    subscribers
+        .iter()
+        .position(|(pk, _)| pk == wallet)
+        .is_none_or(|position| position == leaf_index)
+}
+
+/// What the backend vouches for in a signed attestation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attestation {
+    pub wallet: String,
+    pub expiration: i64,
+    pub verified_at: i64,
+    /// Reject the attestation from this time on, even if the subscription runs longer
+    pub valid_until: i64,
+    pub root_hex: String,
+}
+
+/// An `Attestation` as the exact JSON bytes that were signed, so integrators verify
+/// the signature before parsing anything
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedAttestation {
+    pub message: String,
+    /// Base58 ed25519 signature over `message`
+    pub signature: String,
+    /// Base58 key that signed, expected to be the config authority
+    pub signer: String,
+}
+
+/// Attest that `wallet` is an active member of `snapshot` as of `now_ts`, signed with
+/// `signer`. `None` for wallets that aren't in the tree or have expired.
+///
+/// Valid for `ATTESTATION_TTL_SECS`, and never past the subscription's expiration.
+pub fn attest(
+    snapshot: &TreeSnapshot,
+    signer: &Keypair,
+    wallet: &str,
+    now_ts: i64,
+) -> Result<Option<SignedAttestation>> {
+    let Some((_, expiration)) = snapshot.subscribers.iter().find(|(pk, _)| pk == wallet) else {
+        return Ok(None);
+    };
+    if expiration.as_i64() <= now_ts {
+        return Ok(None);
+    }
+
+    let attestation = Attestation {
+        wallet: wallet.to_string(),
+        expiration: expiration.as_i64(),
+        verified_at: now_ts,
+        valid_until: now_ts
+            .saturating_add(ATTESTATION_TTL_SECS)
+            .min(expiration.as_i64()),
+        root_hex: snapshot.root_hex.clone(),
+    };
+    let message = serde_json::to_string(&attestation)?;
+    let signature = signer.sign_message(message.as_bytes());
+
+    Ok(Some(SignedAttestation {
+        message,
+        signature: signature.to_string(),
+        signer: signer.pubkey().to_string(),
+    }))
+}
+
+/// Check an attestation was signed by `authority` and is still valid at `now_ts`,
+/// returning what it attests to. How integrators consume `POST /attest` responses.
+pub fn verify_attestation(
+    signed: &SignedAttestation,
+    authority: &Pubkey,
+    now_ts: i64,
+) -> Result<Attestation> {
+    let signature: Signature = signed
+        .signature
+        .parse()
+        .context("Invalid attestation signature encoding")?;
+    if !signature.verify(authority.as_ref(), signed.message.as_bytes()) {
+        return Err(anyhow::anyhow!("Attestation not signed by {}", authority));
+    }
+
+    let attestation: Attestation =
+        serde_json::from_str(&signed.message).context("Invalid attestation message")?;
+    if attestation.valid_until <= now_ts {
+        return Err(anyhow::anyhow!(
+            "Attestation expired at {}",
+            attestation.valid_until
+        ));
+    }
+    Ok(attestation)
+}
+
+/// Shared handler state: the current tree plus proofs already computed from it
+#[derive(Clone)]
+pub struct AppState {
+    snapshot: Arc<RwLock<TreeSnapshot>>,
+    proof_cache: Arc<Mutex<HashMap<String, SerializedProof>>>,
+    verify_cache: Arc<Mutex<VerifyCache>>,
+    leaf_secret: Option<[u8; 32]>,
+    expiration_in_leaf: bool,
+    sync_interval: Option<Duration>,
+    last_sync_at: Arc<AtomicI64>,
+    chain: Option<Arc<dyn ChainClient>>,
+    attestation_signer: Option<Arc<Keypair>>,
+    session_issuer: Option<Arc<SessionIssuer>>,
+    tree_cache: Arc<TreeCache>,
+    subscriber_digest: Arc<Mutex<Option<[u8; 32]>>>,
+    drop_expired_leaves: bool,
+    next_expiration: Arc<Mutex<Option<i64>>>,
+    chain_degraded: Arc<AtomicBool>,
+    hash_algo: Arc<Mutex<HashAlgo>>,
+    sync_lock: Arc<tokio::sync::Mutex<()>>,
+    pool: Option<PgPool>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<metrics_exporter_prometheus::PrometheusHandle>,
+}
+
+impl AppState {
+    pub fn new(snapshot: TreeSnapshot) -> Self {
+        Self {
+            snapshot: Arc::new(RwLock::new(snapshot)),
+            proof_cache: Arc::new(Mutex::new(HashMap::new())),
+            verify_cache: Arc::new(Mutex::new(VerifyCache::default())),
+            leaf_secret: None,
+            expiration_in_leaf: true,
+            sync_interval: None,
+            last_sync_at: Arc::new(AtomicI64::new(Utc::now().timestamp())),
+            chain: None,
+            attestation_signer: None,
+            session_issuer: None,
+            tree_cache: Arc::new(TreeCache::default()),
+            subscriber_digest: Arc::new(Mutex::new(None)),
+            drop_expired_leaves: false,
+            next_expiration: Arc::new(Mutex::new(None)),
+            chain_degraded: Arc::new(AtomicBool::new(false)),
+            hash_algo: Arc::new(Mutex::new(HashAlgo::Sha256)),
+            sync_lock: Arc::new(tokio::sync::Mutex::new(())),
+            pool: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+
+    /// Key `POST /attest` signs with (the config authority); without one that route
+    /// is unavailable
+    pub fn with_attestation_signer(mut self, signer: Arc<Keypair>) -> Self {
+        self.attestation_signer = Some(signer);
+        self
+    }
+
+    /// Recorder handle `GET /metrics` renders; without one that route is unavailable
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, handle: metrics_exporter_prometheus::PrometheusHandle) -> Self {
+        self.metrics = Some(handle);
+        self
+    }
+
+    /// Key `POST /verify` signs session tokens with; without one no token is returned
+    pub fn with_session_issuer(mut self, issuer: Arc<SessionIssuer>) -> Self {
+        self.session_issuer = Some(issuer);
+        self
+    }
+
+    /// Chain to check `POST /verify` requests against; without one that route is unavailable
+    pub fn with_chain(mut self, chain: Arc<dyn ChainClient>) -> Self {
+        self.chain = Some(chain);
+        self
+    }
+
+    /// Database `GET /root` and `GET /root/history` read `merkle_state` from; without
+    /// one those routes are unavailable
+    pub fn with_pool(mut self, pool: PgPool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Serve a salted tree: proofs carry each wallet's salt derived from `leaf_secret`
+    pub fn with_leaf_secret(mut self, leaf_secret: Option<[u8; 32]>) -> Self {
+        self.leaf_secret = leaf_secret;
+        self
+    }
+
+    pub fn leaf_secret(&self) -> Option<&[u8; 32]> {
+        self.leaf_secret.as_ref()
+    }
+
+    /// Serve a static allowlist tree (leaves without expirations) when `false`
+    pub fn with_expiration_in_leaf(mut self, expiration_in_leaf: bool) -> Self {
+        self.expiration_in_leaf = expiration_in_leaf;
+        self
+    }
+
+    pub fn expiration_in_leaf(&self) -> bool {
+        self.expiration_in_leaf
+    }
+
+    /// Leave expired subscribers out of the trees sync passes build, so the on-chain
+    /// root drops them once it is next pushed (see `sync::run_expiration_sweeper`)
+    pub fn with_drop_expired_leaves(mut self, drop_expired_leaves: bool) -> Self {
+        self.drop_expired_leaves = drop_expired_leaves;
+        self
+    }
+
+    pub fn drop_expired_leaves(&self) -> bool {
+        self.drop_expired_leaves
+    }
+
+    /// Cadence of the sync loop, used to tell clients when their proof may go stale
+    pub fn with_sync_interval(mut self, sync_interval: Option<Duration>) -> Self {
+        self.sync_interval = sync_interval;
+        self
+    }
+
+    /// Note that a sync pass just ran; the root can next change one interval from now
+    pub fn record_sync_pass(&self) {
+        self.last_sync_at
+            .store(Utc::now().timestamp(), Ordering::SeqCst);
+    }
+
+    /// `tree::subscriber_set_digest` of the subscribers the served tree was built from,
+    /// once a sync pass has built it
+    pub fn subscriber_digest(&self) -> Option<[u8; 32]> {
+        *self.subscriber_digest.lock().unwrap()
+    }
+
+    pub fn record_subscriber_digest(&self, digest: [u8; 32]) {
+        *self.subscriber_digest.lock().unwrap() = Some(digest);
+    }
+
+    /// Soonest expiration among the subscribers of the last tree a sync pass built
+    /// (`tree::next_expiration`), if any is still ahead
+    pub fn next_expiration(&self) -> Option<i64> {
+        *self.next_expiration.lock().unwrap()
+    }
+
+    pub fn record_next_expiration(&self, next_expiration: Option<i64>) {
+        *self.next_expiration.lock().unwrap() = next_expiration;
+    }
+
+    /// Hash algorithm the on-chain config named when a sync pass last read it (sha256
+    /// until one does). The served tree is always sha256, so while this differs its
+    /// proofs can't verify on-chain and `POST /proofs` refuses to hand them out.
+    pub fn hash_algo(&self) -> HashAlgo {
+        *self.hash_algo.lock().unwrap()
+    }
+
+    pub fn record_hash_algo(&self, hash_algo: HashAlgo) {
+        *self.hash_algo.lock().unwrap() = hash_algo;
+    }
+
+    /// Held for a whole sync pass, so the sync loop and the expiration sweeper never
+    /// push, record or regenerate proofs for the same change twice
+    pub async fn lock_sync(&self) -> tokio::sync::MutexGuard<'_, ()> {
+        self.sync_lock.lock().await
+    }
+
+    /// Whether on-chain sync is degraded: the RPC was unreachable at startup or the
+    /// last root push failed. Proofs are still served; sync passes keep retrying the
+    /// chain until it takes the served root (see `sync::sync_once`).
+    pub fn chain_degraded(&self) -> bool {
+        self.chain_degraded.load(Ordering::SeqCst)
+    }
+
+    pub fn set_chain_degraded(&self, degraded: bool) {
+        self.chain_degraded.store(degraded, Ordering::SeqCst);
+    }
+
+    /// When the next sync pass may replace the root, if syncing on a cadence
+    pub fn estimated_stale_after(&self) -> Option<i64> {
+        let interval = self.sync_interval?;
+        let last_sync_at = self.last_sync_at.load(Ordering::SeqCst);
+        Some(last_sync_at.saturating_add(interval.as_secs() as i64))
+    }
+
+    /// Root of the snapshot currently being served
+    pub async fn root_hex(&self) -> String {
+        self.snapshot.read().await.root_hex.clone()
+    }
+
+    /// Number of leaves in the snapshot currently being served
+    pub async fn leaf_count(&self) -> usize {
+        self.snapshot.read().await.subscribers.len()
+    }
+
+    /// The tree as currently stored in the database, in this state's leaf layout.
+    /// Concurrent callers share one build, reused until the served root changes.
+    pub async fn rebuilt_tree(&self, pool: &PgPool) -> Result<Arc<BuiltTree>> {
+        self.tree_cache
+            .get_or_build(pool, self.leaf_secret(), self.expiration_in_leaf())
+            .await
+    }
+
+    /// Swap in a rebuilt tree; cached proofs and rebuilds belong to the old root and
+    /// are dropped
+    pub async fn replace_snapshot(&self, snapshot: TreeSnapshot) {
+        let mut current = self.snapshot.write().await;
+        *current = snapshot;
+        self.proof_cache.lock().unwrap().clear();
+        self.tree_cache.invalidate().await;
+    }
+}
+
+pub fn router(state: AppState) -> Router {
+    let router = Router::new()
+        .route("/proofs", post(batch_proofs))
+        .route("/verify", post(verify_proof))
+        .route("/attest", post(attest_membership))
+        .route("/debug/verify", post(debug_verify))
+        .route("/root", get(latest_root))
+        .route("/root/history", get(root_history));
+    #[cfg(feature = "metrics")]
+    let router = router.route("/metrics", axum::routing::get(render_metrics));
+    #[cfg(feature = "gzip")]
+    let router = router.layer(middleware::from_fn(gzip_responses));
+    router.with_state(state)
+}
+
+/// `GET /metrics`: everything recorded so far, in Prometheus text format. Reads only
+/// the recorder, never the tree.
+#[cfg(feature = "metrics")]
+async fn render_metrics(
+    State(state): State<AppState>,
+) -> Result<([(axum::http::header::HeaderName, &'static str); 1], String), (StatusCode, String)> {
+    let Some(handle) = &state.metrics else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "No metrics recorder installed".to_string(),
+        ));
+    };
+    Ok((
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4",
+        )],
+        handle.render(),
+    ))
+}
+
+/// Gzip responses of at least `gzip::COMPRESSION_THRESHOLD` bytes for clients that
+/// send `Accept-Encoding: gzip`
+#[cfg(feature = "gzip")]
+async fn gzip_responses(request: Request, next: Next) -> Response {
+    let accepts_gzip = request
+        .headers()
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(crate::gzip::accepts_gzip);
+    let response = next.run(request).await;
+    if !accepts_gzip || response.headers().contains_key(header::CONTENT_ENCODING) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, usize::MAX).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    parts
+        .headers
+        .append(header::VARY, HeaderValue::from_static("accept-encoding"));
+    if bytes.len() < crate::gzip::COMPRESSION_THRESHOLD {
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+    match crate::gzip::compress(&bytes) {
+        Ok(compressed) => {
+            parts
+                .headers
+                .insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Response::from_parts(parts, Body::from(compressed))
+        }
+        Err(_) => Response::from_parts(parts, Body::from(bytes)),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProofsRequest {
+    pub wallets: Vec<String>,
+}
+
+/// Whether a cached proof still leads from its wallet's leaf (under the served
+/// layout) to `root_hex`
+fn cached_proof_verifies(
+    proof: &SerializedProof,
+    root_hex: &str,
+    leaf_secret: Option<&[u8; 32]>,
+    expiration_in_leaf: bool,
+) -> bool {
+    let recomputed = || -> Result<String> {
+        let expiration = Expiration::new(proof.expiration)?;
+        let salt = leaf_secret
+            .map(|secret| tree::leaf_salt(secret, &proof.wallet))
+            .transpose()?;
+        let leaf = tree::compute_leaf_parts::<Sha256Hasher>(
+            &proof.wallet,
+            expiration_in_leaf.then_some(expiration),
+            salt.as_ref(),
+        )?;
+        tree::recompute_root_hex(
+            &hex::decode(&proof.proof_hex)?,
+            leaf,
+            proof.leaf_index,
+            proof.total_leaves,
+        )
+    };
+    proof.valid_as_of_root == root_hex && recomputed().is_ok_and(|root| root == root_hex)
+}
+
+/// `POST /proofs`: map each requested wallet to its proof, or `null` if unknown.
+///
+/// Cached proofs are re-checked against the served root first; one that fails (a
+/// missed cache invalidation) is logged and regenerated from the served tree.
+async fn batch_proofs(
+    State(state): State<AppState>,
+    Json(request): Json<ProofsRequest>,
+) -> Result<Json<HashMap<String, Option<SerializedProof>>>, (StatusCode, String)> {
+    if request.wallets.len() > MAX_BATCH_WALLETS {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("At most {} wallets per request", MAX_BATCH_WALLETS),
+        ));
+    }
+    let hash_algo = state.hash_algo();
+    if hash_algo != HashAlgo::Sha256 {
+        return Err((
+            StatusCode::CONFLICT,
+            format!(
+                "Served proofs are sha256, but the on-chain config hashes with {:?}",
+                hash_algo
+            ),
+        ));
+    }
+
+    // Hold the read lock until cache writes are done so a concurrent
+    // `replace_snapshot` can't interleave stale proofs into the new cache
+    let snapshot = state.snapshot.read().await;
+    let mut cache = state.proof_cache.lock().unwrap();
+
+    // Refreshed on every response: cached proofs outlive sync passes that kept the root
+    let estimated_stale_after = state.estimated_stale_after();
+    let mut response = HashMap::with_capacity(request.wallets.len());
+    let mut misses = Vec::new();
+    for wallet in request.wallets {
+        match cache.get(&wallet) {
+            Some(proof)
+                if cached_proof_verifies(
+                    proof,
+                    &snapshot.root_hex,
+                    state.leaf_secret(),
+                    state.expiration_in_leaf(),
+                ) =>
+            {
+                let proof = SerializedProof {
+                    estimated_stale_after,
+                    ..proof.clone()
+                };
+                response.insert(wallet, Some(proof));
+            }
+            Some(_) => {
+                eprintln!(
+                    "⚠️  Cached proof for {} doesn't verify against {}; regenerating",
+                    wallet, snapshot.root_hex
+                );
+                misses.push(wallet);
+            }
+            None => misses.push(wallet),
+        }
+    }
+
+    let computed = tree::get_proofs_for_users(
+        &snapshot.tree,
+        &snapshot.subscribers,
+        &misses,
+        state.leaf_secret(),
+    );
+    for (wallet, mut proof) in misses.into_iter().zip(computed) {
+        match &mut proof {
+            Some(proof) => {
+                proof.estimated_stale_after = estimated_stale_after;
+                cache.insert(wallet.clone(), proof.clone());
+            }
+            None => {
+                cache.remove(&wallet);
+            }
+        }
+        response.insert(wallet, proof);
+    }
+
+    Ok(Json(response))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyRequest {
+    pub wallet: String,
+    pub proof_hex: String,
+    pub expiration: i64,
+    pub leaf_index: usize,
+    pub total_leaves: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyResponse {
+    pub valid: bool,
+    pub reason: VerificationOutcome,
+    /// Session JWT, for valid proofs checked against the on-chain root when a
+    /// session key is configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// The root in an `X-Expected-Root` header, if one was sent; it must be 64 hex chars
+fn expected_root(headers: &HeaderMap) -> Result<Option<[u8; 32]>> {
+    let Some(value) = headers.get(EXPECTED_ROOT_HEADER) else {
+        return Ok(None);
+    };
+    let root_hex = value
+        .to_str()
+        .context("X-Expected-Root must be 64 hex chars")?;
+    MerkleRoot::from_hex(root_hex)
+        .map(|root| Some(root.to_bytes()))
+        .context("X-Expected-Root must be 64 hex chars")
+}
+
+/// `POST /verify`: whether a client's proof verifies against the current on-chain root,
+/// or against the root in an `X-Expected-Root` header (e.g. a candidate root QA is
+/// checking before it's pushed).
+///
+/// The leaf is rebuilt under the served layout, including the wallet's salt for
+/// salted trees, so clients only send what `POST /proofs` gave them.
+///
+/// Nothing about the wallet is looked up: the check needs only the root and the
+/// request's proof, wallet, expiration, index and leaf count. Proofs therefore still
+/// verify for wallets pruned from the database since the root was built, and an
+/// expired leaf is rejected whether or not the wallet is still stored.
+async fn verify_proof(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<VerifyRequest>,
+) -> Result<Json<VerifyResponse>, (StatusCode, String)> {
+    let bad_request = |e: anyhow::Error| (StatusCode::BAD_REQUEST, e.to_string());
+    let expected_root = expected_root(&headers).map_err(bad_request)?;
+
+    let expiration = Expiration::new(request.expiration).map_err(bad_request)?;
+    let salt = state
+        .leaf_secret()
+        .map(|secret| tree::leaf_salt(secret, &request.wallet))
+        .transpose()
+        .map_err(bad_request)?;
+    let expiration = state.expiration_in_leaf().then_some(expiration);
+    let Ok(proof_bytes) = hex::decode(&request.proof_hex) else {
+        return Ok(Json(VerifyResponse {
+            valid: false,
+            reason: VerificationOutcome::MalformedProof,
+            token: None,
+        }));
+    };
+    // Leaf positions are only known for the served tree
+    let index_matches = {
+        let snapshot = state.snapshot.read().await;
+        expected_root.is_some_and(|root| hex::encode(root) != snapshot.root_hex)
+            || leaf_index_matches(&snapshot.subscribers, &request.wallet, request.leaf_index)
+    };
+    if !index_matches {
+        return Ok(Json(VerifyResponse {
+            valid: false,
+            reason: VerificationOutcome::IndexMismatch,
+            token: None,
+        }));
+    }
+
+    let now_ts = Utc::now().timestamp();
+    let leaf_under = |hash_algo| {
+        leaf_with_algo(hash_algo, &request.wallet, expiration, salt.as_ref()).map_err(bad_request)
+    };
+    let reason = match expected_root {
+        // Uncached, so checking a candidate root doesn't evict the live root's outcomes.
+        // Hashed the way the last sync pass saw the on-chain config.
+        Some(root) => {
+            let hash_algo = state.hash_algo();
+            verification_outcome_with_algo(
+                hash_algo,
+                root,
+                &proof_bytes,
+                leaf_under(hash_algo)?,
+                expiration,
+                request.leaf_index,
+                request.total_leaves,
+                now_ts,
+            )
+        }
+        None => {
+            let Some(chain) = &state.chain else {
+                return Err((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "No chain connection configured".to_string(),
+                ));
+            };
+            // Root and hash algorithm come from one read, so they always agree
+            let config = chain
+                .fetch_config()
+                .await
+                .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+            let leaf = leaf_under(config.hash_algo)?;
+            state.verify_cache.lock().unwrap().outcome(
+                config.hash_algo,
+                config.merkle_root,
+                &proof_bytes,
+                leaf,
+                expiration,
+                request.leaf_index,
+                request.total_leaves,
+                now_ts,
+            )
+        }
+    };
+
+    metrics::counter!(telemetry::VERIFY_TOTAL, "outcome" => reason.as_str()).increment(1);
+
+    // Only the live root is vouched for, never a candidate from the header
+    let valid = reason == VerificationOutcome::Valid;
+    let token = match &state.session_issuer {
+        Some(issuer) if valid && expected_root.is_none() => Some(
+            issuer
+                .issue(&request.wallet, expiration.map(Expiration::as_i64), now_ts)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
+        ),
+        _ => None,
+    };
+
+    Ok(Json(VerifyResponse {
+        valid,
+        reason,
+        token,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DebugVerifyRequest {
+    pub wallet: String,
+    pub expiration: i64,
+}
+
+/// `POST /debug/verify`: trace the wallet's verification through the served tree
+/// against the on-chain root (the served root without a chain connection)
+async fn debug_verify(
+    State(state): State<AppState>,
+    Json(request): Json<DebugVerifyRequest>,
+) -> Result<Json<VerifyTrace>, (StatusCode, String)> {
+    let bad_request = |e: anyhow::Error| (StatusCode::BAD_REQUEST, e.to_string());
+    let expiration = Expiration::new(request.expiration).map_err(bad_request)?;
+    let stored_root = match &state.chain {
+        Some(chain) => Some(
+            chain
+                .get_current_root()
+                .await
+                .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?,
+        ),
+        None => None,
+    };
+
+    let snapshot = state.snapshot.read().await;
+    diagnose::trace_verification(
+        &snapshot,
+        state.leaf_secret(),
+        state.expiration_in_leaf(),
+        stored_root,
+        &request.wallet,
+        expiration,
+        Utc::now().timestamp(),
+    )
+    .map(Json)
+    .map_err(bad_request)
+}
+
+/// `GET /root`: the most recently recorded root, or 404 before any is recorded
+async fn latest_root(
+    State(state): State<AppState>,
+) -> Result<Json<MerkleStateResponse>, (StatusCode, String)> {
+    recorded_roots(&state, 1)
+        .await?
+        .into_iter()
+        .next()
+        .map(Json)
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                "No root has been recorded yet".to_string(),
+            )
+        })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RootHistoryQuery {
+    pub limit: Option<i64>,
+}
+
+/// `GET /root/history?limit=N`: recorded roots, newest first, clamped to
+/// `MAX_ROOT_HISTORY_LIMIT`
+async fn root_history(
+    State(state): State<AppState>,
+    Query(query): Query<RootHistoryQuery>,
+) -> Result<Json<Vec<MerkleStateResponse>>, (StatusCode, String)> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_ROOT_HISTORY_LIMIT)
+        .clamp(1, MAX_ROOT_HISTORY_LIMIT);
+    recorded_roots(&state, limit).await.map(Json)
+}
+
+/// The `limit` latest `merkle_state` rows in wire form
+async fn recorded_roots(
+    state: &AppState,
+    limit: i64,
+) -> Result<Vec<MerkleStateResponse>, (StatusCode, String)> {
+    let Some(pool) = &state.pool else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "No database configured".to_string(),
+        ));
+    };
+    let states = updatestate::merkle_state_history(pool, limit)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(states.into_iter().map(MerkleStateResponse::from).collect())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AttestRequest {
+    pub wallet: String,
+}
+
+/// `POST /attest`: a signed, short-lived attestation that the wallet is an active
+/// member of the served tree, or 404 if it isn't
+async fn attest_membership(
+    State(state): State<AppState>,
+    Json(request): Json<AttestRequest>,
+) -> Result<Json<SignedAttestation>, (StatusCode, String)> {
+    let Some(signer) = &state.attestation_signer else {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "No attestation key configured".to_string(),
+        ));
+    };
+
+    let snapshot = state.snapshot.read().await;
+    match attest(&snapshot, signer, &request.wallet, Utc::now().timestamp()) {
+        Ok(Some(signed)) => Ok(Json(signed)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            format!("{} is not an active subscriber", request.wallet),
+        )),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{self, MockChainClient, SUBSCRIBERS};
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    fn fixture_state() -> AppState {
+        let built = tree::build_tree_from_subscribers(test_fixtures::subscribers()).unwrap();
+        AppState::new(built.into())
+    }
+
+    async fn post_proofs(state: AppState, body: Value) -> (StatusCode, Vec<u8>) {
+        let request = Request::post("/proofs")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = router(state).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, bytes.to_vec())
+    }
+
+    #[tokio::test]
+    async fn batch_proofs_maps_known_and_unknown_wallets() {
+        let unknown = "So11111111111111111111111111111111111111112";
+        let wallets = [SUBSCRIBERS[0].0, SUBSCRIBERS[2].0, unknown];
+
+        let (status, body) = post_proofs(fixture_state(), json!({ "wallets": wallets })).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let proofs: HashMap<String, Option<SerializedProof>> =
+            serde_json::from_slice(&body).unwrap();
+        assert_eq!(proofs.len(), 3);
+        assert!(proofs[unknown].is_none());
+        for wallet in &wallets[..2] {
+            let proof = proofs[*wallet].as_ref().unwrap();
+            assert_eq!(proof.wallet, *wallet);
+            assert_eq!(proof.total_leaves, SUBSCRIBERS.len());
+        }
+    }
+
+    #[tokio::test]
+    async fn stale_cached_proofs_are_regenerated() {
+        let state = fixture_state();
+        let (wallet, _) = SUBSCRIBERS[2];
+
+        // A proof from before `SUBSCRIBERS[1]` joined, left behind by a missed invalidation
+        let before_join: Vec<_> = test_fixtures::subscribers()
+            .into_iter()
+            .filter(|(pk, _)| pk != SUBSCRIBERS[1].0)
+            .collect();
+        let (_, old_tree, old_subscribers) =
+            tree::build_tree_from_subscribers(before_join).unwrap();
+        let stale =
+            tree::get_proofs_for_users(&old_tree, &old_subscribers, &[wallet.to_string()], None)
+                .remove(0)
+                .unwrap();
+        let root_hex = state.root_hex().await;
+        assert!(!cached_proof_verifies(&stale, &root_hex, None, true));
+        state
+            .proof_cache
+            .lock()
+            .unwrap()
+            .insert(wallet.to_string(), stale);
+
+        let (status, body) = post_proofs(state.clone(), json!({ "wallets": [wallet] })).await;
+        assert_eq!(status, StatusCode::OK);
+        let proofs: HashMap<String, Option<SerializedProof>> =
+            serde_json::from_slice(&body).unwrap();
+        let proof = proofs[wallet].as_ref().unwrap();
+        assert_eq!(proof.valid_as_of_root, test_fixtures::EXPECTED_ROOT);
+        assert!(cached_proof_verifies(proof, &root_hex, None, true));
+
+        // The cache now holds the fresh proof
+        let cached = state.proof_cache.lock().unwrap()[wallet].clone();
+        assert_eq!(cached.proof_hex, proof.proof_hex);
+    }
+
+    #[tokio::test]
+    async fn proofs_carry_current_root_and_staleness_estimate() {
+        let state = fixture_state().with_sync_interval(Some(Duration::from_secs(60)));
+        let wallet = SUBSCRIBERS[1].0;
+
+        let before = Utc::now().timestamp();
+        let (status, body) = post_proofs(state.clone(), json!({ "wallets": [wallet] })).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let proofs: HashMap<String, Option<SerializedProof>> =
+            serde_json::from_slice(&body).unwrap();
+        let proof = proofs[wallet].as_ref().unwrap();
+        assert_eq!(proof.valid_as_of_root, state.root_hex().await);
+        assert_eq!(proof.valid_as_of_root, test_fixtures::EXPECTED_ROOT);
+        let stale_after = proof.estimated_stale_after.unwrap();
+        assert!(stale_after >= before + 60 - 1 && stale_after <= Utc::now().timestamp() + 60);
+
+        // Without a sync cadence there is nothing to estimate
+        let (_, body) = post_proofs(fixture_state(), json!({ "wallets": [wallet] })).await;
+        let proofs: HashMap<String, Option<SerializedProof>> =
+            serde_json::from_slice(&body).unwrap();
+        assert_eq!(proofs[wallet].as_ref().unwrap().estimated_stale_after, None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn proofs_are_served_while_the_rpc_is_unreachable() {
+        // Nothing listens on port 1, so every RPC call fails
+        let chain = crate::merkle::solana_client::SolanaClient::read_only("http://127.0.0.1:1");
+        assert!(!crate::sync::check_chain_at_startup(&chain).await);
+
+        let state = fixture_state().with_chain(Arc::new(chain));
+        state.set_chain_degraded(true);
+        let wallet = SUBSCRIBERS[0].0;
+        let (status, body) = post_proofs(state, json!({ "wallets": [wallet] })).await;
+        assert_eq!(status, StatusCode::OK);
+        let proofs: HashMap<String, Option<SerializedProof>> =
+            serde_json::from_slice(&body).unwrap();
+        let proof = proofs[wallet].as_ref().unwrap();
+        assert_eq!(proof.valid_as_of_root, test_fixtures::EXPECTED_ROOT);
+    }
+
+    #[sqlx::test]
+    async fn verify_and_fetch_returns_records_only_for_active_members(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+        let snapshot: TreeSnapshot = tree::build_tree_from_db(&pool).await?.into();
+        let now_ts = 1_750_000_000;
+
+        let (active, expiration_ts) = SUBSCRIBERS[2];
+        let record = verify_and_fetch(&pool, &snapshot, active, now_ts)
+            .await?
+            .unwrap();
+        assert_eq!(record.wallet_address, active);
+        assert_eq!(record.expiration_ts, expiration_ts);
+
+        // Expired before `now_ts`
+        let (expired, _) = SUBSCRIBERS[1];
+        assert!(verify_and_fetch(&pool, &snapshot, expired, now_ts)
+            .await?
+            .is_none());
+
+        let unknown = "So11111111111111111111111111111111111111112";
+        assert!(verify_and_fetch(&pool, &snapshot, unknown, now_ts)
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn saved_tree_reloads_with_the_same_root_and_proofs() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("tree-roundtrip-{}.json", std::process::id()));
+        let snapshot: TreeSnapshot =
+            tree::build_tree_from_subscribers(test_fixtures::subscribers())?.into();
+
+        save_tree(&snapshot, &path)?;
+        let loaded = load_tree(&path)?;
+        fs::remove_file(&path)?;
+
+        assert_eq!(loaded.root_hex, test_fixtures::EXPECTED_ROOT);
+        assert_eq!(loaded.subscribers, snapshot.subscribers);
+        let wallet = SUBSCRIBERS[1].0;
+        assert_eq!(
+            tree::get_proof_for_user(&loaded.tree, &loaded.subscribers, wallet),
+            tree::get_proof_for_user(&snapshot.tree, &snapshot.subscribers, wallet)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn corrupted_tree_snapshot_is_rejected() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("tree-corrupt-{}.json", std::process::id()));
+        let snapshot: TreeSnapshot =
+            tree::build_tree_from_subscribers(test_fixtures::subscribers())?.into();
+        save_tree(&snapshot, &path)?;
+
+        // A flipped leaf no longer rebuilds the recorded root
+        let mut file: Value = serde_json::from_slice(&fs::read(&path)?)?;
+        file["leaves"][0] = json!("00".repeat(32));
+        fs::write(&path, file.to_string())?;
+        let error = load_tree(&path).err().unwrap();
+        assert!(error.to_string().contains("corrupt"));
+
+        // As does a truncated file
+        let raw = serde_json::to_vec(&file)?;
+        fs::write(&path, &raw[..raw.len() / 2])?;
+        assert!(load_tree(&path).is_err());
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    // A `/verify` body with the wallet's proof from a tree of `subscribers`
+    fn verify_body(subscribers: Vec<(String, Expiration)>, wallet: &str) -> Value {
+        let snapshot: TreeSnapshot = tree::build_tree_from_subscribers(subscribers)
+            .unwrap()
+            .into();
+        let (proof_bytes, index) =
+            tree::get_proof_for_user(&snapshot.tree, &snapshot.subscribers, wallet).unwrap();
+        json!({
+            "wallet": wallet,
+            "proof_hex": hex::encode(proof_bytes),
+            "expiration": snapshot.subscribers[index].1.as_i64(),
+            "leaf_index": index,
+            "total_leaves": snapshot.subscribers.len(),
+        })
+    }
+
+    async fn post_verify(state: AppState, wallet: &str) -> VerifyResponse {
+        post_verify_body(state, verify_body(test_fixtures::subscribers(), wallet)).await
+    }
+
+    async fn post_verify_body(state: AppState, body: Value) -> VerifyResponse {
+        let request = Request::post("/verify")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = router(state).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn verify_checks_proofs_against_the_live_root() {
+        let root: [u8; 32] = hex::decode(test_fixtures::EXPECTED_ROOT)
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let chain = Arc::new(MockChainClient::with_root(root));
+        let state = fixture_state().with_chain(chain.clone());
+        let (active, _) = SUBSCRIBERS[2];
+
+        let response = post_verify(state.clone(), active).await;
+        assert!(response.valid);
+        assert_eq!(response.reason, VerificationOutcome::Valid);
+
+        // In the current tree, but expired
+        let (expired, _) = SUBSCRIBERS[1];
+        let response = post_verify(state.clone(), expired).await;
+        assert!(!response.valid);
+        assert_eq!(response.reason, VerificationOutcome::Expired);
+
+        // The chain has moved on to a root the cached proof wasn't built against
+        chain.update_merkle_root([7u8; 32]).await.unwrap();
+        let response = post_verify(state.clone(), active).await;
+        assert!(!response.valid);
+        assert_eq!(response.reason, VerificationOutcome::RootMismatch);
+
+        // A zeroed config never validates anything
+        chain.update_merkle_root(tree::UNSET_ROOT).await.unwrap();
+        let response = post_verify(state, active).await;
+        assert!(!response.valid);
+        assert_eq!(response.reason, VerificationOutcome::RootNotSet);
+    }
+
+    #[tokio::test]
+    async fn verify_accepts_proofs_for_wallets_pruned_from_the_database() {
+        let root: [u8; 32] = hex::decode(test_fixtures::EXPECTED_ROOT)
+            .unwrap()
+            .try_into()
+            .unwrap();
+        // The served snapshot (and the database behind it) no longer has these
+        // wallets, but the on-chain root still includes them
+        let (pruned_active, _) = SUBSCRIBERS[2];
+        let (pruned_expired, _) = SUBSCRIBERS[1];
+        let remaining = test_fixtures::subscribers()
+            .into_iter()
+            .filter(|(wallet, _)| wallet != pruned_active && wallet != pruned_expired)
+            .collect();
+        let state = AppState::new(tree::build_tree_from_subscribers(remaining).unwrap().into())
+            .with_chain(Arc::new(MockChainClient::with_root(root)));
+
+        let response = post_verify(state.clone(), pruned_active).await;
+        assert!(response.valid);
+        assert_eq!(response.reason, VerificationOutcome::Valid);
+
+        // Expiration is still enforced without a stored row to check
+        let response = post_verify(state, pruned_expired).await;
+        assert!(!response.valid);
+        assert_eq!(response.reason, VerificationOutcome::Expired);
+    }
+
+    #[tokio::test]
+    async fn verify_returns_a_session_token_for_valid_proofs() {
+        use crate::session::SessionClaims;
+        use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+
+        let root: [u8; 32] = hex::decode(test_fixtures::EXPECTED_ROOT)
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let chain = Arc::new(MockChainClient::with_root(root));
+        let (active, expiration) = SUBSCRIBERS[2];
+
+        // No session key, no token
+        let state = fixture_state().with_chain(chain.clone());
+        assert!(post_verify(state, active).await.token.is_none());
+
+        // A session longer than the remaining subscription is cut short
+        let session_secs = expiration - Utc::now().timestamp() + 3600;
+        let state = fixture_state()
+            .with_chain(chain)
+            .with_session_issuer(Arc::new(SessionIssuer::hs256(b"secret", session_secs)));
+        let token = post_verify(state.clone(), active).await.token.unwrap();
+        let claims = jsonwebtoken::decode::<SessionClaims>(
+            &token,
+            &DecodingKey::from_secret(b"secret"),
+            &Validation::new(Algorithm::HS256),
+        )
+        .unwrap()
+        .claims;
+        assert_eq!(claims.sub, active);
+        assert_eq!(claims.subscription_expires_at, Some(expiration));
+        assert_eq!(claims.exp, expiration);
+
+        // Failed verifications get none
+        let (expired, _) = SUBSCRIBERS[1];
+        assert!(post_verify(state, expired).await.token.is_none());
+    }
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn metrics_endpoint_reports_verify_outcomes() {
+        use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+        use std::sync::OnceLock;
+
+        // One recorder per process, shared by every test that records
+        static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+        let handle = HANDLE.get_or_init(|| {
+            let recorder = PrometheusBuilder::new().build_recorder();
+            let handle = recorder.handle();
+            metrics::set_global_recorder(recorder).unwrap();
+            handle
+        });
+
+        let root: [u8; 32] = hex::decode(test_fixtures::EXPECTED_ROOT)
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let state = fixture_state()
+            .with_chain(Arc::new(MockChainClient::with_root(root)))
+            .with_metrics(handle.clone());
+        let (active, _) = SUBSCRIBERS[2];
+        assert!(post_verify(state.clone(), active).await.valid);
+
+        let request = Request::get("/metrics").body(Body::empty()).unwrap();
+        let response = router(state).oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(&format!("{}{{outcome=\"valid\"}}", telemetry::VERIFY_TOTAL)));
+    }
+
+    #[cfg(feature = "gzip")]
+    #[tokio::test]
+    async fn proofs_are_gzipped_when_accepted() {
+        // Enough wallets to clear the compression threshold
+        let wallets: Vec<String> = SUBSCRIBERS
+            .iter()
+            .map(|(wallet, _)| wallet.to_string())
+            .chain((0..60).map(|n| format!("unknown-wallet-{}", n)))
+            .collect();
+        let request = |accept_encoding: Option<&str>| {
+            let mut request = Request::post("/proofs").header("content-type", "application/json");
+            if let Some(accept_encoding) = accept_encoding {
+                request = request.header("accept-encoding", accept_encoding);
+            }
+            request
+                .body(Body::from(json!({ "wallets": wallets }).to_string()))
+                .unwrap()
+        };
+
+        let response = router(fixture_state())
+            .oneshot(request(Some("gzip, deflate")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-encoding"], "gzip");
+        let compressed = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: HashMap<String, Option<SerializedProof>> =
+            serde_json::from_slice(&crate::gzip::decompress(&compressed).unwrap()).unwrap();
+        assert_eq!(body.len(), wallets.len());
+        assert!(body[SUBSCRIBERS[0].0].is_some());
+
+        let response = router(fixture_state())
+            .oneshot(request(None))
+            .await
+            .unwrap();
+        assert!(!response.headers().contains_key("content-encoding"));
+    }
+
+    #[tokio::test]
+    async fn verify_rejects_a_stale_leaf_index() {
+        let root: [u8; 32] = hex::decode(test_fixtures::EXPECTED_ROOT)
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let state = fixture_state().with_chain(Arc::new(MockChainClient::with_root(root)));
+        let (active, _) = SUBSCRIBERS[2];
+
+        // Correct index
+        let response = post_verify(state.clone(), active).await;
+        assert_eq!(response.reason, VerificationOutcome::Valid);
+
+        // Shifted index with the current proof
+        let mut body = verify_body(test_fixtures::subscribers(), active);
+        body["leaf_index"] = json!(1);
+        let response = post_verify_body(state.clone(), body).await;
+        assert!(!response.valid);
+        assert_eq!(response.reason, VerificationOutcome::IndexMismatch);
+
+        // Proof and index from before `SUBSCRIBERS[1]` joined, which shifted the wallet
+        let before_join = test_fixtures::subscribers()
+            .into_iter()
+            .filter(|(wallet, _)| wallet != SUBSCRIBERS[1].0)
+            .collect();
+        let response = post_verify_body(state, verify_body(before_join, active)).await;
+        assert_eq!(response.reason, VerificationOutcome::IndexMismatch);
+    }
+
+    #[test]
+    fn verify_cache_reuses_outcomes_until_the_root_or_expiration_changes() -> Result<()> {
+        let (root_hex, tree, subscribers) =
+            tree::build_tree_from_subscribers(test_fixtures::subscribers())?;
+        let root: [u8; 32] = hex::decode(root_hex)?.try_into().unwrap();
+        let (wallet, expiration) = &subscribers[2];
+        let (proof_bytes, index) = tree::get_proof_for_user(&tree, &subscribers, wallet).unwrap();
+        let leaf = tree::compute_leaf(wallet, *expiration)?;
+        let before_expiry = expiration.as_i64() - 1;
+
+        let mut cache = VerifyCache::default();
+        let mut check = |root, now_ts| {
+            cache.outcome(
+                HashAlgo::Sha256,
+                root,
+                &proof_bytes,
+                leaf,
+                Some(*expiration),
+                index,
+                3,
+                now_ts,
+            )
+        };
+        assert_eq!(check(root, before_expiry), VerificationOutcome::Valid);
+        assert_eq!(check(root, before_expiry), VerificationOutcome::Valid);
+
+        // A new root drops the cached `Valid`
+        assert_eq!(
+            check([7u8; 32], before_expiry),
+            VerificationOutcome::RootMismatch
+        );
+        assert_eq!(check(root, before_expiry), VerificationOutcome::Valid);
+
+        // Past the expiration, the cached `Valid` isn't served
+        assert_eq!(
+            check(root, expiration.as_i64()),
+            VerificationOutcome::Expired
+        );
+        assert_eq!(cache.hits(), 1);
+        Ok(())
+    }
+
+    async fn post_verify_with_root(
+        state: AppState,
+        wallet: &str,
+        root: &str,
+    ) -> (StatusCode, Vec<u8>) {
+        let request = Request::post("/verify")
+            .header("content-type", "application/json")
+            .header(EXPECTED_ROOT_HEADER, root)
+            .body(Body::from(
+                verify_body(test_fixtures::subscribers(), wallet).to_string(),
+            ))
+            .unwrap();
+        let response = router(state).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, bytes.to_vec())
+    }
+
+    #[tokio::test]
+    async fn verify_uses_the_expected_root_header_instead_of_the_chain() {
+        // The chain is on another root; the header names the candidate the proof is for
+        let chain = Arc::new(MockChainClient::with_root([7u8; 32]));
+        let state = fixture_state().with_chain(chain);
+        let (active, _) = SUBSCRIBERS[2];
+
+        let (status, body) =
+            post_verify_with_root(state.clone(), active, test_fixtures::EXPECTED_ROOT).await;
+        assert_eq!(status, StatusCode::OK);
+        let response: VerifyResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(response.reason, VerificationOutcome::Valid);
+
+        // Without the header, the live root still decides
+        let response = post_verify(state.clone(), active).await;
+        assert_eq!(response.reason, VerificationOutcome::RootMismatch);
+
+        // No chain connection is needed to check against a named root
+        let (status, _) =
+            post_verify_with_root(fixture_state(), active, test_fixtures::EXPECTED_ROOT).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn verify_and_proofs_follow_the_on_chain_hash_algo() {
+        // A keccak deployment, whose client built its proof from a keccak tree
+        let (keccak_root, keccak_tree, subscribers) =
+            tree::build_tree_from_subscribers_with::<Keccak256Hasher>(test_fixtures::subscribers())
+                .unwrap();
+        let root = MerkleRoot::from_hex(&keccak_root).unwrap().to_bytes();
+        let chain = Arc::new(MockChainClient::with_root(root));
+        chain.config.lock().unwrap().as_mut().unwrap().hash_algo = HashAlgo::Keccak256;
+        let state = fixture_state().with_chain(chain);
+        let (active, _) = SUBSCRIBERS[2];
+
+        let (proof_bytes, index) =
+            tree::get_proof_for_user(&keccak_tree, &subscribers, active).unwrap();
+        let body = json!({
+            "wallet": active,
+            "proof_hex": hex::encode(proof_bytes),
+            "expiration": subscribers[index].1.as_i64(),
+            "leaf_index": index,
+            "total_leaves": subscribers.len(),
+        });
+        let response = post_verify_body(state.clone(), body).await;
+        assert_eq!(response.reason, VerificationOutcome::Valid);
+
+        // The served sha256 proof doesn't verify under keccak
+        let response = post_verify(state.clone(), active).await;
+        assert_eq!(response.reason, VerificationOutcome::RootMismatch);
+
+        // Once a sync pass has seen the keccak config, sha256 proofs aren't handed out
+        state.record_hash_algo(HashAlgo::Keccak256);
+        let (status, _) = post_proofs(state, json!({ "wallets": [active] })).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn verify_rejects_a_malformed_expected_root_header() {
+        let (active, _) = SUBSCRIBERS[2];
+        for root in ["not-hex", "abcd", &"0".repeat(66)] {
+            let (status, body) = post_verify_with_root(fixture_state(), active, root).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert!(String::from_utf8(body).unwrap().contains("X-Expected-Root"));
+        }
+    }
+
+    #[test]
+    fn unknown_wallets_skip_the_index_check() {
+        let subscribers = test_fixtures::subscribers();
+        assert!(leaf_index_matches(&subscribers, SUBSCRIBERS[2].0, 2));
+        assert!(!leaf_index_matches(&subscribers, SUBSCRIBERS[2].0, 1));
+        let unknown = "So11111111111111111111111111111111111111112";
+        assert!(leaf_index_matches(&subscribers, unknown, 0));
+    }
+
+    #[tokio::test]
+    async fn debug_verify_endpoint_reports_absent_wallets() {
+        let request = |wallet: &str, expiration: i64| {
+            Request::post("/debug/verify")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({ "wallet": wallet, "expiration": expiration }).to_string(),
+                ))
+                .unwrap()
+        };
+        let trace = |response: axum::response::Response| async move {
+            assert_eq!(response.status(), StatusCode::OK);
+            let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<Value>(&bytes).unwrap()
+        };
+
+        let (active, expiration) = SUBSCRIBERS[2];
+        let response = router(fixture_state())
+            .oneshot(request(active, expiration))
+            .await
+            .unwrap();
+        let body = trace(response).await;
+        assert_eq!(body["verdict"], json!({ "kind": "valid" }));
+        assert_eq!(body["recomputed_root"], test_fixtures::EXPECTED_ROOT);
+
+        let absent = "So11111111111111111111111111111111111111112";
+        let response = router(fixture_state())
+            .oneshot(request(absent, expiration))
+            .await
+            .unwrap();
+        let body = trace(response).await;
+        assert_eq!(body["verdict"], json!({ "kind": "not_in_tree" }));
+        assert_eq!(body["leaf_index"], Value::Null);
+    }
+
+    #[test]
+    fn attestations_verify_only_under_the_signing_key() -> Result<()> {
+        let snapshot: TreeSnapshot =
+            tree::build_tree_from_subscribers(test_fixtures::subscribers())?.into();
+        let authority = Keypair::new();
+        let (wallet, expiration) = SUBSCRIBERS[2];
+        let now_ts = 1_750_000_000;
+
+        let signed = attest(&snapshot, &authority, wallet, now_ts)?.unwrap();
+        assert_eq!(signed.signer, authority.pubkey().to_string());
+        let attestation = verify_attestation(&signed, &authority.pubkey(), now_ts)?;
+        assert_eq!(attestation.wallet, wallet);
+        assert_eq!(attestation.expiration, expiration);
+        assert_eq!(attestation.root_hex, test_fixtures::EXPECTED_ROOT);
+
+        // Another key, or an edited message, doesn't verify
+        assert!(verify_attestation(&signed, &Keypair::new().pubkey(), now_ts).is_err());
+        let tampered = SignedAttestation {
+            message: signed.message.replace(wallet, SUBSCRIBERS[1].0),
+            ..signed.clone()
+        };
+        assert!(verify_attestation(&tampered, &authority.pubkey(), now_ts).is_err());
+
+        // Expired or unknown wallets get no attestation
+        assert!(attest(&snapshot, &authority, SUBSCRIBERS[1].0, now_ts)?.is_none());
+        let unknown = "So11111111111111111111111111111111111111112";
+        assert!(attest(&snapshot, &authority, unknown, now_ts)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn attestations_expire() -> Result<()> {
+        let snapshot: TreeSnapshot =
+            tree::build_tree_from_subscribers(test_fixtures::subscribers())?.into();
+        let authority = Keypair::new();
+        let now_ts = 1_750_000_000;
+
+        let signed = attest(&snapshot, &authority, SUBSCRIBERS[2].0, now_ts)?.unwrap();
+        let valid_until = now_ts + ATTESTATION_TTL_SECS;
+        assert!(verify_attestation(&signed, &authority.pubkey(), valid_until - 1).is_ok());
+        let error = verify_attestation(&signed, &authority.pubkey(), valid_until).unwrap_err();
+        assert!(error.to_string().contains("expired"));
+
+        // Capped at the subscription's own expiration
+        let (wallet, expiration) = SUBSCRIBERS[0];
+        let signed = attest(&snapshot, &authority, wallet, expiration - 10)?.unwrap();
+        assert!(verify_attestation(&signed, &authority.pubkey(), expiration).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn attest_endpoint_signs_for_active_members() {
+        let authority = Arc::new(Keypair::new());
+        let state = fixture_state().with_attestation_signer(authority.clone());
+        let request = |wallet: &str| {
+            Request::post("/attest")
+                .header("content-type", "application/json")
+                .body(Body::from(json!({ "wallet": wallet }).to_string()))
+                .unwrap()
+        };
+
+        let response = router(state.clone())
+            .oneshot(request(SUBSCRIBERS[2].0))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let signed: SignedAttestation = serde_json::from_slice(&bytes).unwrap();
+        let attestation =
+            verify_attestation(&signed, &authority.pubkey(), Utc::now().timestamp()).unwrap();
+        assert_eq!(attestation.wallet, SUBSCRIBERS[2].0);
+
+        // Expired subscription
+        let response = router(state)
+            .oneshot(request(SUBSCRIBERS[1].0))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    async fn get_json(state: AppState, uri: &str) -> (StatusCode, Value) {
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        let response = router(state).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    #[sqlx::test]
+    async fn root_endpoints_serve_recorded_roots_newest_first(pool: PgPool) -> Result<()> {
+        let (status, _) = get_json(fixture_state(), "/root").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        let state = fixture_state().with_pool(pool.clone());
+        let (status, _) = get_json(state.clone(), "/root").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let older = "ab".repeat(32);
+        updatestate::update_merkle_state(&pool, &older, 2, None).await?;
+        updatestate::update_merkle_state(
+            &pool,
+            test_fixtures::EXPECTED_ROOT,
+            SUBSCRIBERS.len(),
+            Some("5sig".to_string()),
+        )
+        .await?;
+
+        let (status, body) = get_json(state.clone(), "/root").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["root_hash"], test_fixtures::EXPECTED_ROOT);
+        assert_eq!(body["synced"], true);
+        assert_eq!(body["tx_signature"], "5sig");
+        assert_eq!(body["total_leaves"], SUBSCRIBERS.len());
+        assert_eq!(body["tree_depth"], 2);
+        // The row id stays internal
+        assert!(body.get("id").is_none());
+
+        let (_, body) = get_json(state.clone(), "/root/history").await;
+        let history: Vec<MerkleStateResponse> = serde_json::from_value(body)?;
+        let roots: Vec<&str> = history.iter().map(|s| s.root_hash.as_str()).collect();
+        assert_eq!(roots, [test_fixtures::EXPECTED_ROOT, older.as_str()]);
+        assert!(!history[1].synced);
+
+        let (_, body) = get_json(state, "/root/history?limit=1").await;
+        assert_eq!(body.as_array().map(Vec::len), Some(1));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn batch_proofs_rejects_oversized_request() {
+        let wallets = vec![SUBSCRIBERS[0].0; MAX_BATCH_WALLETS + 1];
+
+        let (status, _) = post_proofs(fixture_state(), json!({ "wallets": wallets })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
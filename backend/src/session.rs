@@ -0,0 +1,121 @@
+//! Short-lived JWTs handed out by `POST /verify`, so services that already speak
+//! JWT can gate on membership without checking proofs themselves.
+
+use anyhow::{Context, Result};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+
+/// Claims in a session token
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionClaims {
+    /// The verified wallet
+    pub sub: String,
+    /// Subscription expiration; `None` for static allowlists
+    pub subscription_expires_at: Option<i64>,
+    pub iat: i64,
+    /// Session length from `iat`, cut short at the subscription's expiration
+    pub exp: i64,
+}
+
+/// Signs session tokens with a configured key
+pub struct SessionIssuer {
+    key: EncodingKey,
+    algorithm: Algorithm,
+    session_secs: i64,
+}
+
+impl SessionIssuer {
+    /// HS256 with a shared secret, for services that hold the same secret
+    pub fn hs256(secret: &[u8], session_secs: i64) -> Self {
+        Self {
+            key: EncodingKey::from_secret(secret),
+            algorithm: Algorithm::HS256,
+            session_secs,
+        }
+    }
+
+    /// ES256 with a PEM-encoded P-256 private key; services verify with its public key
+    pub fn es256_pem(pem: &[u8], session_secs: i64) -> Result<Self> {
+        Ok(Self {
+            key: EncodingKey::from_ec_pem(pem).context("Invalid ES256 private key")?,
+            algorithm: Algorithm::ES256,
+            session_secs,
+        })
+    }
+
+    /// A token for `wallet`, verified at `now_ts`
+    pub fn issue(
+        &self,
+        wallet: &str,
+        subscription_expires_at: Option<i64>,
+        now_ts: i64,
+    ) -> Result<String> {
+        let session_end = now_ts.saturating_add(self.session_secs);
+        let claims = SessionClaims {
+            sub: wallet.to_string(),
+            subscription_expires_at,
+            iat: now_ts,
+            exp: subscription_expires_at.map_or(session_end, |expires| session_end.min(expires)),
+        };
+        Ok(jsonwebtoken::encode(
+            &Header::new(self.algorithm),
+            &claims,
+            &self.key,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{DecodingKey, Validation};
+
+    const SECRET: &[u8] = b"session-test-secret";
+
+    fn decode(token: &str) -> SessionClaims {
+        let mut validation = Validation::new(Algorithm::HS256);
+        // Tokens here are issued at fixed times, not now
+        validation.validate_exp = false;
+        jsonwebtoken::decode(token, &DecodingKey::from_secret(SECRET), &validation)
+            .unwrap()
+            .claims
+    }
+
+    #[test]
+    fn session_tokens_never_outlive_the_subscription() -> Result<()> {
+        let issuer = SessionIssuer::hs256(SECRET, 3600);
+        let now_ts = 1_700_000_000;
+
+        // Subscription outlasts the session
+        let claims = decode(&issuer.issue("wallet", Some(now_ts + 86_400), now_ts)?);
+        assert_eq!(
+            claims,
+            SessionClaims {
+                sub: "wallet".to_string(),
+                subscription_expires_at: Some(now_ts + 86_400),
+                iat: now_ts,
+                exp: now_ts + 3600,
+            }
+        );
+
+        // Subscription ends first
+        let claims = decode(&issuer.issue("wallet", Some(now_ts + 60), now_ts)?);
+        assert_eq!(claims.exp, now_ts + 60);
+
+        // Allowlist members get the full session
+        let claims = decode(&issuer.issue("wallet", None, now_ts)?);
+        assert_eq!(claims.exp, now_ts + 3600);
+
+        // Other keys don't verify it
+        let token = issuer.issue("wallet", None, now_ts)?;
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.validate_exp = false;
+        assert!(jsonwebtoken::decode::<SessionClaims>(
+            &token,
+            &DecodingKey::from_secret(b"other"),
+            &validation
+        )
+        .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,702 @@
+use crate::db::AppPools;
+use crate::merkle::chain::{ChainClient, OnChainConfig};
+use crate::merkle::{proofstore, tree, updatestate};
+use crate::server::AppState;
+use crate::telemetry;
+use anyhow::Result;
+use chrono::Utc;
+use solana_sdk::signature::Signature;
+use sqlx::PgPool;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// One sync pass: rebuild from the DB and, if the root changed, push it on-chain,
+/// record it, regenerate stored proofs, and swap the served snapshot. The tree is
+/// built from `pools.read`; everything recorded goes to `pools.write`.
+///
+/// The rebuild is skipped while the subscriber set digest matches the one the
+/// served tree was built from. While the state's chain sync is degraded, each pass
+/// first retries catching the chain up to the served root. Passes on one state run
+/// one at a time.
+pub async fn sync_once(pools: &AppPools, chain: &dyn ChainClient, state: &AppState) -> Result<()> {
+    sync_once_at(pools, chain, state, Utc::now().timestamp()).await
+}
+
+/// `sync_once` as of `now_ts`, which decides who has expired when the state drops
+/// expired leaves
+pub async fn sync_once_at(
+    pools: &AppPools,
+    chain: &dyn ChainClient,
+    state: &AppState,
+    now_ts: i64,
+) -> Result<()> {
+    let _sync = state.lock_sync().await;
+    if state.chain_degraded() {
+        if let Err(e) = resume_chain_sync(&pools.write, chain, state).await {
+            eprintln!("⚠️  On-chain sync still degraded: {}", e);
+        }
+    }
+
+    let active_at = state.drop_expired_leaves().then_some(now_ts);
+    // Taken before the build, so a change landing in between is seen next pass
+    let digest = tree::subscriber_set_digest_active_at(&pools.read, active_at).await?;
+    if state.subscriber_digest() == Some(digest) {
+        state.record_sync_pass();
+        return Ok(());
+    }
+
+    let (root_hash, merkle_tree, subscribers) = tree::build_tree_from_db_active_at(
+        &pools.read,
+        active_at,
+        state.leaf_secret(),
+        state.expiration_in_leaf(),
+    )
+    .await?;
+    state.record_sync_pass();
+    state.record_next_expiration(tree::next_expiration(&subscribers, now_ts));
+    if root_hash == state.root_hex().await {
+        state.record_subscriber_digest(digest);
+        return Ok(());
+    }
+
+    // Every client's proof changes size, which can break size-tuned verify batches
+    let served_leaves = state.leaf_count().await;
+    if tree::will_depth_change(served_leaves, subscribers.len()) {
+        eprintln!(
+            "⚠️  Proof depth changes from {} to {} ({} -> {} subscribers); all proofs change size",
+            tree::proof_depth(served_leaves),
+            tree::proof_depth(subscribers.len()),
+            served_leaves,
+            subscribers.len()
+        );
+    }
+
+    // An unreadable config is left to the push below to report
+    if let Ok(config) = chain.fetch_config().await {
+        state.record_hash_algo(config.hash_algo);
+        check_pushable(&config, &root_hash, subscribers.len())?;
+    }
+
+    let root_bytes = tree::MerkleRoot::from_hex(&root_hash)?.to_bytes();
+    let signature = match chain.update_merkle_root(root_bytes).await {
+        Ok(signature) => {
+            metrics::counter!(telemetry::ROOT_PUSH_TOTAL, "result" => "success").increment(1);
+            state.set_chain_degraded(false);
+            Some(signature.to_string())
+        }
+        Err(e) => {
+            metrics::counter!(telemetry::ROOT_PUSH_TOTAL, "result" => "failure").increment(1);
+            eprintln!("❌ Failed to update on-chain: {}", e);
+            state.set_chain_degraded(true);
+            None
+        }
+    };
+    updatestate::update_merkle_state(&pools.write, &root_hash, subscribers.len(), signature).await?;
+    proofstore::regenerate_all_proofs(&pools.write, &merkle_tree, &subscribers).await?;
+
+    println!("🔄 Synced new root {}", root_hash);
+    state
+        .replace_snapshot((root_hash, merkle_tree, subscribers).into())
+        .await;
+    state.record_subscriber_digest(digest);
+    Ok(())
+}
+
+/// Refuse a root whose proofs the on-chain config's verification would reject: one
+/// from a tree over the config's cap, or hashed differently than it (synced trees are
+/// sha256 over raw pubkey bytes). Every path that pushes a built tree checks this.
+pub fn check_pushable(config: &OnChainConfig, root_hash: &str, total_leaves: usize) -> Result<()> {
+    if config.hash_algo != tree::HashAlgo::Sha256 {
+        return Err(anyhow::anyhow!(
+            "Refusing to push root {}: the on-chain config hashes with {:?}, synced trees with Sha256",
+            root_hash,
+            config.hash_algo
+        ));
+    }
+    if config.pubkey_leaf_encoding != tree::PubkeyLeafEncoding::RawBytes {
+        return Err(anyhow::anyhow!(
+            "Refusing to push root {}: the on-chain config hashes pubkeys as {:?}, synced trees as raw bytes",
+            root_hash,
+            config.pubkey_leaf_encoding
+        ));
+    }
+    if !config.allows_total_leaves(total_leaves) {
+        return Err(anyhow::anyhow!(
+            "Refusing to push root {}: {} subscribers exceed the on-chain cap of {}",
+            root_hash,
+            total_leaves,
+            config.max_total_leaves
+        ));
+    }
+    Ok(())
+}
+
+/// Turn on a new deployment: build the tree from the DB, initialize the on-chain
+/// config with its root, record it in `merkle_state`, and store every proof.
+///
+/// Nothing is written to the DB until the chain accepts the root, so a failed push
+/// leaves no trace. Safe to re-run: an existing config is moved to the root instead,
+/// and if that root is already on-chain and recorded, nothing is sent or recorded twice.
+/// Returns the hex root and the signature that put it on-chain.
+pub async fn bootstrap(pool: &PgPool, chain: &dyn ChainClient) -> Result<(String, Signature)> {
+    let (root_hash, merkle_tree, subscribers) = tree::build_tree_from_db(pool).await?;
+    let root_bytes = tree::MerkleRoot::from_hex(&root_hash)?.to_bytes();
+
+    let signature = match chain.initialize_config_if_needed(root_bytes).await? {
+        Some(signature) => signature,
+        None => {
+            let recorded = updatestate::synced_signature(pool, &root_hash).await?;
+            match recorded {
+                Some(signature) if chain.get_current_root().await? == root_bytes => {
+                    proofstore::regenerate_all_proofs(pool, &merkle_tree, &subscribers).await?;
+                    return Ok((root_hash, Signature::from_str(&signature)?));
+                }
+                _ => {
+                    let config = chain.fetch_config().await?;
+                    check_pushable(&config, &root_hash, subscribers.len())?;
+                    chain.update_merkle_root(root_bytes).await?
+                }
+            }
+        }
+    };
+
+    updatestate::update_merkle_state(
+        pool,
+        &root_hash,
+        subscribers.len(),
+        Some(signature.to_string()),
+    )
+    .await?;
+    proofstore::regenerate_all_proofs(pool, &merkle_tree, &subscribers).await?;
+
+    println!("🚀 Bootstrapped root {}", root_hash);
+    Ok((root_hash, signature))
+}
+
+/// Check the on-chain config at startup, initializing it if missing. Proofs are
+/// served without the chain, so an unreachable RPC isn't fatal: the failure is
+/// logged and `false` returned, for the caller to mark chain sync degraded.
+pub async fn check_chain_at_startup(chain: &dyn ChainClient) -> bool {
+    let checked = async {
+        match chain.initialize_config_if_needed(tree::UNSET_ROOT).await? {
+            None => {
+                println!("   ✅ Config account exists");
+                match chain.get_current_root().await? {
+                    tree::UNSET_ROOT => println!("   Current root: not set"),
+                    root => println!("   Current root: {}", hex::encode(root)),
+                }
+            }
+            Some(signature) => println!("   ✅ Config initialized! Signature: {}", signature),
+        }
+        anyhow::Ok(())
+    }
+    .await;
+    match checked {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("   ⚠️  Solana RPC unavailable, on-chain sync degraded: {}", e);
+            false
+        }
+    }
+}
+
+/// Catch the chain up after it was unreachable or refused the served root: make sure
+/// the config exists, then push the served root unless it is already on-chain
+async fn resume_chain_sync(pool: &PgPool, chain: &dyn ChainClient, state: &AppState) -> Result<()> {
+    chain.initialize_config_if_needed(tree::UNSET_ROOT).await?;
+    let root_hash = state.root_hex().await;
+    let root_bytes = tree::MerkleRoot::from_hex(&root_hash)?.to_bytes();
+    let config = chain.fetch_config().await?;
+    state.record_hash_algo(config.hash_algo);
+    if config.merkle_root != root_bytes {
+        let total_leaves = state.leaf_count().await;
+        check_pushable(&config, &root_hash, total_leaves)?;
+        let signature = chain.update_merkle_root(root_bytes).await?;
+        updatestate::update_merkle_state(
+            pool,
+            &root_hash,
+            total_leaves,
+            Some(signature.to_string()),
+        )
+        .await?;
+    }
+    state.set_chain_degraded(false);
+    println!("✅ On-chain sync resumed at root {}", root_hash);
+    Ok(())
+}
+
+/// Run `iteration` every `interval` until `shutdown` is cancelled.
+///
+/// Cancellation is only observed between iterations, so an in-flight iteration
+/// (including confirming its root update) always runs to completion.
+/// Returns the number of iterations run.
+pub async fn run_sync_loop<F, Fut>(
+    interval: Duration,
+    shutdown: CancellationToken,
+    mut iteration: F,
+) -> usize
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut completed = 0;
+    loop {
+        if let Err(e) = iteration().await {
+            eprintln!("❌ Sync iteration failed: {}", e);
+        }
+        completed += 1;
+
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+    println!("🛑 Sync loop stopped after {} iterations", completed);
+    completed
+}
+
+/// Run a sync pass as of `now_ts` if a subscriber of the served tree has expired by
+/// then; returns whether one ran
+pub async fn sweep_expirations(
+    pools: &AppPools,
+    chain: &dyn ChainClient,
+    state: &AppState,
+    now_ts: i64,
+) -> Result<bool> {
+    match state.next_expiration() {
+        Some(next_expiration) if next_expiration <= now_ts => {
+            println!("⏰ Subscription expired at {}, rebuilding", next_expiration);
+            sync_once_at(pools, chain, state, now_ts).await?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Sleep until the next subscriber expiration and sync right after it, so the pushed
+/// root stops including a subscriber soon after they expire instead of at the next
+/// scheduled pass. Only changes the root for a state that drops expired leaves.
+/// Sleeps at most `max_sleep` before re-reading the next expiration, which a sync pass
+/// may have moved earlier. Returns how many sweeps ran a sync pass.
+pub async fn run_expiration_sweeper(
+    pools: &AppPools,
+    chain: &dyn ChainClient,
+    state: &AppState,
+    max_sleep: Duration,
+    shutdown: CancellationToken,
+) -> usize {
+    let mut sweeps = 0;
+    loop {
+        let wait = match state.next_expiration() {
+            Some(next_expiration) => {
+                let secs = next_expiration.saturating_sub(Utc::now().timestamp());
+                Duration::from_secs(secs.max(1) as u64).min(max_sleep)
+            }
+            None => max_sleep,
+        };
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = tokio::time::sleep(wait) => {}
+        }
+
+        match sweep_expirations(pools, chain, state, Utc::now().timestamp()).await {
+            Ok(true) => sweeps += 1,
+            Ok(false) => {}
+            Err(e) => eprintln!("❌ Expiration sweep failed: {}", e),
+        }
+    }
+    sweeps
+}
+
+/// Cancel `shutdown` on SIGINT (Ctrl-C) or, on Unix, SIGTERM
+pub async fn shutdown_on_signal(shutdown: CancellationToken) {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    println!("\n🛑 Shutdown requested, finishing in-flight work...");
+    shutdown.cancel();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{self, MockChainClient};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[sqlx::test]
+    async fn sync_loop_pushes_changed_root_once(pool: PgPool) -> Result<()> {
+        let expected_root = test_fixtures::seed_subscribers(&pool).await?;
+
+        // Serving a stale single-subscriber tree, with the chain on an old root
+        let stale = test_fixtures::subscribers().into_iter().take(1).collect();
+        let state = AppState::new(tree::build_tree_from_subscribers(stale)?.into());
+        let chain = Arc::new(MockChainClient::with_root([0u8; 32]));
+
+        let pools = AppPools::single(pool.clone());
+        let shutdown = CancellationToken::new();
+        let iteration = {
+            let (pools, chain, state, shutdown) =
+                (pools.clone(), chain.clone(), state.clone(), shutdown.clone());
+            move || {
+                let (pools, chain, state, shutdown) =
+                    (pools.clone(), chain.clone(), state.clone(), shutdown.clone());
+                async move {
+                    let result = sync_once(&pools, &*chain, &state).await;
+                    // Stop once the new root has been pushed and is being served
+                    if !chain.root_updates().is_empty() && state.root_hex().await == expected_root
+                    {
+                        shutdown.cancel();
+                    }
+                    result
+                }
+            }
+        };
+        let completed = tokio::time::timeout(
+            Duration::from_secs(5),
+            run_sync_loop(Duration::from_millis(1), shutdown, iteration),
+        )
+        .await
+        .expect("sync loop did not stop");
+        assert_eq!(completed, 1);
+
+        // A second pass against the unchanged DB pushes nothing
+        sync_once(&pools, &*chain, &state).await?;
+        let expected_bytes: [u8; 32] = hex::decode(expected_root)?.try_into().unwrap();
+        assert_eq!(chain.root_updates(), vec![expected_bytes]);
+        assert_eq!(chain.get_current_root().await?, expected_bytes);
+
+        let (synced,): (bool,) = sqlx::query_as(
+            "SELECT is_synced_on_chain FROM merkle_state WHERE root_hash = $1",
+        )
+        .bind(expected_root)
+        .fetch_one(&pool)
+        .await?;
+        assert!(synced);
+
+        let (total_leaves, tree_depth): (i64, i32) = sqlx::query_as(
+            "SELECT total_leaves, tree_depth FROM merkle_state WHERE root_hash = $1",
+        )
+        .bind(expected_root)
+        .fetch_one(&pool)
+        .await?;
+        assert_eq!(total_leaves, 3);
+        assert_eq!(tree_depth as usize, tree::proof_depth(3));
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn concurrent_sync_passes_push_a_change_once(pool: PgPool) -> Result<()> {
+        let expected_root = test_fixtures::seed_subscribers(&pool).await?;
+        let stale = test_fixtures::subscribers().into_iter().take(1).collect();
+        let state = AppState::new(tree::build_tree_from_subscribers(stale)?.into());
+        let chain = MockChainClient::with_root([0u8; 32]);
+        let pools = AppPools::single(pool.clone());
+
+        // The sync loop and the expiration sweeper landing on the same change
+        let (first, second) = tokio::join!(
+            sync_once(&pools, &chain, &state),
+            sync_once(&pools, &chain, &state)
+        );
+        first?;
+        second?;
+
+        let expected_bytes: [u8; 32] = hex::decode(&expected_root)?.try_into().unwrap();
+        assert_eq!(chain.root_updates(), vec![expected_bytes]);
+        let (states,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM merkle_state")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(states, 1);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn bootstrap_initializes_once_and_reruns_cleanly(pool: PgPool) -> Result<()> {
+        let expected_root = test_fixtures::seed_subscribers(&pool).await?;
+        let chain = MockChainClient::default();
+
+        let (root_hash, signature) = bootstrap(&pool, &chain).await?;
+        assert_eq!(root_hash, expected_root);
+        assert_eq!(hex::encode(chain.get_current_root().await?), expected_root);
+
+        // Re-running sends nothing and records nothing new
+        let (rerun_root, rerun_signature) = bootstrap(&pool, &chain).await?;
+        assert_eq!((rerun_root.as_str(), rerun_signature), (expected_root, signature));
+        assert!(chain.root_updates().is_empty());
+
+        let (states,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM merkle_state")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(states, 1);
+        let (proofs,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM subscriber_proofs")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(proofs, test_fixtures::SUBSCRIBERS.len() as i64);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn bootstrap_records_nothing_when_the_push_fails(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+        let chain = MockChainClient::with_root([0u8; 32]);
+        chain.fail_updates.store(true, Ordering::SeqCst);
+
+        assert!(bootstrap(&pool, &chain).await.is_err());
+
+        let (states,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM merkle_state")
+            .fetch_one(&pool)
+            .await?;
+        assert_eq!(states, 0);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn failed_push_is_recorded_unsynced(pool: PgPool) -> Result<()> {
+        let expected_root = test_fixtures::seed_subscribers(&pool).await?;
+        let stale = test_fixtures::subscribers().into_iter().take(1).collect();
+        let state = AppState::new(tree::build_tree_from_subscribers(stale)?.into());
+        let chain = MockChainClient::with_root([0u8; 32]);
+        chain.fail_updates.store(true, Ordering::SeqCst);
+
+        sync_once(&AppPools::single(pool.clone()), &chain, &state).await?;
+
+        assert!(chain.root_updates().is_empty());
+        assert_eq!(state.root_hex().await, expected_root);
+        let (synced,): (bool,) = sqlx::query_as(
+            "SELECT is_synced_on_chain FROM merkle_state WHERE root_hash = $1",
+        )
+        .bind(expected_root)
+        .fetch_one(&pool)
+        .await?;
+        assert!(!synced);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn roots_over_the_leaf_cap_are_not_pushed(pool: PgPool) -> Result<()> {
+        let expected_root = test_fixtures::seed_subscribers(&pool).await?;
+        let stale = test_fixtures::subscribers().into_iter().take(1).collect();
+        let state = AppState::new(tree::build_tree_from_subscribers(stale)?.into());
+        let chain = MockChainClient::with_root([0u8; 32]);
+        chain.config.lock().unwrap().as_mut().unwrap().max_total_leaves = 2;
+
+        // Three subscribers against a cap of two: nothing is pushed, recorded or served
+        assert!(sync_once(&AppPools::single(pool.clone()), &chain, &state)
+            .await
+            .is_err());
+        assert!(chain.root_updates().is_empty());
+        assert_ne!(state.root_hex().await, expected_root);
+
+        chain.config.lock().unwrap().as_mut().unwrap().max_total_leaves = 3;
+        sync_once(&AppPools::single(pool.clone()), &chain, &state).await?;
+        assert_eq!(hex::encode(chain.get_current_root().await?), expected_root);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn roots_under_another_hash_algo_are_not_pushed(pool: PgPool) -> Result<()> {
+        let expected_root = test_fixtures::seed_subscribers(&pool).await?;
+        let stale = test_fixtures::subscribers().into_iter().take(1).collect();
+        let state = AppState::new(tree::build_tree_from_subscribers(stale)?.into());
+        let chain = MockChainClient::with_root([0u8; 32]);
+        chain.config.lock().unwrap().as_mut().unwrap().hash_algo = tree::HashAlgo::Keccak256;
+
+        // A sha256 root would fail every verification under keccak
+        assert!(sync_once(&AppPools::single(pool.clone()), &chain, &state)
+            .await
+            .is_err());
+        assert!(chain.root_updates().is_empty());
+        assert_ne!(state.root_hex().await, expected_root);
+
+        chain.config.lock().unwrap().as_mut().unwrap().hash_algo = tree::HashAlgo::Sha256;
+        sync_once(&AppPools::single(pool.clone()), &chain, &state).await?;
+        assert_eq!(hex::encode(chain.get_current_root().await?), expected_root);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn unchanged_subscriber_sets_skip_the_rebuild(pool: PgPool) -> Result<()> {
+        let expected_root = test_fixtures::seed_subscribers(&pool).await?;
+        let stale: Vec<_> = test_fixtures::subscribers().into_iter().take(1).collect();
+        let state = AppState::new(tree::build_tree_from_subscribers(stale.clone())?.into());
+        let chain = MockChainClient::with_root([0u8; 32]);
+        let pools = AppPools::single(pool.clone());
+
+        sync_once(&pools, &chain, &state).await?;
+        assert_eq!(state.root_hex().await, expected_root);
+
+        // Serving some other tree would normally be replaced, but the digest says the
+        // subscribers haven't changed, so the pass never rebuilds
+        state
+            .replace_snapshot(tree::build_tree_from_subscribers(stale)?.into())
+            .await;
+        sync_once(&pools, &chain, &state).await?;
+        assert_ne!(state.root_hex().await, expected_root);
+        assert_eq!(chain.root_updates().len(), 1);
+
+        // Any subscriber change rebuilds
+        let (wallet, expiration_ts) = test_fixtures::SUBSCRIBERS[0];
+        sqlx::query("UPDATE subscriber_storage SET expiration_ts = $1 WHERE wallet_address = $2")
+            .bind(expiration_ts + 1)
+            .bind(wallet)
+            .execute(&pool)
+            .await?;
+        sync_once(&pools, &chain, &state).await?;
+        assert_eq!(chain.root_updates().len(), 2);
+        assert_eq!(
+            hex::encode(chain.get_current_root().await?),
+            state.root_hex().await
+        );
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn crossing_an_expiration_triggers_a_rebuild(pool: PgPool) -> Result<()> {
+        test_fixtures::seed_subscribers(&pool).await?;
+        // Everyone active until far ahead, one subscriber expiring first
+        let (expiring, _) = test_fixtures::SUBSCRIBERS[2];
+        let (now, expires_at, later) = (1_900_000_000, 1_900_000_600, 1_900_003_600);
+        sqlx::query("UPDATE subscriber_storage SET expiration_ts = $1")
+            .bind(later)
+            .execute(&pool)
+            .await?;
+        sqlx::query("UPDATE subscriber_storage SET expiration_ts = $1 WHERE wallet_address = $2")
+            .bind(expires_at)
+            .bind(expiring)
+            .execute(&pool)
+            .await?;
+
+        let stale = test_fixtures::subscribers().into_iter().take(1).collect();
+        let state = AppState::new(tree::build_tree_from_subscribers(stale)?.into())
+            .with_drop_expired_leaves(true);
+        let chain = MockChainClient::with_root([0u8; 32]);
+        let pools = AppPools::single(pool.clone());
+
+        sync_once_at(&pools, &chain, &state, now).await?;
+        assert_eq!(state.leaf_count().await, test_fixtures::SUBSCRIBERS.len());
+        assert_eq!(state.next_expiration(), Some(expires_at));
+
+        // Nothing has expired yet
+        assert!(!sweep_expirations(&pools, &chain, &state, expires_at - 1).await?);
+        assert_eq!(chain.root_updates().len(), 1);
+
+        // The expiration passes: the subscriber is dropped and the new root pushed
+        assert!(sweep_expirations(&pools, &chain, &state, expires_at).await?);
+        assert_eq!(chain.root_updates().len(), 2);
+        assert_eq!(state.leaf_count().await, test_fixtures::SUBSCRIBERS.len() - 1);
+        assert_eq!(
+            hex::encode(chain.get_current_root().await?),
+            state.root_hex().await
+        );
+        assert_eq!(state.next_expiration(), Some(later));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sync_loop_exits_after_current_iteration() {
+        let shutdown = CancellationToken::new();
+        let finished = Arc::new(AtomicUsize::new(0));
+
+        let iteration = {
+            let (shutdown, finished) = (shutdown.clone(), finished.clone());
+            move || {
+                let (shutdown, finished) = (shutdown.clone(), finished.clone());
+                async move {
+                    // Shutdown arrives mid-iteration; the iteration must still finish
+                    shutdown.cancel();
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    finished.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                }
+            }
+        };
+
+        let completed = tokio::time::timeout(
+            Duration::from_secs(5),
+            run_sync_loop(Duration::from_millis(1), shutdown, iteration),
+        )
+        .await
+        .expect("sync loop did not stop");
+
+        assert_eq!(completed, 1);
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+    }
+
+    #[sqlx::test]
+    async fn degraded_chain_sync_is_retried_until_the_root_lands(pool: PgPool) -> Result<()> {
+        let expected_root = test_fixtures::seed_subscribers(&pool).await?;
+        let pools = AppPools::single(pool.clone());
+
+        // Started while the RPC was down: serving the DB's tree, which never got pushed
+        let built = tree::build_tree_from_subscribers(test_fixtures::subscribers())?;
+        let state = AppState::new(built.into());
+        state.set_chain_degraded(true);
+        let chain = MockChainClient::with_root([0u8; 32]);
+        chain.fail_updates.store(true, Ordering::SeqCst);
+
+        sync_once(&pools, &chain, &state).await?;
+        assert!(state.chain_degraded());
+        assert!(chain.root_updates().is_empty());
+
+        // Reachable again: the next pass pushes the served root and records it
+        chain.fail_updates.store(false, Ordering::SeqCst);
+        sync_once(&pools, &chain, &state).await?;
+        assert!(!state.chain_degraded());
+        assert_eq!(hex::encode(chain.get_current_root().await?), expected_root);
+        let (synced,): (bool,) = sqlx::query_as(
+            "SELECT is_synced_on_chain FROM merkle_state WHERE root_hash = $1",
+        )
+        .bind(expected_root)
+        .fetch_one(&pool)
+        .await?;
+        assert!(synced);
+
+        // Caught up, so later passes push nothing
+        sync_once(&pools, &chain, &state).await?;
+        assert_eq!(chain.root_updates().len(), 1);
+        Ok(())
+    }
+
+    #[sqlx::test]
+    async fn degraded_chain_sync_never_pushes_a_refused_root(pool: PgPool) -> Result<()> {
+        let expected_root = test_fixtures::seed_subscribers(&pool).await?;
+        let pools = AppPools::single(pool.clone());
+
+        // Started with a root the on-chain cap refused, so it is served unpushed
+        let built = tree::build_tree_from_subscribers(test_fixtures::subscribers())?;
+        let state = AppState::new(built.into());
+        state.set_chain_degraded(true);
+        let chain = MockChainClient::with_root([0u8; 32]);
+        chain.config.lock().unwrap().as_mut().unwrap().max_total_leaves = 2;
+
+        sync_once(&pools, &chain, &state).await?;
+        assert!(state.chain_degraded());
+        assert!(chain.root_updates().is_empty());
+
+        // Raising the cap lets the retry through
+        chain.config.lock().unwrap().as_mut().unwrap().max_total_leaves = 3;
+        sync_once(&pools, &chain, &state).await?;
+        assert!(!state.chain_degraded());
+        assert_eq!(hex::encode(chain.get_current_root().await?), expected_root);
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+//! Metric names the backend records through the `metrics` facade. Recording is a
+//! no-op until a recorder is installed; with the `metrics` feature, `GET /metrics`
+//! serves them in Prometheus text format.
+
+/// Histogram: seconds to build the tree from the database
+pub const TREE_BUILD_SECONDS: &str = "merkle_tree_build_seconds";
+
+/// Counter: `POST /verify` results, labelled `outcome` (a `VerificationOutcome`)
+pub const VERIFY_TOTAL: &str = "merkle_verify_total";
+
+/// Counter: on-chain root pushes from the sync loop, labelled `result`
+/// (`success` or `failure`)
+pub const ROOT_PUSH_TOTAL: &str = "merkle_root_push_total";
+
+/// Install the Prometheus recorder process-wide, returning the handle `GET /metrics`
+/// renders from
+#[cfg(feature = "metrics")]
+pub fn install_prometheus() -> anyhow::Result<metrics_exporter_prometheus::PrometheusHandle> {
+    Ok(metrics_exporter_prometheus::PrometheusBuilder::new().install_recorder()?)
+}
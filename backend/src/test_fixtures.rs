@@ -0,0 +1,142 @@
+//! Test-only fixtures.
+//!
+//! Pair with `#[sqlx::test]`, which hands each test a throwaway database with
+//! the crate's migrations applied, then seed it with a known subscriber set.
+
+use crate::merkle::chain::{ChainClient, OnChainConfig};
+use crate::merkle::tree::Expiration;
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Known subscriber set seeded by `seed_subscribers`
+pub const SUBSCRIBERS: [(&str, i64); 3] = [
+    ("5sHXVAK46po96V9syX6Jhmav9qUagnKydnYwuE57KPat", 1769665096),
+    ("11111111111111111111111111111111", 1700000000),
+    ("AHpuc2M3wbZceufaiE4Q2wyDXh198ymB1SxxpbxCzj3H", 1800000000),
+];
+
+/// Sha256 root of `SUBSCRIBERS`, pinned so leaf-format or ordering changes are caught
+pub const EXPECTED_ROOT: &str = "3d60b36987fa781fd4f3eca87f8e835fa5ae2335917273dfa98f88ea95bbdffd";
+
+/// Logs of two `update_root` transactions, each emitting one `RootUpdated` event
+/// (signed by `[2u8; 32]`): `[0; 32] -> [1; 32]` at 1700000000, then
+/// `[1; 32] -> EXPECTED_ROOT` at 1700000600
+pub const ROOT_UPDATE_LOGS: [[&str; 6]; 2] = [
+    [
+        "Program AHpuc2M3wbZceufaiE4Q2wyDXh198ymB1SxxpbxCzj3H invoke [1]",
+        "Program log: Instruction: UpdateRoot",
+        "Program data: XjUWgI1xYucAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgIA8VNlAAAAAA==",
+        "Program log: Merkle Root updated successfully.",
+        "Program AHpuc2M3wbZceufaiE4Q2wyDXh198ymB1SxxpbxCzj3H consumed 7342 of 200000 compute units",
+        "Program AHpuc2M3wbZceufaiE4Q2wyDXh198ymB1SxxpbxCzj3H success",
+    ],
+    [
+        "Program AHpuc2M3wbZceufaiE4Q2wyDXh198ymB1SxxpbxCzj3H invoke [1]",
+        "Program log: Instruction: UpdateRoot",
+        "Program data: XjUWgI1xYucBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAT1gs2mH+ngf1PPsqH+Og1+lriM1kXJz36mPiOqVu9/9AgICAgICAgICAgICAgICAgICAgICAgICAgICAgICAgJY81NlAAAAAA==",
+        "Program log: Merkle Root updated successfully.",
+        "Program AHpuc2M3wbZceufaiE4Q2wyDXh198ymB1SxxpbxCzj3H consumed 7342 of 200000 compute units",
+        "Program AHpuc2M3wbZceufaiE4Q2wyDXh198ymB1SxxpbxCzj3H success",
+    ],
+];
+
+/// `SUBSCRIBERS` as an in-memory list, for tests that don't need a database
+pub fn subscribers() -> Vec<(String, Expiration)> {
+    SUBSCRIBERS
+        .iter()
+        .map(|(wallet, exp)| (wallet.to_string(), Expiration::new(*exp).unwrap()))
+        .collect()
+}
+
+/// Insert `SUBSCRIBERS` and return the root a tree built from them must have
+pub async fn seed_subscribers(pool: &PgPool) -> Result<&'static str> {
+    for (wallet, expiration_ts) in SUBSCRIBERS {
+        sqlx::query(
+            "INSERT INTO subscriber_storage (wallet_address, expiration_ts, last_updated_at) VALUES ($1, $2, $3)",
+        )
+        .bind(wallet)
+        .bind(expiration_ts)
+        .bind(Utc::now().naive_utc())
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(EXPECTED_ROOT)
+}
+
+/// In-memory `ChainClient`: holds a config, records every root pushed to it, and
+/// can be told to fail root updates
+#[derive(Default)]
+pub struct MockChainClient {
+    pub config: Mutex<Option<OnChainConfig>>,
+    pub root_updates: Mutex<Vec<[u8; 32]>>,
+    pub fail_updates: AtomicBool,
+    /// Signing key reported by `authority`; the default matches `with_root` configs
+    pub authority: Pubkey,
+}
+
+impl MockChainClient {
+    /// A chain whose config is already initialized with `root`
+    pub fn with_root(root: [u8; 32]) -> Self {
+        let mock = Self::default();
+        *mock.config.lock().unwrap() = Some(OnChainConfig::initial(Pubkey::default(), root));
+        mock
+    }
+
+    pub fn root_updates(&self) -> Vec<[u8; 32]> {
+        self.root_updates.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl ChainClient for MockChainClient {
+    fn authority(&self) -> Pubkey {
+        self.authority
+    }
+
+    async fn verify_program_deployed(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn get_current_root(&self) -> Result<[u8; 32]> {
+        Ok(self.fetch_config().await?.merkle_root)
+    }
+
+    async fn update_merkle_root(&self, new_root: [u8; 32]) -> Result<Signature> {
+        if self.fail_updates.load(Ordering::SeqCst) {
+            return Err(anyhow::anyhow!("Mock root update failure"));
+        }
+        let mut config = self.config.lock().unwrap();
+        let config = config
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("Mock config not initialized"))?;
+        config.merkle_root = new_root;
+        self.root_updates.lock().unwrap().push(new_root);
+        Ok(Signature::default())
+    }
+
+    async fn fetch_config(&self) -> Result<OnChainConfig> {
+        self.config
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Mock config not initialized"))
+    }
+
+    async fn initialize_config_if_needed(
+        &self,
+        initial_root: [u8; 32],
+    ) -> Result<Option<Signature>> {
+        let mut config = self.config.lock().unwrap();
+        if config.is_some() {
+            return Ok(None);
+        }
+        *config = Some(OnChainConfig::initial(Pubkey::default(), initial_root));
+        Ok(Some(Signature::default()))
+    }
+}
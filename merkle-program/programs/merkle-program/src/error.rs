@@ -3,8 +3,60 @@ use anchor_lang::prelude::*;
 pub enum SubscriptionError {
     #[msg("You are not authorized to update the root.")]
     Unauthorized,
+    // No longer raised (see the `Proof*` errors); kept so error numbers stay stable
     #[msg("Invalid Merkle proof provided.")]
     InvalidProof,
     #[msg("Your subscription has expired.")]
     SubscriptionExpired,
+    #[msg("Expiration must be a non-negative Unix timestamp.")]
+    InvalidExpiration,
+    #[msg("User does not hold the required token.")]
+    MissingToken,
+    #[msg("Clock skew tolerance must be non-negative.")]
+    InvalidClockSkew,
+    #[msg("Number of batch entries must match the number of user accounts.")]
+    BatchLengthMismatch,
+    #[msg("Verification cache TTL must be non-negative.")]
+    InvalidCacheTtl,
+    #[msg("Authority set must have at most 5 unique keys and a threshold between 1 and its size.")]
+    InvalidAuthoritySet,
+    #[msg("Verified too recently; wait for the minimum interval to pass.")]
+    VerifyTooSoon,
+    #[msg("Minimum verify interval must be non-negative.")]
+    InvalidVerifyInterval,
+    #[msg("Rate-limited verification requires the user's rate limit account.")]
+    MissingRateLimit,
+    #[msg("Proof root is unknown or older than the verify window.")]
+    StaleRoot,
+    #[msg("Verify window must not exceed the stored root history.")]
+    InvalidRootAge,
+    #[msg("Your subscription has not started yet.")]
+    SubscriptionNotStarted,
+    #[msg("Leaves include a start time; pass start_ts.")]
+    MissingStartTime,
+    // No longer raised (see `MalformedProofLength`); kept so error numbers stay stable
+    #[msg("Proof bytes are not a whole number of 32-byte hashes.")]
+    ProofParseFailed,
+    #[msg("Proof does not lead from this user's leaf to the root.")]
+    ProofDidNotMatchRoot,
+    #[msg("Empty proof for a tree with more than one leaf.")]
+    EmptyProofForMultiLeaf,
+    #[msg("No merkle root has been set.")]
+    RootNotSet,
+    #[msg("Relayed verification requires the user's ed25519 signature over the claim.")]
+    MissingUserSignature,
+    #[msg("The signed verification claim has expired.")]
+    ClaimExpired,
+    #[msg("Proof length must be exactly the sibling hashes this leaf index needs.")]
+    MalformedProofLength,
+    #[msg("Tree has more leaves than the configured maximum.")]
+    TreeTooLarge,
+    #[msg("New root is not the append-only tree with just this leaf added.")]
+    NotAnExtension,
+    #[msg("The root was updated too recently; wait out the minimum update interval.")]
+    RootUpdatedTooSoon,
+    #[msg("Minimum update interval must not be negative.")]
+    InvalidUpdateInterval,
+    #[msg("The user is not the account this verification was pinned to.")]
+    UnexpectedUser,
 }
@@ -0,0 +1,10 @@
+use anchor_lang::prelude::*;
+
+/// Emitted by `update_root` so indexers can follow the root history
+#[event]
+pub struct RootUpdated {
+    pub old_root: [u8; 32],
+    pub new_root: [u8; 32],
+    pub updated_by: Pubkey,
+    pub timestamp: i64,
+}
@@ -0,0 +1,207 @@
+use crate::error::SubscriptionError;
+use crate::events::RootUpdated;
+use crate::instructions::authority::require_authorized;
+use crate::instructions::verify::{DoubleSha256Hasher, Keccak256Hasher, Sha256Hasher};
+use crate::state::{AppendLog, HashAlgo, SubscriptionConfig};
+use anchor_lang::prelude::*;
+use rs_merkle::Hasher;
+
+/// Append `new_leaf` (an already hashed leaf) to the append-only tree and make
+/// `new_root` the config root, but only if `new_root` is the old tree with just that
+/// leaf added at the end.
+///
+/// `append_proof` is the old tree's peaks: the roots of its perfect subtrees, left to
+/// right, one per set bit of its leaf count. They must hash to the logged root, and
+/// with the new leaf merged in they must hash to `new_root`. The log starts empty, so
+/// its first append is a single-leaf tree, allowed only while no root is set, since it
+/// would otherwise drop every subscriber under the current root. Once the config root
+/// is replaced through `update_root` (or any other root swap) the log no longer
+/// matches and appends are refused. Like `update_root`, rejected within
+/// `min_update_interval_secs` of the previous update.
+pub fn append_leaf(
+    ctx: Context<AppendLeaf>,
+    new_leaf: [u8; 32],
+    new_root: [u8; 32],
+    append_proof: Vec<u8>,
+) -> Result<()> {
+    require_authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    let now = Clock::get()?.unix_timestamp;
+    let config = &mut ctx.accounts.config;
+    let log = &mut ctx.accounts.append_log;
+    require!(
+        config.root_update_allowed(now),
+        SubscriptionError::RootUpdatedTooSoon
+    );
+
+    let leaf_count = log.leaf_count;
+    require!(
+        config.max_total_leaves == 0 || leaf_count < config.max_total_leaves,
+        SubscriptionError::TreeTooLarge
+    );
+    // The log's root, or no root at all before the first append
+    let expected_root = if leaf_count > 0 { log.root } else { [0u8; 32] };
+    require!(
+        config.merkle_root == expected_root,
+        SubscriptionError::NotAnExtension
+    );
+
+    let extends = match config.hash_algo {
+        HashAlgo::Sha256 => {
+            extends::<Sha256Hasher>(&append_proof, leaf_count, log.root, new_leaf, new_root)?
+        }
+        HashAlgo::Keccak256 => {
+            extends::<Keccak256Hasher>(&append_proof, leaf_count, log.root, new_leaf, new_root)?
+        }
+        HashAlgo::DoubleSha256 => {
+            extends::<DoubleSha256Hasher>(&append_proof, leaf_count, log.root, new_leaf, new_root)?
+        }
+    };
+    require!(extends, SubscriptionError::NotAnExtension);
+
+    let old_root = config.merkle_root;
+    config.push_root(new_root);
+    config.last_updated = now;
+    log.leaf_count = leaf_count + 1;
+    log.root = new_root;
+    log.bump = ctx.bumps.append_log;
+    emit!(RootUpdated {
+        old_root,
+        new_root,
+        updated_by: ctx.accounts.authority.key(),
+        timestamp: now,
+    });
+    msg!("Leaf {} appended.", leaf_count);
+    Ok(())
+}
+
+/// Whether `peaks` are the peaks of a `leaf_count`-leaf tree with root `old_root`, and
+/// appending `new_leaf` to that tree gives `new_root`
+fn extends<H: Hasher<Hash = [u8; 32]>>(
+    peaks: &[u8],
+    leaf_count: u64,
+    old_root: [u8; 32],
+    new_leaf: [u8; 32],
+    new_root: [u8; 32],
+) -> Result<bool> {
+    require!(
+        peaks.len() == leaf_count.count_ones() as usize * 32,
+        SubscriptionError::MalformedProofLength
+    );
+    let mut peaks: Vec<[u8; 32]> = peaks
+        .chunks_exact(32)
+        .map(|peak| peak.try_into().unwrap())
+        .collect();
+    if leaf_count > 0 && root_from_peaks::<H>(&peaks) != old_root {
+        return Ok(false);
+    }
+
+    append_to_peaks::<H>(&mut peaks, leaf_count, new_leaf);
+    Ok(root_from_peaks::<H>(&peaks) == new_root)
+}
+
+/// Add a leaf to the peaks of a `leaf_count`-leaf tree. Like incrementing a binary
+/// counter: each trailing set bit is a peak the same size as the carry, merged into it.
+fn append_to_peaks<H: Hasher<Hash = [u8; 32]>>(
+    peaks: &mut Vec<[u8; 32]>,
+    leaf_count: u64,
+    leaf: [u8; 32],
+) {
+    let mut carry = leaf;
+    for _ in 0..leaf_count.trailing_ones() {
+        let left = peaks.pop().expect("one peak per set bit");
+        carry = H::concat_and_hash(&left, Some(&carry));
+    }
+    peaks.push(carry);
+}
+
+/// Root of the tree with these peaks. A level's odd node is promoted unchanged, so
+/// smaller peaks on the right fold into the larger ones on their left.
+fn root_from_peaks<H: Hasher<Hash = [u8; 32]>>(peaks: &[[u8; 32]]) -> [u8; 32] {
+    let mut peaks = peaks.iter().rev();
+    let last = *peaks.next().expect("a non-empty tree has a peak");
+    peaks.fold(last, |right, left| H::concat_and_hash(left, Some(&right)))
+}
+
+#[derive(Accounts)]
+pub struct AppendLeaf<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, SubscriptionConfig>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + AppendLog::INIT_SPACE,
+        seeds = [b"append_log"],
+        bump
+    )]
+    pub append_log: Account<'info, AppendLog>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rs_merkle::MerkleTree;
+
+    fn leaf(n: u8) -> [u8; 32] {
+        Sha256Hasher::hash(&[n])
+    }
+
+    fn root_of(count: u8) -> [u8; 32] {
+        let leaves: Vec<[u8; 32]> = (0..count).map(leaf).collect();
+        MerkleTree::<Sha256Hasher>::from_leaves(&leaves)
+            .root()
+            .unwrap()
+    }
+
+    #[test]
+    fn appends_match_the_full_tree_and_swaps_are_rejected() {
+        let mut peaks = Vec::new();
+        for count in 0..20u8 {
+            let proof: Vec<u8> = peaks.concat();
+            let old_root = if count == 0 {
+                [0u8; 32]
+            } else {
+                root_of(count)
+            };
+            let new_root = root_of(count + 1);
+
+            // Appending the next leaf gives the rs_merkle root of the longer tree
+            assert!(
+                extends::<Sha256Hasher>(&proof, count as u64, old_root, leaf(count), new_root)
+                    .unwrap()
+            );
+            // A root that isn't the old tree plus this leaf is refused
+            assert!(
+                !extends::<Sha256Hasher>(&proof, count as u64, old_root, leaf(99), new_root)
+                    .unwrap()
+            );
+            if count > 0 {
+                assert!(!extends::<Sha256Hasher>(
+                    &proof,
+                    count as u64,
+                    [7u8; 32],
+                    leaf(count),
+                    new_root
+                )
+                .unwrap());
+            }
+
+            append_to_peaks::<Sha256Hasher>(&mut peaks, count as u64, leaf(count));
+            assert_eq!(peaks.len(), (count as u64 + 1).count_ones() as usize);
+        }
+
+        // Peaks must be exactly one hash per set bit of the leaf count
+        assert!(extends::<Sha256Hasher>(&[0u8; 32], 2, root_of(2), leaf(2), root_of(3)).is_ok());
+        assert!(extends::<Sha256Hasher>(&[0u8; 64], 2, root_of(2), leaf(2), root_of(3)).is_err());
+    }
+}
@@ -0,0 +1,25 @@
+use crate::error::SubscriptionError;
+use crate::state::SubscriptionConfig;
+use anchor_lang::prelude::*;
+
+/// Generalized `has_one = authority`: the named authority plus any signing
+/// remaining accounts must satisfy the config's authority set
+pub fn require_authorized(
+    config: &SubscriptionConfig,
+    authority: &Signer,
+    remaining_accounts: &[AccountInfo],
+) -> Result<()> {
+    let signers: Vec<Pubkey> = std::iter::once(authority.key())
+        .chain(
+            remaining_accounts
+                .iter()
+                .filter(|account| account.is_signer)
+                .map(|account| account.key()),
+        )
+        .collect();
+    require!(
+        config.is_authorized(&signers),
+        SubscriptionError::Unauthorized
+    );
+    Ok(())
+}
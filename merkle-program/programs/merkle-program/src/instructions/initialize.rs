@@ -1,11 +1,33 @@
-use crate::state::SubscriptionConfig;
+use crate::state::{HashAlgo, PubkeyLeafEncoding, SubscriptionConfig};
 use anchor_lang::prelude::*;
 
-pub fn initialize(ctx: Context<Initialize>, initial_root: [u8; 32]) -> Result<()> {
+/// `chain_domain` tags the network this deployment serves (e.g. a hash of the
+/// cluster name) so the backend can refuse roots meant for another network
+pub fn initialize(
+    ctx: Context<Initialize>,
+    initial_root: [u8; 32],
+    chain_domain: [u8; 8],
+) -> Result<()> {
     let config = &mut ctx.accounts.config;
     config.authority = ctx.accounts.authority.key();
     config.merkle_root = initial_root;
     config.bump = ctx.bumps.config;
+    config.required_mint = None;
+    config.clock_skew_secs = 0;
+    config.hash_algo = HashAlgo::Sha256;
+    config.verification_cache_ttl_secs = 0;
+    config.authorities = Vec::new();
+    config.threshold = 0;
+    config.expiration_in_leaf = true;
+    config.min_interval_secs = 0;
+    config.chain_domain = chain_domain;
+    config.start_in_leaf = false;
+    config.verbose_logging = true;
+    config.max_total_leaves = 0;
+    config.lifetime_root = [0u8; 32];
+    config.last_updated = 0;
+    config.min_update_interval_secs = 0;
+    config.pubkey_leaf_encoding = PubkeyLeafEncoding::RawBytes;
     Ok(())
 }
 
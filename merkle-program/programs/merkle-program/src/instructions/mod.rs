@@ -1,7 +1,20 @@
+pub mod append_leaf;
+pub mod authority;
 pub mod initialize;
+pub mod realloc_config;
+pub mod update_config;
 pub mod update_root;
 pub mod verify;
+pub mod verify_batch;
+pub mod verify_lifetime;
+pub mod verify_relayed;
 
+pub use append_leaf::*;
 pub use initialize::*;
+pub use realloc_config::*;
+pub use update_config::*;
 pub use update_root::*;
 pub use verify::*;
+pub use verify_batch::*;
+pub use verify_lifetime::*;
+pub use verify_relayed::*;
@@ -0,0 +1,128 @@
+use crate::instructions::authority::require_authorized;
+use crate::state::SubscriptionConfig;
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+// Size of the first layout with `expiration_in_leaf` (its final byte). Smaller configs
+// predate it, when every leaf included the expiration; zero-extending them would
+// otherwise silently switch them to allowlist leaves.
+const EXPIRATION_IN_LEAF_MIN_LEN: usize = 8 + 281;
+// Size of the first layout with `verbose_logging`, whose zero default would turn
+// success logs off for configs that always had them
+const VERBOSE_LOGGING_MIN_LEN: usize = 8 + 557;
+
+/// Grow a config created under an older, smaller layout to the current size.
+///
+/// The new trailing bytes are zeroed, which decodes to the same defaults
+/// `initialize` sets (except `expiration_in_leaf` and `verbose_logging`, which
+/// are set explicitly), and the authority pays the extra rent. A config that is
+/// already current is left as is.
+pub fn realloc_config(ctx: Context<ReallocConfig>) -> Result<()> {
+    let config_info = ctx.accounts.config.to_account_info();
+    let old_len = config_info.data_len();
+    let new_len = 8 + SubscriptionConfig::INIT_SPACE;
+
+    if old_len < new_len {
+        let rent_due = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(config_info.lamports());
+        if rent_due > 0 {
+            transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    Transfer {
+                        from: ctx.accounts.authority.to_account_info(),
+                        to: config_info.clone(),
+                    },
+                ),
+                rent_due,
+            )?;
+        }
+        config_info.resize(new_len)?;
+    }
+
+    // Only after resizing is the account decodable under the current layout;
+    // failing the authority check reverts the resize along with everything else
+    let mut config = SubscriptionConfig::try_deserialize(&mut &config_info.try_borrow_data()?[..])?;
+    require_authorized(&config, &ctx.accounts.authority, ctx.remaining_accounts)?;
+
+    if old_len < VERBOSE_LOGGING_MIN_LEN {
+        if old_len < EXPIRATION_IN_LEAF_MIN_LEN {
+            config.expiration_in_leaf = true;
+        }
+        config.verbose_logging = true;
+        config.try_serialize(&mut &mut config_info.try_borrow_mut_data()?[..])?;
+    }
+
+    msg!(
+        "Config resized from {} to {} bytes.",
+        old_len,
+        config_info.data_len()
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ReallocConfig<'info> {
+    /// CHECK: may still be at an older layout that `Account` can't decode. The PDA
+    /// address and owner are checked here; discriminator and authority in the handler.
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        owner = crate::ID
+    )]
+    pub config: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{HashAlgo, PubkeyLeafEncoding};
+
+    #[test]
+    fn zero_extended_legacy_config_decodes_with_defaults() {
+        // Original layout: discriminator + authority + merkle_root + bump
+        let authority = Pubkey::new_unique();
+        let mut data = SubscriptionConfig::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(authority.as_ref());
+        data.extend_from_slice(&[7u8; 32]);
+        data.push(254);
+        data.resize(8 + SubscriptionConfig::INIT_SPACE, 0);
+
+        let mut config = SubscriptionConfig::try_deserialize(&mut &data[..]).unwrap();
+        assert_eq!(config.authority, authority);
+        assert_eq!(config.merkle_root, [7u8; 32]);
+        assert_eq!(config.bump, 254);
+        assert_eq!(config.required_mint, None);
+        assert_eq!(config.hash_algo, HashAlgo::Sha256);
+        assert!(config.authorities.is_empty());
+        assert_eq!(config.threshold, 0);
+        // Zero-extended as allowlist leaves; `realloc_config` restores `true`
+        assert!(!config.expiration_in_leaf);
+        assert_eq!(config.chain_domain, [0u8; 8]);
+        // Empty root history: only the current root is accepted
+        assert_eq!(config.max_verify_root_age, 0);
+        assert_eq!(config.root_age(&[7u8; 32]), Some(0));
+        assert_eq!(config.root_age(&[0u8; 32]), None);
+        assert!(!config.start_in_leaf);
+        // Also restored to `true` by `realloc_config`
+        assert!(!config.verbose_logging);
+        assert_eq!(config.max_total_leaves, 0);
+        assert_eq!(config.lifetime_root, [0u8; 32]);
+        assert_eq!(config.last_updated, 0);
+        assert_eq!(config.min_update_interval_secs, 0);
+        assert_eq!(config.pubkey_leaf_encoding, PubkeyLeafEncoding::RawBytes);
+
+        // New fields can be written and still fit the resized account
+        config.clock_skew_secs = 30;
+        config.authorities = vec![Pubkey::new_unique(); crate::state::MAX_AUTHORITIES];
+        config.threshold = 3;
+        let mut resized = Vec::new();
+        config.try_serialize(&mut resized).unwrap();
+        assert!(resized.len() <= data.len());
+    }
+}
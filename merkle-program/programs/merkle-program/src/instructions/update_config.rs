@@ -0,0 +1,274 @@
+use crate::error::SubscriptionError;
+use crate::instructions::authority::require_authorized;
+use crate::state::{
+    HashAlgo, PubkeyLeafEncoding, SubscriptionConfig, MAX_AUTHORITIES, ROOT_HISTORY_LEN,
+};
+use anchor_lang::prelude::*;
+
+/// Set (or clear with `None`) the mint a user must hold to pass verification
+pub fn set_required_mint(ctx: Context<UpdateConfig>, required_mint: Option<Pubkey>) -> Result<()> {
+    require_authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    let config = &mut ctx.accounts.config;
+    config.required_mint = required_mint;
+    msg!("Required mint updated.");
+    Ok(())
+}
+
+/// Set how many seconds past expiration a subscription is still accepted
+pub fn set_clock_skew(ctx: Context<UpdateConfig>, clock_skew_secs: i64) -> Result<()> {
+    require_authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    require!(clock_skew_secs >= 0, SubscriptionError::InvalidClockSkew);
+    let config = &mut ctx.accounts.config;
+    config.clock_skew_secs = clock_skew_secs;
+    msg!("Clock skew updated to {}s.", clock_skew_secs);
+    Ok(())
+}
+
+/// Switch the hash algorithm and the root computed under it in one step,
+/// so there is never a window where the two disagree. Root history is cleared,
+/// since its roots were built under the old hash.
+pub fn migrate_hash_algo(
+    ctx: Context<UpdateConfig>,
+    hash_algo: HashAlgo,
+    new_root: [u8; 32],
+) -> Result<()> {
+    require_authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    let config = &mut ctx.accounts.config;
+    config.hash_algo = hash_algo;
+    config.reset_root(new_root);
+    msg!("Hash algorithm migrated to {:?}.", hash_algo);
+    Ok(())
+}
+
+/// Switch between subscription leaves `Hash(pubkey + expiration)` and static
+/// allowlist leaves `Hash(pubkey)`, together with a root built the same way.
+/// Root history is cleared, since its roots were built under the other layout.
+pub fn set_expiration_in_leaf(
+    ctx: Context<UpdateConfig>,
+    expiration_in_leaf: bool,
+    new_root: [u8; 32],
+) -> Result<()> {
+    require_authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    let config = &mut ctx.accounts.config;
+    config.expiration_in_leaf = expiration_in_leaf;
+    config.reset_root(new_root);
+    msg!("Expiration in leaf set to {}.", expiration_in_leaf);
+    Ok(())
+}
+
+/// Switch how the user's pubkey is hashed into leaves (raw bytes or base58 text),
+/// together with a root built the same way. Root history is cleared, since its roots
+/// were built under the other encoding.
+pub fn set_pubkey_leaf_encoding(
+    ctx: Context<UpdateConfig>,
+    pubkey_leaf_encoding: PubkeyLeafEncoding,
+    new_root: [u8; 32],
+) -> Result<()> {
+    require_authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    let config = &mut ctx.accounts.config;
+    config.pubkey_leaf_encoding = pubkey_leaf_encoding;
+    config.reset_root(new_root);
+    msg!("Pubkey leaf encoding set to {:?}.", pubkey_leaf_encoding);
+    Ok(())
+}
+
+/// Switch leaves to `Hash(pubkey + start + expiration)`, or back to leaves without a
+/// start time, together with a root built the same way. Root history is cleared.
+pub fn set_start_in_leaf(
+    ctx: Context<UpdateConfig>,
+    start_in_leaf: bool,
+    new_root: [u8; 32],
+) -> Result<()> {
+    require_authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    let config = &mut ctx.accounts.config;
+    config.start_in_leaf = start_in_leaf;
+    config.reset_root(new_root);
+    msg!("Start in leaf set to {}.", start_in_leaf);
+    Ok(())
+}
+
+/// Set how long a successful verification is cached per user (0 disables caching)
+pub fn set_verification_cache_ttl(ctx: Context<UpdateConfig>, ttl_secs: i64) -> Result<()> {
+    require_authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    require!(ttl_secs >= 0, SubscriptionError::InvalidCacheTtl);
+    let config = &mut ctx.accounts.config;
+    config.verification_cache_ttl_secs = ttl_secs;
+    msg!("Verification cache TTL updated to {}s.", ttl_secs);
+    Ok(())
+}
+
+/// Set the minimum seconds between two verifications by the same user (0 disables)
+pub fn set_min_interval(ctx: Context<UpdateConfig>, min_interval_secs: i64) -> Result<()> {
+    require_authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    require!(
+        min_interval_secs >= 0,
+        SubscriptionError::InvalidVerifyInterval
+    );
+    let config = &mut ctx.accounts.config;
+    config.min_interval_secs = min_interval_secs;
+    msg!("Minimum verify interval updated to {}s.", min_interval_secs);
+    Ok(())
+}
+
+/// Cap the `total_leaves` verification accepts, bounding proof and transaction size
+/// (0 removes the cap)
+pub fn set_max_total_leaves(ctx: Context<UpdateConfig>, max_total_leaves: u64) -> Result<()> {
+    require_authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    let config = &mut ctx.accounts.config;
+    config.max_total_leaves = max_total_leaves;
+    msg!("Maximum total leaves set to {}.", max_total_leaves);
+    Ok(())
+}
+
+/// Set the root of the lifetime-member tree checked by `verify_lifetime`, whose
+/// leaves are `Hash(pubkey)` and never expire (all zeros turns it off)
+pub fn set_lifetime_root(ctx: Context<UpdateConfig>, lifetime_root: [u8; 32]) -> Result<()> {
+    require_authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    let config = &mut ctx.accounts.config;
+    config.lifetime_root = lifetime_root;
+    msg!("Lifetime root updated.");
+    Ok(())
+}
+
+/// Set the minimum seconds between two `update_root` calls (0 disables), a backstop
+/// against a runaway sync loop
+pub fn set_min_update_interval(
+    ctx: Context<UpdateConfig>,
+    min_update_interval_secs: i64,
+) -> Result<()> {
+    require_authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    require!(
+        min_update_interval_secs >= 0,
+        SubscriptionError::InvalidUpdateInterval
+    );
+    let config = &mut ctx.accounts.config;
+    config.min_update_interval_secs = min_update_interval_secs;
+    msg!(
+        "Minimum root update interval updated to {}s.",
+        min_update_interval_secs
+    );
+    Ok(())
+}
+
+/// Turn verification success logs on or off; failures are always logged
+pub fn set_verbose_logging(ctx: Context<UpdateConfig>, verbose_logging: bool) -> Result<()> {
+    require_authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    let config = &mut ctx.accounts.config;
+    config.verbose_logging = verbose_logging;
+    msg!("Verbose logging set to {}.", verbose_logging);
+    Ok(())
+}
+
+/// Set how many root updates back a proof's root may be when the user names it
+/// (0 accepts only the current root)
+pub fn set_max_verify_root_age(ctx: Context<UpdateConfig>, max_verify_root_age: u8) -> Result<()> {
+    require_authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    require!(
+        max_verify_root_age as usize <= ROOT_HISTORY_LEN,
+        SubscriptionError::InvalidRootAge
+    );
+    let config = &mut ctx.accounts.config;
+    config.max_verify_root_age = max_verify_root_age;
+    msg!("Verify window updated to {} roots.", max_verify_root_age);
+    Ok(())
+}
+
+/// Replace the M-of-N authority set; an empty set with threshold 0 falls
+/// back to the single `authority` key
+pub fn set_authorities(
+    ctx: Context<UpdateConfig>,
+    authorities: Vec<Pubkey>,
+    threshold: u8,
+) -> Result<()> {
+    require_authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    let unique = authorities
+        .iter()
+        .enumerate()
+        .all(|(i, key)| !authorities[..i].contains(key));
+    let valid = if authorities.is_empty() {
+        threshold == 0
+    } else {
+        threshold >= 1 && threshold as usize <= authorities.len()
+    };
+    require!(
+        unique && valid && authorities.len() <= MAX_AUTHORITIES,
+        SubscriptionError::InvalidAuthoritySet
+    );
+    let config = &mut ctx.accounts.config;
+    config.authorities = authorities;
+    config.threshold = threshold;
+    msg!(
+        "Authority set updated: {} of {}.",
+        threshold,
+        config.authorities.len()
+    );
+    Ok(())
+}
+
+/// Setters accept co-signing authorities as signer remaining accounts
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, SubscriptionConfig>,
+    pub authority: Signer<'info>,
+}
@@ -1,10 +1,32 @@
 use crate::error::SubscriptionError;
+use crate::events::RootUpdated;
+use crate::instructions::authority::require_authorized;
 use crate::state::SubscriptionConfig;
 use anchor_lang::prelude::*;
 
+/// Co-signing authorities beyond `authority` are passed as signer remaining accounts.
+/// Rejected within `min_update_interval_secs` of the previous update.
 pub fn update_root(ctx: Context<UpdateRoot>, new_root: [u8; 32]) -> Result<()> {
+    require_authorized(
+        &ctx.accounts.config,
+        &ctx.accounts.authority,
+        ctx.remaining_accounts,
+    )?;
+    let now = Clock::get()?.unix_timestamp;
     let config = &mut ctx.accounts.config;
-    config.merkle_root = new_root;
+    require!(
+        config.root_update_allowed(now),
+        SubscriptionError::RootUpdatedTooSoon
+    );
+    let old_root = config.merkle_root;
+    config.push_root(new_root);
+    config.last_updated = now;
+    emit!(RootUpdated {
+        old_root,
+        new_root,
+        updated_by: ctx.accounts.authority.key(),
+        timestamp: now,
+    });
     msg!("Merkle Root updated successfully.");
     Ok(())
 }
@@ -13,7 +35,6 @@ pub fn update_root(ctx: Context<UpdateRoot>, new_root: [u8; 32]) -> Result<()> {
 pub struct UpdateRoot<'info> {
     #[account(
         mut,
-        has_one = authority @ SubscriptionError::Unauthorized,
         seeds = [b"config"],
         bump = config.bump
     )]
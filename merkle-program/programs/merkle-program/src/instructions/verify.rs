@@ -1,6 +1,7 @@
 use crate::error::SubscriptionError;
-use crate::state::SubscriptionConfig;
+use crate::state::{HashAlgo, SubscriptionConfig, VerificationReceipt, VerifyRateLimit};
 use anchor_lang::prelude::*;
+use anchor_spl::token::TokenAccount;
 use rs_merkle::{Hasher, MerkleProof};
 use sha2::{Digest, Sha256};
 
@@ -16,46 +17,347 @@ impl Hasher for Sha256Hasher {
     }
 }
 
+#[derive(Clone)]
+pub struct Keccak256Hasher {}
+
+impl Hasher for Keccak256Hasher {
+    type Hash = [u8; 32];
+    fn hash(data: &[u8]) -> [u8; 32] {
+        solana_keccak_hasher::hash(data).to_bytes()
+    }
+}
+
+/// `sha256(sha256(data))`, as used by Bitcoin-derived tooling
+#[derive(Clone)]
+pub struct DoubleSha256Hasher {}
+
+impl Hasher for DoubleSha256Hasher {
+    type Hash = [u8; 32];
+    fn hash(data: &[u8]) -> [u8; 32] {
+        Sha256Hasher::hash(&Sha256Hasher::hash(data))
+    }
+}
+
+/// Subscription expiration as a Unix timestamp, guaranteed non-negative
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Expiration(i64);
+
+impl Expiration {
+    pub fn new(timestamp: i64) -> Result<Self> {
+        require!(timestamp >= 0, SubscriptionError::InvalidExpiration);
+        Ok(Self(timestamp))
+    }
+
+    pub fn as_i64(self) -> i64 {
+        self.0
+    }
+
+    pub fn to_le_bytes(self) -> [u8; 8] {
+        self.0.to_le_bytes()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn verify_subscription(
     ctx: Context<VerifySubscription>,
     proof_bytes: Vec<u8>,
     expiration: i64,
     leaf_index: usize,
     total_leaves: usize,
+    leaf_salt: Option<[u8; 32]>,
+    proof_root: Option<[u8; 32]>,
+    start_ts: Option<i64>,
+    expected_user: Option<Pubkey>,
 ) -> Result<()> {
     let user_key = ctx.accounts.user.key();
+    require_expected_user(&user_key, expected_user)?;
     let clock = Clock::get()?;
+    let cache_ttl_secs = ctx.accounts.config.verification_cache_ttl_secs;
+    let min_interval_secs = ctx.accounts.config.min_interval_secs;
 
-    // 1. Check expiration FIRST
-    require!(
-        expiration > clock.unix_timestamp,
-        SubscriptionError::SubscriptionExpired
+    // Rate limit every verification, cached or not
+    if min_interval_secs > 0 {
+        let rate_limit = ctx
+            .accounts
+            .rate_limit
+            .as_mut()
+            .ok_or(SubscriptionError::MissingRateLimit)?;
+        require!(
+            clock.unix_timestamp
+                >= rate_limit
+                    .last_verified_at
+                    .saturating_add(min_interval_secs),
+            SubscriptionError::VerifyTooSoon
+        );
+        rate_limit.user = user_key;
+        rate_limit.last_verified_at = clock.unix_timestamp;
+        rate_limit.bump = ctx
+            .bumps
+            .rate_limit
+            .ok_or(SubscriptionError::MissingRateLimit)?;
+    }
+
+    // Token gate: checked on every call, so a receipt never outlives the holding
+    if let Some(required_mint) = ctx.accounts.config.required_mint {
+        require_gating_token(
+            ctx.accounts.token_account.as_deref(),
+            &user_key,
+            &required_mint,
+        )?;
+    }
+
+    // A still-valid receipt issued under the current root skips the proof entirely
+    if cache_ttl_secs > 0 {
+        if let Some(receipt) = &ctx.accounts.receipt {
+            if receipt.verified_until > clock.unix_timestamp
+                && receipt.merkle_root == ctx.accounts.config.merkle_root
+            {
+                if ctx.accounts.config.verbose_logging {
+                    msg!("Cached verification for user: {}", user_key);
+                }
+                return Ok(());
+            }
+        }
+    }
+
+    check_membership(
+        &ctx.accounts.config,
+        &user_key,
+        MembershipClaim {
+            proof_bytes: &proof_bytes,
+            expiration,
+            leaf_salt,
+            leaf_index,
+            total_leaves,
+            proof_root,
+            start_ts,
+        },
+        clock.unix_timestamp,
+    )?;
+
+    // Cache the result, never past the subscription's own expiration
+    if cache_ttl_secs > 0 {
+        let mut verified_until = clock.unix_timestamp.saturating_add(cache_ttl_secs);
+        if ctx.accounts.config.expiration_in_leaf {
+            verified_until = verified_until.min(expiration);
+        }
+        if let (Some(receipt), Some(bump)) = (&mut ctx.accounts.receipt, ctx.bumps.receipt) {
+            receipt.user = user_key;
+            receipt.merkle_root = ctx.accounts.config.merkle_root;
+            receipt.verified_until = verified_until;
+            receipt.bump = bump;
+        }
+    }
+
+    // Failures still log through their error; success logs cost compute on every call
+    if ctx.accounts.config.verbose_logging {
+        msg!("Verification successful for user: {}", user_key);
+    }
+    Ok(())
+}
+
+/// A calling context can pin verification to one account; `None` accepts any member
+pub(crate) fn require_expected_user(
+    user_key: &Pubkey,
+    expected_user: Option<Pubkey>,
+) -> Result<()> {
+    if let Some(expected_user) = expected_user {
+        require_keys_eq!(*user_key, expected_user, SubscriptionError::UnexpectedUser);
+    }
+    Ok(())
+}
+
+/// Token gate: the user must hold a nonzero balance of `required_mint`
+fn require_gating_token(
+    token_account: Option<&TokenAccount>,
+    user_key: &Pubkey,
+    required_mint: &Pubkey,
+) -> Result<()> {
+    let token_account = token_account.ok_or(SubscriptionError::MissingToken)?;
+    require_keys_eq!(
+        token_account.owner,
+        *user_key,
+        SubscriptionError::MissingToken
     );
+    require_keys_eq!(
+        token_account.mint,
+        *required_mint,
+        SubscriptionError::MissingToken
+    );
+    require!(token_account.amount > 0, SubscriptionError::MissingToken);
+    Ok(())
+}
+
+/// What a user claims about their leaf, as passed to either verify instruction
+pub(crate) struct MembershipClaim<'a> {
+    pub proof_bytes: &'a [u8],
+    pub expiration: i64,
+    pub leaf_salt: Option<[u8; 32]>,
+    pub leaf_index: usize,
+    pub total_leaves: usize,
+    /// Root the proof was built against; `None` means the current root
+    pub proof_root: Option<[u8; 32]>,
+    /// Subscription start, required when the config's `start_in_leaf` is on
+    pub start_ts: Option<i64>,
+}
 
-    // 2. Reconstruct leaf: Hash(pubkey_bytes + expiration_bytes)
-    let mut leaf_data = Vec::with_capacity(40);
-    leaf_data.extend_from_slice(&user_key.to_bytes());
-    leaf_data.extend_from_slice(&expiration.to_le_bytes());
-    let leaf = Sha256Hasher::hash(&leaf_data);
-
-    // 3. Parse the merkle proof
-    let proof = MerkleProof::<Sha256Hasher>::try_from(proof_bytes.as_slice())
-        .map_err(|_| SubscriptionError::InvalidProof)?;
-
-    // 4. Verify proof against stored root
-    let is_valid = proof.verify(
-        ctx.accounts.config.merkle_root,
-        &[leaf_index],
-        &[leaf],
+/// Expiration, leaf reconstruction and proof checks shared by every verify path
+pub(crate) fn check_membership(
+    config: &SubscriptionConfig,
+    user_key: &Pubkey,
+    claim: MembershipClaim,
+    now: i64,
+) -> Result<()> {
+    let MembershipClaim {
+        proof_bytes,
+        expiration,
+        leaf_salt,
+        leaf_index,
         total_leaves,
+        proof_root,
+        start_ts,
+    } = claim;
+
+    require!(
+        config.max_total_leaves == 0 || total_leaves as u64 <= config.max_total_leaves,
+        SubscriptionError::TreeTooLarge
     );
 
-    require!(is_valid, SubscriptionError::InvalidProof);
+    // A named root must be current or within the configured verify window
+    let root = match proof_root {
+        Some(root) => {
+            let age = config.root_age(&root).ok_or(SubscriptionError::StaleRoot)?;
+            require!(
+                age <= config.max_verify_root_age as usize,
+                SubscriptionError::StaleRoot
+            );
+            root
+        }
+        None => config.merkle_root,
+    };
+    // All zeros is the "no root yet" sentinel (and fills unused root history slots);
+    // never trust it, whatever proof comes with it
+    require!(root != [0u8; 32], SubscriptionError::RootNotSet);
+
+    let mut leaf_data = Vec::with_capacity(80);
+    leaf_data.extend_from_slice(&config.pubkey_leaf_encoding.encode(user_key));
+
+    // Time-window memberships also commit to when the subscription began
+    if config.start_in_leaf {
+        let start_ts = start_ts.ok_or(SubscriptionError::MissingStartTime)?;
+        require!(start_ts <= now, SubscriptionError::SubscriptionNotStarted);
+        leaf_data.extend_from_slice(&start_ts.to_le_bytes());
+    }
+
+    // Static allowlists carry no expiration: the argument is ignored entirely
+    if config.expiration_in_leaf {
+        // 0. Reject negative expirations before they can reach the leaf
+        let expiration = Expiration::new(expiration)?;
+
+        // 1. Check expiration FIRST, tolerating the configured validator clock skew
+        require!(
+            expiration.as_i64().saturating_add(config.clock_skew_secs) > now,
+            SubscriptionError::SubscriptionExpired
+        );
+        leaf_data.extend_from_slice(&expiration.to_le_bytes());
+    }
+
+    // 2. Reconstruct leaf: Hash(pubkey [+ start_bytes] [+ expiration_bytes] [+ salt]), the
+    // pubkey as raw bytes or base58 text per the config's `pubkey_leaf_encoding`
+    // The leaf is always derived from the signer, never taken from instruction data,
+    // so a caller can't present another member's leaf. The optional salt only
+    // blinds the leaf; it can't make another signer's key hash into the tree.
+    if let Some(salt) = leaf_salt {
+        leaf_data.extend_from_slice(&salt);
+    }
+
+    // 3. Parse the proof and verify it against that root under the configured hash
+    let is_valid = verify_proof_under(
+        config.hash_algo,
+        proof_bytes,
+        root,
+        &leaf_data,
+        leaf_index,
+        total_leaves,
+    )?;
 
-    msg!("Verification successful for user: {}", user_key);
+    require!(is_valid, SubscriptionError::ProofDidNotMatchRoot);
     Ok(())
 }
 
+/// `verify_proof` under the hasher `hash_algo` names
+pub(crate) fn verify_proof_under(
+    hash_algo: HashAlgo,
+    proof_bytes: &[u8],
+    root: [u8; 32],
+    leaf_data: &[u8],
+    leaf_index: usize,
+    total_leaves: usize,
+) -> Result<bool> {
+    match hash_algo {
+        HashAlgo::Sha256 => {
+            verify_proof::<Sha256Hasher>(proof_bytes, root, leaf_data, leaf_index, total_leaves)
+        }
+        HashAlgo::Keccak256 => {
+            verify_proof::<Keccak256Hasher>(proof_bytes, root, leaf_data, leaf_index, total_leaves)
+        }
+        HashAlgo::DoubleSha256 => verify_proof::<DoubleSha256Hasher>(
+            proof_bytes,
+            root,
+            leaf_data,
+            leaf_index,
+            total_leaves,
+        ),
+    }
+}
+
+/// Hash the leaf and check the proof against `root` under hasher `H`
+fn verify_proof<H: Hasher<Hash = [u8; 32]>>(
+    proof_bytes: &[u8],
+    root: [u8; 32],
+    leaf_data: &[u8],
+    leaf_index: usize,
+    total_leaves: usize,
+) -> Result<bool> {
+    let leaf = H::hash(leaf_data);
+
+    // Only a single-leaf tree, whose root is the leaf itself, has an empty proof
+    require!(
+        !proof_bytes.is_empty() || total_leaves <= 1,
+        SubscriptionError::EmptyProofForMultiLeaf
+    );
+    // Exactly the hashes this leaf needs, so no trailing bytes are silently ignored
+    require!(
+        expected_proof_len(leaf_index, total_leaves)
+            .is_some_and(|hashes| proof_bytes.len() == hashes * 32),
+        SubscriptionError::MalformedProofLength
+    );
+    // Whole 32-byte hashes after the length check, so parsing can't fail here
+    let proof = MerkleProof::<H>::try_from(proof_bytes)
+        .map_err(|_| SubscriptionError::MalformedProofLength)?;
+
+    Ok(proof.verify(root, &[leaf_index], &[leaf], total_leaves))
+}
+
+/// Sibling hashes in a well-formed proof for leaf `index`, or `None` outside the tree.
+/// An odd node out is promoted without a sibling, so it adds no hash at that level.
+/// Mirrors `expected_proof_len` in the backend.
+fn expected_proof_len(index: usize, total_leaves: usize) -> Option<usize> {
+    if index >= total_leaves {
+        return None;
+    }
+
+    let (mut index, mut layer_len, mut hashes) = (index, total_leaves, 0);
+    while layer_len > 1 {
+        if index ^ 1 < layer_len {
+            hashes += 1;
+        }
+        index /= 2;
+        layer_len = layer_len.div_ceil(2);
+    }
+    Some(hashes)
+}
+
 #[derive(Accounts)]
 pub struct VerifySubscription<'info> {
     #[account(
@@ -63,5 +365,119 @@ pub struct VerifySubscription<'info> {
         bump = config.bump
     )]
     pub config: Account<'info, SubscriptionConfig>,
+    #[account(mut)]
     pub user: Signer<'info>,
+    /// Only required when `config.required_mint` is set
+    pub token_account: Option<Account<'info, TokenAccount>>,
+    /// Cached verification; only used when `config.verification_cache_ttl_secs > 0`
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + VerificationReceipt::INIT_SPACE,
+        seeds = [b"receipt", user.key().as_ref()],
+        bump
+    )]
+    pub receipt: Option<Account<'info, VerificationReceipt>>,
+    /// Last verification time; only used when `config.min_interval_secs > 0`
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + VerifyRateLimit::INIT_SPACE,
+        seeds = [b"rate_limit", user.key().as_ref()],
+        bump
+    )]
+    pub rate_limit: Option<Account<'info, VerifyRateLimit>>,
+    pub system_program: Option<Program<'info, System>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{PubkeyLeafEncoding, ROOT_HISTORY_LEN};
+    use rs_merkle::MerkleTree;
+
+    fn config(merkle_root: [u8; 32], encoding: PubkeyLeafEncoding) -> SubscriptionConfig {
+        SubscriptionConfig {
+            authority: Pubkey::default(),
+            merkle_root,
+            bump: 0,
+            required_mint: None,
+            clock_skew_secs: 0,
+            hash_algo: HashAlgo::Sha256,
+            verification_cache_ttl_secs: 0,
+            authorities: Vec::new(),
+            threshold: 0,
+            expiration_in_leaf: true,
+            min_interval_secs: 0,
+            chain_domain: [0u8; 8],
+            root_history: [[0u8; 32]; ROOT_HISTORY_LEN],
+            root_history_head: 0,
+            max_verify_root_age: 0,
+            start_in_leaf: false,
+            verbose_logging: true,
+            max_total_leaves: 0,
+            lifetime_root: [0u8; 32],
+            last_updated: 0,
+            min_update_interval_secs: 0,
+            pubkey_leaf_encoding: encoding,
+        }
+    }
+
+    #[test]
+    fn pinned_verification_accepts_only_the_expected_user() {
+        let (user, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert!(require_expected_user(&user, None).is_ok());
+        assert!(require_expected_user(&user, Some(user)).is_ok());
+        assert!(require_expected_user(&user, Some(other)).is_err());
+    }
+
+    #[test]
+    fn each_pubkey_leaf_encoding_verifies_only_its_own_trees() {
+        let (user, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let expiration: i64 = 2_000_000_000;
+        let leaf = |key: &Pubkey, encoding: PubkeyLeafEncoding| {
+            let mut data = encoding.encode(key);
+            data.extend_from_slice(&expiration.to_le_bytes());
+            Sha256Hasher::hash(&data)
+        };
+        assert_ne!(
+            leaf(&user, PubkeyLeafEncoding::RawBytes),
+            leaf(&user, PubkeyLeafEncoding::Base58String)
+        );
+
+        for (encoding, other_encoding) in [
+            (
+                PubkeyLeafEncoding::RawBytes,
+                PubkeyLeafEncoding::Base58String,
+            ),
+            (
+                PubkeyLeafEncoding::Base58String,
+                PubkeyLeafEncoding::RawBytes,
+            ),
+        ] {
+            let tree = MerkleTree::<Sha256Hasher>::from_leaves(&[
+                leaf(&user, encoding),
+                leaf(&other, encoding),
+            ]);
+            let root = tree.root().unwrap();
+            let proof_bytes = tree.proof(&[0]).to_bytes();
+            let claim = || MembershipClaim {
+                proof_bytes: &proof_bytes,
+                expiration,
+                leaf_salt: None,
+                leaf_index: 0,
+                total_leaves: 2,
+                proof_root: None,
+                start_ts: None,
+            };
+
+            assert!(
+                check_membership(&config(root, encoding), &user, claim(), 1_700_000_000).is_ok()
+            );
+            assert!(
+                check_membership(&config(root, other_encoding), &user, claim(), 1_700_000_000)
+                    .is_err()
+            );
+        }
+    }
 }
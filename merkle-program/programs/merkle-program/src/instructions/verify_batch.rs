@@ -0,0 +1,73 @@
+use crate::error::SubscriptionError;
+use crate::instructions::verify::{check_membership, MembershipClaim};
+use crate::state::SubscriptionConfig;
+use anchor_lang::prelude::*;
+
+/// One user's proof within a `verify_batch` call
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct BatchEntry {
+    pub proof_bytes: Vec<u8>,
+    pub expiration: i64,
+    pub leaf_index: u64,
+    pub leaf_salt: Option<[u8; 32]>,
+}
+
+/// Verify several users in one instruction.
+///
+/// Users are passed as signing remaining accounts, in the same order as `entries`.
+/// Token-gated and rate-limited configs need an account per user, so they must use
+/// `verify_subscription`, as must configs whose leaves include a start time.
+pub fn verify_batch(
+    ctx: Context<VerifyBatch>,
+    entries: Vec<BatchEntry>,
+    total_leaves: usize,
+) -> Result<()> {
+    let config = &ctx.accounts.config;
+    require!(
+        entries.len() == ctx.remaining_accounts.len(),
+        SubscriptionError::BatchLengthMismatch
+    );
+    require!(
+        config.required_mint.is_none(),
+        SubscriptionError::MissingToken
+    );
+    require!(
+        config.min_interval_secs == 0,
+        SubscriptionError::MissingRateLimit
+    );
+
+    let clock = Clock::get()?;
+
+    for (entry, user) in entries.iter().zip(ctx.remaining_accounts.iter()) {
+        require!(user.is_signer, ErrorCode::AccountNotSigner);
+
+        check_membership(
+            config,
+            user.key,
+            MembershipClaim {
+                proof_bytes: &entry.proof_bytes,
+                expiration: entry.expiration,
+                leaf_salt: entry.leaf_salt,
+                leaf_index: entry.leaf_index as usize,
+                total_leaves,
+                proof_root: None,
+                start_ts: None,
+            },
+            clock.unix_timestamp,
+        )?;
+    }
+
+    if config.verbose_logging {
+        msg!("Batch verification successful for {} users", entries.len());
+    }
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct VerifyBatch<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, SubscriptionConfig>,
+}
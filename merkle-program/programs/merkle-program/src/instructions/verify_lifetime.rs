@@ -0,0 +1,63 @@
+use crate::error::SubscriptionError;
+use crate::instructions::verify::verify_proof_under;
+use crate::state::SubscriptionConfig;
+use anchor_lang::prelude::*;
+
+/// Verify a member of the lifetime tree: the leaf is `Hash(pubkey)` and the proof is
+/// checked against `config.lifetime_root`, with no expiration involved.
+///
+/// The tree is built under the config's hash algorithm. Receipts only apply to
+/// `verify_subscription`, and token-gated or rate-limited configs need its per-user
+/// accounts, so they are refused here.
+pub fn verify_lifetime(
+    ctx: Context<VerifyLifetime>,
+    proof_bytes: Vec<u8>,
+    leaf_index: usize,
+    total_leaves: usize,
+) -> Result<()> {
+    let config = &ctx.accounts.config;
+    let user_key = ctx.accounts.user.key();
+    require!(
+        config.required_mint.is_none(),
+        SubscriptionError::MissingToken
+    );
+    require!(
+        config.min_interval_secs == 0,
+        SubscriptionError::MissingRateLimit
+    );
+
+    require!(
+        config.max_total_leaves == 0 || total_leaves as u64 <= config.max_total_leaves,
+        SubscriptionError::TreeTooLarge
+    );
+    // All zeros means this deployment has no lifetime members
+    require!(
+        config.lifetime_root != [0u8; 32],
+        SubscriptionError::RootNotSet
+    );
+
+    let is_valid = verify_proof_under(
+        config.hash_algo,
+        &proof_bytes,
+        config.lifetime_root,
+        &config.pubkey_leaf_encoding.encode(&user_key),
+        leaf_index,
+        total_leaves,
+    )?;
+    require!(is_valid, SubscriptionError::ProofDidNotMatchRoot);
+
+    if config.verbose_logging {
+        msg!("Lifetime verification successful for user: {}", user_key);
+    }
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct VerifyLifetime<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, SubscriptionConfig>,
+    pub user: Signer<'info>,
+}
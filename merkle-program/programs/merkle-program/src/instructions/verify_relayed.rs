@@ -0,0 +1,161 @@
+use crate::error::SubscriptionError;
+use crate::instructions::verify::{check_membership, MembershipClaim};
+use crate::state::SubscriptionConfig;
+use anchor_lang::prelude::*;
+use solana_program::ed25519_program;
+use solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+
+/// Domain prefix of the claim a user signs to authorize a relayed verification
+pub const RELAYED_CLAIM_PREFIX: &[u8] = b"merkle-subscription:verify";
+
+// Layout of ed25519 program instruction data: signature count, padding, then one
+// 14-byte offsets record per signature
+const ED25519_OFFSETS_START: usize = 2;
+const ED25519_OFFSETS_LEN: usize = 14;
+// Offsets that point into the ed25519 instruction's own data
+const ED25519_CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// The exact bytes the user signs: prefix, config, user, expiration and the claim's
+/// own expiry, so a signature can't be replayed for another deployment, user or
+/// subscription, or after it lapses
+pub fn relayed_claim_message(
+    config: &Pubkey,
+    user: &Pubkey,
+    expiration: i64,
+    claim_expires_at: i64,
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(RELAYED_CLAIM_PREFIX.len() + 80);
+    message.extend_from_slice(RELAYED_CLAIM_PREFIX);
+    message.extend_from_slice(config.as_ref());
+    message.extend_from_slice(user.as_ref());
+    message.extend_from_slice(&expiration.to_le_bytes());
+    message.extend_from_slice(&claim_expires_at.to_le_bytes());
+    message
+}
+
+/// Verify a user who didn't sign the transaction: a relayer pays, and the user's
+/// consent is an ed25519 signature over `relayed_claim_message`, checked by an
+/// ed25519 program instruction placed right before this one.
+///
+/// Like `verify_batch`, token-gated and rate-limited configs aren't supported, nor
+/// are leaves with a start time; no receipt is cached.
+pub fn verify_subscription_relayed(
+    ctx: Context<VerifySubscriptionRelayed>,
+    proof_bytes: Vec<u8>,
+    expiration: i64,
+    leaf_index: usize,
+    total_leaves: usize,
+    leaf_salt: Option<[u8; 32]>,
+    claim_expires_at: i64,
+) -> Result<()> {
+    let config = &ctx.accounts.config;
+    let user_key = ctx.accounts.user.key();
+    require!(
+        config.required_mint.is_none(),
+        SubscriptionError::MissingToken
+    );
+    require!(
+        config.min_interval_secs == 0,
+        SubscriptionError::MissingRateLimit
+    );
+
+    let clock = Clock::get()?;
+    require!(
+        claim_expires_at > clock.unix_timestamp,
+        SubscriptionError::ClaimExpired
+    );
+    let message = relayed_claim_message(&config.key(), &user_key, expiration, claim_expires_at);
+    require_user_signature(&ctx.accounts.instructions, &user_key, &message)?;
+
+    check_membership(
+        config,
+        &user_key,
+        MembershipClaim {
+            proof_bytes: &proof_bytes,
+            expiration,
+            leaf_salt,
+            leaf_index,
+            total_leaves,
+            proof_root: None,
+            start_ts: None,
+        },
+        clock.unix_timestamp,
+    )?;
+
+    if config.verbose_logging {
+        msg!(
+            "Relayed verification successful for user: {} (relayer {})",
+            user_key,
+            ctx.accounts.relayer.key()
+        );
+    }
+    Ok(())
+}
+
+/// Require the instruction before this one to be an ed25519 check of `signer`'s
+/// signature over exactly `message`. The ed25519 program already failed the
+/// transaction if the signature itself is invalid, so only what was checked
+/// needs confirming here.
+fn require_user_signature(
+    instructions: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let current_index = load_current_index_checked(instructions)?;
+    let previous_index = current_index
+        .checked_sub(1)
+        .ok_or(SubscriptionError::MissingUserSignature)?;
+    let ed25519_ix = load_instruction_at_checked(previous_index as usize, instructions)?;
+    require_keys_eq!(
+        ed25519_ix.program_id,
+        ed25519_program::ID,
+        SubscriptionError::MissingUserSignature
+    );
+
+    let data = &ed25519_ix.data;
+    require!(
+        data.len() >= ED25519_OFFSETS_START + ED25519_OFFSETS_LEN && data[0] == 1,
+        SubscriptionError::MissingUserSignature
+    );
+    let offsets = &data[ED25519_OFFSETS_START..ED25519_OFFSETS_START + ED25519_OFFSETS_LEN];
+    let read_u16 = |at: usize| u16::from_le_bytes([offsets[at], offsets[at + 1]]);
+    let (signature_ix, public_key_offset, public_key_ix) = (read_u16(2), read_u16(4), read_u16(6));
+    let (message_offset, message_size, message_ix) = (read_u16(8), read_u16(10), read_u16(12));
+
+    // Everything the signature covers must come from the ed25519 instruction itself
+    require!(
+        [signature_ix, public_key_ix, message_ix]
+            .iter()
+            .all(|&index| index == ED25519_CURRENT_INSTRUCTION),
+        SubscriptionError::MissingUserSignature
+    );
+    let slice = |offset: u16, len: usize| data.get(offset as usize..offset as usize + len);
+    require!(
+        slice(public_key_offset, 32) == Some(signer.as_ref()),
+        SubscriptionError::MissingUserSignature
+    );
+    require!(
+        slice(message_offset, message_size as usize) == Some(message),
+        SubscriptionError::MissingUserSignature
+    );
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct VerifySubscriptionRelayed<'info> {
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config: Account<'info, SubscriptionConfig>,
+    /// CHECK: the user doesn't sign; their consent is the ed25519 signature over the
+    /// claim, checked against this key
+    pub user: UncheckedAccount<'info>,
+    /// Pays the fee in the user's place
+    pub relayer: Signer<'info>,
+    /// CHECK: address-constrained to the instructions sysvar
+    #[account(address = solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+}
@@ -1,10 +1,12 @@
 use anchor_lang::prelude::*;
 
 pub mod error;
+pub mod events;
 pub mod instructions;
 pub mod state;
 
 pub use error::*;
+pub use events::*;
 #[allow(ambiguous_glob_reexports)]
 pub use instructions::*;
 pub use state::*;
@@ -15,23 +17,148 @@ declare_id!("AHpuc2M3wbZceufaiE4Q2wyDXh198ymB1SxxpbxCzj3H");
 pub mod merkle_program {
     use super::*;
 
-    /// Initialize the subscription config with an initial merkle root
-    pub fn initialize(ctx: Context<Initialize>, initial_root: [u8; 32]) -> Result<()> {
-        instructions::initialize(ctx, initial_root)
+    /// Initialize the subscription config with an initial merkle root and network domain
+    pub fn initialize(
+        ctx: Context<Initialize>,
+        initial_root: [u8; 32],
+        chain_domain: [u8; 8],
+    ) -> Result<()> {
+        instructions::initialize(ctx, initial_root, chain_domain)
+    }
+
+    /// Grow a config created under an older layout to the current size
+    pub fn realloc_config(ctx: Context<ReallocConfig>) -> Result<()> {
+        instructions::realloc_config(ctx)
     }
 
-    /// Update the merkle root (only authority can do this)
+    /// Update the merkle root (only authority, or M of the authority set, can do this)
     pub fn update_root(ctx: Context<UpdateRoot>, new_root: [u8; 32]) -> Result<()> {
         instructions::update_root(ctx, new_root)
     }
 
-    /// Verify a user's subscription using merkle proof
+    /// Append one leaf, proving the new root only extends the append-only tree
+    pub fn append_leaf(
+        ctx: Context<AppendLeaf>,
+        new_leaf: [u8; 32],
+        new_root: [u8; 32],
+        append_proof: Vec<u8>,
+    ) -> Result<()> {
+        instructions::append_leaf(ctx, new_leaf, new_root, append_proof)
+    }
+
+    /// Set the token a user must hold in addition to passing the merkle check
+    pub fn set_required_mint(
+        ctx: Context<UpdateConfig>,
+        required_mint: Option<Pubkey>,
+    ) -> Result<()> {
+        instructions::set_required_mint(ctx, required_mint)
+    }
+
+    /// Set the clock skew tolerance applied to expiration checks
+    pub fn set_clock_skew(ctx: Context<UpdateConfig>, clock_skew_secs: i64) -> Result<()> {
+        instructions::set_clock_skew(ctx, clock_skew_secs)
+    }
+
+    /// Atomically switch the hash algorithm and set a root computed under it
+    pub fn migrate_hash_algo(
+        ctx: Context<UpdateConfig>,
+        hash_algo: HashAlgo,
+        new_root: [u8; 32],
+    ) -> Result<()> {
+        instructions::migrate_hash_algo(ctx, hash_algo, new_root)
+    }
+
+    /// Switch between expiring and static-allowlist leaves with a matching root
+    pub fn set_expiration_in_leaf(
+        ctx: Context<UpdateConfig>,
+        expiration_in_leaf: bool,
+        new_root: [u8; 32],
+    ) -> Result<()> {
+        instructions::set_expiration_in_leaf(ctx, expiration_in_leaf, new_root)
+    }
+
+    /// Switch between raw-byte and base58-string pubkeys in leaves, with a matching root
+    pub fn set_pubkey_leaf_encoding(
+        ctx: Context<UpdateConfig>,
+        pubkey_leaf_encoding: PubkeyLeafEncoding,
+        new_root: [u8; 32],
+    ) -> Result<()> {
+        instructions::set_pubkey_leaf_encoding(ctx, pubkey_leaf_encoding, new_root)
+    }
+
+    /// Switch to leaves that include a start time, with a matching root
+    pub fn set_start_in_leaf(
+        ctx: Context<UpdateConfig>,
+        start_in_leaf: bool,
+        new_root: [u8; 32],
+    ) -> Result<()> {
+        instructions::set_start_in_leaf(ctx, start_in_leaf, new_root)
+    }
+
+    /// Set how long a successful verification is cached per user
+    pub fn set_verification_cache_ttl(ctx: Context<UpdateConfig>, ttl_secs: i64) -> Result<()> {
+        instructions::set_verification_cache_ttl(ctx, ttl_secs)
+    }
+
+    /// Set the minimum interval between one user's verifications
+    pub fn set_min_interval(ctx: Context<UpdateConfig>, min_interval_secs: i64) -> Result<()> {
+        instructions::set_min_interval(ctx, min_interval_secs)
+    }
+
+    /// Set how many roots back a proof's named root may be
+    pub fn set_max_verify_root_age(
+        ctx: Context<UpdateConfig>,
+        max_verify_root_age: u8,
+    ) -> Result<()> {
+        instructions::set_max_verify_root_age(ctx, max_verify_root_age)
+    }
+
+    /// Cap the tree size verification accepts (0 = unbounded)
+    pub fn set_max_total_leaves(ctx: Context<UpdateConfig>, max_total_leaves: u64) -> Result<()> {
+        instructions::set_max_total_leaves(ctx, max_total_leaves)
+    }
+
+    /// Set the root of the non-expiring lifetime-member tree (all zeros turns it off)
+    pub fn set_lifetime_root(ctx: Context<UpdateConfig>, lifetime_root: [u8; 32]) -> Result<()> {
+        instructions::set_lifetime_root(ctx, lifetime_root)
+    }
+
+    /// Set the minimum seconds between root updates (0 disables)
+    pub fn set_min_update_interval(
+        ctx: Context<UpdateConfig>,
+        min_update_interval_secs: i64,
+    ) -> Result<()> {
+        instructions::set_min_update_interval(ctx, min_update_interval_secs)
+    }
+
+    /// Turn verification success logs on or off
+    pub fn set_verbose_logging(ctx: Context<UpdateConfig>, verbose_logging: bool) -> Result<()> {
+        instructions::set_verbose_logging(ctx, verbose_logging)
+    }
+
+    /// Require `threshold` of `authorities` to sign root and config updates
+    pub fn set_authorities(
+        ctx: Context<UpdateConfig>,
+        authorities: Vec<Pubkey>,
+        threshold: u8,
+    ) -> Result<()> {
+        instructions::set_authorities(ctx, authorities, threshold)
+    }
+
+    /// Verify a user's subscription using merkle proof (`leaf_salt` for salted trees,
+    /// `proof_root` to check against a recent root instead of the current one,
+    /// `start_ts` when leaves include a start time)
+    #[allow(clippy::too_many_arguments)]
     pub fn verify_subscription(
         ctx: Context<VerifySubscription>,
         proof_bytes: Vec<u8>,
         expiration: i64,
         leaf_index: u64,
         total_leaves: u64,
+        leaf_salt: Option<[u8; 32]>,
+        proof_root: Option<[u8; 32]>,
+        start_ts: Option<i64>,
+        expected_user: Option<Pubkey>,
     ) -> Result<()> {
         instructions::verify_subscription(
             ctx,
@@ -39,6 +166,51 @@ pub mod merkle_program {
             expiration,
             leaf_index as usize,
             total_leaves as usize,
+            leaf_salt,
+            proof_root,
+            start_ts,
+            expected_user,
+        )
+    }
+
+    /// Verify a user who signed an off-chain claim instead of the transaction, so a
+    /// relayer can pay; an ed25519 instruction checking the claim must come first
+    pub fn verify_subscription_relayed(
+        ctx: Context<VerifySubscriptionRelayed>,
+        proof_bytes: Vec<u8>,
+        expiration: i64,
+        leaf_index: u64,
+        total_leaves: u64,
+        leaf_salt: Option<[u8; 32]>,
+        claim_expires_at: i64,
+    ) -> Result<()> {
+        instructions::verify_subscription_relayed(
+            ctx,
+            proof_bytes,
+            expiration,
+            leaf_index as usize,
+            total_leaves as usize,
+            leaf_salt,
+            claim_expires_at,
         )
     }
+
+    /// Verify a lifetime member against the lifetime root; no expiration is checked
+    pub fn verify_lifetime(
+        ctx: Context<VerifyLifetime>,
+        proof_bytes: Vec<u8>,
+        leaf_index: u64,
+        total_leaves: u64,
+    ) -> Result<()> {
+        instructions::verify_lifetime(ctx, proof_bytes, leaf_index as usize, total_leaves as usize)
+    }
+
+    /// Verify several users' subscriptions in one instruction
+    pub fn verify_batch(
+        ctx: Context<VerifyBatch>,
+        entries: Vec<BatchEntry>,
+        total_leaves: u64,
+    ) -> Result<()> {
+        instructions::verify_batch(ctx, entries, total_leaves as usize)
+    }
 }
@@ -1,9 +1,214 @@
 use anchor_lang::prelude::*;
 
+/// Most authorities a config can require co-signatures from
+pub const MAX_AUTHORITIES: usize = 5;
+
+/// Number of replaced roots kept for verifying proofs built against a recent root
+pub const ROOT_HISTORY_LEN: usize = 8;
+
 #[account]
 #[derive(InitSpace)]
 pub struct SubscriptionConfig {
-    pub authority: Pubkey,     // Your backend's public key
-    pub merkle_root: [u8; 32], // The only data that changes
+    pub authority: Pubkey,                // Your backend's public key
+    pub merkle_root: [u8; 32],            // The only data that changes
+    pub bump: u8,                         // PDA bump seed
+    pub required_mint: Option<Pubkey>,    // Token gate: user must also hold this mint
+    pub clock_skew_secs: i64,             // Tolerance for validator clock lag at expiry
+    pub hash_algo: HashAlgo,              // Hash used for leaves and internal nodes
+    pub verification_cache_ttl_secs: i64, // How long a receipt skips re-verification (0 = off)
+    #[max_len(MAX_AUTHORITIES)]
+    pub authorities: Vec<Pubkey>, // M-of-N signer set (empty = single authority)
+    pub threshold: u8,                    // Signatures from `authorities` required (M)
+    pub expiration_in_leaf: bool,         // false = static allowlist, leaf is Hash(pubkey)
+    pub min_interval_secs: i64,           // Minimum gap between one user's verifies (0 = off)
+    pub chain_domain: [u8; 8],            // Operator-chosen network tag roots are bound to
+    pub root_history: [[u8; 32]; ROOT_HISTORY_LEN], // Ring buffer of replaced roots
+    pub root_history_head: u8,            // Slot the next replaced root is written to
+    pub max_verify_root_age: u8,          // Roots back a named proof root may be (0 = current only)
+    pub start_in_leaf: bool,              // true = leaf also commits to a start time
+    pub verbose_logging: bool,            // false = only failures are logged by verify
+    pub max_total_leaves: u64,            // Largest tree verify accepts (0 = unbounded)
+    pub lifetime_root: [u8; 32],          // Non-expiring Hash(pubkey) members (zeros = none)
+    pub last_updated: i64,                // Last update_root or append_leaf (0 = never)
+    pub min_update_interval_secs: i64,    // Minimum gap between root updates (0 = off)
+    pub pubkey_leaf_encoding: PubkeyLeafEncoding, // How the user's pubkey is hashed into leaves
+}
+
+impl SubscriptionConfig {
+    /// Whether `signers` may act as the authority: the single `authority` key
+    /// when no set is configured, otherwise at least `threshold` of `authorities`
+    pub fn is_authorized(&self, signers: &[Pubkey]) -> bool {
+        if self.authorities.is_empty() {
+            return signers.contains(&self.authority);
+        }
+        let approvals = self
+            .authorities
+            .iter()
+            .filter(|key| signers.contains(key))
+            .count();
+        approvals >= self.threshold as usize
+    }
+
+    /// Whether `update_root` or `append_leaf` may replace the root at `now`: always
+    /// when no interval is set, otherwise once `min_update_interval_secs` have passed
+    /// since the last one. Config changes that reset the root are not limited.
+    pub fn root_update_allowed(&self, now: i64) -> bool {
+        self.min_update_interval_secs == 0
+            || now.saturating_sub(self.last_updated) >= self.min_update_interval_secs
+    }
+
+    /// Replace the root, keeping the old one in `root_history`
+    pub fn push_root(&mut self, new_root: [u8; 32]) {
+        let head = self.root_history_head as usize % ROOT_HISTORY_LEN;
+        self.root_history[head] = self.merkle_root;
+        self.root_history_head = ((head + 1) % ROOT_HISTORY_LEN) as u8;
+        self.merkle_root = new_root;
+    }
+
+    /// Replace the root and forget every earlier one, for changes to the leaf
+    /// layout under which older roots' proofs no longer mean the same thing
+    pub fn reset_root(&mut self, new_root: [u8; 32]) {
+        self.root_history = [[0u8; 32]; ROOT_HISTORY_LEN];
+        self.root_history_head = 0;
+        self.merkle_root = new_root;
+    }
+
+    /// How many updates ago `root` was current: 0 for the current root, `None` if
+    /// it isn't in the history. Unused (zeroed) slots never match.
+    pub fn root_age(&self, root: &[u8; 32]) -> Option<usize> {
+        if *root == self.merkle_root {
+            return Some(0);
+        }
+        if *root == [0u8; 32] {
+            return None;
+        }
+        let head = self.root_history_head as usize % ROOT_HISTORY_LEN;
+        (1..=ROOT_HISTORY_LEN).find(|age| {
+            self.root_history[(head + ROOT_HISTORY_LEN - age) % ROOT_HISTORY_LEN] == *root
+        })
+    }
+}
+
+/// Hash function the merkle tree is built with
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum HashAlgo {
+    Sha256,
+    Keccak256,
+    DoubleSha256,
+}
+
+/// How the user's pubkey enters a leaf. `RawBytes` hashes its 32 bytes, the
+/// convention the backend builds trees with; `Base58String` hashes its base58 text,
+/// for trees built by tooling that hashes the string form. A tree built one way never
+/// verifies under the other.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum PubkeyLeafEncoding {
+    RawBytes,
+    Base58String,
+}
+
+impl PubkeyLeafEncoding {
+    /// The bytes a leaf starts with for `key`
+    pub fn encode(self, key: &Pubkey) -> Vec<u8> {
+        match self {
+            PubkeyLeafEncoding::RawBytes => key.to_bytes().to_vec(),
+            PubkeyLeafEncoding::Base58String => key.to_string().into_bytes(),
+        }
+    }
+}
+
+/// The append-only tree behind `append_leaf`: how many leaves it has and its root,
+/// which stays the config root until something other than an append replaces it
+#[account]
+#[derive(InitSpace)]
+pub struct AppendLog {
+    pub leaf_count: u64, // Leaves appended so far
+    pub root: [u8; 32],  // Root after the last append
+    pub bump: u8,        // PDA bump seed
+}
+
+/// Per-user record of the last verification, for rate limiting
+#[account]
+#[derive(InitSpace)]
+pub struct VerifyRateLimit {
+    pub user: Pubkey,          // User this record belongs to
+    pub last_verified_at: i64, // Timestamp of the last accepted verify
+    pub bump: u8,              // PDA bump seed
+}
+
+/// Per-user cache of a successful verification
+#[account]
+#[derive(InitSpace)]
+pub struct VerificationReceipt {
+    pub user: Pubkey,          // User this receipt belongs to
+    pub verified_until: i64,   // Skip proof checks until this timestamp
     pub bump: u8,              // PDA bump seed
+    pub merkle_root: [u8; 32], // Root the receipt was issued under; any other root misses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SubscriptionConfig {
+        SubscriptionConfig {
+            authority: Pubkey::default(),
+            merkle_root: [1u8; 32],
+            bump: 0,
+            required_mint: None,
+            clock_skew_secs: 0,
+            hash_algo: HashAlgo::Sha256,
+            verification_cache_ttl_secs: 0,
+            authorities: Vec::new(),
+            threshold: 0,
+            expiration_in_leaf: true,
+            min_interval_secs: 0,
+            chain_domain: [0u8; 8],
+            root_history: [[0u8; 32]; ROOT_HISTORY_LEN],
+            root_history_head: 0,
+            max_verify_root_age: 0,
+            start_in_leaf: false,
+            verbose_logging: true,
+            max_total_leaves: 0,
+            lifetime_root: [0u8; 32],
+            last_updated: 0,
+            min_update_interval_secs: 0,
+            pubkey_leaf_encoding: PubkeyLeafEncoding::RawBytes,
+        }
+    }
+
+    #[test]
+    fn root_age_counts_back_through_the_ring_buffer() {
+        let mut config = config();
+
+        // Wrap the buffer: roots 2..=N+2 replace 1..=N+1
+        let last = ROOT_HISTORY_LEN as u8 + 2;
+        for root in 2..=last {
+            config.push_root([root; 32]);
+        }
+        assert_eq!(config.root_age(&[last; 32]), Some(0));
+        assert_eq!(config.root_age(&[last - 1; 32]), Some(1));
+        assert_eq!(config.root_age(&[2; 32]), Some(ROOT_HISTORY_LEN));
+        // Overwritten by the wrap
+        assert_eq!(config.root_age(&[1; 32]), None);
+
+        config.reset_root([42u8; 32]);
+        assert_eq!(config.root_age(&[last; 32]), None);
+        assert_eq!(config.root_age(&[42u8; 32]), Some(0));
+    }
+
+    #[test]
+    fn root_updates_wait_out_the_minimum_interval() {
+        let mut config = SubscriptionConfig {
+            last_updated: 1_000,
+            ..config()
+        };
+        // Off: back-to-back updates are fine
+        assert!(config.root_update_allowed(1_000));
+
+        config.min_update_interval_secs = 60;
+        assert!(!config.root_update_allowed(1_000));
+        assert!(!config.root_update_allowed(1_059));
+        assert!(config.root_update_allowed(1_060));
+    }
 }
@@ -2,8 +2,17 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { MerkleProgram } from "../target/types/merkle_program";
 import { assert, config, expect } from "chai";
-import { Keypair, PublicKey } from "@solana/web3.js";
+import {
+  ComputeBudgetProgram,
+  Ed25519Program,
+  Keypair,
+  PublicKey,
+  SystemProgram,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
+} from "@solana/web3.js";
+import { burn, createAccount, createMint, mintTo } from "@solana/spl-token";
 import { createHash } from "crypto";
+import { keccak_256 } from "@noble/hashes/sha3";
 
 describe("merkle-program", () => {
   const provider = anchor.AnchorProvider.env();
@@ -19,16 +28,92 @@ describe("merkle-program", () => {
     program.programId
   );
 
-  function createLeaf(userPubkey: PublicKey, expiration: number): Buffer {
+  function createLeaf(
+    userPubkey: PublicKey,
+    expiration: number,
+    salt: Buffer = Buffer.alloc(0)
+  ): Buffer {
     const userBytes = userPubkey.toBuffer();
     const expirationBytes = Buffer.alloc(8);
     expirationBytes.writeBigInt64LE(BigInt(expiration));
 
     return createHash("sha256")
-      .update(Buffer.concat([userBytes, expirationBytes]))
+      .update(Buffer.concat([userBytes, expirationBytes, salt]))
       .digest();
   }
 
+  // Time-window leaf: Hash(pubkey + start + expiration)
+  function createWindowedLeaf(
+    userPubkey: PublicKey,
+    start: number,
+    expiration: number
+  ): Buffer {
+    const startBytes = Buffer.alloc(8);
+    startBytes.writeBigInt64LE(BigInt(start));
+    const expirationBytes = Buffer.alloc(8);
+    expirationBytes.writeBigInt64LE(BigInt(expiration));
+
+    return createHash("sha256")
+      .update(Buffer.concat([userPubkey.toBuffer(), startBytes, expirationBytes]))
+      .digest();
+  }
+
+  // Single-leaf tree: the root is the leaf itself and the proof is empty
+  async function setSingleLeafRoot(
+    userPubkey: PublicKey,
+    expiration: number,
+    salt?: Buffer
+  ): Promise<void> {
+    const leaf = createLeaf(userPubkey, expiration, salt);
+    await program.methods
+      .updateRoot(Array.from(leaf))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+  }
+
+  // Verify against a single-leaf root set by setSingleLeafRoot, or against
+  // `proofRoot` when it names an earlier one
+  function verifySingleLeaf(
+    user: Keypair,
+    expiration: number,
+    accounts: Record<string, PublicKey | null> = {},
+    leafSalt: Buffer | null = null,
+    proofRoot: Buffer | null = null,
+    startTs: number | null = null,
+    expectedUser: PublicKey | null = null
+  ): Promise<string> {
+    return program.methods
+      .verifySubscription(
+        Buffer.alloc(0),
+        new anchor.BN(expiration),
+        new anchor.BN(0),
+        new anchor.BN(1),
+        leafSalt && Array.from(leafSalt),
+        proofRoot && Array.from(proofRoot),
+        startTs === null ? null : new anchor.BN(startTs),
+        expectedUser
+      )
+      .accounts({
+        user: user.publicKey,
+        tokenAccount: null,
+        receipt: null,
+        rateLimit: null,
+        systemProgram: null,
+        ...accounts,
+      })
+      .signers([user])
+      .rpc({ commitment: "confirmed" });
+  }
+
+  // Current validator clock, which may lag wall-clock time
+  async function chainTime(): Promise<number> {
+    const slot = await connection.getSlot("confirmed");
+    return await connection.getBlockTime(slot);
+  }
+
   function buildMerkleTree(leaves: Buffer[]): {
     root: Buffer;
     proofs: Map<string, Buffer[]>;
@@ -105,9 +190,15 @@ describe("merkle-program", () => {
       initialRoot[i] = i;
     }
 
+    // Bind the config to this network: first 8 bytes of sha256(cluster name)
+    const chainDomain = createHash("sha256")
+      .update("localnet")
+      .digest()
+      .subarray(0, 8);
+
     // Initialize the config
     const tx = await program.methods
-      .initialize(Array.from(initialRoot))
+      .initialize(Array.from(initialRoot), Array.from(chainDomain))
       .accounts({
         authority: wallet.publicKey,
       })
@@ -131,6 +222,11 @@ describe("merkle-program", () => {
       "Merkle root should match initial root"
     );
     assert.isAbove(configAccount.bump, 0, "Bump should be greater than 0");
+    assert.deepEqual(
+      Buffer.from(configAccount.chainDomain),
+      chainDomain,
+      "Chain domain should match the initialized network"
+    );
 
     console.log("Config initialized successfully:", {
       authority: configAccount.authority.toString(),
@@ -169,6 +265,37 @@ describe("merkle-program", () => {
     );
   });
 
+  it("Root updates emit RootUpdated with the prior root", async () => {
+    const before = await program.account.subscriptionConfig.fetch(configPDA);
+    const newRoot = Buffer.alloc(32, 7);
+
+    const tx = await program.methods
+      .updateRoot(Array.from(newRoot))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const txInfo = await connection.getTransaction(tx, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const parser = new anchor.EventParser(program.programId, program.coder);
+    const events = [...parser.parseLogs(txInfo.meta.logMessages)];
+    assert.lengthOf(events, 1);
+
+    const event = events[0];
+    assert.equal(event.name, "rootUpdated");
+    assert.deepEqual(
+      Buffer.from(event.data.oldRoot as number[]),
+      Buffer.from(before.merkleRoot)
+    );
+    assert.deepEqual(Buffer.from(event.data.newRoot as number[]), newRoot);
+    assert.isTrue((event.data.updatedBy as PublicKey).equals(wallet.publicKey));
+    assert.isAbove((event.data.timestamp as anchor.BN).toNumber(), 0);
+  });
+
   it("Verify Subscription", async () => {
     // Initialize keypairs
     const user1 = Keypair.generate();
@@ -262,4 +389,1303 @@ describe("merkle-program", () => {
       assert.include(error.toString(), "SubscriptionExpired");
     }
   });
+
+  it("Rejects negative expiration", async () => {
+    const user = Keypair.generate();
+
+    try {
+      await verifySingleLeaf(user, -1);
+      assert.fail("Should have failed with negative expiration");
+    } catch (error) {
+      console.log("Negative expiration correctly rejected");
+      assert.include(error.toString(), "InvalidExpiration");
+    }
+  });
+
+  it("Proof failures report a precise error", async () => {
+    const user = Keypair.generate();
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+    await setSingleLeafRoot(user.publicKey, expiration);
+
+    const verifyWith = (proof: Buffer, totalLeaves: number, exp = expiration) =>
+      program.methods
+        .verifySubscription(
+          proof,
+          new anchor.BN(exp),
+          new anchor.BN(0),
+          new anchor.BN(totalLeaves),
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          user: user.publicKey,
+          tokenAccount: null,
+          receipt: null,
+          rateLimit: null,
+          systemProgram: null,
+        })
+        .signers([user])
+        .rpc({ commitment: "confirmed" });
+
+    const cases: [string, () => Promise<string>][] = [
+      // A valid-length proof with a trailing hash appended
+      ["MalformedProofLength", () => verifyWith(Buffer.alloc(64), 2)],
+      ["EmptyProofForMultiLeaf", () => verifyWith(Buffer.alloc(0), 2)],
+      ["ProofDidNotMatchRoot", () => verifyWith(Buffer.alloc(0), 1, expiration + 1)],
+    ];
+    for (const [expected, attempt] of cases) {
+      try {
+        await attempt();
+        assert.fail(`Should have failed with ${expected}`);
+      } catch (error) {
+        assert.include(error.toString(), expected);
+      }
+    }
+    console.log("Each proof failure reported distinctly");
+  });
+
+  it("Rejects verification while no root is set", async () => {
+    const user = Keypair.generate();
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+    await program.methods
+      .updateRoot(Array.from(Buffer.alloc(32)))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    // Against the current root, or naming the zero root from history
+    for (const proofRoot of [null, Buffer.alloc(32)]) {
+      try {
+        await verifySingleLeaf(user, expiration, {}, null, proofRoot);
+        assert.fail("Should have failed with RootNotSet");
+      } catch (error) {
+        assert.include(error.toString(), "RootNotSet");
+      }
+    }
+    console.log("All-zero root rejected as unset");
+  });
+
+  it("Relayed verification requires the user's signed claim", async () => {
+    const user = Keypair.generate();
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+    await setSingleLeafRoot(user.publicKey, expiration);
+
+    // prefix + config + user + expiration + claim expiry, as relayed_claim_message
+    const claimMessage = (claimExpiresAt: number) => {
+      const numbers = Buffer.alloc(16);
+      numbers.writeBigInt64LE(BigInt(expiration), 0);
+      numbers.writeBigInt64LE(BigInt(claimExpiresAt), 8);
+      return Buffer.concat([
+        Buffer.from("merkle-subscription:verify"),
+        configPDA.toBuffer(),
+        user.publicKey.toBuffer(),
+        numbers,
+      ]);
+    };
+    const relay = (signer: Keypair | null, claimExpiresAt: number) => {
+      const preInstructions = signer
+        ? [
+            Ed25519Program.createInstructionWithPrivateKey({
+              privateKey: signer.secretKey,
+              message: claimMessage(claimExpiresAt),
+            }),
+          ]
+        : [];
+      // The relayer (provider wallet) pays and signs; the user doesn't
+      return program.methods
+        .verifySubscriptionRelayed(
+          Buffer.alloc(0),
+          new anchor.BN(expiration),
+          new anchor.BN(0),
+          new anchor.BN(1),
+          null,
+          new anchor.BN(claimExpiresAt)
+        )
+        .accounts({
+          user: user.publicKey,
+          relayer: wallet.publicKey,
+          instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+        })
+        .preInstructions(preInstructions)
+        .rpc({ commitment: "confirmed" });
+    };
+
+    const claimExpiresAt = (await chainTime()) + 300;
+    await relay(user, claimExpiresAt);
+    console.log("Relayed verification with the user's signature accepted");
+
+    const cases: [string, () => Promise<string>][] = [
+      ["MissingUserSignature", () => relay(null, claimExpiresAt)],
+      ["MissingUserSignature", () => relay(Keypair.generate(), claimExpiresAt)],
+      ["ClaimExpired", () => relay(user, claimExpiresAt - 600)],
+    ];
+    for (const [expected, attempt] of cases) {
+      try {
+        await attempt();
+        assert.fail(`Should have failed with ${expected}`);
+      } catch (error) {
+        assert.include(error.toString(), expected);
+      }
+    }
+    console.log("Missing, foreign and expired claims rejected");
+  });
+
+  it("Token-gated verification", async () => {
+    const user = Keypair.generate();
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+    await setSingleLeafRoot(user.publicKey, expiration);
+
+    // Require the user to hold a freshly created mint
+    const mint = await createMint(
+      connection,
+      wallet.payer,
+      wallet.publicKey,
+      null,
+      0
+    );
+    await program.methods
+      .setRequiredMint(mint)
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const tokenAccount = await createAccount(
+      connection,
+      wallet.payer,
+      mint,
+      user.publicKey
+    );
+
+    const verify = () => verifySingleLeaf(user, expiration, { tokenAccount });
+
+    // Lacks token: the account exists but holds a zero balance
+    try {
+      await verify();
+      assert.fail("Should have failed without the required token");
+    } catch (error) {
+      console.log("User without token correctly rejected");
+      assert.include(error.toString(), "MissingToken");
+    }
+
+    // Holds token
+    await mintTo(connection, wallet.payer, mint, tokenAccount, wallet.payer, 1);
+    await verify();
+    console.log("User holding token verified successfully");
+
+    // A cached receipt doesn't let the user through once the token is gone
+    await program.methods
+      .setVerificationCacheTtl(new anchor.BN(60))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+    const [receiptPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("receipt"), user.publicKey.toBuffer()],
+      program.programId
+    );
+    const cached = () =>
+      verifySingleLeaf(user, expiration, {
+        tokenAccount,
+        receipt: receiptPDA,
+        systemProgram: SystemProgram.programId,
+      });
+    await cached();
+    await burn(connection, wallet.payer, tokenAccount, mint, user, 1);
+    try {
+      await cached();
+      assert.fail("Should have failed once the token was burned");
+    } catch (error) {
+      console.log("Cached user without token correctly rejected");
+      assert.include(error.toString(), "MissingToken");
+    }
+
+    // Clear the gate and the cache so later tests are unaffected
+    await program.methods
+      .setRequiredMint(null)
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+    await program.methods
+      .setVerificationCacheTtl(new anchor.BN(0))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+  });
+
+  it("Clock skew tolerance at the expiration boundary", async () => {
+    const user = Keypair.generate();
+    // Just past expiration according to the validator clock
+    const expiration = (await chainTime()) - 1;
+    await setSingleLeafRoot(user.publicKey, expiration);
+
+    // Without skew the boundary expiration is rejected
+    try {
+      await verifySingleLeaf(user, expiration);
+      assert.fail("Should have failed without clock skew");
+    } catch (error) {
+      assert.include(error.toString(), "SubscriptionExpired");
+    }
+
+    // With skew configured the same expiration is accepted
+    await program.methods
+      .setClockSkew(new anchor.BN(60))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    await verifySingleLeaf(user, expiration);
+    console.log("Boundary expiration accepted within clock skew");
+
+    // Restore the default so later tests are unaffected
+    await program.methods
+      .setClockSkew(new anchor.BN(0))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+  });
+
+  it("Migrate hash algorithm from sha256 to keccak", async () => {
+    const user = Keypair.generate();
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+
+    // Single-leaf keccak tree: root = keccak(pubkey + expiration)
+    const expirationBytes = Buffer.alloc(8);
+    expirationBytes.writeBigInt64LE(BigInt(expiration));
+    const keccakRoot = Buffer.from(
+      keccak_256(Buffer.concat([user.publicKey.toBuffer(), expirationBytes]))
+    );
+
+    await program.methods
+      .migrateHashAlgo({ keccak256: {} }, Array.from(keccakRoot))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const configAccount = await program.account.subscriptionConfig.fetch(
+      configPDA
+    );
+    assert.deepEqual(configAccount.hashAlgo, { keccak256: {} });
+    assert.deepEqual(Buffer.from(configAccount.merkleRoot), keccakRoot);
+
+    await verifySingleLeaf(user, expiration);
+    console.log("Keccak proof verified after migration");
+
+    // Migrate back so later tests use sha256 again
+    await program.methods
+      .migrateHashAlgo(
+        { sha256: {} },
+        Array.from(createLeaf(user.publicKey, expiration))
+      )
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+  });
+
+  it("Migrate hash algorithm to double-sha256", async () => {
+    const user = Keypair.generate();
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+
+    // Single-leaf double-sha256 tree: root = sha256(sha256(pubkey + expiration))
+    const expirationBytes = Buffer.alloc(8);
+    expirationBytes.writeBigInt64LE(BigInt(expiration));
+    const once = createHash("sha256")
+      .update(Buffer.concat([user.publicKey.toBuffer(), expirationBytes]))
+      .digest();
+    const doubleRoot = createHash("sha256").update(once).digest();
+
+    await program.methods
+      .migrateHashAlgo({ doubleSha256: {} }, Array.from(doubleRoot))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const configAccount = await program.account.subscriptionConfig.fetch(
+      configPDA
+    );
+    assert.deepEqual(configAccount.hashAlgo, { doubleSha256: {} });
+
+    await verifySingleLeaf(user, expiration);
+    console.log("Double-sha256 proof verified after migration");
+
+    // Migrate back so later tests use sha256 again
+    await program.methods
+      .migrateHashAlgo(
+        { sha256: {} },
+        Array.from(createLeaf(user.publicKey, expiration))
+      )
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+  });
+
+  it("Benchmark: sha256 vs keccak verification compute units by tree depth", async () => {
+    const user = Keypair.generate();
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+    const expirationBytes = Buffer.alloc(8);
+    expirationBytes.writeBigInt64LE(BigInt(expiration));
+    const leafData = Buffer.concat([
+      user.publicKey.toBuffer(),
+      expirationBytes,
+    ]);
+
+    const hashers = {
+      sha256: {
+        hashAlgo: { sha256: {} },
+        hash: (data: Buffer) => createHash("sha256").update(data).digest(),
+      },
+      keccak256: {
+        hashAlgo: { keccak256: {} },
+        hash: (data: Buffer) => Buffer.from(keccak_256(data)),
+      },
+    };
+
+    // Leaf 0 of a full tree of 2^depth leaves: it is the left child at every
+    // level, so the root is the leaf hashed up with each sibling in turn
+    const verifyAtDepth = async (algo: keyof typeof hashers, depth: number) => {
+      const { hashAlgo, hash } = hashers[algo];
+      const siblings = Array.from({ length: depth }, (_, i) =>
+        hash(Buffer.from(`sibling-${i}`))
+      );
+      const root = siblings.reduce(
+        (node, sibling) => hash(Buffer.concat([node, sibling])),
+        hash(leafData)
+      );
+
+      await program.methods
+        .migrateHashAlgo(hashAlgo, Array.from(root))
+        .accounts({
+          config: configPDA,
+          authority: wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+
+      const tx = await program.methods
+        .verifySubscription(
+          Buffer.concat(siblings),
+          new anchor.BN(expiration),
+          new anchor.BN(0),
+          new anchor.BN(2 ** depth),
+          null,
+          null,
+          null,
+          null
+        )
+        .accounts({
+          user: user.publicKey,
+          tokenAccount: null,
+          receipt: null,
+          rateLimit: null,
+          systemProgram: null,
+        })
+        // Measure past the default limit rather than fail at it
+        .preInstructions([
+          ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 }),
+        ])
+        .signers([user])
+        .rpc({ commitment: "confirmed" });
+      const txInfo = await connection.getTransaction(tx, {
+        commitment: "confirmed",
+        maxSupportedTransactionVersion: 0,
+      });
+      return txInfo.meta.computeUnitsConsumed;
+    };
+
+    const depths = [0, 4, 8, 12, 16, 20];
+    const results: { depth: number; sha256: number; keccak256: number }[] = [];
+    try {
+      for (const depth of depths) {
+        results.push({
+          depth,
+          sha256: await verifyAtDepth("sha256", depth),
+          keccak256: await verifyAtDepth("keccak256", depth),
+        });
+      }
+    } finally {
+      // Back to sha256 for later tests
+      await program.methods
+        .migrateHashAlgo(
+          { sha256: {} },
+          Array.from(createLeaf(user.publicKey, expiration))
+        )
+        .accounts({
+          config: configPDA,
+          authority: wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+    }
+    // Compute units per proof level, over the fixed cost of an empty proof
+    const perLevel = (r: (typeof results)[0], algo: keyof typeof hashers) =>
+      r.depth && Math.round((r[algo] - results[0][algo]) / r.depth);
+    console.table(
+      results.map((r) => ({
+        ...r,
+        sha256PerLevel: perLevel(r, "sha256"),
+        keccak256PerLevel: perLevel(r, "keccak256"),
+      }))
+    );
+
+    // Every extra proof level costs one more hash under either algorithm
+    for (let i = 1; i < results.length; i++) {
+      assert.isAbove(results[i].sha256, results[i - 1].sha256);
+      assert.isAbove(results[i].keccak256, results[i - 1].keccak256);
+    }
+  });
+
+  it("Verification success logs are suppressed without verbose logging", async () => {
+    const user = Keypair.generate();
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+    await setSingleLeafRoot(user.publicKey, expiration);
+
+    const logsOf = async (tx: string) => {
+      const txInfo = await connection.getTransaction(tx, {
+        commitment: "confirmed",
+        maxSupportedTransactionVersion: 0,
+      });
+      return txInfo.meta.logMessages.join("\n");
+    };
+    const setVerboseLogging = (verbose: boolean) =>
+      program.methods
+        .setVerboseLogging(verbose)
+        .accounts({
+          config: configPDA,
+          authority: wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+
+    const configAccount = await program.account.subscriptionConfig.fetch(
+      configPDA
+    );
+    assert.isTrue(configAccount.verboseLogging);
+    assert.include(
+      await logsOf(await verifySingleLeaf(user, expiration)),
+      "Verification successful"
+    );
+
+    await setVerboseLogging(false);
+    try {
+      assert.notInclude(
+        await logsOf(await verifySingleLeaf(user, expiration)),
+        "Verification successful"
+      );
+
+      // Failures are still logged
+      try {
+        await verifySingleLeaf(user, expiration + 1);
+        assert.fail("Should have failed with a tampered expiration");
+      } catch (error) {
+        assert.include(error.toString(), "ProofDidNotMatchRoot");
+      }
+    } finally {
+      await setVerboseLogging(true);
+    }
+  });
+
+  it("Verification rejects trees over the configured maximum size", async () => {
+    const user = Keypair.generate();
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+    await setSingleLeafRoot(user.publicKey, expiration);
+
+    const setMaxTotalLeaves = (maxTotalLeaves: number) =>
+      program.methods
+        .setMaxTotalLeaves(new anchor.BN(maxTotalLeaves))
+        .accounts({
+          config: configPDA,
+          authority: wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+
+    await setMaxTotalLeaves(1);
+    try {
+      // Within the cap
+      await verifySingleLeaf(user, expiration);
+
+      // Over the cap, rejected before the proof is looked at
+      try {
+        await program.methods
+          .verifySubscription(
+            Buffer.alloc(32),
+            new anchor.BN(expiration),
+            new anchor.BN(0),
+            new anchor.BN(2),
+            null,
+            null,
+            null,
+            null
+          )
+          .accounts({
+            user: user.publicKey,
+            tokenAccount: null,
+            receipt: null,
+            rateLimit: null,
+            systemProgram: null,
+          })
+          .signers([user])
+          .rpc({ commitment: "confirmed" });
+        assert.fail("Should have failed with TreeTooLarge");
+      } catch (error) {
+        assert.include(error.toString(), "TreeTooLarge");
+      }
+    } finally {
+      await setMaxTotalLeaves(0);
+    }
+  });
+
+  it("Lifetime members verify against the lifetime root without an expiration", async () => {
+    const member = Keypair.generate();
+    const otherMember = Keypair.generate();
+    const outsider = Keypair.generate();
+
+    // Lifetime leaves are Hash(pubkey), in a tree separate from subscriptions
+    const lifetimeLeaf = (key: PublicKey) =>
+      createHash("sha256").update(key.toBuffer()).digest();
+    const leaves = [member, otherMember].map((k) => lifetimeLeaf(k.publicKey));
+    const { root, proofs } = buildMerkleTree(leaves);
+    const sortedLeaves = [...leaves].sort(Buffer.compare);
+
+    const setLifetimeRoot = (lifetimeRoot: Buffer) =>
+      program.methods
+        .setLifetimeRoot(Array.from(lifetimeRoot))
+        .accounts({
+          config: configPDA,
+          authority: wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+    const verifyLifetime = (user: Keypair, proof: Buffer, leafIndex: number) =>
+      program.methods
+        .verifyLifetime(proof, new anchor.BN(leafIndex), new anchor.BN(2))
+        .accounts({ user: user.publicKey })
+        .signers([user])
+        .rpc({ commitment: "confirmed" });
+
+    await setLifetimeRoot(root);
+    try {
+      const configAccount = await program.account.subscriptionConfig.fetch(
+        configPDA
+      );
+      assert.deepEqual(Buffer.from(configAccount.lifetimeRoot), root);
+
+      // A member verifies with no expiration at all
+      const leaf = lifetimeLeaf(member.publicKey);
+      await verifyLifetime(
+        member,
+        Buffer.concat(proofs.get(leaf.toString("hex"))),
+        sortedLeaves.findIndex((l) => l.equals(leaf))
+      );
+
+      // An outsider replaying a member's proof and index is rejected
+      try {
+        await verifyLifetime(
+          outsider,
+          Buffer.concat(proofs.get(leaf.toString("hex"))),
+          sortedLeaves.findIndex((l) => l.equals(leaf))
+        );
+        assert.fail("Should have failed for a non-member");
+      } catch (error) {
+        assert.include(error.toString(), "ProofDidNotMatchRoot");
+      }
+
+      // A rate-limited config needs per-user accounts this path doesn't take
+      await program.methods
+        .setMinInterval(new anchor.BN(2))
+        .accounts({
+          config: configPDA,
+          authority: wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+      try {
+        await verifyLifetime(
+          member,
+          Buffer.concat(proofs.get(leaf.toString("hex"))),
+          sortedLeaves.findIndex((l) => l.equals(leaf))
+        );
+        assert.fail("Should have failed with MissingRateLimit");
+      } catch (error) {
+        assert.include(error.toString(), "MissingRateLimit");
+      } finally {
+        await program.methods
+          .setMinInterval(new anchor.BN(0))
+          .accounts({
+            config: configPDA,
+            authority: wallet.publicKey,
+          })
+          .rpc({ commitment: "confirmed" });
+      }
+    } finally {
+      await setLifetimeRoot(Buffer.alloc(32));
+    }
+
+    // With the lifetime root cleared, nobody verifies through it
+    try {
+      await verifyLifetime(member, Buffer.alloc(32), 0);
+      assert.fail("Should have failed with RootNotSet");
+    } catch (error) {
+      assert.include(error.toString(), "RootNotSet");
+    }
+  });
+
+  it("Append-only roots accept one-leaf extensions and refuse swaps", async () => {
+    // Positional pairs, odd node promoted: the tree rs_merkle builds in leaf order
+    const hashPair = (left: Buffer, right: Buffer) =>
+      createHash("sha256").update(Buffer.concat([left, right])).digest();
+    const rootFromPeaks = (peaks: Buffer[]) =>
+      peaks
+        .slice(0, -1)
+        .reduceRight((right, left) => hashPair(left, right), peaks[peaks.length - 1]);
+    const appendToPeaks = (peaks: Buffer[], count: number, leaf: Buffer) => {
+      const next = [...peaks];
+      let carry = leaf;
+      for (let bits = count; bits & 1; bits >>= 1) {
+        carry = hashPair(next.pop(), carry);
+      }
+      return [...next, carry];
+    };
+    const appendLeaf = (leaf: Buffer, newRoot: Buffer, peaks: Buffer[]) =>
+      program.methods
+        .appendLeaf(Array.from(leaf), Array.from(newRoot), Buffer.concat(peaks))
+        .accounts({
+          config: configPDA,
+          authority: wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+    const leaves = [0, 1, 2].map(() =>
+      createLeaf(Keypair.generate().publicKey, expiration)
+    );
+
+    // A first append would replace the existing root with a one-leaf tree
+    try {
+      await appendLeaf(leaves[0], leaves[0], []);
+      assert.fail("Should have failed with NotAnExtension");
+    } catch (error) {
+      assert.include(error.toString(), "NotAnExtension");
+    }
+    await program.methods
+      .updateRoot(Array.from(Buffer.alloc(32)))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    // Grow an append-only tree one leaf at a time from an unset root
+    let peaks: Buffer[] = [];
+    for (const [count, leaf] of leaves.slice(0, 2).entries()) {
+      const next = appendToPeaks(peaks, count, leaf);
+      await appendLeaf(leaf, rootFromPeaks(next), peaks);
+      peaks = next;
+    }
+    const [appendLogPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("append_log")],
+      program.programId
+    );
+    const log = await program.account.appendLog.fetch(appendLogPDA);
+    assert.equal(log.leafCount.toNumber(), 2);
+    const configAccount = await program.account.subscriptionConfig.fetch(
+      configPDA
+    );
+    assert.deepEqual(
+      Buffer.from(configAccount.merkleRoot),
+      hashPair(leaves[0], leaves[1])
+    );
+
+    // A root that drops an existing leaf isn't an extension
+    try {
+      await appendLeaf(leaves[2], hashPair(leaves[0], leaves[2]), peaks);
+      assert.fail("Should have failed with NotAnExtension");
+    } catch (error) {
+      assert.include(error.toString(), "NotAnExtension");
+    }
+
+    // Once the root is swapped outside the log, appends are refused
+    await program.methods
+      .updateRoot(Array.from(Buffer.alloc(32, 9)))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+    try {
+      await appendLeaf(
+        leaves[2],
+        rootFromPeaks(appendToPeaks(peaks, 2, leaves[2])),
+        peaks
+      );
+      assert.fail("Should have failed with NotAnExtension");
+    } catch (error) {
+      assert.include(error.toString(), "NotAnExtension");
+    }
+  });
+
+  it("Verification cache skips proof checks within the TTL", async () => {
+    const user = Keypair.generate();
+    const airdropSig = await connection.requestAirdrop(
+      user.publicKey,
+      1 * anchor.web3.LAMPORTS_PER_SOL
+    );
+    await connection.confirmTransaction(airdropSig);
+
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+    await setSingleLeafRoot(user.publicKey, expiration);
+
+    await program.methods
+      .setVerificationCacheTtl(new anchor.BN(2))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const [receiptPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("receipt"), user.publicKey.toBuffer()],
+      program.programId
+    );
+    const cached = {
+      receipt: receiptPDA,
+      systemProgram: SystemProgram.programId,
+    };
+
+    // First verification checks the proof and writes the receipt
+    await verifySingleLeaf(user, expiration, cached);
+
+    // A wrong expiration would fail the proof, but the receipt skips it
+    await verifySingleLeaf(user, expiration + 1, cached);
+    console.log("Cached user skipped proof verification");
+
+    // Once the receipt expires the proof is checked again and rejected
+    const receipt = await program.account.verificationReceipt.fetch(
+      receiptPDA
+    );
+    while ((await chainTime()) <= receipt.verifiedUntil.toNumber()) {
+      await new Promise((resolve) => setTimeout(resolve, 500));
+    }
+    try {
+      await verifySingleLeaf(user, expiration + 1, cached);
+      assert.fail("Should have re-verified after the cache expired");
+    } catch (error) {
+      console.log("Expired cache forced re-verification");
+      assert.include(error.toString(), "ProofDidNotMatchRoot");
+    }
+
+    // A receipt issued under a replaced root is a miss, even within the TTL
+    await verifySingleLeaf(user, expiration, cached);
+    await program.methods
+      .updateRoot(Array.from(Buffer.alloc(32, 7)))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+    try {
+      await verifySingleLeaf(user, expiration, cached);
+      assert.fail("Should have re-verified under the new root");
+    } catch (error) {
+      console.log("Receipt from a replaced root ignored");
+      assert.include(error.toString(), "ProofDidNotMatchRoot");
+    }
+
+    // Disable caching so later tests are unaffected
+    await program.methods
+      .setVerificationCacheTtl(new anchor.BN(0))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+  });
+
+  it("Root updates require M of N authorities", async () => {
+    const second = Keypair.generate();
+    const third = Keypair.generate();
+    const coSigner = (key: Keypair) => ({
+      pubkey: key.publicKey,
+      isSigner: true,
+      isWritable: false,
+    });
+
+    await program.methods
+      .setAuthorities([wallet.publicKey, second.publicKey, third.publicKey], 2)
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const newRoot = Buffer.alloc(32, 9);
+
+    // One of three signers is below the threshold
+    try {
+      await program.methods
+        .updateRoot(Array.from(newRoot))
+        .accounts({
+          config: configPDA,
+          authority: wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+      assert.fail("Should have rejected a single signer");
+    } catch (error) {
+      console.log("Single signer rejected");
+      assert.include(error.toString(), "Unauthorized");
+    }
+
+    // Two of three signers meet the threshold
+    await program.methods
+      .updateRoot(Array.from(newRoot))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .remainingAccounts([coSigner(second)])
+      .signers([second])
+      .rpc({ commitment: "confirmed" });
+
+    const configAccount = await program.account.subscriptionConfig.fetch(
+      configPDA
+    );
+    assert.deepEqual(Buffer.from(configAccount.merkleRoot), newRoot);
+    console.log("Root updated with 2 of 3 authorities");
+
+    // Clearing the set also needs the threshold; restore single authority
+    await program.methods
+      .setAuthorities([], 0)
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .remainingAccounts([coSigner(third)])
+      .signers([third])
+      .rpc({ commitment: "confirmed" });
+  });
+
+  it("Salted leaves verify only with the salt", async () => {
+    const user = Keypair.generate();
+    const airdropSig = await connection.requestAirdrop(
+      user.publicKey,
+      1 * anchor.web3.LAMPORTS_PER_SOL
+    );
+    await connection.confirmTransaction(airdropSig);
+
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+    const salt = Buffer.alloc(32, 42);
+    assert.notDeepEqual(
+      createLeaf(user.publicKey, expiration, salt),
+      createLeaf(user.publicKey, expiration)
+    );
+    await setSingleLeafRoot(user.publicKey, expiration, salt);
+
+    await verifySingleLeaf(user, expiration, {}, salt);
+    console.log("Salted leaf verified");
+
+    try {
+      await verifySingleLeaf(user, expiration);
+      assert.fail("Should have rejected the unsalted leaf");
+    } catch (error) {
+      console.log("Unsalted leaf rejected against a salted root");
+      assert.include(error.toString(), "ProofDidNotMatchRoot");
+    }
+  });
+
+  it("Static allowlist leaves omit the expiration", async () => {
+    const user = Keypair.generate();
+    const allowlistRoot = createHash("sha256")
+      .update(user.publicKey.toBuffer())
+      .digest();
+
+    await program.methods
+      .setExpirationInLeaf(false, Array.from(allowlistRoot))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    // Any expiration is ignored, even one long past
+    await verifySingleLeaf(user, 0);
+    console.log("Allowlist member verified without an expiration");
+
+    // Back to subscription leaves: the same expired argument is checked again
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+    await program.methods
+      .setExpirationInLeaf(
+        true,
+        Array.from(createLeaf(user.publicKey, expiration))
+      )
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    await verifySingleLeaf(user, expiration);
+    try {
+      await verifySingleLeaf(user, 0);
+      assert.fail("Should have rejected the expired subscription");
+    } catch (error) {
+      assert.include(error.toString(), "SubscriptionExpired");
+    }
+  });
+
+  it("Base58-string leaves verify only under the base58 encoding", async () => {
+    const user = Keypair.generate();
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+    const expirationBytes = Buffer.alloc(8);
+    expirationBytes.writeBigInt64LE(BigInt(expiration));
+    const base58Leaf = createHash("sha256")
+      .update(
+        Buffer.concat([Buffer.from(user.publicKey.toBase58()), expirationBytes])
+      )
+      .digest();
+    assert.isFalse(base58Leaf.equals(createLeaf(user.publicKey, expiration)));
+
+    await program.methods
+      .setPubkeyLeafEncoding({ base58String: {} }, Array.from(base58Leaf))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+    await verifySingleLeaf(user, expiration);
+    console.log("Base58-string leaf verified");
+
+    // Back to raw bytes: the base58 root no longer matches the rebuilt leaf
+    await program.methods
+      .setPubkeyLeafEncoding({ rawBytes: {} }, Array.from(base58Leaf))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+    try {
+      await verifySingleLeaf(user, expiration);
+      assert.fail("Should have rejected a base58 leaf under raw-byte encoding");
+    } catch (error) {
+      assert.include(error.toString(), "ProofDidNotMatchRoot");
+    }
+
+    await setSingleLeafRoot(user.publicKey, expiration);
+    await verifySingleLeaf(user, expiration);
+  });
+
+  it("Start-time leaves check the subscription window", async () => {
+    const user = Keypair.generate();
+    const now = await chainTime();
+    const setWindowedRoot = (start: number, expiration: number) =>
+      program.methods
+        .setStartInLeaf(
+          true,
+          Array.from(createWindowedLeaf(user.publicKey, start, expiration))
+        )
+        .accounts({
+          config: configPDA,
+          authority: wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+    const verifyWindow = (start: number | null, expiration: number) =>
+      verifySingleLeaf(user, expiration, {}, null, null, start);
+
+    // Active: started in the past, expires in the future
+    await setWindowedRoot(now - 60, now + 86400);
+    await verifyWindow(now - 60, now + 86400);
+    console.log("Active window verified");
+
+    try {
+      await verifyWindow(null, now + 86400);
+      assert.fail("Should have required the start time");
+    } catch (error) {
+      assert.include(error.toString(), "MissingStartTime");
+    }
+
+    // Not yet started
+    await setWindowedRoot(now + 86400, now + 2 * 86400);
+    try {
+      await verifyWindow(now + 86400, now + 2 * 86400);
+      assert.fail("Should have rejected a subscription that hasn't started");
+    } catch (error) {
+      console.log("Not-yet-started window rejected");
+      assert.include(error.toString(), "SubscriptionNotStarted");
+    }
+
+    // Expired
+    await setWindowedRoot(now - 86400, now - 3600);
+    try {
+      await verifyWindow(now - 86400, now - 3600);
+      assert.fail("Should have rejected an expired window");
+    } catch (error) {
+      console.log("Expired window rejected");
+      assert.include(error.toString(), "SubscriptionExpired");
+    }
+
+    // Back to leaves without a start time for later tests
+    await program.methods
+      .setStartInLeaf(
+        false,
+        Array.from(createLeaf(user.publicKey, now + 86400))
+      )
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+  });
+
+  it("Rate limit rejects a second verify within the interval", async () => {
+    const user = Keypair.generate();
+    const airdropSig = await connection.requestAirdrop(
+      user.publicKey,
+      1 * anchor.web3.LAMPORTS_PER_SOL
+    );
+    await connection.confirmTransaction(airdropSig);
+
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+    await setSingleLeafRoot(user.publicKey, expiration);
+
+    await program.methods
+      .setMinInterval(new anchor.BN(2))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const [rateLimitPDA] = PublicKey.findProgramAddressSync(
+      [Buffer.from("rate_limit"), user.publicKey.toBuffer()],
+      program.programId
+    );
+    const limited = {
+      rateLimit: rateLimitPDA,
+      systemProgram: SystemProgram.programId,
+    };
+
+    await verifySingleLeaf(user, expiration, limited);
+
+    // An immediate second verify is rejected
+    try {
+      await verifySingleLeaf(user, expiration, limited);
+      assert.fail("Should have rejected a verify within the interval");
+    } catch (error) {
+      console.log("Rapid second verify rejected");
+      assert.include(error.toString(), "VerifyTooSoon");
+    }
+
+    // Once the interval has passed the user can verify again
+    const record = await program.account.verifyRateLimit.fetch(rateLimitPDA);
+    while ((await chainTime()) < record.lastVerifiedAt.toNumber() + 2) {
+      await new Promise((resolve) => setTimeout(resolve, 500));
+    }
+    await verifySingleLeaf(user, expiration, limited);
+    console.log("Verify after the interval accepted");
+
+    // Disable the limit so later tests are unaffected
+    await program.methods
+      .setMinInterval(new anchor.BN(0))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+  });
+
+  it("Root updates are rejected within the minimum update interval", async () => {
+    const updateRoot = (fill: number) =>
+      program.methods
+        .updateRoot(Array.from(Buffer.alloc(32, fill)))
+        .accounts({
+          config: configPDA,
+          authority: wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+
+    await program.methods
+      .setMinUpdateInterval(new anchor.BN(2))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    await updateRoot(21);
+
+    // An immediate second update is rejected
+    try {
+      await updateRoot(22);
+      assert.fail("Should have rejected an update within the interval");
+    } catch (error) {
+      console.log("Rapid second root update rejected");
+      assert.include(error.toString(), "RootUpdatedTooSoon");
+    }
+
+    // Appending a leaf is a root update too
+    try {
+      await program.methods
+        .appendLeaf(
+          Array.from(Buffer.alloc(32, 23)),
+          Array.from(Buffer.alloc(32, 23)),
+          Buffer.alloc(0)
+        )
+        .accounts({
+          config: configPDA,
+          authority: wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+      assert.fail("Should have rejected an append within the interval");
+    } catch (error) {
+      assert.include(error.toString(), "RootUpdatedTooSoon");
+    }
+
+    // Once the interval has passed the root can be updated again
+    const config = await program.account.subscriptionConfig.fetch(configPDA);
+    while ((await chainTime()) < config.lastUpdated.toNumber() + 2) {
+      await new Promise((resolve) => setTimeout(resolve, 500));
+    }
+    await updateRoot(22);
+    const updated = await program.account.subscriptionConfig.fetch(configPDA);
+    assert.deepEqual(Buffer.from(updated.merkleRoot), Buffer.alloc(32, 22));
+
+    // Disable the limit so later tests are unaffected
+    await program.methods
+      .setMinUpdateInterval(new anchor.BN(0))
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+  });
+
+  it("Verify window accepts proofs against recent roots only", async () => {
+    const user = Keypair.generate();
+    const airdropSig = await connection.requestAirdrop(
+      user.publicKey,
+      1 * anchor.web3.LAMPORTS_PER_SOL
+    );
+    await connection.confirmTransaction(airdropSig);
+
+    const setWindow = (maxAge: number) =>
+      program.methods
+        .setMaxVerifyRootAge(maxAge)
+        .accounts({
+          config: configPDA,
+          authority: wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+    const pushUnrelatedRoot = () =>
+      program.methods
+        .updateRoot(Array.from(Keypair.generate().publicKey.toBuffer()))
+        .accounts({
+          config: configPDA,
+          authority: wallet.publicKey,
+        })
+        .rpc({ commitment: "confirmed" });
+
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+    const proofRoot = createLeaf(user.publicKey, expiration);
+    await setSingleLeafRoot(user.publicKey, expiration);
+    await setWindow(2);
+
+    // Current root
+    await verifySingleLeaf(user, expiration, {}, null, proofRoot);
+
+    // One and two updates back are within the window
+    for (let age = 1; age <= 2; age++) {
+      await pushUnrelatedRoot();
+      await verifySingleLeaf(user, expiration, {}, null, proofRoot);
+    }
+    console.log("Proof against a recent root accepted");
+
+    // Without naming its root, the proof is checked against the current one
+    try {
+      await verifySingleLeaf(user, expiration);
+      assert.fail("Should have rejected the proof against the current root");
+    } catch (error) {
+      assert.include(error.toString(), "ProofDidNotMatchRoot");
+    }
+
+    // Three updates back is too old
+    await pushUnrelatedRoot();
+    try {
+      await verifySingleLeaf(user, expiration, {}, null, proofRoot);
+      assert.fail("Should have rejected a root outside the window");
+    } catch (error) {
+      console.log("Proof against a too-old root rejected");
+      assert.include(error.toString(), "StaleRoot");
+    }
+
+    // Restore current-root-only verification for later tests
+    await setWindow(0);
+  });
+
+  it("Verification pinned to an expected user rejects other signers", async () => {
+    const user = Keypair.generate();
+    const expiration = Math.floor(Date.now() / 1000) + 86400;
+    await setSingleLeafRoot(user.publicKey, expiration);
+
+    await verifySingleLeaf(user, expiration, {}, null, null, null, user.publicKey);
+    console.log("Verification pinned to the signing user accepted");
+
+    try {
+      const other = Keypair.generate();
+      await verifySingleLeaf(user, expiration, {}, null, null, null, other.publicKey);
+      assert.fail("Should have rejected a signer other than the expected user");
+    } catch (error) {
+      assert.include(error.toString(), "UnexpectedUser");
+    }
+  });
+
+  it("Realloc config is an authority-only no-op at the current size", async () => {
+    const before = await connection.getAccountInfo(configPDA);
+
+    await program.methods
+      .reallocConfig()
+      .accounts({
+        config: configPDA,
+        authority: wallet.publicKey,
+      })
+      .rpc({ commitment: "confirmed" });
+
+    const after = await connection.getAccountInfo(configPDA);
+    assert.equal(after.data.length, before.data.length);
+    assert.deepEqual(after.data, before.data);
+
+    const stranger = Keypair.generate();
+    const airdropSig = await connection.requestAirdrop(
+      stranger.publicKey,
+      1 * anchor.web3.LAMPORTS_PER_SOL
+    );
+    await connection.confirmTransaction(airdropSig);
+    try {
+      await program.methods
+        .reallocConfig()
+        .accounts({
+          config: configPDA,
+          authority: stranger.publicKey,
+        })
+        .signers([stranger])
+        .rpc({ commitment: "confirmed" });
+      assert.fail("Should have rejected a non-authority signer");
+    } catch (error) {
+      assert.include(error.toString(), "Unauthorized");
+    }
+  });
 });